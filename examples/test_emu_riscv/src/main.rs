@@ -25,11 +25,20 @@ fn main() {
 
     let load_info = ld.load_object(&file_bytes, &mut mmu,
         &["test_fuzz"], 
-        &[], 
+        &["LD_LIBRARY_PATH=/lib"], 
         &[], 
     );
 
     let mut start_emu = LinuxEmu::new(mmu); 
+    start_emu.add_symbols(load_info.symbols);
+    start_emu.core.call_stack = Some(CallStack::default());
+
+    // the shared libraries the dynamic loader will open
+    for lib in ["libc.so.6", "libgcc_s.so.1", "libm.so.6"] {
+        let lib_bytes = std::fs::read(format!("/usr/riscv64-linux-gnu/lib/{}", lib)).unwrap();
+        start_emu.vfs.add_file(format!("/lib/{}", lib).as_bytes(), lib_bytes);
+    }
+    start_emu.vfs.add_link(b"/proc/self/exe", b"/test_fuzz");
 
     // setup the emulator registers
     start_emu.core.pc = load_info.loader_entry.0 as _;
@@ -43,6 +52,9 @@ fn main() {
     emu.core.mem.vmmap();
 
     println!("{:?}", emu.run());
+    print!("{}", emu.backtrace());
+    println!("stdout: {}", String::from_utf8_lossy(&emu.vfs.stdout));
+    println!("stderr: {}", String::from_utf8_lossy(&emu.vfs.stderr));
 
     emu.core.mem.vmmap();
     //emu.reset(&start_emu);
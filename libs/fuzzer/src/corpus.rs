@@ -0,0 +1,77 @@
+use emu::riscv64gc::{CmpProgress, Coverage, Edge};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// The coverage reached by all the workers
+#[derive(Debug, Default)]
+struct GlobalCoverage {
+    edges: BTreeSet<Edge>,
+    cmps: BTreeSet<CmpProgress>,
+}
+
+/// The inputs worth mutating, shared by the workers. An input is kept when
+/// it reaches an edge or a comparison progress no input reached before
+#[derive(Debug, Default)]
+pub struct Corpus {
+    inputs: RwLock<Vec<Arc<Vec<u8>>>>,
+    coverage: Mutex<GlobalCoverage>,
+}
+
+impl Corpus {
+    /// Add `input` regardless of its coverage, e.g. a seed
+    pub fn push(&self, input: Vec<u8>) {
+        self.inputs.write().unwrap().push(Arc::new(input));
+    }
+
+    pub fn len(&self) -> usize {
+        self.inputs.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `idx`-th input
+    pub fn get(&self, idx: usize) -> Arc<Vec<u8>> {
+        self.inputs.read().unwrap()[idx].clone()
+    }
+
+    /// A copy of all the inputs, in the order they were added
+    pub fn inputs(&self) -> Vec<Arc<Vec<u8>>> {
+        self.inputs.read().unwrap().clone()
+    }
+
+    /// Number of edges reached by the corpus
+    pub fn edges(&self) -> usize {
+        self.coverage.lock().unwrap().edges.len()
+    }
+
+    /// Number of comparison progresses reached by the corpus
+    pub fn cmps(&self) -> usize {
+        self.coverage.lock().unwrap().cmps.len()
+    }
+
+    /// Add `input` if the case it ran in reached anything new for the whole
+    /// corpus, `coverage` is the one of the worker that ran it. Return if
+    /// it was added. The workers must [`Coverage::forget_case`] the cases
+    /// not passed here, so that what they have seen is in the corpus too
+    pub fn add_if_new(&self, input: &[u8], coverage: &Coverage) -> bool {
+        // the worker didn't see anything new, so the corpus didn't either
+        if coverage.new_edges().is_empty() && coverage.new_cmps().is_empty() {
+            return false;
+        }
+        let mut global = self.coverage.lock().unwrap();
+        let mut new = false;
+        for edge in coverage.new_edges() {
+            new |= global.edges.insert(*edge);
+        }
+        for cmp in coverage.new_cmps() {
+            new |= global.cmps.insert(*cmp);
+        }
+        drop(global);
+        if new {
+            self.push(input.to_vec());
+        }
+        new
+    }
+}
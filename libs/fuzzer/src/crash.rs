@@ -0,0 +1,71 @@
+use crate::{Bucket, Triage};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// How a case ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Outcome {
+    /// The program exited, with any code
+    Exit,
+    /// The program ran out of its budget
+    Hang,
+    /// Any other stop
+    Crash,
+}
+
+/// The first case of a [`Bucket`]
+#[derive(Debug, Clone)]
+pub struct Crash {
+    pub input: Vec<u8>,
+    /// Iteration of the case, see [`Fuzzer::generate`](crate::Fuzzer::generate)
+    pub iteration: u64,
+    /// Number of inputs of the corpus the case was generated from
+    pub corpus_len: usize,
+    /// Number of tokens of the dictionary the case was generated from
+    pub dictionary_len: usize,
+    /// `Debug` of the stop
+    pub stop: String,
+    pub triage: Triage,
+    /// See [`Triage::report`]
+    pub report: String,
+    /// Number of cases in the bucket
+    pub count: u64,
+}
+
+/// The buckets of the crashes and hangs found, shared by the workers
+#[derive(Debug, Default)]
+pub struct Crashes {
+    buckets: Mutex<BTreeMap<Bucket, Crash>>,
+}
+
+impl Crashes {
+    /// Add a case to `bucket`, `new` is called to describe it if the bucket
+    /// is new. Return if the bucket is new
+    pub fn add(&self, bucket: Bucket, new: impl FnOnce() -> Crash) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        match buckets.get_mut(&bucket) {
+            Some(crash) => {
+                crash.count += 1;
+                false
+            }
+            None => {
+                buckets.insert(bucket, new());
+                true
+            }
+        }
+    }
+
+    /// Number of buckets
+    pub fn len(&self) -> usize {
+        self.buckets.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// A copy of all the buckets
+    pub fn buckets(&self) -> BTreeMap<Bucket, Crash> {
+        self.buckets.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,102 @@
+use emu::riscv64gc::CmpOperands;
+use mmu::{Mmu, PermField};
+use std::collections::BTreeSet;
+use std::sync::{Arc, RwLock};
+
+/// The most tokens a [`Dictionary`] keeps, the later ones are dropped
+pub const MAX_TOKENS: usize = 1 << 14;
+/// Longest token, the longer strings are truncated
+pub const MAX_TOKEN_LEN: usize = 64;
+/// Shortest string of the memory added by [`Dictionary::add_strings`]
+pub const MIN_STRING_LEN: usize = 4;
+
+#[derive(Debug, Default)]
+struct Tokens {
+    tokens: Vec<Arc<[u8]>>,
+    seen: BTreeSet<Arc<[u8]>>,
+}
+
+/// Tokens inserted in the inputs by the mutator, shared by the workers.
+/// The fuzzer fills it with the strings of the program and the operands of
+/// the comparisons it does
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    inner: RwLock<Tokens>,
+}
+
+impl Dictionary {
+    /// Add `token` if it's new and the dictionary is not full, return if it
+    /// was added. Empty tokens are ignored and the long ones are truncated
+    pub fn add(&self, token: &[u8]) -> bool {
+        let token = &token[..token.len().min(MAX_TOKEN_LEN)];
+        if token.is_empty() || self.inner.read().unwrap().seen.contains(token) {
+            return false;
+        }
+        let mut inner = self.inner.write().unwrap();
+        if inner.tokens.len() >= MAX_TOKENS {
+            return false;
+        }
+        let token: Arc<[u8]> = token.into();
+        if !inner.seen.insert(token.clone()) {
+            return false;
+        }
+        inner.tokens.push(token);
+        true
+    }
+
+    /// Add the operands of the comparisons, as little endian integers as
+    /// wide as needed to hold their value
+    pub fn add_cmps(&self, cmps: &[CmpOperands]) {
+        for cmp in cmps {
+            for value in [cmp.lhs, cmp.rhs] {
+                // zero is already everywhere
+                if value == 0 {
+                    continue;
+                }
+                let width = (71 - value.leading_zeros() as usize) / 8;
+                self.add(&value.to_le_bytes()[..width]);
+            }
+        }
+    }
+
+    /// Add the runs of at least [`MIN_STRING_LEN`] printable ASCII
+    /// characters of the readable memory, e.g. the strings of the segments
+    /// of the loaded ELF
+    pub fn add_strings(&self, mem: &Mmu) {
+        for (_, segment) in &mem.segments {
+            let mut start = 0;
+            for (idx, (byte, perm)) in segment.memory.iter()
+                .zip(&segment.permissions).enumerate() {
+                let printable = perm.is_superset_of(PermField::Read)
+                    && (byte.is_ascii_graphic() || *byte == b' ');
+                if !printable {
+                    if idx - start >= MIN_STRING_LEN {
+                        self.add(&segment.memory[start..idx]);
+                    }
+                    start = idx + 1;
+                }
+            }
+            if segment.memory.len() - start >= MIN_STRING_LEN {
+                self.add(&segment.memory[start..]);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The `idx`-th token
+    pub fn get(&self, idx: usize) -> Arc<[u8]> {
+        self.inner.read().unwrap().tokens[idx].clone()
+    }
+
+    /// A copy of all the tokens, in the order they were added
+    pub fn tokens(&self) -> Vec<Arc<[u8]>> {
+        self.inner.read().unwrap().tokens.clone()
+    }
+}
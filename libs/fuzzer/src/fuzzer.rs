@@ -0,0 +1,326 @@
+use crate::*;
+use emu::riscv64gc::{CallStack, Coverage, LinuxEmu, LinuxEmuError, RaceDetector, TaintTracker, UninitTracker};
+use std::path::PathBuf;
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the main thread checks the stop conditions
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct FuzzerConfig {
+    /// Number of worker threads
+    pub threads: usize,
+    /// Seed of the random generators of the workers
+    pub seed: u64,
+    /// Instructions a case can execute before it's a hang
+    pub instruction_budget: usize,
+    /// Time a case can run before it's a hang
+    pub time_budget: Option<Duration>,
+    /// Longest input generated, the [`InputDelivery`] might limit it more
+    pub max_input_len: usize,
+    /// log2 of the number of entries of the coverage bitmap of the workers
+    pub coverage_bits: u32,
+    /// Use the comparison feedback, see [`Coverage::set_cmpcov`], and add
+    /// the operands of the comparisons to the dictionary
+    pub cmpcov: bool,
+    /// Check the cases for data races between the threads, see
+    /// [`RaceDetector`]. It's slow and the JIT is not used
+    pub race_detector: bool,
+    /// Report where the uninitialized memory read by the cases came from,
+    /// see [`UninitTracker`]. The JIT is not used
+    pub uninit_origins: bool,
+    /// How often [`Fuzzer::run`] prints the [`Stats`], never if `None`
+    pub stats_interval: Option<Duration>,
+    /// Stop after this many cases
+    pub max_cases: Option<u64>,
+    /// Stop after this long
+    pub max_time: Option<Duration>,
+    /// Stop at the first crash
+    pub stop_on_crash: bool,
+    /// Where to save the first input of each bucket, in the `crashes` and
+    /// `hangs` subdirectories
+    pub output: Option<PathBuf>,
+}
+
+impl Default for FuzzerConfig {
+    fn default() -> Self {
+        FuzzerConfig {
+            threads: 1,
+            seed: 0,
+            instruction_budget: 10_000_000,
+            time_budget: None,
+            max_input_len: 4096,
+            coverage_bits: emu::riscv64gc::DEFAULT_COVERAGE_BITS,
+            cmpcov: true,
+            race_detector: false,
+            uninit_origins: false,
+            stats_interval: Some(Duration::from_secs(1)),
+            max_cases: None,
+            max_time: None,
+            stop_on_crash: false,
+            output: None,
+        }
+    }
+}
+
+/// The fuzzer, see the crate documentation
+pub struct Fuzzer {
+    pub config: FuzzerConfig,
+    pub delivery: InputDelivery,
+    /// The inputs that found new coverage, plus the seeds
+    pub corpus: Corpus,
+    /// The crashes and the hangs found
+    pub crashes: Crashes,
+    /// The tokens used by the mutator, initially the strings of the memory
+    /// of the pristine emulator
+    pub dictionary: Dictionary,
+    /// The state every case starts from
+    pristine: LinuxEmu,
+    counters: Counters,
+    start: Instant,
+    stop: AtomicBool,
+}
+
+impl Fuzzer {
+    /// A fuzzer running the cases from the state of `emu`
+    pub fn new(mut emu: LinuxEmu, delivery: InputDelivery, config: FuzzerConfig) -> Self {
+        delivery.setup(&mut emu);
+        let mut coverage = Coverage::new(config.coverage_bits);
+        coverage.set_cmpcov(config.cmpcov);
+        emu.core.coverage = Some(coverage);
+        // the buckets hash the backtraces, it's better to track the calls
+        // since the start of the program
+        emu.core.call_stack.get_or_insert_with(CallStack::default);
+        if config.race_detector {
+            emu.core.race_detector = Some(RaceDetector::new(emu.threading.current().tid));
+        }
+        if config.uninit_origins {
+            emu.core.uninit = Some(UninitTracker::default());
+        }
+        let dictionary = Dictionary::default();
+        dictionary.add_strings(&emu.core.mem);
+        Fuzzer {
+            config,
+            delivery,
+            corpus: Corpus::default(),
+            crashes: Crashes::default(),
+            dictionary,
+            pristine: emu,
+            counters: Counters::default(),
+            start: Instant::now(),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    /// The state every case starts from
+    pub fn pristine(&self) -> &LinuxEmu {
+        &self.pristine
+    }
+
+    /// Add a seed input to the corpus
+    pub fn add_input(&self, input: Vec<u8>) {
+        self.corpus.push(input);
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            elapsed: self.start.elapsed(),
+            cases: self.counters.cases.load(Ordering::Relaxed),
+            instructions: self.counters.instructions.load(Ordering::Relaxed),
+            corpus: self.corpus.len(),
+            edges: self.corpus.edges(),
+            cmps: self.corpus.cmps(),
+            crashes: self.counters.crashes.load(Ordering::Relaxed),
+            hangs: self.counters.hangs.load(Ordering::Relaxed),
+            buckets: self.crashes.len(),
+        }
+    }
+
+    /// Fuzz with [`FuzzerConfig::threads`] workers until one of the stop
+    /// conditions of the config, or forever, and return the final stats
+    pub fn run(&mut self) -> Stats {
+        if self.corpus.is_empty() {
+            self.corpus.push(Vec::new());
+        }
+        self.start = Instant::now();
+        self.stop.store(false, Ordering::Relaxed);
+
+        let this = &*self;
+        std::thread::scope(|scope| {
+            for _ in 0..this.config.threads {
+                scope.spawn(move || {
+                    // the others stop too, so that the panic is propagated
+                    if let Err(panic) = catch_unwind(AssertUnwindSafe(|| this.worker())) {
+                        this.stop.store(true, Ordering::Relaxed);
+                        resume_unwind(panic);
+                    }
+                });
+            }
+            let mut last_print = Instant::now();
+            while !this.stop.load(Ordering::Relaxed) {
+                std::thread::sleep(POLL_INTERVAL);
+                if this.config.max_time.is_some_and(|max| this.start.elapsed() >= max) {
+                    this.stop.store(true, Ordering::Relaxed);
+                }
+                if let Some(interval) = this.config.stats_interval {
+                    if last_print.elapsed() >= interval {
+                        println!("{}", this.stats());
+                        last_print = Instant::now();
+                    }
+                }
+            }
+        });
+
+        let stats = self.stats();
+        if self.config.stats_interval.is_some() {
+            println!("{}", stats);
+        }
+        stats
+    }
+
+    /// Longest input generated
+    pub fn max_len(&self) -> usize {
+        match self.delivery.max_len() {
+            Some(max_len) => max_len.min(self.config.max_input_len),
+            None => self.config.max_input_len,
+        }
+    }
+
+    /// Generate the input of the `iteration`-th case in `input`, using only
+    /// the first `corpus_len` inputs of the corpus and `dictionary_len`
+    /// tokens of the dictionary. They only grow, so the same arguments
+    /// always give the same input, e.g. the ones of a [`Crash`]
+    pub fn generate(&self, iteration: u64, corpus_len: usize, dictionary_len: usize,
+        input: &mut Vec<u8>) {
+        let mut rng = Rng::for_case(self.config.seed, iteration);
+        input.clear();
+        input.extend_from_slice(&self.corpus.get(rng.below(corpus_len)));
+        Mutator {
+            rng,
+            max_len: self.max_len(),
+            corpus: &self.corpus,
+            corpus_len,
+            dictionary: &self.dictionary,
+            dictionary_len,
+        }.mutate(input);
+    }
+
+    /// Run `input` in `emu`, which must be a fork of the pristine emulator,
+    /// from the pristine state
+    pub fn run_case(&self, emu: &mut LinuxEmu, input: &[u8]) -> LinuxEmuError {
+        emu.reset(&self.pristine);
+        self.delivery.deliver(emu, input);
+        emu.core.set_instruction_budget(self.config.instruction_budget);
+        emu.core.set_time_budget(self.config.time_budget);
+        if let Some(coverage) = &mut emu.core.coverage {
+            coverage.clear_case();
+        }
+        emu.run()
+    }
+
+    /// Run `input` from the pristine state tracking its taint, to know which
+    /// of its bytes control each comparison, see
+    /// [`TaintTracker::comparisons`]. It's slow and the JIT is not used
+    pub fn taint(&self, input: &[u8]) -> TaintTracker {
+        let mut emu = self.pristine.fork();
+        // the coverage is not needed
+        emu.core.coverage = None;
+        emu.core.taint = Some(TaintTracker::default());
+        self.delivery.deliver(&mut emu, input);
+        emu.core.set_instruction_budget(self.config.instruction_budget);
+        emu.core.set_time_budget(self.config.time_budget);
+        emu.run();
+        emu.core.taint.take().unwrap()
+    }
+
+    /// Reserve the next case and return its iteration, unless the fuzzer
+    /// has to stop
+    fn next_case(&self) -> Option<u64> {
+        if self.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        let case = self.counters.cases.fetch_add(1, Ordering::Relaxed);
+        if self.config.max_cases.is_some_and(|max| case >= max) {
+            self.counters.cases.fetch_sub(1, Ordering::Relaxed);
+            self.stop.store(true, Ordering::Relaxed);
+            return None;
+        }
+        Some(case)
+    }
+
+    fn worker(&self) {
+        let mut emu = self.pristine.fork();
+        let mut input = Vec::with_capacity(self.max_len());
+
+        while let Some(iteration) = self.next_case() {
+            let (corpus_len, dictionary_len) = (self.corpus.len(), self.dictionary.len());
+            self.generate(iteration, corpus_len, dictionary_len, &mut input);
+
+            let stop = self.run_case(&mut emu, &input);
+            let instructions = emu.core.instructions_executed - self.pristine.core.instructions_executed;
+            self.counters.instructions.fetch_add(instructions as u64, Ordering::Relaxed);
+
+            // only the inputs that don't crash nor hang are mutated
+            let Some(triage) = Triage::new(&emu, &stop) else {
+                let coverage = emu.core.coverage.as_ref().unwrap();
+                if self.corpus.add_if_new(&input, coverage) {
+                    self.dictionary.add_cmps(coverage.cmp_log());
+                }
+                continue;
+            };
+            // the input is not kept, so what it reached first is still new
+            emu.core.coverage.as_mut().unwrap().forget_case();
+            let outcome = triage.kind.outcome();
+            let counter = match outcome {
+                Outcome::Hang => &self.counters.hangs,
+                _ => &self.counters.crashes,
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+            let bucket = triage.bucket;
+            let mut new = None;
+            self.crashes.add(bucket, || {
+                let report = format!("seed: {}\niteration: {}\ncorpus: {}\ndictionary: {}\n{}",
+                    self.config.seed, iteration, corpus_len, dictionary_len,
+                    triage.report(&emu, &stop, &input));
+                let crash = Crash {
+                    input: input.clone(),
+                    iteration,
+                    corpus_len,
+                    dictionary_len,
+                    stop: format!("{:?}", stop),
+                    triage,
+                    report,
+                    count: 1,
+                };
+                new = Some(crash.clone());
+                crash
+            });
+            if let Some(crash) = new {
+                self.save(outcome, bucket, &crash);
+            }
+            if outcome == Outcome::Crash && self.config.stop_on_crash {
+                self.stop.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Write the first input of a new bucket and its report in the output
+    /// directory
+    fn save(&self, outcome: Outcome, bucket: Bucket, crash: &Crash) {
+        let Some(output) = &self.config.output else {
+            return;
+        };
+        let dir = output.join(match outcome {
+            Outcome::Hang => "hangs",
+            _ => "crashes",
+        });
+        let path = dir.join(bucket.to_string());
+        if let Err(error) = std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(&path, &crash.input))
+            .and_then(|_| std::fs::write(path.with_extension("txt"), &crash.report)) {
+            eprintln!("Cannot save the input of {}: {}", bucket, error);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use emu::riscv64gc::{LinuxEmu, Register};
+use mmu::VirtAddr;
+use std::sync::Arc;
+
+/// Deliver an input to an emulator that was just reset
+pub type DeliverFn = Arc<dyn Fn(&mut LinuxEmu, &[u8]) + Send + Sync>;
+
+/// Where the target finds the input of a case
+#[derive(Clone)]
+pub enum InputDelivery {
+    /// Written in the guest memory at `addr`, which must be mapped and
+    /// writable for `max_len` bytes, with the length in `len_reg` if set.
+    /// Longer inputs are truncated
+    Memory {
+        addr: u64,
+        max_len: usize,
+        len_reg: Option<Register>,
+    },
+    /// Read from fd 0
+    Stdin,
+    /// Read from the file opened at this path
+    File(Vec<u8>),
+    /// Anything else the harness needs
+    Custom(DeliverFn),
+}
+
+impl core::fmt::Debug for InputDelivery {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            InputDelivery::Memory { addr, max_len, len_reg } => f.debug_struct("Memory")
+                .field("addr", addr)
+                .field("max_len", max_len)
+                .field("len_reg", len_reg)
+                .finish(),
+            InputDelivery::Stdin => f.write_str("Stdin"),
+            InputDelivery::File(path) => f.debug_tuple("File")
+                .field(&String::from_utf8_lossy(path))
+                .finish(),
+            InputDelivery::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+impl InputDelivery {
+    /// Prepare the pristine emulator, e.g. open the input on stdin
+    pub fn setup(&self, emu: &mut LinuxEmu) {
+        match self {
+            InputDelivery::Memory { addr, max_len, .. } => {
+                let (start, segment) = emu.core.mem.resolve_segment(VirtAddr(*addr as usize))
+                    .expect("The input buffer is not mapped");
+                assert!(
+                    *addr as usize - start.0 + max_len <= segment.len(),
+                    "The input buffer is not in a single segment",
+                );
+            }
+            InputDelivery::Stdin => emu.vfs.set_input_fd(0),
+            InputDelivery::File(path) => emu.vfs.set_input_path(path),
+            InputDelivery::Custom(_) => {},
+        }
+    }
+
+    /// The longest input the target can get, if limited
+    pub fn max_len(&self) -> Option<usize> {
+        match self {
+            InputDelivery::Memory { max_len, .. } => Some(*max_len),
+            _ => None,
+        }
+    }
+
+    /// Give `input` to the target in `emu`, which was just reset
+    pub fn deliver(&self, emu: &mut LinuxEmu, input: &[u8]) {
+        match self {
+            InputDelivery::Memory { addr, max_len, len_reg } => {
+                let input = &input[..input.len().min(*max_len)];
+                // Safety: the buffer was checked by `setup`
+                unsafe{emu.core.mem.write_from_slice(VirtAddr(*addr as usize), input)}
+                    .expect("The input buffer is not mapped");
+                if let Some(taint) = &mut emu.core.taint {
+                    taint.taint_input(*addr, 0, input.len());
+                }
+                if let Some(len_reg) = len_reg {
+                    emu.core.write_reg(*len_reg, input.len() as u64);
+                }
+            }
+            InputDelivery::Stdin | InputDelivery::File(_) => emu.vfs.set_input(input),
+            InputDelivery::Custom(deliver) => deliver(emu, input),
+        }
+    }
+}
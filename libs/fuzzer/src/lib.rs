@@ -0,0 +1,48 @@
+//! Coverage guided fuzzer of the programs emulated by [`LinuxEmu`].
+//!
+//! The emulator given to [`Fuzzer::new`] is the pristine state every case
+//! starts from, e.g. a program stopped right before it reads its input.
+//! Each worker thread forks it once and then, for every case, resets its
+//! fork to it, delivers a mutated input as described by the
+//! [`InputDelivery`] and runs it within the budgets of the
+//! [`FuzzerConfig`].
+//!
+//! The inputs that reach edges or comparison progress never seen by any
+//! worker are added to the shared [`Corpus`], the ones that crash or hang
+//! are classified by a [`Triage`] and grouped in [`Bucket`]s, and the
+//! [`Stats`] are printed periodically.
+//!
+//! The input of a case is a corpus entry changed by the [`Mutator`], with
+//! tokens from the [`Dictionary`]. The randomness of the `n`-th case comes
+//! only from [`Rng::for_case`] with the seed of the config and `n`, and
+//! the corpus and the dictionary only grow, so [`Fuzzer::generate`] can
+//! generate it again from `n` and their lengths when it started, which are
+//! kept in each [`Crash`].
+//!
+//! [`LinuxEmu`]: emu::riscv64gc::LinuxEmu
+mod rng;
+pub use rng::*;
+
+mod input;
+pub use input::*;
+
+mod dictionary;
+pub use dictionary::*;
+
+mod mutator;
+pub use mutator::*;
+
+mod corpus;
+pub use corpus::*;
+
+mod crash;
+pub use crash::*;
+
+mod triage;
+pub use triage::*;
+
+mod stats;
+pub use stats::*;
+
+mod fuzzer;
+pub use fuzzer::*;
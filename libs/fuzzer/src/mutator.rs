@@ -0,0 +1,248 @@
+use crate::{Corpus, Dictionary, Rng};
+
+/// The values that often trigger edge cases, written with any width
+const INTERESTING: [i64; 27] = [
+    // 8 bits
+    -128, -1, 0, 1, 16, 32, 64, 100, 127,
+    // 16 bits
+    -32768, -129, 128, 255, 256, 512, 1000, 1024, 4096, 32767,
+    // 32 bits
+    -2147483648, -100663046, -32769, 32768, 65535, 65536, 100663045, 2147483647,
+];
+
+/// Largest value added or subtracted by [`Mutation::Arithmetic`]
+const ARITH_MAX: u64 = 35;
+
+/// A single change of an input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mutation {
+    /// Flip a random bit
+    FlipBit,
+    /// Flip all the bits of a random byte
+    FlipByte,
+    /// Overwrite a byte with a random value
+    RandomByte,
+    /// Add or subtract a small value to an integer of 1, 2, 4 or 8 bytes of
+    /// either endianness
+    Arithmetic,
+    /// Overwrite an integer with one of the interesting values
+    Interesting,
+    /// Insert a block of random bytes, or of a repeated one
+    InsertBlock,
+    /// Remove a block
+    DeleteBlock,
+    /// Insert a copy of a block of the input somewhere else
+    DuplicateBlock,
+    /// Replace the tail of the input with the one of a corpus entry
+    Splice,
+    /// Insert a token of the dictionary
+    InsertToken,
+    /// Overwrite the input with a token of the dictionary
+    OverwriteToken,
+}
+
+impl Mutation {
+    pub const ALL: [Mutation; 11] = [
+        Mutation::FlipBit,
+        Mutation::FlipByte,
+        Mutation::RandomByte,
+        Mutation::Arithmetic,
+        Mutation::Interesting,
+        Mutation::InsertBlock,
+        Mutation::DeleteBlock,
+        Mutation::DuplicateBlock,
+        Mutation::Splice,
+        Mutation::InsertToken,
+        Mutation::OverwriteToken,
+    ];
+}
+
+/// Mutates the inputs of the cases. All the randomness comes from `rng`,
+/// so the same seed, base input, corpus and dictionary lengths always give
+/// the same mutations
+pub struct Mutator<'a> {
+    pub rng: Rng,
+    /// Longest input generated
+    pub max_len: usize,
+    /// Where the spliced inputs come from
+    pub corpus: &'a Corpus,
+    /// Number of inputs of the corpus that can be spliced
+    pub corpus_len: usize,
+    /// Where the tokens come from
+    pub dictionary: &'a Dictionary,
+    /// Number of tokens of the dictionary that can be inserted
+    pub dictionary_len: usize,
+}
+
+impl<'a> Mutator<'a> {
+    /// Apply a stack of 1 to 8 random mutations to `input`
+    pub fn mutate(&mut self, input: &mut Vec<u8>) {
+        let rounds = 1 << self.rng.below(4);
+        let mut applied = 0;
+        // some mutations can't be applied, e.g. on an empty input
+        for _ in 0..4 * rounds {
+            let mutation = Mutation::ALL[self.rng.below(Mutation::ALL.len())];
+            applied += self.apply(mutation, input) as usize;
+            if applied == rounds {
+                break;
+            }
+        }
+    }
+
+    /// Apply `mutation` to `input`, return false if it could not be
+    /// applied, e.g. there's no block to remove from an empty input
+    pub fn apply(&mut self, mutation: Mutation, input: &mut Vec<u8>) -> bool {
+        match mutation {
+            Mutation::FlipBit => {
+                if input.is_empty() {
+                    return false;
+                }
+                let idx = self.rng.below(input.len());
+                input[idx] ^= 1 << self.rng.below(8);
+            }
+            Mutation::FlipByte => {
+                if input.is_empty() {
+                    return false;
+                }
+                let idx = self.rng.below(input.len());
+                input[idx] ^= 0xff;
+            }
+            Mutation::RandomByte => {
+                if input.is_empty() {
+                    return false;
+                }
+                let idx = self.rng.below(input.len());
+                // always change it
+                input[idx] ^= 1 + self.rng.below(255) as u8;
+            }
+            Mutation::Arithmetic => {
+                let Some((idx, width, big_endian)) = self.integer(input) else {
+                    return false;
+                };
+                let value = read_int(&input[idx..idx + width], big_endian);
+                let delta = 1 + self.rng.below(ARITH_MAX as usize) as u64;
+                let value = if self.rng.one_in(2) {
+                    value.wrapping_add(delta)
+                } else {
+                    value.wrapping_sub(delta)
+                };
+                write_int(&mut input[idx..idx + width], value, big_endian);
+            }
+            Mutation::Interesting => {
+                let Some((idx, width, big_endian)) = self.integer(input) else {
+                    return false;
+                };
+                let value = INTERESTING[self.rng.below(INTERESTING.len())];
+                write_int(&mut input[idx..idx + width], value as u64, big_endian);
+            }
+            Mutation::InsertBlock => {
+                if input.len() >= self.max_len {
+                    return false;
+                }
+                let len = self.block_len(self.max_len - input.len());
+                let idx = self.rng.below(input.len() + 1);
+                if self.rng.one_in(2) {
+                    let byte = self.rng.next_u64() as u8;
+                    input.splice(idx..idx, core::iter::repeat_n(byte, len));
+                } else {
+                    let rng = &mut self.rng;
+                    input.splice(idx..idx, (0..len).map(|_| rng.next_u64() as u8));
+                }
+            }
+            Mutation::DeleteBlock => {
+                if input.is_empty() {
+                    return false;
+                }
+                let len = self.block_len(input.len());
+                let idx = self.rng.below(input.len() - len + 1);
+                input.drain(idx..idx + len);
+            }
+            Mutation::DuplicateBlock => {
+                if input.is_empty() || input.len() >= self.max_len {
+                    return false;
+                }
+                let len = self.block_len(input.len().min(self.max_len - input.len()));
+                let src = self.rng.below(input.len() - len + 1);
+                let dst = self.rng.below(input.len() + 1);
+                let block = input[src..src + len].to_vec();
+                input.splice(dst..dst, block);
+            }
+            Mutation::Splice => {
+                if self.corpus_len == 0 {
+                    return false;
+                }
+                let other = self.corpus.get(self.rng.below(self.corpus_len));
+                if other.is_empty() || other.as_slice() == input.as_slice() {
+                    return false;
+                }
+                let split = self.rng.below(input.len().min(self.max_len) + 1);
+                let from = self.rng.below(other.len());
+                let len = (other.len() - from).min(self.max_len - split);
+                input.truncate(split);
+                input.extend_from_slice(&other[from..from + len]);
+            }
+            Mutation::InsertToken => {
+                let Some(token) = self.token(self.max_len.saturating_sub(input.len())) else {
+                    return false;
+                };
+                let idx = self.rng.below(input.len() + 1);
+                input.splice(idx..idx, token.iter().copied());
+            }
+            Mutation::OverwriteToken => {
+                let Some(token) = self.token(input.len()) else {
+                    return false;
+                };
+                let idx = self.rng.below(input.len() - token.len() + 1);
+                input[idx..idx + token.len()].copy_from_slice(&token);
+            }
+        }
+        true
+    }
+
+    /// Length of a block of at most `limit` bytes, which must not be zero,
+    /// the small ones are more likely
+    fn block_len(&mut self, limit: usize) -> usize {
+        let max = [8, 32, 128, limit][self.rng.below(4)];
+        1 + self.rng.below(max.min(limit))
+    }
+
+    /// Position, width and endianness of a random integer of the input
+    fn integer(&mut self, input: &[u8]) -> Option<(usize, usize, bool)> {
+        let width = 1 << self.rng.below(4);
+        if input.len() < width {
+            return None;
+        }
+        let idx = self.rng.below(input.len() - width + 1);
+        Some((idx, width, width > 1 && self.rng.one_in(2)))
+    }
+
+    /// A random token of the dictionary, if it's not empty and the token is
+    /// not longer than `limit`
+    fn token(&mut self, limit: usize) -> Option<std::sync::Arc<[u8]>> {
+        if self.dictionary_len == 0 {
+            return None;
+        }
+        let token = self.dictionary.get(self.rng.below(self.dictionary_len));
+        (token.len() <= limit).then_some(token)
+    }
+}
+
+fn read_int(bytes: &[u8], big_endian: bool) -> u64 {
+    let mut buf = [0; 8];
+    if big_endian {
+        buf[8 - bytes.len()..].copy_from_slice(bytes);
+        u64::from_be_bytes(buf)
+    } else {
+        buf[..bytes.len()].copy_from_slice(bytes);
+        u64::from_le_bytes(buf)
+    }
+}
+
+fn write_int(bytes: &mut [u8], value: u64, big_endian: bool) {
+    let width = bytes.len();
+    if big_endian {
+        bytes.copy_from_slice(&value.to_be_bytes()[8 - width..]);
+    } else {
+        bytes.copy_from_slice(&value.to_le_bytes()[..width]);
+    }
+}
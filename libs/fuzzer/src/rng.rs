@@ -0,0 +1,43 @@
+/// Small and fast pseudo random generator, xorshift64* seeded with
+/// splitmix64 so that close seeds give unrelated sequences
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        // xorshift can't leave zero
+        Rng { state: z | 1 }
+    }
+
+    /// The generator of the `iteration`-th case of a run seeded with `seed`,
+    /// so that any case can be generated again on its own
+    pub fn for_case(seed: u64, iteration: u64) -> Self {
+        Rng::new(Rng::new(seed).next_u64() ^ iteration)
+    }
+
+    #[inline]
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..bound`, `bound` must not be zero
+    #[inline]
+    pub fn below(&mut self, bound: usize) -> usize {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as usize
+    }
+
+    /// True once every `n` times on average
+    #[inline]
+    pub fn one_in(&mut self, n: usize) -> bool {
+        self.below(n) == 0
+    }
+}
@@ -0,0 +1,54 @@
+use std::sync::atomic::AtomicU64;
+use std::time::Duration;
+
+/// Counters updated by the workers
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    pub cases: AtomicU64,
+    pub instructions: AtomicU64,
+    pub crashes: AtomicU64,
+    pub hangs: AtomicU64,
+}
+
+/// A snapshot of the progress of the fuzzer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub elapsed: Duration,
+    pub cases: u64,
+    pub instructions: u64,
+    pub corpus: usize,
+    /// Edges reached by the corpus
+    pub edges: usize,
+    /// Comparison progresses reached by the corpus
+    pub cmps: usize,
+    /// Cases that crashed
+    pub crashes: u64,
+    /// Cases that hung
+    pub hangs: u64,
+    /// Distinct crashes and hangs
+    pub buckets: usize,
+}
+
+impl Stats {
+    pub fn execs_per_sec(&self) -> f64 {
+        self.cases as f64 / self.elapsed.as_secs_f64().max(1e-9)
+    }
+}
+
+impl core::fmt::Display for Stats {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f,
+            "[{:>9.2}s] cases {:>10} | {:>9.0} exec/s | {:>7.1} Minst/s | corpus {:>6} | edges {:>6} | cmps {:>6} | crashes {:>6} | hangs {:>6} | buckets {:>4}",
+            self.elapsed.as_secs_f64(),
+            self.cases,
+            self.execs_per_sec(),
+            self.instructions as f64 / self.elapsed.as_secs_f64().max(1e-9) / 1e6,
+            self.corpus,
+            self.edges,
+            self.cmps,
+            self.crashes,
+            self.hangs,
+            self.buckets,
+        )
+    }
+}
@@ -0,0 +1,362 @@
+//! Classification, deduplication and reports of the crashes and hangs.
+//!
+//! A [`Triage`] tells what kind of bug stopped a case, where, and which
+//! [`Bucket`] it belongs to. The bucket is identified by a hash of the kind,
+//! of the faulting pc and of the callers, which is stable between runs and
+//! machines as the emulation is deterministic.
+use crate::Outcome;
+use core::fmt::Write;
+use emu::riscv64gc::{HeapBug, HeapSanitizer, LinuxEmu, LinuxEmuError, UninitOrigin};
+use mmu::{Mmu, MmuError, PermField, VirtAddr};
+
+/// Number of frames of the call stack, the faulting one included, hashed in
+/// the [`Bucket`]
+pub const SIGNATURE_FRAMES: usize = 4;
+
+/// Why a case crashed or hung
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CrashKind {
+    /// Load from memory that is not mapped or not readable
+    ReadFault,
+    /// Store to memory that is not mapped or not writable
+    WriteFault,
+    /// Jump to memory that is not mapped or not executable
+    ExecFault,
+    /// Load from memory that is readable only after it's written, and was
+    /// never written
+    UninitRead,
+    /// Free of memory that was not allocated
+    InvalidFree,
+    /// Access out of the bounds of a chunk of the heap sanitizer
+    HeapOverflow,
+    /// Access to a chunk of the heap sanitizer after it was freed
+    UseAfterFree,
+    /// Free of a chunk of the heap sanitizer that was already freed
+    DoubleFree,
+    /// The case ran out of its budget
+    Timeout,
+    /// Every thread is blocked forever
+    Deadlock,
+    /// Two threads accessed the same memory without synchronizing
+    DataRace,
+    Breakpoint,
+    BadSyscall,
+    IllegalInstruction,
+    UnknownCsr,
+    MisalignedAtomic,
+    /// A syscall hook stopped the execution
+    HookStop,
+    RegWrite,
+    /// Any other error of the memory
+    Mmu,
+}
+
+impl CrashKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CrashKind::ReadFault => "read_fault",
+            CrashKind::WriteFault => "write_fault",
+            CrashKind::ExecFault => "exec_fault",
+            CrashKind::UninitRead => "uninit_read",
+            CrashKind::InvalidFree => "invalid_free",
+            CrashKind::HeapOverflow => "heap_overflow",
+            CrashKind::UseAfterFree => "use_after_free",
+            CrashKind::DoubleFree => "double_free",
+            CrashKind::Timeout => "timeout",
+            CrashKind::Deadlock => "deadlock",
+            CrashKind::DataRace => "data_race",
+            CrashKind::Breakpoint => "breakpoint",
+            CrashKind::BadSyscall => "bad_syscall",
+            CrashKind::IllegalInstruction => "illegal_instruction",
+            CrashKind::UnknownCsr => "unknown_csr",
+            CrashKind::MisalignedAtomic => "misaligned_atomic",
+            CrashKind::HookStop => "hook_stop",
+            CrashKind::RegWrite => "reg_write",
+            CrashKind::Mmu => "mmu",
+        }
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        match self {
+            CrashKind::Timeout | CrashKind::Deadlock => Outcome::Hang,
+            _ => Outcome::Crash,
+        }
+    }
+}
+
+impl core::fmt::Display for CrashKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The crashes or hangs considered to be the same bug
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Bucket {
+    pub kind: CrashKind,
+    /// Hash of the kind and of the first [`SIGNATURE_FRAMES`] frames
+    pub hash: u64,
+}
+
+impl core::fmt::Display for Bucket {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}_{:016x}", self.kind, self.hash)
+    }
+}
+
+/// What is known about a crash or a hang
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Triage {
+    pub kind: CrashKind,
+    pub pc: u64,
+    /// The address accessed, for the faults
+    pub fault_address: Option<u64>,
+    /// The pcs of the backtrace, see [`CoreEmu::backtrace`]
+    ///
+    /// [`CoreEmu::backtrace`]: emu::riscv64gc::CoreEmu::backtrace
+    pub frames: Vec<u64>,
+    pub bucket: Bucket,
+}
+
+impl Triage {
+    /// Classify the stop of `emu`, `None` if the program exited
+    pub fn new(emu: &LinuxEmu, stop: &LinuxEmuError) -> Option<Self> {
+        let pc = emu.core.pc;
+        let mem = &emu.core.mem;
+        let (kind, fault_address) = match stop {
+            LinuxEmuError::Exit(_) => return None,
+            LinuxEmuError::Timeout => (CrashKind::Timeout, None),
+            LinuxEmuError::Deadlock => (CrashKind::Deadlock, None),
+            LinuxEmuError::DataRace(race) => (CrashKind::DataRace, Some(race.addr)),
+            LinuxEmuError::UninitRead(read) => (CrashKind::UninitRead, Some(read.addr)),
+            LinuxEmuError::Breakpoint => (CrashKind::Breakpoint, None),
+            LinuxEmuError::BadSyscall(_) => (CrashKind::BadSyscall, None),
+            LinuxEmuError::IllegalInstruction => (CrashKind::IllegalInstruction, None),
+            LinuxEmuError::UnknownCsr(_) => (CrashKind::UnknownCsr, None),
+            LinuxEmuError::MisalignedAtomic(address) => (CrashKind::MisalignedAtomic, Some(*address)),
+            LinuxEmuError::HookStop(_) => (CrashKind::HookStop, None),
+            LinuxEmuError::RegWrite => (CrashKind::RegWrite, None),
+            LinuxEmuError::MmuError(error) => {
+                let address = fault_address(error);
+                // the fetch of the instruction at pc failed
+                let kind = if instruction(mem, pc).is_none() {
+                    CrashKind::ExecFault
+                } else {
+                    match error {
+                        MmuError::PermissionsFault { is_read: true, permissions, size, .. } => {
+                            let uninit = permissions[..(*size).min(8)].iter().any(|perm| {
+                                perm.is_superset_of(PermField::ReadAfterWrite)
+                                    && !perm.is_superset_of(PermField::Read)
+                            });
+                            if uninit {
+                                CrashKind::UninitRead
+                            } else {
+                                CrashKind::ReadFault
+                            }
+                        }
+                        MmuError::PermissionsFault { is_read: false, .. }
+                        | MmuError::OutOfBound { is_read: false, .. } => CrashKind::WriteFault,
+                        MmuError::OutOfBound { is_read: true, .. } => CrashKind::ReadFault,
+                        // the error doesn't say the kind of access
+                        MmuError::SegmentNotFound { .. } => {
+                            match instruction(mem, pc) {
+                                Some(inst) if is_store(inst) => CrashKind::WriteFault,
+                                _ => CrashKind::ReadFault,
+                            }
+                        }
+                        MmuError::InvalidFree(_) => CrashKind::InvalidFree,
+                        _ => CrashKind::Mmu,
+                    }
+                };
+                (kind, address.or((kind == CrashKind::ExecFault).then_some(pc)))
+            }
+        };
+        let kind = match (&emu.heap_sanitizer, fault_address) {
+            (Some(sanitizer), Some(address)) => heap_kind(sanitizer, kind, address),
+            _ => kind,
+        };
+
+        let frames = emu.core.backtrace();
+        let mut hash = Fnv::default();
+        hash.write(kind.name().as_bytes());
+        for frame in frames.iter().take(SIGNATURE_FRAMES) {
+            hash.write(&frame.to_le_bytes());
+        }
+        // the same access can race with different ones
+        if let LinuxEmuError::DataRace(race) = stop {
+            hash.write(&race.previous_pc.to_le_bytes());
+        }
+
+        Some(Triage {
+            kind,
+            pc,
+            fault_address,
+            frames,
+            bucket: Bucket { kind, hash: hash.0 },
+        })
+    }
+
+    /// A human readable report of the crash of `input`, with everything
+    /// needed to reproduce it
+    pub fn report(&self, emu: &LinuxEmu, stop: &LinuxEmuError, input: &[u8]) -> String {
+        let mut report = String::new();
+        self.write_report(&mut report, emu, stop, input).unwrap();
+        report
+    }
+
+    fn write_report(&self, w: &mut String, emu: &LinuxEmu, stop: &LinuxEmuError,
+        input: &[u8]) -> core::fmt::Result {
+        let mem = &emu.core.mem;
+        writeln!(w, "bucket: {}", self.bucket)?;
+        writeln!(w, "kind: {}", self.kind)?;
+        writeln!(w, "stop: {:?}", stop)?;
+        writeln!(w, "pc: {:#x}", self.pc)?;
+        if let Some(address) = self.fault_address {
+            writeln!(w, "fault address: {:#x}", address)?;
+            match mem.lookup_segment(VirtAddr(address as usize)) {
+                Some(idx) => {
+                    w.write_str("segment: ")?;
+                    mem.write_vmmap_entry(w, idx)?;
+                }
+                None => writeln!(w, "segment: not mapped")?,
+            }
+            let chunk = emu.heap_sanitizer.as_ref().and_then(|sanitizer| sanitizer.chunk(address));
+            if let Some(chunk) = chunk {
+                writeln!(w, "chunk: {:#x} of {} bytes, {:?}, allocated from {:#x}",
+                    chunk.addr, chunk.size, chunk.state, chunk.allocated_from)?;
+                if chunk.freed_from != 0 {
+                    writeln!(w, "freed from: {:#x}", chunk.freed_from)?;
+                }
+            }
+        }
+        if let LinuxEmuError::DataRace(race) = stop {
+            let access = |is_write| if is_write { "write" } else { "read" };
+            writeln!(w, "race: {} of {} bytes by thread {} at {:#x}", access(race.is_write),
+                race.size, race.tid, race.pc)?;
+            writeln!(w, "previous access: {} by thread {} at {:#x}",
+                access(race.previous_is_write), race.previous_tid, race.previous_pc)?;
+        }
+        if let LinuxEmuError::UninitRead(read) = stop {
+            match read.region {
+                Some(region) => {
+                    let origin = match region.origin {
+                        UninitOrigin::StackFrame { pc } => format!("stack frame allocated at {:#x}", pc),
+                        UninitOrigin::Brk { pc } => format!("brk at {:#x}", pc),
+                        UninitOrigin::Mmap { pc } => format!("mmap at {:#x}", pc),
+                        UninitOrigin::HeapAlloc { pc } => format!("heap allocation from {:#x}", pc),
+                    };
+                    writeln!(w, "uninitialized: {:#x}-{:#x} from {}", region.start, region.end, origin)?;
+                }
+                None => writeln!(w, "uninitialized: unknown origin")?,
+            }
+        }
+        writeln!(w, "\nbacktrace:")?;
+        write!(w, "{}", emu.symbols.symbolize(&self.frames))?;
+        writeln!(w, "\nregisters:")?;
+        emu.core.write_debug(w)?;
+        writeln!(w, "\nmemory map:")?;
+        mem.write_vmmap(w)?;
+        writeln!(w, "\ninput ({} bytes):", input.len())?;
+        write_hexdump(w, input)
+    }
+}
+
+/// Refine the `kind` of a fault at `address` with what the heap sanitizer
+/// knows about it
+fn heap_kind(sanitizer: &HeapSanitizer, kind: CrashKind, address: u64) -> CrashKind {
+    let bug = match kind {
+        CrashKind::ReadFault | CrashKind::WriteFault => match sanitizer.classify(address) {
+            Some((bug, _)) => bug,
+            None => return kind,
+        },
+        CrashKind::InvalidFree => sanitizer.classify_free(address),
+        _ => return kind,
+    };
+    match bug {
+        HeapBug::Overflow => CrashKind::HeapOverflow,
+        HeapBug::UseAfterFree => CrashKind::UseAfterFree,
+        HeapBug::DoubleFree => CrashKind::DoubleFree,
+        HeapBug::InvalidFree => CrashKind::InvalidFree,
+    }
+}
+
+fn fault_address(error: &MmuError) -> Option<u64> {
+    match error {
+        MmuError::OutOfBound { virtual_address, .. }
+        | MmuError::PermissionsFault { virtual_address, .. }
+        | MmuError::SegmentNotFound { virtual_address }
+        | MmuError::CStrTooLong { virtual_address }
+        | MmuError::AddressInUse { virtual_address, .. }
+        | MmuError::InvalidFree(virtual_address) => Some(virtual_address.0 as u64),
+        _ => None,
+    }
+}
+
+/// The instruction at `pc`, if it can be fetched
+fn instruction(mem: &Mmu, pc: u64) -> Option<u32> {
+    let (base, segment) = &mem.segments[mem.lookup_segment(VirtAddr(pc as usize))?];
+    let offset = pc as usize - base.0;
+    let executable = |offset: usize| segment.permissions.get(offset)
+        .is_some_and(|perm| perm.is_superset_of(PermField::Read | PermField::Executable));
+    let half = |offset: usize| executable(offset) && executable(offset + 1);
+    if !half(offset) {
+        return None;
+    }
+    let low = u16::from_le_bytes([segment.memory[offset], segment.memory[offset + 1]]) as u32;
+    if low & 0b11 != 0b11 {
+        return Some(low);
+    }
+    if !half(offset + 2) {
+        return None;
+    }
+    let high = u16::from_le_bytes([segment.memory[offset + 2], segment.memory[offset + 3]]) as u32;
+    Some(low | (high << 16))
+}
+
+/// If `inst` writes to memory
+fn is_store(inst: u32) -> bool {
+    match inst & 0b11 {
+        0b11 => match inst & 0x7f {
+            // store and float store
+            0b010_0011 | 0b010_0111 => true,
+            // all the atomics but the load reserved
+            0b010_1111 => inst >> 27 != 0b00010,
+            _ => false,
+        },
+        // c.fsd, c.sw, c.sd and their sp relative versions
+        _ => (inst >> 13) & 0b111 >= 0b101 && inst & 0b11 != 0b01,
+    }
+}
+
+/// Write `bytes` as lines of 16 hex bytes followed by their ASCII
+fn write_hexdump(w: &mut String, bytes: &[u8]) -> core::fmt::Result {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        write!(w, "{:08x}:", line * 16)?;
+        for byte in chunk {
+            write!(w, " {:02x}", byte)?;
+        }
+        write!(w, "{:1$}  ", "", 3 * (16 - chunk.len()))?;
+        for byte in chunk {
+            w.push(if byte.is_ascii_graphic() || *byte == b' ' { *byte as char } else { '.' });
+        }
+        w.push('\n');
+    }
+    Ok(())
+}
+
+/// 64 bits FNV-1a, whose values don't change between versions of Rust
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Helpers shared by the integration tests, they can't use the ones of
+//! `emu` which aren't part of its library
+#![allow(dead_code)]
+use emu::riscv64gc::*;
+use mmu::{Mmu, Perm, VirtAddr};
+
+pub const CODE: u64 = 0x1_0000;
+pub const DATA: u64 = 0x2_0000;
+
+/// A [`Mmu`] with `code` alone in the pages at [`CODE`] with the
+/// permissions `perm`
+pub fn mmu_with_code(code: &[u8], perm: Perm) -> Mmu {
+    let mut mem = Mmu::new();
+    let size = code.len().next_multiple_of(0x1000);
+    mem.allocate_segment(Some(VirtAddr(CODE as usize)), size, perm).unwrap();
+    unsafe{mem.write_from_slice(VirtAddr(CODE as usize), code)}.unwrap();
+    mem
+}
+
+/// A process about to run the instructions `code`, mapped by
+/// [`mmu_with_code`]
+pub fn emu_with_code(code: &[u32], perm: Perm) -> LinuxEmu {
+    let code: Vec<u8> = code.iter().copied().flat_map(u32::to_le_bytes).collect();
+    let mut emu = LinuxEmu::new(mmu_with_code(&code, perm));
+    emu.core.pc = CODE;
+    emu
+}
@@ -0,0 +1,322 @@
+//! Tests of the fuzzer on small programs that crash on the input `FUZ` and
+//! hang on the inputs starting with `H`
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use fuzzer::*;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+/// Where the programs reading a file find its path
+const PATH: u64 = DATA + 0x400;
+
+/// A program that gets its input with `prologue`, which has to leave the
+/// input in `a0` and its length in `a1`
+fn program(prologue: &[u32]) -> LinuxEmu {
+    // offset of the instruction `to` of the check from the one `from`
+    let to = |from: i32, to: i32| (to - from) * 4;
+    let (hang, exit) = (14, 15);
+    let check = [
+        AssemblerRV64GC.addi(T2, Zero, 3).unwrap(),
+        AssemblerRV64GC.bltu(A1, T2, to(1, exit)).unwrap(),
+        AssemblerRV64GC.lbu(T0, A0, 0).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'H' as i32).unwrap(),
+        AssemblerRV64GC.beq(T0, T1, to(4, hang)).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'F' as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, to(6, exit)).unwrap(),
+        AssemblerRV64GC.lbu(T0, A0, 1).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'U' as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, to(9, exit)).unwrap(),
+        AssemblerRV64GC.lbu(T0, A0, 2).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'Z' as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, to(12, exit)).unwrap(),
+        // crash
+        AssemblerRV64GC.sd(Zero, Zero, 0).unwrap(),
+        // hang
+        AssemblerRV64GC.jal(Zero, 0).unwrap(),
+        // exit(0)
+        AssemblerRV64GC.addi(A0, Zero, 0).unwrap(),
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ];
+    assert_eq!(check.len() as i32, exit + 3);
+    load(&[prologue, &check].concat())
+}
+
+/// Load `code` in an emulator with a data segment
+fn load(code: &[u32]) -> LinuxEmu {
+    let mut emu = emu_with_code(code, PermField::Read | PermField::Executable);
+    emu.core.mem.allocate_segment(
+        Some(VirtAddr(DATA as usize)), 0x1000,
+        PermField::Read | PermField::Write,
+    ).unwrap();
+    unsafe{emu.core.mem.write_from_slice(VirtAddr(PATH as usize), b"/input\0")}.unwrap();
+    emu
+}
+
+/// pc of the instruction of the check at index `idx`
+fn check_pc(prologue: &[u32], idx: u64) -> u64 {
+    CODE + (prologue.len() as u64 + idx) * 4
+}
+
+/// `read(fd, DATA, 64)` and set the registers for the check
+fn read_prologue(fd: Option<i64>) -> Vec<u32> {
+    let mut prologue = Vec::new();
+    // otherwise the fd is the one returned by openat
+    if let Some(fd) = fd {
+        prologue.push(AssemblerRV64GC.addi(A0, Zero, fd as i32).unwrap());
+    }
+    prologue.extend([
+        AssemblerRV64GC.lui(A1, DATA as u32).unwrap(),
+        AssemblerRV64GC.addi(A2, Zero, 64).unwrap(),
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::read as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+        AssemblerRV64GC.addi(A1, A0, 0).unwrap(),
+        AssemblerRV64GC.lui(A0, DATA as u32).unwrap(),
+    ]);
+    prologue
+}
+
+fn config() -> FuzzerConfig {
+    FuzzerConfig {
+        instruction_budget: 10_000,
+        stats_interval: None,
+        max_cases: Some(200_000),
+        stop_on_crash: true,
+        ..Default::default()
+    }
+}
+
+/// The first case of the bucket of the `kind` stops at `pc`
+fn find(fuzzer: &Fuzzer, kind: CrashKind, pc: u64) -> Option<(Bucket, Crash)> {
+    fuzzer.crashes.buckets().into_iter()
+        .find(|(bucket, crash)| bucket.kind == kind && crash.triage.pc == pc)
+}
+
+/// Check that the fuzzer found the crash of `program`
+fn assert_crash(fuzzer: &Fuzzer, prologue: &[u32]) {
+    let (_, crash) = find(fuzzer, CrashKind::WriteFault, check_pc(prologue, 13))
+        .expect("the crash was not found");
+    assert_eq!(&crash.input[..3], b"FUZ");
+    assert_eq!(crash.triage.fault_address, Some(0));
+    assert!(fuzzer.stats().cases < 200_000);
+}
+
+#[test]
+fn test_memory_delivery() {
+    let output = std::env::temp_dir().join(format!("fuzzer_test_{}", std::process::id()));
+    let mut emu = program(&[]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig {
+        threads: 4,
+        output: Some(output.clone()),
+        ..config()
+    });
+    let stats = fuzzer.run();
+    assert_crash(&fuzzer, &[]);
+    assert!(stats.corpus >= 4);
+    assert!(stats.edges >= 6);
+
+    // the first input of the bucket was saved
+    let (bucket, crash) = find(&fuzzer, CrashKind::WriteFault, check_pc(&[], 13)).unwrap();
+    let path = output.join("crashes").join(bucket.to_string());
+    assert_eq!(std::fs::read(&path).unwrap(), crash.input);
+    let report = std::fs::read_to_string(path.with_extension("txt")).unwrap();
+    assert_eq!(report, crash.report);
+    assert!(report.starts_with(&format!("seed: 0\niteration: {}\n", crash.iteration)));
+    std::fs::remove_dir_all(output).unwrap();
+}
+
+#[test]
+fn test_stdin_delivery() {
+    let prologue = read_prologue(Some(0));
+    let mut fuzzer = Fuzzer::new(program(&prologue), InputDelivery::Stdin, config());
+    fuzzer.run();
+    assert_crash(&fuzzer, &prologue);
+}
+
+#[test]
+fn test_file_delivery() {
+    let mut prologue: Vec<u32> = [
+        AssemblerRV64GC.addi(A0, Zero, AT_FDCWD).unwrap(),
+        AssemblerRV64GC.lui(A1, DATA as u32).unwrap(),
+        AssemblerRV64GC.addi(A1, A1, (PATH - DATA) as i32).unwrap(),
+        AssemblerRV64GC.addi(A2, Zero, 0).unwrap(),
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::openat as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ].to_vec();
+    prologue.extend(read_prologue(None));
+    let delivery = InputDelivery::File(b"/input".to_vec());
+    let mut fuzzer = Fuzzer::new(program(&prologue), delivery, config());
+    fuzzer.run();
+    assert_crash(&fuzzer, &prologue);
+}
+
+#[test]
+fn test_hangs_and_stats() {
+    let mut emu = program(&[]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig {
+        max_cases: Some(5000),
+        stop_on_crash: false,
+        ..config()
+    });
+    fuzzer.add_input(b"abc".to_vec());
+    let stats = fuzzer.run();
+    assert_eq!(stats.cases, 5000);
+    assert!(stats.hangs > 0);
+    assert!(stats.instructions >= stats.hangs * 10_000);
+    let (_, hang) = find(&fuzzer, CrashKind::Timeout, check_pc(&[], 14)).unwrap();
+    assert_eq!(hang.input[0], b'H');
+
+    // the cases can be replayed
+    let input = hang.input;
+    let mut emu = fuzzer.pristine().fork();
+    assert!(matches!(fuzzer.run_case(&mut emu, &input), LinuxEmuError::Timeout));
+    assert!(matches!(fuzzer.run_case(&mut emu, b"abc"), LinuxEmuError::Exit(0)));
+}
+
+#[test]
+fn test_illegal_instruction() {
+    // the inputs starting with `F` execute the all-zero word
+    let mut emu = load(&[
+        AssemblerRV64GC.lbu(T0, A0, 0).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'F' as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, 8).unwrap(),
+        0,
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: None };
+    let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig { threads: 2, ..config() });
+    fuzzer.run();
+    let (_, crash) = find(&fuzzer, CrashKind::IllegalInstruction, CODE + 12)
+        .expect("the crash was not found");
+    assert_eq!(crash.input[0], b'F');
+}
+
+#[test]
+fn test_dictionary_from_cmps() {
+    // crash if the input starts with the 32 bits magic
+    let magic: u32 = 0x1337_c0de;
+    let mut emu = load(&[
+        AssemblerRV64GC.addi(T2, Zero, 4).unwrap(),
+        AssemblerRV64GC.bltu(A1, T2, 24).unwrap(),
+        AssemblerRV64GC.lw(T0, A0, 0).unwrap(),
+        AssemblerRV64GC.lui(T1, magic & !0xfff).unwrap(),
+        AssemblerRV64GC.addi(T1, T1, (magic & 0xfff) as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, 8).unwrap(),
+        AssemblerRV64GC.sd(Zero, Zero, 0).unwrap(),
+        AssemblerRV64GC.addi(A0, Zero, 0).unwrap(),
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut fuzzer = Fuzzer::new(emu, delivery, config());
+    fuzzer.run();
+    let (_, crash) = find(&fuzzer, CrashKind::WriteFault, CODE + 6 * 4).unwrap();
+    assert_eq!(crash.input[..4], magic.to_le_bytes());
+    assert!(fuzzer.dictionary.tokens().iter().any(|token| **token == magic.to_le_bytes()));
+}
+
+#[test]
+fn test_coverage_of_crashes() {
+    // the inputs starting with `A` crash unless the second byte is zero,
+    // the ones that don't reach nothing more than the crashing ones
+    let mut emu = load(&[
+        AssemblerRV64GC.lbu(T0, A0, 0).unwrap(),
+        AssemblerRV64GC.addi(T1, Zero, b'A' as i32).unwrap(),
+        AssemblerRV64GC.bne(T0, T1, 20).unwrap(),
+        AssemblerRV64GC.lbu(T0, A0, 1).unwrap(),
+        AssemblerRV64GC.slli(T0, T0, 20).unwrap(),
+        AssemblerRV64GC.add(T0, T0, A0).unwrap(),
+        AssemblerRV64GC.lbu(T1, T0, 0).unwrap(),
+        AssemblerRV64GC.addi(A0, Zero, 0).unwrap(),
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig {
+        seed: 1,
+        max_cases: Some(2000),
+        stop_on_crash: false,
+        ..config()
+    });
+    fuzzer.add_input(b"AB".to_vec());
+    fuzzer.run();
+    assert!(find(&fuzzer, CrashKind::ReadFault, CODE + 6 * 4).is_some());
+    // the edges first reached by a crash are still new for the others
+    assert!(fuzzer.corpus.inputs().iter().any(|input| {
+        input.first() == Some(&b'A') && input.get(1).is_none_or(|byte| *byte == 0)
+    }));
+}
+
+#[test]
+fn test_reproducible_cases() {
+    let mut emu = program(&[]);
+    emu.core.write_reg(A0, DATA);
+    let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig {
+        threads: 2,
+        ..config()
+    });
+    fuzzer.run();
+    // the crashes are generated again after the corpus and the dictionary grew
+    fuzzer.add_input(b"FUZZ".to_vec());
+    fuzzer.dictionary.add(b"ZUF");
+    let mut input = Vec::new();
+    for (_, crash) in fuzzer.crashes.buckets() {
+        assert!(crash.corpus_len < fuzzer.corpus.len());
+        assert!(crash.dictionary_len < fuzzer.dictionary.len());
+        fuzzer.generate(crash.iteration, crash.corpus_len, crash.dictionary_len, &mut input);
+        assert_eq!(input, crash.input);
+    }
+    assert!(!fuzzer.crashes.is_empty());
+
+    // the whole run is reproducible with a single worker
+    let run = || {
+        let mut emu = program(&[]);
+        emu.core.write_reg(A0, DATA);
+        let delivery = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+        let mut fuzzer = Fuzzer::new(emu, delivery, FuzzerConfig {
+            seed: 42,
+            ..config()
+        });
+        fuzzer.run();
+        let (bucket, crash) = find(&fuzzer, CrashKind::WriteFault, check_pc(&[], 13)).unwrap();
+        (bucket, crash.iteration, crash.input, fuzzer.corpus.inputs())
+    };
+    assert_eq!(run(), run());
+}
+
+#[test]
+fn test_taint() {
+    let prologue = read_prologue(Some(0));
+    let memory = InputDelivery::Memory { addr: DATA, max_len: 16, len_reg: Some(A1) };
+    let mut emu = program(&[]);
+    emu.core.write_reg(A0, DATA);
+    for (emu, delivery, prologue) in [
+        (program(&prologue), InputDelivery::Stdin, &prologue[..]),
+        (emu, memory, &[][..]),
+    ] {
+        let fuzzer = Fuzzer::new(emu, delivery, config());
+        let taint = fuzzer.taint(b"FUZZ");
+        // each byte of the input controls its own comparison
+        for (idx, offset) in [(4, 0), (6, 0), (9, 1), (12, 2)] {
+            let (lhs, rhs) = taint.comparison(check_pc(prologue, idx)).unwrap();
+            assert_eq!(taint.offsets(lhs), [offset]);
+            assert!(rhs.is_clean());
+        }
+        // the length is not tracked
+        assert_eq!(taint.comparison(check_pc(prologue, 1)), None);
+        assert_eq!(taint.comparisons().count(), 4);
+        // the workers don't track it
+        assert!(fuzzer.pristine().core.taint.is_none());
+    }
+}
@@ -0,0 +1,172 @@
+//! Tests of the single mutations and of the dictionary
+use fuzzer::*;
+use emu::riscv64gc::CmpOperands;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+const INPUT: &[u8] = b"0123456789abcdef";
+
+/// Apply `mutation` to [`INPUT`] with many seeds and check every result
+fn check(mutation: Mutation, corpus: &Corpus, dictionary: &Dictionary,
+    check: impl Fn(&[u8])) {
+    for seed in 0..1000 {
+        let mut mutator = Mutator {
+            rng: Rng::new(seed),
+            max_len: 24,
+            corpus,
+            corpus_len: corpus.len(),
+            dictionary,
+            dictionary_len: dictionary.len(),
+        };
+        let mut input = INPUT.to_vec();
+        assert!(mutator.apply(mutation, &mut input), "{:?}", mutation);
+        assert!(input.len() <= 24);
+        check(&input);
+    }
+}
+
+/// Number of bits different between two inputs of the same length
+fn bit_distance(a: &[u8], b: &[u8]) -> u32 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).map(|(a, b)| (a ^ b).count_ones()).sum()
+}
+
+#[test]
+fn test_mutations() {
+    let corpus = Corpus::default();
+    let dictionary = Dictionary::default();
+
+    check(Mutation::FlipBit, &corpus, &dictionary, |input| {
+        assert_eq!(bit_distance(input, INPUT), 1);
+    });
+    check(Mutation::FlipByte, &corpus, &dictionary, |input| {
+        assert_eq!(bit_distance(input, INPUT), 8);
+    });
+    check(Mutation::RandomByte, &corpus, &dictionary, |input| {
+        let changed = input.iter().zip(INPUT).filter(|(a, b)| a != b).count();
+        assert_eq!(changed, 1);
+    });
+    check(Mutation::Arithmetic, &corpus, &dictionary, |input| {
+        assert_eq!(input.len(), INPUT.len());
+        assert_ne!(input, INPUT);
+    });
+    check(Mutation::Interesting, &corpus, &dictionary, |input| {
+        assert_eq!(input.len(), INPUT.len());
+    });
+    check(Mutation::InsertBlock, &corpus, &dictionary, |input| {
+        assert!(input.len() > INPUT.len());
+    });
+    check(Mutation::DeleteBlock, &corpus, &dictionary, |input| {
+        assert!(input.len() < INPUT.len());
+        // what's left is a prefix and a suffix of the input
+        let prefix = input.iter().zip(INPUT).take_while(|(a, b)| a == b).count();
+        assert!(INPUT.ends_with(&input[prefix..]));
+    });
+    check(Mutation::DuplicateBlock, &corpus, &dictionary, |input| {
+        assert!(input.len() > INPUT.len());
+    });
+
+    // the mutations that need the corpus or the dictionary
+    let mut mutator = Mutator {
+        rng: Rng::new(0),
+        max_len: 24,
+        corpus: &corpus,
+        corpus_len: corpus.len(),
+        dictionary: &dictionary,
+        dictionary_len: dictionary.len(),
+    };
+    for mutation in [Mutation::Splice, Mutation::InsertToken, Mutation::OverwriteToken] {
+        assert!(!mutator.apply(mutation, &mut INPUT.to_vec()));
+    }
+    assert!(!mutator.apply(Mutation::FlipBit, &mut Vec::new()));
+
+    corpus.push(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ".to_vec());
+    dictionary.add(b"TOKEN");
+    // only the inputs and tokens within the lengths are used
+    for mutation in [Mutation::Splice, Mutation::InsertToken, Mutation::OverwriteToken] {
+        assert!(!mutator.apply(mutation, &mut INPUT.to_vec()));
+    }
+    check(Mutation::Splice, &corpus, &dictionary, |input| {
+        let prefix = input.iter().zip(INPUT).take_while(|(a, b)| a == b).count();
+        let tail = &input[prefix..];
+        assert!(tail.iter().all(u8::is_ascii_uppercase));
+    });
+    check(Mutation::InsertToken, &corpus, &dictionary, |input| {
+        assert_eq!(input.len(), INPUT.len() + 5);
+        assert!(input.windows(5).any(|window| window == b"TOKEN"));
+    });
+    check(Mutation::OverwriteToken, &corpus, &dictionary, |input| {
+        assert_eq!(input.len(), INPUT.len());
+        assert!(input.windows(5).any(|window| window == b"TOKEN"));
+    });
+}
+
+#[test]
+fn test_mutate() {
+    let corpus = Corpus::default();
+    let dictionary = Dictionary::default();
+    let mutate = |seed| {
+        let mut input = INPUT.to_vec();
+        Mutator {
+            rng: Rng::for_case(seed, 7),
+            max_len: 20,
+            corpus: &corpus,
+            corpus_len: corpus.len(),
+            dictionary: &dictionary,
+            dictionary_len: dictionary.len(),
+        }.mutate(&mut input);
+        input
+    };
+    let mut different = 0;
+    for seed in 0..100 {
+        let input = mutate(seed);
+        assert!(input.len() <= 20);
+        assert_eq!(input, mutate(seed));
+        different += (input != INPUT) as usize;
+    }
+    assert!(different > 90);
+}
+
+#[test]
+fn test_dictionary() {
+    let dictionary = Dictionary::default();
+    assert!(dictionary.add(b"abc"));
+    assert!(!dictionary.add(b"abc"));
+    assert!(!dictionary.add(b""));
+    assert!(dictionary.add(&[b'x'; 100]));
+    assert_eq!(dictionary.get(1).len(), MAX_TOKEN_LEN);
+
+    // the operands are as wide as needed
+    dictionary.add_cmps(&[
+        CmpOperands { pc: 0, lhs: 0, rhs: 0x41 },
+        CmpOperands { pc: 0, lhs: 0x1337_c0de, rhs: 0x100 },
+        CmpOperands { pc: 0, lhs: u64::MAX, rhs: 0x41 },
+    ]);
+    let tokens = dictionary.tokens();
+    let tokens: Vec<&[u8]> = tokens.iter().map(|token| &**token).collect();
+    assert_eq!(tokens[2..], [
+        &[0x41][..],
+        &[0xde, 0xc0, 0x37, 0x13],
+        &[0x00, 0x01],
+        &[0xff; 8],
+    ]);
+}
+
+#[test]
+fn test_strings() {
+    let mut mem = mmu_with_code(b"\x01/etc/passwd\0ab\0%s: %d\n", PermField::Read.into());
+    // unreadable, e.g. a guard page
+    mem.allocate_segment(
+        Some(VirtAddr(DATA as usize)), 0x100,
+        PermField::None.into(),
+    ).unwrap();
+    unsafe{mem.write_from_slice(VirtAddr(DATA as usize), b"secret")}.unwrap();
+
+    let dictionary = Dictionary::default();
+    dictionary.add_strings(&mem);
+    let tokens = dictionary.tokens();
+    let tokens: Vec<&[u8]> = tokens.iter().map(|token| &**token).collect();
+    assert_eq!(tokens, [&b"/etc/passwd"[..], b"%s: %d"]);
+}
@@ -0,0 +1,210 @@
+//! Tests of the classification and of the reports of the crashes
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use fuzzer::*;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+const RODATA: u64 = 0x3_0000;
+/// Readable only after it's written
+const HEAP: u64 = 0x4_0000;
+
+/// Run `code`, with `t0` pointing to `DATA`, and triage its stop
+fn triage(code: &[u32]) -> (LinuxEmu, LinuxEmuError, Option<Triage>) {
+    triage_with(code, |_| {})
+}
+
+/// [`triage`] with `setup` applied to the process before it runs
+fn triage_with(code: &[u32], setup: impl FnOnce(&mut LinuxEmu))
+    -> (LinuxEmu, LinuxEmuError, Option<Triage>) {
+    let mut emu = emu_with_code(code, PermField::Read | PermField::Executable);
+    let mem = &mut emu.core.mem;
+    mem.allocate_segment(
+        Some(VirtAddr(DATA as usize)), 0x1000,
+        PermField::Read | PermField::Write,
+    ).unwrap();
+    mem.allocate_segment(Some(VirtAddr(RODATA as usize)), 0x1000, PermField::Read.into()).unwrap();
+    mem.allocate_segment(
+        Some(VirtAddr(HEAP as usize)), 0x1000,
+        PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    emu.core.write_reg(T0, DATA);
+    emu.core.call_stack = Some(CallStack::default());
+    emu.core.set_instruction_budget(1000);
+    setup(&mut emu);
+    let stop = emu.run();
+    let triage = Triage::new(&emu, &stop);
+    (emu, stop, triage)
+}
+
+fn kind(code: &[u32]) -> (CrashKind, Option<u64>) {
+    let triage = triage(code).2.unwrap();
+    (triage.kind, triage.fault_address)
+}
+
+#[test]
+fn test_kinds() {
+    let exit = [
+        AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit as i32).unwrap(),
+        AssemblerRV64GC.ecall().unwrap(),
+    ];
+    assert!(triage(&exit).2.is_none());
+
+    assert_eq!(kind(&[
+        AssemblerRV64GC.ld(T1, Zero, 8).unwrap(),
+    ]), (CrashKind::ReadFault, Some(8)));
+    assert_eq!(kind(&[
+        AssemblerRV64GC.sw(Zero, Zero, 16).unwrap(),
+    ]), (CrashKind::WriteFault, Some(16)));
+    assert_eq!(kind(&[
+        AssemblerRV64GC.lui(T1, RODATA as u32).unwrap(),
+        AssemblerRV64GC.sb(T1, Zero, 3).unwrap(),
+    ]), (CrashKind::WriteFault, Some(RODATA + 3)));
+    // past the end of the segment
+    assert_eq!(kind(&[
+        AssemblerRV64GC.addi(T1, T0, 0x7fc).unwrap(),
+        AssemblerRV64GC.ld(T2, T1, 0x7fe).unwrap(),
+    ]), (CrashKind::ReadFault, Some(DATA + 0xffa)));
+    assert_eq!(kind(&[
+        AssemblerRV64GC.lui(T1, HEAP as u32).unwrap(),
+        AssemblerRV64GC.sd(T1, Zero, 0).unwrap(),
+        AssemblerRV64GC.ld(T2, T1, 0).unwrap(),
+        AssemblerRV64GC.ld(T2, T1, 8).unwrap(),
+    ]), (CrashKind::UninitRead, Some(HEAP + 8)));
+
+    // jump to data and to unmapped memory
+    assert_eq!(kind(&[
+        AssemblerRV64GC.jalr(Ra, T0, 0x10).unwrap(),
+    ]), (CrashKind::ExecFault, Some(DATA + 0x10)));
+    assert_eq!(kind(&[
+        AssemblerRV64GC.jalr(Zero, Zero, 0x100).unwrap(),
+    ]), (CrashKind::ExecFault, Some(0x100)));
+
+    assert_eq!(kind(&[
+        AssemblerRV64GC.jal(Zero, 0).unwrap(),
+    ]), (CrashKind::Timeout, None));
+    assert_eq!(kind(&[
+        AssemblerRV64GC.ebreak().unwrap(),
+    ]), (CrashKind::Breakpoint, None));
+    assert_eq!(CrashKind::Timeout.outcome(), Outcome::Hang);
+    assert_eq!(CrashKind::ExecFault.outcome(), Outcome::Crash);
+}
+
+#[test]
+fn test_heap_kinds() {
+    // `t1` points to a live chunk of 8 bytes and `t2` to a freed one
+    let heap = |code: &[u32]| {
+        let (emu, stop, triage) = triage_with(code, |emu| {
+            let mut sanitizer = HeapSanitizer::default();
+            sanitizer.map(&mut emu.core.mem).unwrap();
+            let live = sanitizer.malloc(&mut emu.core.mem, 8, CODE);
+            let freed = sanitizer.malloc(&mut emu.core.mem, 8, CODE);
+            sanitizer.free(&mut emu.core.mem, freed, CODE + 4).unwrap();
+            emu.core.write_reg(T1, live);
+            emu.core.write_reg(T2, freed);
+            emu.heap_sanitizer = Some(sanitizer);
+        });
+        let triage = triage.unwrap();
+        let report = triage.report(&emu, &stop, b"");
+        (triage.kind, report)
+    };
+
+    let (kind, report) = heap(&[AssemblerRV64GC.sb(T1, Zero, 8).unwrap()]);
+    assert_eq!(kind, CrashKind::HeapOverflow);
+    assert!(report.contains("kind: heap_overflow"));
+    assert!(report.contains(&format!("allocated from {:#x}", CODE)));
+    let (kind, report) = heap(&[AssemblerRV64GC.ld(T0, T2, 0).unwrap()]);
+    assert_eq!(kind, CrashKind::UseAfterFree);
+    assert!(report.contains(&format!("freed from: {:#x}", CODE + 4)));
+    // the accesses out of the heap are left as they are
+    assert_eq!(heap(&[AssemblerRV64GC.ld(T0, Zero, 8).unwrap()]).0, CrashKind::ReadFault);
+}
+
+#[test]
+fn test_buckets() {
+    let load = AssemblerRV64GC.ld(T1, Zero, 0).unwrap();
+    let nop = AssemblerRV64GC.addi(Zero, Zero, 0).unwrap();
+    // same instruction, different register values
+    let first = triage(&[AssemblerRV64GC.addi(T2, Zero, 1).unwrap(), load]).2.unwrap();
+    let second = triage(&[AssemblerRV64GC.addi(T2, Zero, 2).unwrap(), load]).2.unwrap();
+    assert_eq!(first.bucket, second.bucket);
+    // different pc
+    let third = triage(&[nop, nop, load]).2.unwrap();
+    assert_eq!(first.kind, third.kind);
+    assert_ne!(first.bucket, third.bucket);
+    // different caller
+    let fourth = triage(&[AssemblerRV64GC.jal(Ra, 4).unwrap(), load]).2.unwrap();
+    assert_eq!(fourth.pc, first.pc);
+    assert_eq!(fourth.frames, [CODE + 4, CODE]);
+    assert_ne!(fourth.bucket, first.bucket);
+
+    assert_eq!(first.bucket.to_string(), format!("read_fault_{:016x}", first.bucket.hash));
+}
+
+#[test]
+fn test_report() {
+    let (emu, stop, triage) = triage(&[
+        AssemblerRV64GC.addi(A0, Zero, 0x123).unwrap(),
+        AssemblerRV64GC.lui(T1, RODATA as u32).unwrap(),
+        AssemblerRV64GC.sd(T1, A0, 0x10).unwrap(),
+    ]);
+    let report = triage.unwrap().report(&emu, &stop, b"0123456789abcdef\x00\xff");
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(lines[0].starts_with("bucket: write_fault_"));
+    assert_eq!(lines[1], "kind: write_fault");
+    assert_eq!(lines[3], "pc: 0x10008");
+    assert_eq!(lines[4], "fault address: 0x30010");
+    assert_eq!(lines[5], "segment: 0000000000030000-0000000000030ff8 - 0x001000 - Read");
+    assert!(lines.contains(&"#0   0000000000010008"));
+    assert!(report.contains("A0:              123"));
+    assert!(report.contains("0000000000020000-0000000000020ff8 - 0x001000 - Read | Write"));
+    assert!(report.ends_with(concat!(
+        "input (18 bytes):\n",
+        "00000000: 30 31 32 33 34 35 36 37 38 39 61 62 63 64 65 66  0123456789abcdef\n",
+        "00000010: 00 ff                                            ..\n",
+    )));
+}
+
+#[test]
+fn test_uninit_report() {
+    let code = [
+        AssemblerRV64GC.lui(T1, HEAP as u32).unwrap(),
+        AssemblerRV64GC.ld(A0, T1, 0x18).unwrap(),
+    ];
+    let (emu, stop, triage) = triage_with(&code, |emu| {
+        let mut uninit = UninitTracker::default();
+        uninit.record(HEAP + 0x10, HEAP + 0x20, UninitOrigin::Mmap { pc: 0x1234 });
+        emu.core.uninit = Some(uninit);
+    });
+    let triage = triage.unwrap();
+    assert_eq!((triage.kind, triage.fault_address), (CrashKind::UninitRead, Some(HEAP + 0x18)));
+    let report = triage.report(&emu, &stop, b"");
+    assert!(report.contains("uninitialized: 0x40010-0x40020 from mmap at 0x1234\n"));
+
+    // the reads outside of the regions recorded
+    let (emu, stop, triage) = triage_with(&code, |emu| {
+        emu.core.uninit = Some(UninitTracker::default());
+    });
+    let report = triage.unwrap().report(&emu, &stop, b"");
+    assert!(report.contains("uninitialized: unknown origin\n"));
+}
+
+#[test]
+fn test_report_empty_segments() {
+    let (emu, stop, triage) = triage_with(&[
+        AssemblerRV64GC.lui(T1, RODATA as u32).unwrap(),
+        AssemblerRV64GC.sd(T1, Zero, 0).unwrap(),
+    ], |emu| {
+        let (brk_idx, _) = emu.core.mem.allocate_segment(
+            Some(VirtAddr(0x5_0000)), 0, PermField::Write | PermField::ReadAfterWrite,
+        ).unwrap();
+        emu.core.mem.brk_idx = brk_idx;
+        emu.core.mem.brk(VirtAddr(0x5_0003)).unwrap();
+        emu.core.mem.allocate_segment(Some(VirtAddr(0x6_0000)), 0, PermField::Read.into()).unwrap();
+    });
+    let report = triage.unwrap().report(&emu, &stop, b"");
+    assert!(report.contains("0000000000050000-0000000000050000 - 0x000003 - Write | ReadAfterWrite\n"));
+    assert!(report.contains("0000000000060000-0000000000060000 - 0x000000 - empty\n"));
+}
@@ -5,23 +5,23 @@ pub struct AssemblerRV64GC;
 impl AssemblerRV64GC {
     #[inline]
     pub fn lui(&mut self, rd: Register, imm: u32) -> Result<u32, &str> {
-        if imm.extract_bitfield(0, 13) != 0 {
+        if imm.extract_bitfield(0, 12) != 0 {
             return Err("the lower 12 bits of the LUI offset have to be zero!");
         }
         Ok(Utype{
             opcode: 0b0110111,
-            imm,
+            imm: imm >> 12,
             rd: rd.into(),
         }.into())
     }
     #[inline]
     pub fn auipc(&mut self, rd: Register, imm: u32) -> Result<u32, &str> {
-        if imm.extract_bitfield(0, 13) != 0 {
+        if imm.extract_bitfield(0, 12) != 0 {
             return Err("the lower 12 bits of the AUIPC offset have to be zero!");
         }
         Ok(Utype{
             opcode: 0b0010111,
-            imm,
+            imm: imm >> 12,
             rd: rd.into(),
         }.into())
     }
@@ -87,18 +87,42 @@ impl AssemblerRV64GC {
     }
     #[inline]
     pub fn slli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..64).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 64)!");
+        }
+        Ok(Itype{
+            opcode: 0b0010011,
+            funct3: 0b001,
+            imm: shamt,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn srli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..64).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 64)!");
+        }
+        Ok(Itype{
+            opcode: 0b0010011,
+            funct3: 0b101,
+            imm: shamt,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn srai(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..64).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 64)!");
+        }
+        Ok(Itype{
+            opcode: 0b0010011,
+            funct3: 0b101,
+            imm: shamt | 0b010000_000000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn add(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<u32, &str> {
@@ -211,34 +235,91 @@ impl AssemblerRV64GC {
         }.into())
     }
     #[inline]
-    pub fn csrrw(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrw(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b001,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
-    pub fn csrrs(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrs(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b010,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
-    pub fn csrrc(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrc(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b011,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
-    pub fn csrrwi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrwi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        if zimm >= 32 {
+            return Err("the CSR immediate has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b101,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: zimm as u32,
+        }.into())
     }
     #[inline]
-    pub fn csrrsi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrsi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        if zimm >= 32 {
+            return Err("the CSR immediate has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b110,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: zimm as u32,
+        }.into())
     }
     #[inline]
-    pub fn csrrci(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn csrrci(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<u32, &str> {
+        if csr >= 4096 {
+            return Err("the CSR address has to be in [0, 4096)!");
+        }
+        if zimm >= 32 {
+            return Err("the CSR immediate has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b1110011,
+            funct3: 0b111,
+            imm: csr as i32,
+            rd: rd.into(),
+            rs1: zimm as u32,
+        }.into())
     }
     #[inline]
     pub fn lb(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<u32, &str> {
@@ -440,18 +521,42 @@ impl AssemblerRV64GC {
     }
     #[inline]
     pub fn slliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..32).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b0011011,
+            funct3: 0b001,
+            imm: shamt,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn srliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..32).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b0011011,
+            funct3: 0b101,
+            imm: shamt,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn sraiw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        if !(0..32).contains(&shamt) {
+            return Err("the shift amount has to be in [0, 32)!");
+        }
+        Ok(Itype{
+            opcode: 0b0011011,
+            funct3: 0b101,
+            imm: shamt | 0b0100000_00000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
     pub fn addw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<u32, &str> {
@@ -652,326 +757,950 @@ impl AssemblerRV64GC {
         }.into())
     }
     #[inline]
-    pub fn fmadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
-    }
-    #[inline]
-    pub fn fmsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn lr_w(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b00010, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
-    pub fn fnmsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn sc_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b00011, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fnmadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoswap_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b00001, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoadd_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b00000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoxor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b00100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fmul_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoand_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b01100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fdiv_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b01000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsqrt_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amomin_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b10000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsgnj_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amomax_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b10100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsgnjn_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amominu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b11000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsgnjx_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amomaxu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b010,
+            funct7: atomic_funct7(0b11100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fmin_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn lr_d(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b00010, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
-    pub fn fmax_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn sc_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b00011, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fcvt_w_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoswap_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b00001, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fcvt_wu_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoadd_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b00000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fmv_x_w(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoxor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b00100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn feq_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoand_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b01100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn flt_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amoor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b01000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fle_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amomin_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b10000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fclass_s(&mut self, rd: Register, rs1: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amomax_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b10100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fcvt_s_w(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn amominu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b11000, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn amomaxu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b0101111,
+            funct3: 0b011,
+            funct7: atomic_funct7(0b11100, aq, rl),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fmadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(R4type{
+            opcode: 0b1000011,
+            funct2: 0b00,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fmsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(R4type{
+            opcode: 0b1000111,
+            funct2: 0b00,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fnmsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(R4type{
+            opcode: 0b1001011,
+            funct2: 0b00,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fnmadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(R4type{
+            opcode: 0b1001111,
+            funct2: 0b00,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0000000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fsub_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0000100,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fmul_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0001000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fdiv_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0001100,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fsqrt_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0101100,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
+    }
+    #[inline]
+    pub fn fsgnj_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010000,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fsgnjn_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010000,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fsgnjx_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010000,
+            funct3: 0b010,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fmin_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010100,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fmax_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010100,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fcvt_w_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00000,
+        }.into())
+    }
+    #[inline]
+    pub fn fcvt_wu_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00001,
+        }.into())
+    }
+    #[inline]
+    pub fn fmv_x_w(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1110000,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
+    }
+    #[inline]
+    pub fn feq_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010000,
+            funct3: 0b010,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn flt_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010000,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fle_s(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010000,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
+    }
+    #[inline]
+    pub fn fclass_s(&mut self, rd: Register, rs1: FloatRegister) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1110000,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
+    }
+    #[inline]
+    pub fn fcvt_s_w(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00000,
+        }.into())
     }
     #[inline]
     pub fn fcvt_s_wu(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00001,
+        }.into())
     }
     #[inline]
     pub fn fmv_w_x(&mut self, rd: FloatRegister, rs1: Register) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1111000,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn fmadd_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(R4type{
+            opcode: 0b1000011,
+            funct2: 0b01,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
     }
     #[inline]
     pub fn fmsub_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(R4type{
+            opcode: 0b1000111,
+            funct2: 0b01,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
     }
     #[inline]
     pub fn fnmsub_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(R4type{
+            opcode: 0b1001011,
+            funct2: 0b01,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
     }
     #[inline]
     pub fn fnmadd_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(R4type{
+            opcode: 0b1001111,
+            funct2: 0b01,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            rs3: rs3.into(),
+        }.into())
     }
     #[inline]
     pub fn fadd_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0000001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fsub_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0000101,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fmul_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0001001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fdiv_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0001101,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fsqrt_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0101101,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn fsgnj_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010001,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fsgnjn_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010001,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fsgnjx_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010001,
+            funct3: 0b010,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fmin_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010101,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fmax_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0010101,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
-    pub fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0100000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00001,
+        }.into())
     }
     #[inline]
     pub fn fcvt_d_s(&mut self, rd: FloatRegister, rs1: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b0100001,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00000,
+        }.into())
     }
     #[inline]
     pub fn feq_d(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010001,
+            funct3: 0b010,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn flt_d(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010001,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fle_d(&mut self, rd: Register, rs1: FloatRegister, rs2: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1010001,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+        }.into())
     }
     #[inline]
     pub fn fclass_d(&mut self, rd: Register, rs1: FloatRegister) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1110001,
+            funct3: 0b001,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn fcvt_w_d(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00000,
+        }.into())
     }
     #[inline]
     pub fn fcvt_wu_d(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00001,
+        }.into())
     }
     #[inline]
     pub fn fcvt_d_w(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00000,
+        }.into())
     }
     #[inline]
     pub fn fcvt_d_wu(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00001,
+        }.into())
     }
     #[inline]
-    pub fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: i32) -> Result<u32, &str> {
+        Ok(Itype{
+            opcode: 0b0000111,
+            funct3: 0b010,
+            imm,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsw(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn fsw(&mut self, rs1: Register, rs2: FloatRegister, imm: i32) -> Result<u32, &str> {
+        Ok(Stype{
+            opcode: 0b0100111,
+            funct3: 0b010,
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            imm,
+        }.into())
     }
     #[inline]
-    pub fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn fld(&mut self, rd: FloatRegister, rs1: Register, imm: i32) -> Result<u32, &str> {
+        Ok(Itype{
+            opcode: 0b0000111,
+            funct3: 0b011,
+            imm,
+            rd: rd.into(),
+            rs1: rs1.into(),
+        }.into())
     }
     #[inline]
-    pub fn fsd(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn fsd(&mut self, rs1: Register, rs2: FloatRegister, imm: i32) -> Result<u32, &str> {
+        Ok(Stype{
+            opcode: 0b0100111,
+            funct3: 0b011,
+            rs1: rs1.into(),
+            rs2: rs2.into(),
+            imm,
+        }.into())
     }
     #[inline]
     pub fn fcvt_l_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00010,
+        }.into())
     }
     #[inline]
-    pub fn fcvt_lu_s(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+    pub fn fcvt_lu_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00011,
+        }.into())
     }
     #[inline]
     pub fn fcvt_s_l(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00010,
+        }.into())
     }
     #[inline]
     pub fn fcvt_s_lu(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101000,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00011,
+        }.into())
     }
     #[inline]
     pub fn fcvt_l_d(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00010,
+        }.into())
     }
     #[inline]
     pub fn fcvt_lu_d(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1100001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00011,
+        }.into())
     }
     #[inline]
     pub fn fmv_x_d(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1110001,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn fcvt_d_l(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00010,
+        }.into())
     }
     #[inline]
     pub fn fcvt_d_lu(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1101001,
+            funct3: rm.into(),
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0b00011,
+        }.into())
     }
     #[inline]
     pub fn fmv_d_x(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<u32, &str> {
-        todo!();
-        Ok(4)
+        Ok(Rtype{
+            opcode: 0b1010011,
+            funct7: 0b1111001,
+            funct3: 0b000,
+            rd: rd.into(),
+            rs1: rs1.into(),
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn c_addi4spn(&mut self, rd: Register, uimm: u16) -> Result<u16, &str> {
+        if uimm == 0 || uimm & 0b11 != 0 || uimm >= 1 << 10 {
+            return Err("the C.ADDI4SPN offset has to be a non-zero multiple of 4 less than 1024!");
+        }
         Ok(CIWtype{
             opcode: 0b00,
             funct3: 0b000,
             rd_prime: rd.into_prime(),
-            imm: uimm,
+            imm: uimm.extract_bitfield(3, 4)
+                | (uimm.extract_bitfield(2, 3) << 1)
+                | (uimm.extract_bitfield(6, 10) << 2)
+                | (uimm.extract_bitfield(4, 6) << 6),
         }.into())
     }
     #[inline]
-    pub fn c_fld(&mut self, rd: Register, rs1: Register, uimm: u16) -> Result<u16, &str> {
+    pub fn c_fld(&mut self, rd: FloatRegister, rs1: Register, uimm: u16) -> Result<u16, &str> {
         Ok(CLtype{
             opcode: 0b00,
             funct3: 0b001,
@@ -1005,14 +1734,20 @@ impl AssemblerRV64GC {
     }
     #[inline]
     pub fn c_fsd(&mut self, rs1: Register, rs2: FloatRegister, uimm: u16) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CStype{
+            opcode: 0b00,
+            funct3: 0b101,
+            rs2_prime: rs2.into_prime(),
+            rs1_prime: rs1.into_prime(),
+            imm2: uimm.extract_bitfield(3, 6),
+            imm1: uimm.extract_bitfield(6, 8),
+        }.into())
     }
     #[inline]
     pub fn c_sw(&mut self, rs1: Register, rs2: Register, uimm: u16) -> Result<u16, &str> {
         Ok(CStype{
             opcode: 0b00,
-            funct3: 0b010,
+            funct3: 0b110,
             rs2_prime: rs2.into_prime(),
             rs1_prime: rs1.into_prime(),
             imm2: uimm.extract_bitfield(3, 6),
@@ -1032,158 +1767,298 @@ impl AssemblerRV64GC {
     }
     #[inline]
     pub fn c_addi(&mut self, rd: Register, imm: i8) -> Result<u16, &str> {
+        let imm = imm as u8 as u16;
         Ok(CItype{
-            opcode: 0b00,
+            opcode: 0b01,
             funct3: 0b000,
-            rd_rs1: rd.into_prime(),
-            imm2: imm.extract_bitfield(5, 6) as _,
-            imm1: imm.extract_bitfield(0, 5) as _,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: imm.extract_bitfield(5, 6),
+            imm1: imm.extract_bitfield(0, 5),
         }.into())
     }
     #[inline]
     pub fn c_addiw(&mut self, rd: Register, imm: i8) -> Result<u16, &str> {
+        let imm = imm as u8 as u16;
         Ok(CItype{
-            opcode: 0b00,
+            opcode: 0b01,
             funct3: 0b001,
-            rd_rs1: rd.into_prime(),
-            imm2: imm.extract_bitfield(5, 6) as _,
-            imm1: imm.extract_bitfield(0, 5) as _,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: imm.extract_bitfield(5, 6),
+            imm1: imm.extract_bitfield(0, 5),
         }.into())
     }
     #[inline]
     pub fn c_li(&mut self, rd: Register, imm: i8) -> Result<u16, &str> {
+        let imm = imm as u8 as u16;
         Ok(CItype{
-            opcode: 0b00,
-            funct3: 0b100,
-            rd_rs1: rd.into_prime(),
-            imm2: imm.extract_bitfield(5, 6) as _,
-            imm1: imm.extract_bitfield(0, 5) as _,
+            opcode: 0b01,
+            funct3: 0b010,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: imm.extract_bitfield(5, 6),
+            imm1: imm.extract_bitfield(0, 5),
         }.into())
     }
     #[inline]
     pub fn c_addi16sp(&mut self, imm: i16) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        if imm == 0 || imm & 0b1111 != 0 {
+            return Err("the C.ADDI16SP offset has to be a non-zero multiple of 16!");
+        }
+        let imm = imm as u16;
+        Ok(CItype{
+            opcode: 0b01,
+            funct3: 0b011,
+            rd_rs1: u32::from(Register::Sp) as u16,
+            imm2: imm.extract_bitfield(9, 10),
+            imm1: imm.extract_bitfield(5, 6)
+                | (imm.extract_bitfield(7, 9) << 1)
+                | (imm.extract_bitfield(6, 7) << 3)
+                | (imm.extract_bitfield(4, 5) << 4),
+        }.into())
     }
     #[inline]
     pub fn c_lui(&mut self, rd: Register, imm: i32) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        if imm == 0 || imm.extract_bitfield(0, 12) != 0 {
+            return Err("the lower 12 bits of the C.LUI offset have to be zero!");
+        }
+        let imm = imm as u32;
+        Ok(CItype{
+            opcode: 0b01,
+            funct3: 0b011,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: imm.extract_bitfield(17, 18) as u16,
+            imm1: imm.extract_bitfield(12, 17) as u16,
+        }.into())
     }
     #[inline]
     pub fn c_srli(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let imm = uimm as u16;
+        Ok(CBtype{
+            opcode: 0b01,
+            funct3: 0b100,
+            rs1_prime: rd.into_prime(),
+            offset2: (imm.extract_bitfield(5, 6) << 2) | 0b00,
+            offset1: imm.extract_bitfield(0, 5),
+        }.into())
     }
     #[inline]
     pub fn c_srai(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let imm = uimm as u16;
+        Ok(CBtype{
+            opcode: 0b01,
+            funct3: 0b100,
+            rs1_prime: rd.into_prime(),
+            offset2: (imm.extract_bitfield(5, 6) << 2) | 0b01,
+            offset1: imm.extract_bitfield(0, 5),
+        }.into())
     }
     #[inline]
-    pub fn c_andi(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+    pub fn c_andi(&mut self, rd: Register, imm: i8) -> Result<u16, &str> {
+        let imm = imm as u8 as u16;
+        Ok(CBtype{
+            opcode: 0b01,
+            funct3: 0b100,
+            rs1_prime: rd.into_prime(),
+            offset2: (imm.extract_bitfield(5, 6) << 2) | 0b10,
+            offset1: imm.extract_bitfield(0, 5),
+        }.into())
     }
     #[inline]
     pub fn c_sub(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100011,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b00,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_xor(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100011,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b01,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_or(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100011,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b10,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_and(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100011,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b11,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_subw(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100111,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b00,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_addw(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CAtype{
+            opcode: 0b01,
+            funct6: 0b100111,
+            rd_rs1_prime: rd.into_prime(),
+            funct2: 0b01,
+            rs2_prime: rs2.into_prime(),
+        }.into())
     }
     #[inline]
     pub fn c_j(&mut self, imm: i16) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CJtype{
+            opcode: 0b01,
+            funct3: 0b101,
+            jump_target: imm,
+        }.into())
     }
     #[inline]
     pub fn c_beqz(&mut self, rs1: Register, offset: i16) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let offset = offset as u16;
+        Ok(CBtype{
+            opcode: 0b01,
+            funct3: 0b110,
+            rs1_prime: rs1.into_prime(),
+            offset2: offset.extract_bitfield(3, 5) 
+                | (offset.extract_bitfield(8, 9) << 2),
+            offset1: offset.extract_bitfield(5, 6) 
+                | (offset.extract_bitfield(1, 3) << 1)
+                | (offset.extract_bitfield(6, 8) << 3),
+        }.into())
     }
     #[inline]
     pub fn c_bnez(&mut self, rs1: Register, offset: i16) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let offset = offset as u16;
+        Ok(CBtype{
+            opcode: 0b01,
+            funct3: 0b111,
+            rs1_prime: rs1.into_prime(),
+            offset2: offset.extract_bitfield(3, 5) 
+                | (offset.extract_bitfield(8, 9) << 2),
+            offset1: offset.extract_bitfield(5, 6) 
+                | (offset.extract_bitfield(1, 3) << 1)
+                | (offset.extract_bitfield(6, 8) << 3),
+        }.into())
     }
     #[inline]
     pub fn c_slli(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let uimm = uimm as u16;
+        Ok(CItype{
+            opcode: 0b10,
+            funct3: 0b000,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: uimm.extract_bitfield(5, 6),
+            imm1: uimm.extract_bitfield(0, 5),
+        }.into())
     }
     #[inline]
-    pub fn c_fldsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+    pub fn c_fldsp(&mut self, rd: FloatRegister, uimm: u16) -> Result<u16, &str> {
+        Ok(CItype{
+            opcode: 0b10,
+            funct3: 0b001,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: uimm.extract_bitfield(5, 6),
+            imm1: (uimm.extract_bitfield(3, 5) << 3) | uimm.extract_bitfield(6, 9),
+        }.into())
     }
     #[inline]
     pub fn c_lwsp(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let uimm = uimm as u16;
+        Ok(CItype{
+            opcode: 0b10,
+            funct3: 0b010,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: uimm.extract_bitfield(5, 6),
+            imm1: (uimm.extract_bitfield(2, 5) << 2) | uimm.extract_bitfield(6, 8),
+        }.into())
     }
     #[inline]
-    pub fn c_flwsp(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
+    pub fn c_flwsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<u16, &str> {
         todo!();
         Ok(2)
     }
     #[inline]
-    pub fn c_ldsp(&mut self, rd: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+    pub fn c_ldsp(&mut self, rd: Register, uimm: u16) -> Result<u16, &str> {
+        Ok(CItype{
+            opcode: 0b10,
+            funct3: 0b011,
+            rd_rs1: u32::from(rd) as u16,
+            imm2: uimm.extract_bitfield(5, 6),
+            imm1: (uimm.extract_bitfield(3, 5) << 3) | uimm.extract_bitfield(6, 9),
+        }.into())
     }
     #[inline]
     pub fn c_jr(&mut self, rs1: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CRtype{
+            opcode: 0b10,
+            funct4: 0b1000,
+            rd_rs1: u32::from(rs1) as u16,
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn c_mv(&mut self, rs1: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CRtype{
+            opcode: 0b10,
+            funct4: 0b1000,
+            rd_rs1: u32::from(rs1) as u16,
+            rs2: u32::from(rs2) as u16,
+        }.into())
     }
     #[inline]
     pub fn c_jalr(&mut self, rs1: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CRtype{
+            opcode: 0b10,
+            funct4: 0b1001,
+            rd_rs1: u32::from(rs1) as u16,
+            rs2: 0,
+        }.into())
     }
     #[inline]
     pub fn c_add(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        Ok(CRtype{
+            opcode: 0b10,
+            funct4: 0b1001,
+            rd_rs1: u32::from(rd) as u16,
+            rs2: u32::from(rs2) as u16,
+        }.into())
     }
     #[inline]
-    pub fn c_fsdsp(&mut self, rd: Register, rs2: Register) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+    pub fn c_fsdsp(&mut self, rs2: FloatRegister, uimm: u16) -> Result<u16, &str> {
+        Ok(CSStype{
+            opcode: 0b10,
+            funct3: 0b101,
+            rs2: u32::from(rs2) as u16,
+            imm: uimm.extract_bitfield(6, 9) | (uimm.extract_bitfield(3, 6) << 3),
+        }.into())
     }
     #[inline]
     pub fn c_swsp(&mut self, rs2: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+        let uimm = uimm as u16;
+        Ok(CSStype{
+            opcode: 0b10,
+            funct3: 0b110,
+            rs2: u32::from(rs2) as u16,
+            imm: uimm.extract_bitfield(6, 8) | (uimm.extract_bitfield(2, 6) << 2),
+        }.into())
     }
     #[inline]
     pub fn c_fswsp(&mut self, rs2: FloatRegister, uimm: u8) -> Result<u16, &str> {
@@ -1191,28 +2066,30 @@ impl AssemblerRV64GC {
         Ok(2)
     }
     #[inline]
-    pub fn c_sdsp(&mut self, rs2: Register, uimm: u8) -> Result<u16, &str> {
-        todo!();
-        Ok(2)
+    pub fn c_sdsp(&mut self, rs2: Register, uimm: u16) -> Result<u16, &str> {
+        Ok(CSStype{
+            opcode: 0b10,
+            funct3: 0b111,
+            rs2: u32::from(rs2) as u16,
+            imm: uimm.extract_bitfield(6, 9) | (uimm.extract_bitfield(3, 6) << 3),
+        }.into())
     }
-
     #[inline]
 	pub fn fence(&mut self) -> Result<u32, &str>{
-        todo!();
-        Ok(4)
+        // fence iorw, iorw
+        Ok(0b0000_1111_1111_00000_000_00000_0001111)
     }
     #[inline]
 	pub fn fence_i(&mut self) -> Result<u32, &str>{
-        todo!();
-        Ok(4)
+        Ok(0b000000000000_00000_001_00000_0001111)
     }
     #[inline]
 	pub fn ecall(&mut self) -> Result<u32, &str>{
-        Ok(0b000000000000_0000_000_00000_1110011)
+        Ok(0b000000000000_00000_000_00000_1110011)
     }
     #[inline]
 	pub fn ebreak(&mut self) -> Result<u32, &str>{
-        Ok(0b000000000001_0000_000_00000_1110011)
+        Ok(0b000000000001_00000_000_00000_1110011)
     }
     #[inline]
 	pub fn c_nop(&mut self) -> Result<u16, &str>{
@@ -1223,3 +2100,10 @@ impl AssemblerRV64GC {
         Ok(0b100_1_00000_00000_10)
     }
 }
+
+/// Build the funct7 of an atomic instruction from its funct5 and the
+/// acquire / release ordering bits
+#[inline]
+fn atomic_funct7(funct5: u32, aq: bool, rl: bool) -> u32 {
+    (funct5 << 2) | ((aq as u32) << 1) | (rl as u32)
+}
@@ -1,6 +1,16 @@
 use super::*;
 use traits::*;
 
+/// The rounding mode in `funct3`, the instruction is illegal if it's reserved
+macro_rules! rm {
+    ($user:ident, $inst:ident, $funct3:ident) => {
+        match FloatRoundingMode::try_from($funct3) {
+            Ok(rm) => rm,
+            Err(_) => return $user.illegal($inst),
+        }
+    };
+}
+
 /// Disassemble a u32 instruction and call the visitor `User` with the relative
 /// Instruction
 pub fn diss_riscv64gc<T, User: RV64GCUser<T>>(user: &mut User, inst: u32) 
@@ -28,15 +38,16 @@ fn diss_riscv64gc_2b_q0_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             let CIWtype{
                 imm, rd_prime, ..
             } = CIWtype::from(inst);
+            // it includes the all-zero instruction
             if imm == 0 {
-                panic!("Illegal instruction");
+                return user.illegal(inst as u32);
             }
             let nzuimm = (
                 (imm.extract_bitfield(0, 1) << 3) 
                 | (imm.extract_bitfield(1, 2) << 2)
                 | (imm.extract_bitfield(2, 6) << 6) 
                 | (imm.extract_bitfield(6, 8) << 4) 
-            ).zero_extend(9);
+            ).zero_extend(10);
             user.c_addi4spn(Register::from_prime(rd_prime), nzuimm)
         }
         0b001 => {
@@ -44,7 +55,7 @@ fn diss_riscv64gc_2b_q0_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 imm2, rs1_prime, imm1, rd_prime, ..
             } = CLtype::from(inst);
             user.c_fld(
-                Register::from_prime(rd_prime),
+                FloatRegister::from_prime(rd_prime),
                 Register::from_prime(rs1_prime),
                 compose_imms_53_76(imm1, imm2),
             )
@@ -69,7 +80,7 @@ fn diss_riscv64gc_2b_q0_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 compose_imms_53_76(imm1, imm2),
             )
         }
-        0b100 => unimplemented!("Reserved Compact Instruction"),
+        0b100 => user.illegal(inst as u32),
         0b101 => {
             let CStype {
                 imm2, rs1_prime, imm1, rs2_prime, ..
@@ -119,12 +130,8 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 user.c_nop()
             } else {
                 let rd = Register::from(rd_rs1 as u32);
-                let imm = if imm2 == 0 {
-                    imm1 as i8
-                } else {
-                    -(imm1 as i8)
-                };
-                user.c_addi(rd, imm as i8)
+                let imm = (imm1 | (imm2 << 5)).sign_extend(6) as i8;
+                user.c_addi(rd, imm)
             }
         }
         0b001 => {
@@ -135,11 +142,7 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 ..
             } = CItype::from(inst);
             let rd = Register::from(rd_rs1 as u32);
-            let imm = if imm2 == 0 {
-                imm1 as i8
-            } else {
-                -(imm1 as i8)
-            };
+            let imm = (imm1 | (imm2 << 5)).sign_extend(6) as i8;
             user.c_addiw(rd, imm)
         }
         0b010 => {
@@ -150,7 +153,7 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 ..
             } = CItype::from(inst);
             let rd = Register::from(rd_rs1 as u32);
-            user.c_li(rd, (imm1 | (imm2 << 5)).sign_extend(5) as i8)
+            user.c_li(rd, (imm1 | (imm2 << 5)).sign_extend(6) as i8)
         }
         0b011 => {
             let CItype{
@@ -161,18 +164,18 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             } = CItype::from(inst);
             let rd = Register::from(rd_rs1 as u32);
             match rd_rs1 {
-                0 => unimplemented!(),
+                0 => user.illegal(inst as u32),
                 2 => {
                     let imm = (imm2.extract_bitfield(0, 1) << 9) 
                         | (imm1.extract_bitfield(0, 1) << 5)
                         | (imm1.extract_bitfield(1, 3) << 7)
                         | (imm1.extract_bitfield(3, 4) << 6)
                         | (imm1.extract_bitfield(4, 5) << 4);
-                    user.c_addi16sp(imm.sign_extend(9) as i16)
+                    user.c_addi16sp(imm.sign_extend(10) as i16)
                 }
                 _ => {
                     let imm = ((imm2 as u32) << 17) | ((imm1 as u32) << 12);
-                    user.c_lui(rd, imm.sign_extend(17) as i32)
+                    user.c_lui(rd, imm.sign_extend(18) as i32)
                 }
             }
         }
@@ -186,14 +189,14 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             let rd = Register::from_prime(rs1_prime);
             match offset2 & 0b111 {
                 0b000 => user.c_srli(rd, offset1 as u8),
-                0b100 => user.c_srli(rd, 0b10000 | offset1 as u8),
+                0b100 => user.c_srli(rd, 0b100000 | offset1 as u8),
                 0b001 => user.c_srai(rd, offset1 as u8),
-                0b101 => user.c_srai(rd, 0b10000 | offset1 as u8),
-                0b010 => user.c_andi(rd, offset1 as u8),
-                0b110 => user.c_andi(rd, 0b10000 | offset1 as u8),
+                0b101 => user.c_srai(rd, 0b100000 | offset1 as u8),
+                0b010 => user.c_andi(rd, offset1.sign_extend(6) as i8),
+                0b110 => user.c_andi(rd, (0b100000 | offset1).sign_extend(6) as i8),
                 0b011 => {
                     let rs2 = Register::from_prime(offset1 & 0b111);
-                    match (offset1 >> 4) & 0b11 {
+                    match (offset1 >> 3) & 0b11 {
                         0b00 => user.c_sub(rd, rs2),
                         0b01 => user.c_xor(rd, rs2),
                         0b10 => user.c_or(rd, rs2),
@@ -203,13 +206,13 @@ fn diss_riscv64gc_2b_q1_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 },
                 0b111 => {
                     let rs2 = Register::from_prime(offset1 & 0b111);
-                    match (offset1 >> 4) & 0b11 {
+                    match (offset1 >> 3) & 0b11 {
                         0b00 => user.c_subw(rd, rs2),
                         0b01 => user.c_addw(rd, rs2),
-                        _ => unimplemented!("Reserved"),
+                        _ => user.illegal(inst as u32),
                     }
                 },
-                _ => todo!(),                
+                _ => unreachable!(),
             }
         }
         0b101 => {
@@ -274,6 +277,21 @@ fn diss_riscv64gc_2b_q2_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             let shamt = ((imm2 & 1) << 5) | imm1;
             user.c_slli(rd, shamt as u8)
         }
+        0b001 => {
+            let CRtype{
+                funct4,
+                rd_rs1,
+                rs2,
+                ..
+            } = CRtype::from(inst);
+            let rd = FloatRegister::from(rd_rs1 as u32);
+            let imm = (funct4 & 1) << 5 | (
+                (rs2 & 0b00111) << 6
+            ) | (
+                (rs2 & 0b11000)
+            );
+            user.c_fldsp(rd, imm)
+        }
         0b011 => {
             let CRtype{
                 funct4,
@@ -287,7 +305,22 @@ fn diss_riscv64gc_2b_q2_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             ) | (
                 (rs2 & 0b11000)
             );
-            user.c_ldsp(rd, imm as u8)
+            user.c_ldsp(rd, imm)
+        }
+        0b010 => {
+            let CRtype{
+                funct4,
+                rd_rs1,
+                rs2,
+                ..
+            } = CRtype::from(inst);
+            let rd = Register::from(rd_rs1 as u32);
+            let imm = (funct4 & 1) << 5 | (
+                (rs2 & 0b00011) << 6
+            ) | (
+                (rs2 & 0b11100)
+            );
+            user.c_lwsp(rd, imm as u8)
         }
         0b100 => {
             let CRtype{
@@ -323,6 +356,19 @@ fn diss_riscv64gc_2b_q2_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
                 }
             }
         },
+        0b101 => {
+            let CSStype{
+                imm,
+                rs2,
+                ..
+            } = CSStype::from(inst);
+            let rs2 = FloatRegister::from(rs2 as u32);
+
+            let uimm = (imm & 0b111) << 6
+                | (imm & 0b111000);
+
+            user.c_fsdsp(rs2, uimm)
+        }
         0b111 => {
             let CSStype{
                 imm,
@@ -334,10 +380,22 @@ fn diss_riscv64gc_2b_q2_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u16)
             let uimm = (imm & 0b111) << 6
                 | (imm & 0b111000);
 
-            debug_assert!(uimm < 256);
-            user.c_sdsp(rs2, uimm as u8)
+            user.c_sdsp(rs2, uimm)
+        }
+        0b110 => {
+            let CSStype{
+                imm,
+                rs2,
+                ..
+            } = CSStype::from(inst);
+            let rs2 = Register::from(rs2 as u32);
+
+            let uimm = (imm & 0b11) << 6
+                | (imm & 0b111100);
+
+            user.c_swsp(rs2, uimm as u8)
         }
-        _ => todo!(),
+        _ => unreachable!(),
     }
 }
 
@@ -353,7 +411,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
         }
         0b0010111 => {
             let Utype{imm, rd, ..} = Utype::from(inst);
-            user.auipc(rd.into(), imm)
+            user.auipc(rd.into(), imm << 12)
         }
         0b1101111 => {
             let Jtype{imm, rd, ..} = Jtype::from(inst);
@@ -367,7 +425,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 0b000 => {
                     user.jalr(rd.into(), rs1.into(), imm)
                 }
-                _ => unimplemented!("Unexpected 0b1100111"),
+                _ => user.illegal(inst),
             }
         }
         0b1100011 => {
@@ -381,7 +439,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 0b101 => user.bge( rs1.into(), rs2.into(), imm),
                 0b110 => user.bltu(rs1.into(), rs2.into(), imm),
                 0b111 => user.bgeu(rs1.into(), rs2.into(), imm),
-                _ => unimplemented!("Unexpected 0b1100011"),
+                _ => user.illegal(inst),
             }
         }
         0b0000111 => {
@@ -389,28 +447,22 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 imm, rs1, funct3, rd, ..
             } = Itype::from(inst);
 
-            todo!();
-            /*
             match funct3 {
                 0b010 => user.flw(rd.into(), rs1.into(), imm),
                 0b011 => user.fld(rd.into(), rs1.into(), imm),
-                _ => unimplemented!("Unexpected 0b0000111"),
+                _ => user.illegal(inst),
             }
-            */
         }
         0b0100111 => {
             let Stype {
                 imm, rs2, rs1, funct3, ..
             } = Stype::from(inst);
 
-            todo!();
-            /*
             match funct3 {
                 0b010 => user.fsw(rs1.into(), rs2.into(), imm),
                 0b011 => user.fsd(rs1.into(), rs2.into(), imm),
-                _ => unimplemented!("Unexpected 0b0000111"),
+                _ => user.illegal(inst),
             }
-            */
         }
         0b1000011 => {
             let R4type {
@@ -420,13 +472,13 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
             match funct2 {
                 00 => user.fmadd_s(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
                 01 => user.fmadd_d(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
-                _ => unimplemented!("Unexpected 0b1000011"),
+                _ => user.illegal(inst),
             }
         }
         0b1000111 => {
@@ -437,13 +489,13 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
             match funct2 {
                 00 => user.fmsub_s(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
                 01 => user.fmsub_d(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
-                _ => unimplemented!("Unexpected 0b1000111"),
+                _ => user.illegal(inst),
             }
         }
         0b1001011 => {
@@ -454,13 +506,13 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
             match funct2 {
                 00 => user.fnmsub_s(
                     rd.into(), rs1.into(), rs2.into(),
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
                 01 => user.fnmsub_d(
                     rd.into(), rs1.into(), rs2.into(),
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
-                _ => unimplemented!("Unexpected 0b1001011"),
+                _ => user.illegal(inst),
             }
         }
         0b1001111 => {
@@ -471,13 +523,13 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
             match funct2 {
                 00 => user.fnmadd_s(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
                 01 => user.fnmadd_d(
                     rd.into(), rs1.into(), rs2.into(), 
-                    rs3.into(), funct3.into(),
+                    rs3.into(), rm!(user, inst, funct3),
                 ),
-                _ => unimplemented!("Unexpected 0b1001111"),
+                _ => user.illegal(inst),
             }
         }
         0b1010011 => {
@@ -486,51 +538,54 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 ..
             } = Rtype::from(inst);
             match funct7 {
-                _ => todo!(),
                 0b0000000 => user.fadd_s(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0000001 => user.fadd_d(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0000100 => user.fsub_s(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
-                0b0000100 => user.fsub_d(
+                0b0000101 => user.fsub_d(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0001000 => user.fmul_s(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0001001 => user.fmul_d(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0001100 => user.fdiv_s(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0001101 => user.fdiv_d(
                     rd.into(), rs1.into(), 
-                    rs2.into(), funct3.into(),
+                    rs2.into(), rm!(user, inst, funct3),
                 ),
                 0b0101100 => {
-                    assert_eq!(rs2, 0b00000);
+                    if rs2 != 0 {
+                        return user.illegal(inst);
+                    }
                     user.fsqrt_s(
                         rd.into(), rs1.into(), 
-                        funct3.into(),
+                        rm!(user, inst, funct3),
                     )
                 },
                 0b0101101 => {
-                    assert_eq!(rs2, 0b00000);
+                    if rs2 != 0 {
+                        return user.illegal(inst);
+                    }
                     user.fsqrt_d(
                         rd.into(), rs1.into(), 
-                        funct3.into(),
+                        rm!(user, inst, funct3),
                     )
                 },
                 0b0010000 => {
@@ -544,7 +599,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b010 => user.fsgnjx_s(
                             rd.into(), rs1.into(), rs2.into()
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0010000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b0010001 => {
@@ -558,7 +613,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b010 => user.fsgnjx_d(
                             rd.into(), rs1.into(), rs2.into()
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0010001"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b0010100 => {
@@ -569,7 +624,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b001 => user.fmax_s(
                             rd.into(), rs1.into(), rs2.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0010100"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b0010101 => {
@@ -580,15 +635,15 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b001 => user.fmax_d(
                             rd.into(), rs1.into(), rs2.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0010100"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b0100000 => {
                     match rs2 {
                         0b00001 => user.fcvt_s_d(
-                            rd.into(), rs1.into()
+                            rd.into(), rs1.into(), rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0100000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b0100001 => {
@@ -596,73 +651,73 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b00000 => user.fcvt_d_s(
                             rd.into(), rs1.into()
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b0100001"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1100000 => {
                     match rs2 {
                         0b00000 => user.fcvt_w_s(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00001 => user.fcvt_wu_s(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00010 => user.fcvt_l_s(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00011 => user.fcvt_lu_s(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1100000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1100001 => {
                     match rs2 {
                         0b00000 => user.fcvt_w_d(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00001 => user.fcvt_wu_d(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00010 => user.fcvt_l_d(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00011 => user.fcvt_lu_d(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1100000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1110000 => {
                     match (rs2, funct3) {
                         (0b00000, 0b000) => user.fmv_x_w(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         (0b00000, 0b001) => user.fclass_s(
                             rd.into(), rs1.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1110000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1110001 => {
                     match (rs2, funct3) {
                         (0b00000, 0b000) => user.fmv_x_d(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         (0b00000, 0b001) => user.fclass_d(
                             rd.into(), rs1.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1110001"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1010000 => {
@@ -676,7 +731,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b000 => user.fle_s(
                             rd.into(), rs1.into(), rs2.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1010000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1010001 => {
@@ -690,49 +745,49 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         0b000 => user.fle_d(
                             rd.into(), rs1.into(), rs2.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1010000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1101000 => {
                     match rs2 {
                         0b00000 => user.fcvt_s_w(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00001 => user.fcvt_s_wu(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00010 => user.fcvt_s_l(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00011 => user.fcvt_s_lu(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1101000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1101001 => {
                     match rs2 {
                         0b00000 => user.fcvt_d_w(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00001 => user.fcvt_d_wu(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00010 => user.fcvt_d_l(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
                         0b00011 => user.fcvt_d_lu(
                             rd.into(), rs1.into(),
-                            funct3.into(),
+                            rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1101000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1111000 => {
@@ -740,17 +795,18 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                         (0b00000, 0b000) => user.fmv_w_x(
                             rd.into(), rs1.into(),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1111000"),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b1111001 => {
                     match (rs2, funct3) {
                         (0b00000, 0b000) => user.fmv_d_x(
-                            rd.into(), rs1.into(), funct3.into(),
+                            rd.into(), rs1.into(), rm!(user, inst, funct3),
                         ),
-                        _ => unimplemented!("Unexpected 0b1010011 - 0b1111001"),
+                        _ => user.illegal(inst),
                     }
                 }
+                _ => user.illegal(inst),
             }
         }
         0b0000011 => {
@@ -766,7 +822,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 0b100 => user.lbu(rd.into(), rs1.into(), imm),
                 0b101 => user.lhu(rd.into(), rs1.into(), imm),
                 0b110 => user.lwu(rd.into(), rs1.into(), imm),
-                _ => unimplemented!("Unexpected 0b0000011"),
+                _ => user.illegal(inst),
             }
         }
         0b0100011 => {
@@ -779,7 +835,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 0b001 => user.sh(rs1.into(), rs2.into(), imm),
                 0b010 => user.sw(rs1.into(), rs2.into(), imm),
                 0b011 => user.sd(rs1.into(), rs2.into(), imm),
-                _ => unimplemented!("Unexpected 0b0100011"),
+                _ => user.illegal(inst),
             }
         }
         0b0010011 => {
@@ -802,7 +858,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                     
                     match mode {
                         0b000000 => user.slli(rd.into(), rs1.into(), shamt),
-                        _ => unreachable!(),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b101 => {
@@ -812,10 +868,10 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                     match mode {
                         0b000000 => user.srli(rd.into(), rs1.into(), shamt),
                         0b010000 => user.srai(rd.into(), rs1.into(), shamt),
-                        _ => unreachable!(),
+                        _ => user.illegal(inst),
                     }
                 }
-                _ => unreachable!(),
+                _ => user.illegal(inst),
             }
         }
         0b0110011 => {
@@ -842,7 +898,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 (0b0000001, 0b101) => user.divu(  rd.into(), rs1.into(), rs2.into()),
                 (0b0000001, 0b110) => user.rem(   rd.into(), rs1.into(), rs2.into()),
                 (0b0000001, 0b111) => user.remu(  rd.into(), rs1.into(), rs2.into()),
-                _ => unreachable!(),
+                _ => user.illegal(inst),
             }
         }
         0b0111011 => {
@@ -861,7 +917,42 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                 (0b0000001, 0b101) => user.divuw(rd.into(), rs1.into(), rs2.into()),
                 (0b0000001, 0b110) => user.remw( rd.into(), rs1.into(), rs2.into()),
                 (0b0000001, 0b111) => user.remuw(rd.into(), rs1.into(), rs2.into()),
-                _ => unreachable!(),
+                _ => user.illegal(inst),
+            }
+        }
+        0b0101111 => {
+            let Rtype{
+                funct7, rs2, rs1, funct3, rd, ..
+            } = Rtype::from(inst);
+            // funct7 is funct5 followed by the aq and rl ordering bits
+            let funct5 = funct7 >> 2;
+            let aq = (funct7 & 0b10) != 0;
+            let rl = (funct7 & 0b01) != 0;
+
+            match (funct5, funct3) {
+                (0b00010, 0b010) if rs2 == 0 => user.lr_w(rd.into(), rs1.into(), aq, rl),
+                (0b00011, 0b010) => user.sc_w(      rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00001, 0b010) => user.amoswap_w( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00000, 0b010) => user.amoadd_w(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00100, 0b010) => user.amoxor_w(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b01100, 0b010) => user.amoand_w(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b01000, 0b010) => user.amoor_w(   rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b10000, 0b010) => user.amomin_w(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b10100, 0b010) => user.amomax_w(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b11000, 0b010) => user.amominu_w( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b11100, 0b010) => user.amomaxu_w( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00010, 0b011) if rs2 == 0 => user.lr_d(rd.into(), rs1.into(), aq, rl),
+                (0b00011, 0b011) => user.sc_d(      rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00001, 0b011) => user.amoswap_d( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00000, 0b011) => user.amoadd_d(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b00100, 0b011) => user.amoxor_d(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b01100, 0b011) => user.amoand_d(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b01000, 0b011) => user.amoor_d(   rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b10000, 0b011) => user.amomin_d(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b10100, 0b011) => user.amomax_d(  rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b11000, 0b011) => user.amominu_d( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                (0b11100, 0b011) => user.amomaxu_d( rd.into(), rs1.into(), rs2.into(), aq, rl),
+                _ => user.illegal(inst),
             }
         }
         0b0001111 => {
@@ -872,13 +963,15 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
             match funct3 {
                 0b000 => user.fence(),
                 0b001 => user.fence_i(),
-                _ => unreachable!(),
+                _ => user.illegal(inst),
             }
         }
         0b1110011 => {
             let Itype{
                 imm, rs1, funct3, rd, ..
             } = Itype::from(inst);
+            // the CSR address is unsigned
+            let csr = imm as u32 & 0xfff;
 
             match funct3 {
                 0b000 => {
@@ -887,16 +980,18 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                     } else if inst == 0b00000000000100000000000001110011 {
                         user.ebreak()
                     } else {
-                        unreachable!();
+                        // the privileged instructions, e.g. mret and wfi
+                        user.illegal(inst)
                     }
                 }
-                _ => todo!(),
-                //0b001 => user.csrrw( rd.into(), rs1.into(), imm),
-                //0b010 => user.csrrs( rd.into(), rs1.into(), imm),
-                //0b011 => user.csrrc( rd.into(), rs1.into(), imm),
-                //0b101 => user.csrrwi(rd.into(), rs1.into(), imm),
-                //0b110 => user.csrrsi(rd.into(), rs1.into(), imm),
-                //0b111 => user.csrrci(rd.into(), rs1.into(), imm),
+                0b001 => user.csrrw( rd.into(), rs1.into(), csr),
+                0b010 => user.csrrs( rd.into(), rs1.into(), csr),
+                0b011 => user.csrrc( rd.into(), rs1.into(), csr),
+                0b101 => user.csrrwi(rd.into(), rs1 as u8,  csr),
+                0b110 => user.csrrsi(rd.into(), rs1 as u8,  csr),
+                0b111 => user.csrrci(rd.into(), rs1 as u8,  csr),
+                // funct3 0b100 is reserved
+                _ => user.illegal(inst),
             }
 
         }
@@ -913,7 +1008,7 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                     
                     match mode {
                         0b0000000 => user.slliw(rd.into(), rs1.into(), shamt),
-                        _ => unreachable!(),
+                        _ => user.illegal(inst),
                     }
                 }
                 0b101 => {
@@ -923,12 +1018,12 @@ fn diss_riscv64gc_4b_inst<T, User: RV64GCUser<T>>(user: &mut User, inst: u32)
                     match mode {
                         0b0000000 => user.srliw(rd.into(), rs1.into(), shamt),
                         0b0100000 => user.sraiw(rd.into(), rs1.into(), shamt),
-                        _ => unreachable!(),
+                        _ => user.illegal(inst),
                     }
                 }
-                _ => unreachable!(),
+                _ => user.illegal(inst),
             }
         }
-        _ => unimplemented!("Unhandled opcode {:#09b}\n", opcode),
+        _ => user.illegal(inst),
     }
 }
\ No newline at end of file
@@ -5,7 +5,7 @@ use super::RV64GCUser;
 
 pub struct RV64GCPrint;
 
-impl RV64GCUser<usize, usize> for RV64GCPrint {
+impl RV64GCUser<usize> for RV64GCPrint {
     type Error = Infallible;
     fn lui(&mut self, rd: Register, imm: u32) -> Result<usize, Self::Error> {
         println!("lui {:?} {}", rd, imm);
@@ -112,87 +112,87 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
-    fn csrrw(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrw {:?} {:?} {}", rd, rs1, offset);
+    fn csrrw(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrw {:?} {:?} {:#x}", rd, rs1, csr);
         Ok(4)
     }
     
-    fn csrrs(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrs {:?} {:?} {}", rd, rs1, offset);
+    fn csrrs(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrs {:?} {:?} {:#x}", rd, rs1, csr);
         Ok(4)
     }
     
-    fn csrrc(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrc {:?} {:?} {}", rd, rs1, offset);
+    fn csrrc(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrc {:?} {:?} {:#x}", rd, rs1, csr);
         Ok(4)
     }
     
-    fn csrrwi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrwi {:?} {} {}", rd, zimm, offset);
+    fn csrrwi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrwi {:?} {} {:#x}", rd, zimm, csr);
         Ok(4)
     }
     
-    fn csrrsi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrsi {:?} {} {}", rd, zimm, offset);
+    fn csrrsi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrsi {:?} {} {:#x}", rd, zimm, csr);
         Ok(4)
     }
     
-    fn csrrci(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<usize, Self::Error> {
-        println!("csrrci {:?} {} {}", rd, zimm, offset);
+    fn csrrci(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<usize, Self::Error> {
+        println!("csrrci {:?} {} {:#x}", rd, zimm, csr);
         Ok(4)
     }
     
-    fn lb(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lb {:?} {}", rd, imm);
+    fn lb(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lb {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn lh(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lh {:?} {}", rd, imm);
+    fn lh(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lh {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn lw(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lw {:?} {}", rd, imm);
+    fn lw(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lw {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn ld(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("ld {:?} {}", rd, imm);
+    fn ld(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("ld {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn lbu(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lbu {:?} {}", rd, imm);
+    fn lbu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lbu {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn lhu(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lhu {:?} {}", rd, imm);
+    fn lhu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lhu {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn lwu(&mut self, rd: Register, imm: u64) -> Result<usize, Self::Error> {
-        println!("lwu {:?} {}", rd, imm);
+    fn lwu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("lwu {:?} [{:?}+{}]", rd, rs1, imm);
         Ok(4)
     }
     
-    fn sb(&mut self, rs1: Register, rs2: Register, imm: u64) -> Result<usize, Self::Error> {
+    fn sb(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<usize, Self::Error> {
         println!("sb {:?} {:?} {}", rs1, rs2, imm);
         Ok(4)
     }
     
-    fn sh(&mut self, rs1: Register, rs2: Register, imm: u64) -> Result<usize, Self::Error> {
+    fn sh(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<usize, Self::Error> {
         println!("sh {:?} {:?} {}", rs1, rs2, imm);
         Ok(4)
     }
     
-    fn sw(&mut self, rs1: Register, rs2: Register, imm: u64) -> Result<usize, Self::Error> {
+    fn sw(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<usize, Self::Error> {
         println!("sw {:?} {:?} {}", rs1, rs2, imm);
         Ok(4)
     }
     
-    fn sd(&mut self, rs1: Register, rs2: Register, imm: u64) -> Result<usize, Self::Error> {
+    fn sd(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<usize, Self::Error> {
         println!("sd {:?} {:?} {}", rs1, rs2, imm);
         Ok(4)
     }
@@ -237,8 +237,8 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
-    fn addiw(&mut self, rd: Register, rsq: Register, imm: u32) -> Result<usize, Self::Error> {
-        println!("addiw {:?} {:?} {}", rd, rsq, imm);
+    fn addiw(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
+        println!("addiw {:?} {:?} {}", rd, rs1, imm);
         Ok(4)
     }
     
@@ -347,6 +347,116 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
+    fn lr_w(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("lr_w {:?} [{:?}] aq={} rl={}", rd, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn sc_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("sc_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoswap_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoswap_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoadd_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoadd_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoxor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoxor_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoand_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoand_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoor_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomin_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomin_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomax_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomax_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amominu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amominu_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomaxu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomaxu_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn lr_d(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("lr_d {:?} [{:?}] aq={} rl={}", rd, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn sc_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("sc_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoswap_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoswap_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoadd_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoadd_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoxor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoxor_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoand_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoand_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amoor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amoor_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomin_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomin_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomax_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomax_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amominu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amominu_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
+    fn amomaxu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) -> Result<usize, Self::Error> {
+        println!("amomaxu_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, aq, rl);
+        Ok(4)
+    }
+    
     fn fmadd_s(&mut self, rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode) -> Result<usize, Self::Error> {
         println!("fmadd_s {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
         Ok(4)
@@ -537,8 +647,8 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
-    fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister) -> Result<usize, Self::Error> {
-        println!("fcvt_s_d {:?} {:?}", rd, rs1);
+    fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<usize, Self::Error> {
+        println!("fcvt_s_d {:?} {:?} {:?}", rd, rs1, rm);
         Ok(4)
     }
     
@@ -587,22 +697,22 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
-    fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: u32) -> Result<usize, Self::Error> {
+    fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: i32) -> Result<usize, Self::Error> {
         println!("flw {:?} {:?} {}", rd, rs1, imm);
         Ok(4)
     }
     
-    fn fsw(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) -> Result<usize, Self::Error> {
+    fn fsw(&mut self, rs1: Register, rs2: FloatRegister, offset: i32) -> Result<usize, Self::Error> {
         println!("fsw {:?} {:?} {}", rs1, rs2, offset);
         Ok(4)
     }
     
-    fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: u32) -> Result<usize, Self::Error> {
+    fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: i32) -> Result<usize, Self::Error> {
         println!("fld {:?} {:?} {}", rd, rs1, offset);
         Ok(4)
     }
     
-    fn fsd(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) -> Result<usize, Self::Error> {
+    fn fsd(&mut self, rs1: Register, rs2: FloatRegister, offset: i32) -> Result<usize, Self::Error> {
         println!("fsd {:?} {:?} {}", rs1, rs2, offset);
         Ok(4)
     }
@@ -612,7 +722,7 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(4)
     }
     
-    fn fcvt_lu_s(&mut self, rd: FloatRegister, rs1: Register, rm: FloatRoundingMode) -> Result<usize, Self::Error> {
+    fn fcvt_lu_s(&mut self, rd: Register, rs1: FloatRegister, rm: FloatRoundingMode) -> Result<usize, Self::Error> {
         println!("fcvt_lu_s {:?} {:?} {:?}", rd, rs1, rm);
         Ok(4)
     }
@@ -662,7 +772,7 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_fld(&mut self, rd: Register, rs1: Register, imm: u16) -> Result<usize, Self::Error> {
+    fn c_fld(&mut self, rd: FloatRegister, rs1: Register, imm: u16) -> Result<usize, Self::Error> {
         println!("c_fld {:?} {:?} {}", rd, rs1, imm);
         Ok(2)
     }
@@ -742,8 +852,8 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_andi(&mut self, rd: Register, uimm: u8) -> Result<usize, Self::Error> {
-        println!("c_andi {:?} {}", rd, uimm);
+    fn c_andi(&mut self, rd: Register, imm: i8) -> Result<usize, Self::Error> {
+        println!("c_andi {:?} {}", rd, imm);
         Ok(2)
     }
     
@@ -782,12 +892,12 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_beqz(&mut self, rs1: Register, offset: u16) -> Result<usize, Self::Error> {
+    fn c_beqz(&mut self, rs1: Register, offset: i16) -> Result<usize, Self::Error> {
         println!("c_beqz {:?} {}", rs1, offset);
         Ok(2)
     }
     
-    fn c_bnez(&mut self, rs1: Register, offset: u16) -> Result<usize, Self::Error> {
+    fn c_bnez(&mut self, rs1: Register, offset: i16) -> Result<usize, Self::Error> {
         println!("c_bnez {:?} {}", rs1, offset);
         Ok(2)
     }
@@ -797,7 +907,7 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<usize, Self::Error> {
+    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u16) -> Result<usize, Self::Error> {
         println!("c_fldsp {:?} {}", rd, uimm);
         Ok(2)
     }
@@ -807,12 +917,12 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_flwsp(&mut self, rd: Register, uimm: u8) -> Result<usize, Self::Error> {
+    fn c_flwsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<usize, Self::Error> {
         println!("c_flwsp {:?} {}", rd, uimm);
         Ok(2)
     }
     
-    fn c_ldsp(&mut self, rd: Register, uimm: u8) -> Result<usize, Self::Error> {
+    fn c_ldsp(&mut self, rd: Register, uimm: u16) -> Result<usize, Self::Error> {
         println!("c_ldsp {:?} {}", rd, uimm);
         Ok(2)
     }
@@ -837,8 +947,8 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_fsdsp(&mut self, rd: Register, rs2: Register) -> Result<usize, Self::Error> {
-        println!("c_fsdsp {:?} {:?}", rd, rs2);
+    fn c_fsdsp(&mut self, rs2: FloatRegister, uimm: u16) -> Result<usize, Self::Error> {
+        println!("c_fsdsp {:?} {}", rs2, uimm);
         Ok(2)
     }
     
@@ -852,7 +962,7 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
         Ok(2)
     }
     
-    fn c_sdsp(&mut self, rs2: Register, uimm: u8) -> Result<usize, Self::Error> {
+    fn c_sdsp(&mut self, rs2: Register, uimm: u16) -> Result<usize, Self::Error> {
         println!("c_sdsp {:?} {}", rs2, uimm);
         Ok(2)
     }
@@ -863,4 +973,8 @@ impl RV64GCUser<usize, usize> for RV64GCPrint {
 	fn ebreak(&mut self) -> Result<usize, Self::Error>{println!("ebreak"); Ok(4)}
 	fn c_nop(&mut self) -> Result<usize, Self::Error>{println!("c_nop"); Ok(2)}
 	fn c_ebreak(&mut self) -> Result<usize, Self::Error>{println!("c_ebreak"); Ok(2)}
+	fn illegal(&mut self, inst: u32) -> Result<usize, Self::Error>{
+		println!("illegal {:#x}", inst);
+		Ok(if inst & 0b11 == 0b11 { 4 } else { 2 })
+	}
 }
@@ -188,6 +188,20 @@ pub enum FloatRegister {
 }
 
 impl FloatRegister {
+    pub(crate) fn into_prime(&self) -> u16 {
+        match self {
+            FloatRegister::FS0 => 0b000,
+            FloatRegister::FS1 => 0b001,
+            FloatRegister::FA0 => 0b010,
+            FloatRegister::FA1 => 0b011,
+            FloatRegister::FA2 => 0b100,
+            FloatRegister::FA3 => 0b101,
+            FloatRegister::FA4 => 0b110,
+            FloatRegister::FA5 => 0b111,
+            _ => panic!("Unsupported prime float reg")
+        }
+    } 
+
     pub(crate) fn from_prime(val: u16) -> FloatRegister {
         match val {
             0b000 => FloatRegister::FS0,
@@ -214,6 +228,12 @@ impl From<u32> for FloatRegister {
     }
 }
 
+impl From<FloatRegister> for u32 {
+    fn from(value: FloatRegister) -> Self {
+        value as u32
+    }
+}
+
 /// 64-bit RISC-V float rounding modes
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FloatRoundingMode {
@@ -232,17 +252,32 @@ pub enum FloatRoundingMode {
     DYN,
 }
 
-impl From<u32> for FloatRoundingMode {
-    fn from(val: u32) -> Self {
+impl TryFrom<u32> for FloatRoundingMode {
+    type Error = u32;
+
+    fn try_from(val: u32) -> Result<Self, Self::Error> {
         match val {
-            0b000 => FloatRoundingMode::RNE,
-            0b001 => FloatRoundingMode::RTZ,
-            0b010 => FloatRoundingMode::RDN,
-            0b011 => FloatRoundingMode::RUP,
-            0b100 => FloatRoundingMode::RMM,
-            0b111 => FloatRoundingMode::DYN,
-            _ => unreachable!("Invalid rounding mode"),
+            0b000 => Ok(FloatRoundingMode::RNE),
+            0b001 => Ok(FloatRoundingMode::RTZ),
+            0b010 => Ok(FloatRoundingMode::RDN),
+            0b011 => Ok(FloatRoundingMode::RUP),
+            0b100 => Ok(FloatRoundingMode::RMM),
+            0b111 => Ok(FloatRoundingMode::DYN),
+            // 0b101 and 0b110 are reserved
+            _ => Err(val),
         }
     }
 }
 
+impl From<FloatRoundingMode> for u32 {
+    fn from(value: FloatRoundingMode) -> Self {
+        match value {
+            FloatRoundingMode::RNE => 0b000,
+            FloatRoundingMode::RTZ => 0b001,
+            FloatRoundingMode::RDN => 0b010,
+            FloatRoundingMode::RUP => 0b011,
+            FloatRoundingMode::RMM => 0b100,
+            FloatRoundingMode::DYN => 0b111,
+        }
+    }
+}
@@ -130,7 +130,7 @@ impl From<u32> for Jtype {
             | (imm11 << 11) | (imm101 << 1);
 
         Jtype {
-            imm: imm.sign_extend(20).to_signed(),
+            imm: imm.sign_extend(21).to_signed(),
             rd:  inst.extract_bitfield(7, 12),
             opcode: inst.extract_bitfield(0, 7),
         }
@@ -142,8 +142,8 @@ impl From<Jtype> for u32 {
     fn from(value: Jtype) -> Self {
         let imm = value.imm as u32;
         let imm20   = imm.extract_bitfield(20, 21);
-        let imm1912 = imm.extract_bitfield(11, 20);
-        let imm11   = imm.extract_bitfield(10, 11);
+        let imm1912 = imm.extract_bitfield(12, 20);
+        let imm11   = imm.extract_bitfield(11, 12);
         let imm101  = imm.extract_bitfield(1, 11);
         value.opcode | (value.rd << 7) | (imm1912 << 12) | (imm11 << 20) 
             | (imm101 << 21) | (imm20 << 31)
@@ -175,7 +175,7 @@ impl From<u32> for Btype {
                     | (imm41  << 1);
 
         Btype {
-            imm:    imm.sign_extend(12).to_signed(),
+            imm:    imm.sign_extend(13).to_signed(),
             rs2:    inst.extract_bitfield(20, 25),
             rs1:    inst.extract_bitfield(15, 20),
             funct3: inst.extract_bitfield(12, 15),
@@ -189,9 +189,9 @@ impl From<Btype> for u32 {
     fn from(value: Btype) -> Self {
         let imm = value.imm as u32;
         let imm_4_1 = imm.extract_bitfield(1, 5);
-        let imm_11 = imm.extract_bitfield(10, 11);
+        let imm_11 = imm.extract_bitfield(11, 12);
         let imm_10_5 = imm.extract_bitfield(5, 11);
-        let imm_12 = imm.extract_bitfield(11, 12);
+        let imm_12 = imm.extract_bitfield(12, 13);
 
         value.opcode | (imm_11 << 7) | (imm_4_1 << 8) | (value.funct3 << 12) 
             | (value.rs1 << 15) | (value.rs2 << 20) 
@@ -522,12 +522,12 @@ impl From<u16> for CJtype {
             (imm.extract_bitfield(5, 6) << 6) |
             (imm.extract_bitfield(6, 7) << 10) |
             (imm.extract_bitfield(7, 9) << 8) |
-            (imm.extract_bitfield(10, 11) << 4) |
-            (imm.extract_bitfield(11, 12) << 11)
+            (imm.extract_bitfield(9, 10) << 4) |
+            (imm.extract_bitfield(10, 11) << 11)
         ;
         CJtype {
             funct3:      inst.extract_bitfield(13, 16),
-            jump_target: jump_target.sign_extend(11) as i16,
+            jump_target: jump_target.sign_extend(12) as i16,
             opcode:    inst.extract_bitfield( 0,  2),
         }
     }
@@ -536,7 +536,7 @@ impl From<u16> for CJtype {
 impl From<CJtype> for u16 {
     #[inline]
     fn from(value: CJtype) -> Self {
-        let jmp = (value.jump_target as u16).extract_bitfield(1, 13);
+        let jmp = value.jump_target as u16;
         let jmp = 
             (jmp.extract_bitfield(5, 6)) | 
             (jmp.extract_bitfield(1, 4) << 1) | 
@@ -558,7 +558,7 @@ pub(crate) fn compose_imms_53_76(imm1: u16, imm2: u16) -> u16 {
 
 /// Helper function to build compact integers
 pub(crate) fn compose_imms_53_2_or_6(imm1: u16, imm2: u16) -> u16 {
-    ((imm1 & 0b1) << 6) | (imm2 << 3) | (imm1 & 0b10) 
+    ((imm1 & 0b1) << 6) | (imm2 << 3) | ((imm1 & 0b10) << 1)
 }
 
 
@@ -756,4 +756,20 @@ mod tests {
         let conv = CJtype::from(u16::from(v));
         assert_eq!(v, conv);
     }
+
+    #[test]
+    fn test_immediates_range() {
+        for imm in [-(1 << 20), -0x12346, 0x7ae9c, (1 << 20) - 2] {
+            let v = Jtype {rd: 1, imm, opcode: 0b1101111};
+            assert_eq!(v, Jtype::from(u32::from(v)));
+        }
+        for imm in [-4096, -1366, 1366, 4094] {
+            let v = Btype {rs1: 1, rs2: 2, funct3: 6, imm, opcode: 0b1100011};
+            assert_eq!(v, Btype::from(u32::from(v)));
+        }
+        for jump_target in [-2048, -1366, 1366, 2046] {
+            let v = CJtype {funct3: 0b101, jump_target, opcode: 0b01};
+            assert_eq!(v, CJtype::from(u16::from(v)));
+        }
+    }
 }
\ No newline at end of file
@@ -1,7 +1,6 @@
 use super::{Register, FloatRegister, FloatRoundingMode};
 
 /// Instructions skipped: `uret, srtet, mret, wfi, sfence.vma`
-/// Extensions Skipped: `RV32A, RV64A` do I need atomic right now?
 /// G = IMAFD, Zicsr, Zifencei
 /// 
 /// Compact instructions will receive already translated registers
@@ -217,7 +216,7 @@ pub trait RV64GCUser<T> {
     /// cause any of the side effects that might occur on a CSR read.
     /// 
     /// `t = CSRs[csr]; CSRs[csr] = x[rs1]; x[rd] = t`
-    fn csrrw(&mut self, rd: Register, rs1: Register, offset: u32) 
+    fn csrrw(&mut self, rd: Register, rs1: Register, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Atomic read and set bits in CSR (RV32Zicsr)
@@ -232,7 +231,7 @@ pub trait RV64GCUser<T> {
     /// effects when written).
     /// 
     /// `t = CSRs[csr]; CSRs[csr] = t | x[rs1]; x[rd] = t`
-    fn csrrs(&mut self, rd: Register, rs1: Register, offset: u32) 
+    fn csrrs(&mut self, rd: Register, rs1: Register, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Atomic read and clear bits in CSR (RV32Zicsr)
@@ -246,7 +245,7 @@ pub trait RV64GCUser<T> {
     /// Other bits in the CSR are unaffected.
     /// 
     /// `t = CSRs[csr]; CSRs[csr] = t &∼x[rs1]; x[rd] = t`
-    fn csrrc(&mut self, rd: Register, rs1: Register, offset: u32) 
+    fn csrrc(&mut self, rd: Register, rs1: Register, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Update CSR Immediate (RV32Zicsr)
@@ -255,7 +254,7 @@ pub trait RV64GCUser<T> {
     /// 5-bit unsigned immediate (uimm[4:0]) field encoded in the rs1 field.
     /// 
     /// `x[rd] = CSRs[csr]; CSRs[csr] = zimm`
-    fn csrrwi(&mut self, rd: Register, zimm: u8, offset: u32) 
+    fn csrrwi(&mut self, rd: Register, zimm: u8, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Set CSR Immediate (RV32Zicsr)
@@ -264,7 +263,7 @@ pub trait RV64GCUser<T> {
     /// unsigned immediate (uimm[4:0]) field encoded in the rs1 field.
     /// 
     /// `t = CSRs[csr]; CSRs[csr] = t | zimm; x[rd] = t`
-    fn csrrsi(&mut self, rd: Register, zimm: u8, offset: u32) 
+    fn csrrsi(&mut self, rd: Register, zimm: u8, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Clear CSR Immediate (RV32Zicsr)
@@ -273,7 +272,7 @@ pub trait RV64GCUser<T> {
     /// unsigned immediate (uimm[4:0]) field encoded in the rs1 field.
     /// 
     /// `t = CSRs[csr]; CSRs[csr] = t &∼zimm; x[rd] = t`
-    fn csrrci(&mut self, rd: Register, zimm: u8, offset: u32) 
+    fn csrrci(&mut self, rd: Register, zimm: u8, csr: u32) 
         -> Result<T, Self::Error>;
 
     /// # Load Byte (RV32I)
@@ -636,6 +635,190 @@ pub trait RV64GCUser<T> {
     fn remuw(&mut self, rd: Register, rs1: Register, rs2: Register) 
         -> Result<T, Self::Error>;
 
+    /// # Load Reserved Word (RV32A)
+    /// 
+    /// Loads a 32-bit value from the address in rs1, places it in rd and 
+    /// registers a reservation set on the bytes of the loaded value.
+    /// `aq` and `rl` are the acquire and release ordering bits.
+    /// 
+    /// `x[rd] = sext(LoadReserved32(M[x[rs1]]))`
+    fn lr_w(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Store Conditional Word (RV32A)
+    /// 
+    /// Writes the 32-bit value in rs2 to the address in rs1 only if a 
+    /// valid reservation exists on that address, writing 0 to rd on success 
+    /// and 1 on failure. The reservation is invalidated in both cases.
+    /// 
+    /// `x[rd] = StoreConditional32(M[x[rs1]], x[rs2])`
+    fn sc_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Swap Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the value of rs2 at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] SWAP x[rs2]))`
+    fn amoswap_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Add Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, add it to rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] + x[rs2]))`
+    fn amoadd_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Xor Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, xor it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] ^ x[rs2]))`
+    fn amoxor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: And Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, and it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] & x[rs2]))`
+    fn amoand_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Or Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, or it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] | x[rs2]))`
+    fn amoor_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Minimum Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the signed minimum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] MIN x[rs2]))`
+    fn amomin_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Maximum Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the signed maximum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] MAX x[rs2]))`
+    fn amomax_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Minimum Unsigned Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the unsigned minimum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] MINU x[rs2]))`
+    fn amominu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Maximum Unsigned Word (RV32A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the unsigned maximum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = sext(AMO32(M[x[rs1]] MAXU x[rs2]))`
+    fn amomaxu_w(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Load Reserved Doubleword (RV64A)
+    /// 
+    /// Loads a 64-bit value from the address in rs1, places it in rd and 
+    /// registers a reservation set on the bytes of the loaded value.
+    /// `aq` and `rl` are the acquire and release ordering bits.
+    /// 
+    /// `x[rd] = LoadReserved64(M[x[rs1]])`
+    fn lr_d(&mut self, rd: Register, rs1: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Store Conditional Doubleword (RV64A)
+    /// 
+    /// Writes the 64-bit value in rs2 to the address in rs1 only if a 
+    /// valid reservation exists on that address, writing 0 to rd on success 
+    /// and 1 on failure. The reservation is invalidated in both cases.
+    /// 
+    /// `x[rd] = StoreConditional64(M[x[rs1]], x[rs2])`
+    fn sc_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Swap Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the value of rs2 at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] SWAP x[rs2])`
+    fn amoswap_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Add Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, add it to rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] + x[rs2])`
+    fn amoadd_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Xor Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, xor it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] ^ x[rs2])`
+    fn amoxor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: And Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, and it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] & x[rs2])`
+    fn amoand_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Or Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd, or it with rs2 and store the result at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] | x[rs2])`
+    fn amoor_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Minimum Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the signed minimum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] MIN x[rs2])`
+    fn amomin_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Maximum Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the signed maximum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] MAX x[rs2])`
+    fn amomax_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Minimum Unsigned Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the unsigned minimum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] MINU x[rs2])`
+    fn amominu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
+    /// # Atomic Memory Operation: Maximum Unsigned Doubleword (RV64A)
+    /// 
+    /// Atomically load a value from the address in rs1 into rd and store the unsigned maximum between it and rs2 at the same address.
+    /// 
+    /// `x[rd] = AMO64(M[x[rs1]] MAXU x[rs2])`
+    fn amomaxu_d(&mut self, rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool) 
+        -> Result<T, Self::Error>;
+
     /// # Fused Multiply Addition Single Precision (RV32F)
     /// 
     /// Perform single-precision fused multiply addition.
@@ -1037,8 +1220,9 @@ pub trait RV64GCUser<T> {
     /// number in floating-point register rd.
     /// 
     /// `f[rd] = f32_{f64}(f[rs1])`
-    fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister) 
-        -> Result<T, Self::Error>;
+    fn fcvt_s_d(&mut self, 
+        rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode,
+    ) -> Result<T, Self::Error>;
 
     /// # Float Convert Single Precision to Double Precision (RV32D)
     /// 
@@ -1147,7 +1331,7 @@ pub trait RV64GCUser<T> {
     /// floating-point register rd.
     /// 
     /// `f[rd] = M[x[rs1] + sext(offset)][31:0]`
-    fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: u32) 
+    fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: i32) 
         -> Result<T, Self::Error>;
     
     /// # Float Store Single Precision (RV32F)
@@ -1156,7 +1340,7 @@ pub trait RV64GCUser<T> {
     /// memory.
     /// 
     /// `M[x[rs1] + sext(offset)] = f[rs2][31:0]`
-    fn fsw(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) 
+    fn fsw(&mut self, rs1: Register, rs2: FloatRegister, offset: i32) 
         -> Result<T, Self::Error>;
 
     /// # Float Load Double Precision (RV32D)
@@ -1165,7 +1349,7 @@ pub trait RV64GCUser<T> {
     /// floating-point register rd.
     /// 
     /// `f[rd] = M[x[rs1] + sext(offset)][63:0]`
-    fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: u32) 
+    fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: i32) 
         -> Result<T, Self::Error>;
     
     /// # Float Store Double Precision (RV32D)
@@ -1174,25 +1358,27 @@ pub trait RV64GCUser<T> {
     /// memory.
     /// 
     /// `M[x[rs1] + sext(offset)] = f[rs2][63:0]`
-    fn fsd(&mut self, rs1: Register, rs2: FloatRegister, offset: u32) 
+    fn fsd(&mut self, rs1: Register, rs2: FloatRegister, offset: i32) 
         -> Result<T, Self::Error>;
 
-    /// # Float Convert ? (RV64F)
+    /// # Float Convert to Long from Single Precision (RV64F)
     /// 
-    /// ?
+    /// Convert the single-precision value in rs1 to a signed 64-bit integer
+    /// using the rounding mode rm, saturating if out of range.
     /// 
     /// `x[rd] = s64_{f32}(f[rs1])`
     fn fcvt_l_s(&mut self, 
         rd: Register, rs1: FloatRegister, rm: FloatRoundingMode,
     ) -> Result<T, Self::Error>;
 
-    /// # Float Convert ? (RV64F)
+    /// # Float Convert to Long Unsigned from Single Precision (RV64F)
     /// 
-    /// ?
+    /// Convert the single-precision value in rs1 to an unsigned 64-bit 
+    /// integer using the rounding mode rm, saturating if out of range.
     /// 
-    /// `x[rd] = s64_{f32}(f[rs1])`
+    /// `x[rd] = u64_{f32}(f[rs1])`
     fn fcvt_lu_s(&mut self, 
-        rd: FloatRegister, rs1: Register, rm: FloatRoundingMode,
+        rd: Register, rs1: FloatRegister, rm: FloatRoundingMode,
     ) -> Result<T, Self::Error>;
 
     /// # Float Convert ? (RV64F)
@@ -1290,7 +1476,7 @@ pub trait RV64GCUser<T> {
     /// `f[8+rd'] = M[x[8+rs1'] + uimm][63:0]`
     /// Translated:
     /// `f[rd] = M[x[rs1] + uimm][63:0]`
-    fn c_fld(&mut self, rd: Register, rs1: Register, imm: u16) 
+    fn c_fld(&mut self, rd: FloatRegister, rs1: Register, imm: u16) 
         -> Result<T, Self::Error>;
 
     /// # Compact Load Word (RV32C)
@@ -1459,7 +1645,7 @@ pub trait RV64GCUser<T> {
     /// `x[8+rd'] = x[8+rd'] & sext(imm)`
     /// Translated:
     /// `x[rd] = x[rd] & sext(imm)`
-    fn c_andi(&mut self, rd: Register, uimm: u8) -> Result<T, Self::Error>;
+    fn c_andi(&mut self, rd: Register, imm: i8) -> Result<T, Self::Error>;
 
     /// # Compact Sub (RV32C)
     /// 
@@ -1566,7 +1752,7 @@ pub trait RV64GCUser<T> {
     /// scaled by 8, to the stack pointer, x2.
     /// 
     /// `f[rd] = M[x[2] + uimm][63:0]`
-    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<T, Self::Error>;
+    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u16) -> Result<T, Self::Error>;
 
     /// # Compact Load Word from Stack (RV32C)
     /// 
@@ -1585,7 +1771,7 @@ pub trait RV64GCUser<T> {
     /// scaled by 4, to the stack pointer, x2.
     /// 
     /// `f[rd] = M[x[2] + uimm][31:0]`
-    fn c_flwsp(&mut self, rd: Register, uimm: u8) -> Result<T, Self::Error>;
+    fn c_flwsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<T, Self::Error>;
 
     /// # Compact Load Double Word from Stack (RV64C)
     /// 
@@ -1594,7 +1780,7 @@ pub trait RV64GCUser<T> {
     /// scaled by 8, to the stack pointer, x2.
     /// 
     /// `x[rd] = M[x[2] + uimm][63:0]`
-    fn c_ldsp(&mut self, rd: Register, uimm: u8) -> Result<T, Self::Error>;
+    fn c_ldsp(&mut self, rd: Register, uimm: u16) -> Result<T, Self::Error>;
 
     /// # Compact Jump Register (RV32C)
     /// 
@@ -1633,7 +1819,7 @@ pub trait RV64GCUser<T> {
     /// scaled by 8, to the stack pointer, x2.
     /// 
     /// `M[x[2] + uimm][63:0] = f[rs2]`
-    fn c_fsdsp(&mut self, rd: Register, rs2: Register) -> Result<T, Self::Error>;
+    fn c_fsdsp(&mut self, rs2: FloatRegister, uimm: u16) -> Result<T, Self::Error>;
 
     /// # Compact Store Word Stack (RV32C)
     /// 
@@ -1661,7 +1847,7 @@ pub trait RV64GCUser<T> {
     /// scaled by 8, to the stack pointer, x2.
     /// 
     /// `M[x[2] + uimm][63:0] = x[rs2]`
-    fn c_sdsp(&mut self, rs2: Register, uimm: u8) -> Result<T, Self::Error>;
+    fn c_sdsp(&mut self, rs2: Register, uimm: u16) -> Result<T, Self::Error>;
 
 
 
@@ -1711,4 +1897,11 @@ pub trait RV64GCUser<T> {
     /// 
     /// Cause control to be transferred back to the debugging environment.
     fn c_ebreak(&mut self) -> Result<T, Self::Error>;
+
+    /// # Illegal Instruction
+    /// 
+    /// `inst` is reserved or isn't an RV64GC instruction, e.g. the all-zero
+    /// word or a reserved rounding mode. It raises an illegal-instruction
+    /// exception, compact instructions are in the low 16 bits.
+    fn illegal(&mut self, inst: u32) -> Result<T, Self::Error>;
 }
\ No newline at end of file
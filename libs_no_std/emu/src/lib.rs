@@ -4,6 +4,7 @@
 //#![deny(unreachable_patterns)]
 //#![deny(unreachable_code)]
 
+extern crate alloc;
 #[cfg(feature="std")]
 extern crate std;
 
@@ -0,0 +1,488 @@
+//! Cache of predecoded basic blocks for [`CoreEmu`].
+//!
+//! Instead of fetching each instruction through the [`Mmu`] and running the
+//! whole decoder every time it's executed, the straight-line code starting
+//! at a pc is decoded once in a [`Block`] of [`DecodedInst`], a function
+//! pointer to the implementation of the instruction and its operands.
+//! The blocks are recorded while the code runs for the first time, so only
+//! the instructions that are actually reached are decoded.
+//!
+//! The blocks are dropped when [`Mmu::code_version`] changes, i.e. when
+//! executable memory is written, unmapped or has its permissions changed,
+//! and on `fence.i`. Forks share the blocks of their parent read-only and
+//! keep the ones they decode for themselves.
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::intrinsics::unlikely;
+use core::convert::Infallible;
+use diss::riscv64gc::*;
+use mmu::{Mmu, MmuError, PermField, VirtAddr};
+use super::{CoreEmu, CoreEmuError};
+
+/// Maximum number of instructions in a block
+pub const MAX_BLOCK_LEN: usize = 64;
+
+/// Number of entries of the direct mapped table checked before the map of
+/// all the blocks, must be a power of two
+const JUMP_TABLE_SIZE: usize = 1 << 12;
+
+/// The most arguments an instruction has, e.g. `fmadd.s rd, rs1, rs2, rs3, rm`
+const MAX_OPERANDS: usize = 5;
+
+type Handler = fn(&mut CoreEmu, &[u32; MAX_OPERANDS]) -> Result<(), CoreEmuError>;
+
+/// An instruction argument that can be stored in a [`DecodedInst`]
+trait Operand: Copy {
+    fn pack(self) -> u32;
+    fn unpack(value: u32) -> Self;
+}
+
+impl Operand for Register {
+    #[inline(always)]
+    fn pack(self) -> u32 { self.into() }
+    #[inline(always)]
+    fn unpack(value: u32) -> Self { value.into() }
+}
+
+impl Operand for FloatRegister {
+    #[inline(always)]
+    fn pack(self) -> u32 { self.into() }
+    #[inline(always)]
+    fn unpack(value: u32) -> Self { value.into() }
+}
+
+impl Operand for FloatRoundingMode {
+    #[inline(always)]
+    fn pack(self) -> u32 { self.into() }
+    #[inline(always)]
+    fn unpack(value: u32) -> Self { value.try_into().unwrap() }
+}
+
+impl Operand for bool {
+    #[inline(always)]
+    fn pack(self) -> u32 { self as u32 }
+    #[inline(always)]
+    fn unpack(value: u32) -> Self { value != 0 }
+}
+
+/// Implement [`Operand`] for integers, which are truncated back
+macro_rules! impl_operand {
+    ($($ty:ty),*) => {
+$(
+impl Operand for $ty {
+    #[inline(always)]
+    fn pack(self) -> u32 { self as u32 }
+    #[inline(always)]
+    fn unpack(value: u32) -> Self { value as $ty }
+}
+)*
+    };
+}
+
+impl_operand!{
+    u8, u16, u32, i8, i16, i32
+}
+
+/// An instruction ready to be executed
+#[derive(Debug, Clone, Copy)]
+pub struct DecodedInst {
+    handler: Handler,
+    operands: [u32; MAX_OPERANDS],
+    /// The raw instruction, compressed ones are in the low 16 bits
+    pub inst: u32,
+}
+
+impl DecodedInst {
+    /// Decode `inst`, also returning if the block ends after it
+    #[inline]
+    pub fn decode(inst: u32) -> (Self, bool) {
+        let (mut decoded, ends_block) = match diss_riscv64gc(&mut Decoder, inst) {
+            Ok(decoded) => decoded,
+            Err(never) => match never {},
+        };
+        decoded.inst = inst;
+        (decoded, ends_block)
+    }
+
+    /// Size in bytes of the instruction
+    #[inline(always)]
+    pub fn len(&self) -> u64 {
+        if self.inst & 0b11 == 0b11 { 4 } else { 2 }
+    }
+
+    /// If the instruction loads from or stores to memory, atomics included
+    #[inline(always)]
+    pub fn accesses_memory(&self) -> bool {
+        match self.inst & 0b11 {
+            // loads, stores and atomics, the float ones too
+            0b11 => matches!(self.inst & 0x7f, 0b000_0011 | 0b000_0111 | 0b010_0011 | 0b010_0111 | 0b010_1111),
+            // quadrant 1 has no memory accesses
+            0b01 => false,
+            // all but c.addi4spn, c.slli and c.jr, c.mv, c.add and c.ebreak
+            _ => (self.inst >> 13) & 0b11 != 0,
+        }
+    }
+
+    /// Execute the instruction on `core`, exactly like the decoder would
+    #[inline(always)]
+    pub fn execute(&self, core: &mut CoreEmu) -> Result<(), CoreEmuError> {
+        (self.handler)(core, &self.operands)
+    }
+}
+
+/// Decodes the instructions into [`DecodedInst`], also returning if the
+/// block ends after them
+struct Decoder;
+
+/// Implement [`RV64GCUser`] for [`Decoder`] building the handlers that call
+/// the same method on [`CoreEmu`]. The first list has the instructions that
+/// can change the control flow or stop the emulation, so they end the block
+macro_rules! impl_decoder {
+    (
+        [$($end:ident($($end_arg:ident: $end_ty:ty),*);)*]
+        [$($name:ident($($arg:ident: $ty:ty),*);)*]
+    ) => {
+impl RV64GCUser<(DecodedInst, bool)> for Decoder {
+    type Error = Infallible;
+    $(
+    impl_decoder!(@inst $end, true, $($end_arg: $end_ty),*);
+    )*
+    $(
+    impl_decoder!(@inst $name, false, $($arg: $ty),*);
+    )*
+}
+    };
+    (@inst $name:ident, $ends_block:expr, $($arg:ident: $ty:ty),*) => {
+    #[allow(unused_mut, unused_variables, unused_assignments)]
+    fn $name(&mut self, $($arg: $ty),*) -> Result<(DecodedInst, bool), Self::Error> {
+        let mut operands = [0; MAX_OPERANDS];
+        let mut idx = 0;
+        $(
+            operands[idx] = $arg.pack();
+            idx += 1;
+        )*
+        let handler: Handler = |core, operands| {
+            let mut idx = 0;
+            $(
+                let $arg = <$ty>::unpack(operands[idx]);
+                idx += 1;
+            )*
+            <CoreEmu as RV64GCUser<()>>::$name(core, $($arg),*)
+        };
+        Ok((DecodedInst { handler, operands, inst: 0 }, $ends_block))
+    }
+    };
+}
+
+impl_decoder!{
+    [
+        jal(rd: Register, imm: i32);
+        jalr(rd: Register, rs1: Register, imm: i32);
+        beq(rs1: Register, rs2: Register, imm: i32);
+        bne(rs1: Register, rs2: Register, imm: i32);
+        blt(rs1: Register, rs2: Register, imm: i32);
+        bge(rs1: Register, rs2: Register, imm: i32);
+        bltu(rs1: Register, rs2: Register, imm: i32);
+        bgeu(rs1: Register, rs2: Register, imm: i32);
+        c_jal(imm: u16);
+        c_j(imm: i16);
+        c_beqz(rs1: Register, offset: i16);
+        c_bnez(rs1: Register, offset: i16);
+        c_jr(rs1: Register);
+        c_jalr(rs1: Register);
+        fence();
+        fence_i();
+        ecall();
+        ebreak();
+        c_ebreak();
+        illegal(inst: u32);
+    ]
+    [
+        lui(rd: Register, imm: u32);
+        auipc(rd: Register, imm: u32);
+        addi(rd: Register, rs1: Register, imm: i32);
+        slti(rd: Register, rs1: Register, imm: i32);
+        sltiu(rd: Register, rs1: Register, imm: u32);
+        xori(rd: Register, rs1: Register, imm: i32);
+        ori(rd: Register, rs1: Register, imm: i32);
+        andi(rd: Register, rs1: Register, imm: i32);
+        slli(rd: Register, rs1: Register, shamt: i32);
+        srli(rd: Register, rs1: Register, shamt: i32);
+        srai(rd: Register, rs1: Register, shamt: i32);
+        add(rd: Register, rs1: Register, rs2: Register);
+        sub(rd: Register, rs1: Register, rs2: Register);
+        sll(rd: Register, rs1: Register, rs2: Register);
+        slt(rd: Register, rs1: Register, rs2: Register);
+        sltu(rd: Register, rs1: Register, rs2: Register);
+        xor(rd: Register, rs1: Register, rs2: Register);
+        srl(rd: Register, rs1: Register, rs2: Register);
+        sra(rd: Register, rs1: Register, rs2: Register);
+        or(rd: Register, rs1: Register, rs2: Register);
+        and(rd: Register, rs1: Register, rs2: Register);
+        csrrw(rd: Register, rs1: Register, csr: u32);
+        csrrs(rd: Register, rs1: Register, csr: u32);
+        csrrc(rd: Register, rs1: Register, csr: u32);
+        csrrwi(rd: Register, zimm: u8, csr: u32);
+        csrrsi(rd: Register, zimm: u8, csr: u32);
+        csrrci(rd: Register, zimm: u8, csr: u32);
+        lb(rd: Register, rs1: Register, imm: i32);
+        lh(rd: Register, rs1: Register, imm: i32);
+        lw(rd: Register, rs1: Register, imm: i32);
+        ld(rd: Register, rs1: Register, imm: i32);
+        lbu(rd: Register, rs1: Register, imm: i32);
+        lhu(rd: Register, rs1: Register, imm: i32);
+        lwu(rd: Register, rs1: Register, imm: i32);
+        sb(rs1: Register, rs2: Register, imm: i32);
+        sh(rs1: Register, rs2: Register, imm: i32);
+        sw(rs1: Register, rs2: Register, imm: i32);
+        sd(rs1: Register, rs2: Register, imm: i32);
+        addiw(rd: Register, rs1: Register, imm: i32);
+        slliw(rd: Register, rs1: Register, shamt: i32);
+        srliw(rd: Register, rs1: Register, shamt: i32);
+        sraiw(rd: Register, rs1: Register, shamt: i32);
+        addw(rd: Register, rs1: Register, rs2: Register);
+        subw(rd: Register, rs1: Register, rs2: Register);
+        sllw(rd: Register, rs1: Register, rs2: Register);
+        srlw(rd: Register, rs1: Register, rs2: Register);
+        sraw(rd: Register, rs1: Register, rs2: Register);
+        mul(rd: Register, rs1: Register, rs2: Register);
+        mulh(rd: Register, rs1: Register, rs2: Register);
+        mulhsu(rd: Register, rs1: Register, rs2: Register);
+        mulhu(rd: Register, rs1: Register, rs2: Register);
+        div(rd: Register, rs1: Register, rs2: Register);
+        divu(rd: Register, rs1: Register, rs2: Register);
+        rem(rd: Register, rs1: Register, rs2: Register);
+        remu(rd: Register, rs1: Register, rs2: Register);
+        mulw(rd: Register, rs1: Register, rs2: Register);
+        divw(rd: Register, rs1: Register, rs2: Register);
+        divuw(rd: Register, rs1: Register, rs2: Register);
+        remw(rd: Register, rs1: Register, rs2: Register);
+        remuw(rd: Register, rs1: Register, rs2: Register);
+        lr_w(rd: Register, rs1: Register, aq: bool, rl: bool);
+        sc_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoswap_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoadd_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoxor_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoand_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoor_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomin_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomax_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amominu_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomaxu_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        lr_d(rd: Register, rs1: Register, aq: bool, rl: bool);
+        sc_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoswap_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoadd_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoxor_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoand_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoor_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomin_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomax_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amominu_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomaxu_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        fmadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fmsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fmul_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fdiv_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsqrt_s(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fsgnj_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjn_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjx_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmin_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmax_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fcvt_w_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_wu_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fmv_x_w(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        feq_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        flt_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fle_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fclass_s(rd: Register, rs1: FloatRegister);
+        fcvt_s_w(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_s_wu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fmv_w_x(rd: FloatRegister, rs1: Register);
+        fmadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fmsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fmul_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fdiv_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsqrt_d(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fsgnj_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjn_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjx_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmin_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmax_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fcvt_s_d(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_s(rd: FloatRegister, rs1: FloatRegister);
+        feq_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        flt_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fle_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fclass_d(rd: Register, rs1: FloatRegister);
+        fcvt_w_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_wu_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_w(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_d_wu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        flw(rd: FloatRegister, rs1: Register, imm: i32);
+        fsw(rs1: Register, rs2: FloatRegister, offset: i32);
+        fld(rd: FloatRegister, rs1: Register, offset: i32);
+        fsd(rs1: Register, rs2: FloatRegister, offset: i32);
+        fcvt_l_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_lu_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_s_l(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_s_lu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_l_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_lu_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fmv_x_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_l(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_d_lu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fmv_d_x(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        c_addi4spn(rd: Register, uimm: u16);
+        c_fld(rd: FloatRegister, rs1: Register, imm: u16);
+        c_lw(rd: Register, rs1: Register, uimm: u16);
+        c_flw(rd: FloatRegister, rs1: Register, uimm: u16);
+        c_ld(rd: Register, rs1: Register, uimm: u16);
+        c_fsd(rs1: Register, rs2: FloatRegister, uimm: u16);
+        c_sw(rs1: Register, rs2: Register, uimm: u16);
+        c_fsw(rs1: Register, rs2: FloatRegister, uimm: u8);
+        c_sd(rs1: Register, rs2: Register, uimm: u16);
+        c_addi(rd: Register, imm: i8);
+        c_addiw(rd: Register, imm: i8);
+        c_li(rd: Register, imm: i8);
+        c_addi16sp(imm: i16);
+        c_lui(rd: Register, imm: i32);
+        c_srli(rd: Register, uimm: u8);
+        c_srai(rd: Register, uimm: u8);
+        c_andi(rd: Register, imm: i8);
+        c_sub(rd: Register, rs2: Register);
+        c_xor(rd: Register, rs2: Register);
+        c_or(rd: Register, rs2: Register);
+        c_and(rd: Register, rs2: Register);
+        c_subw(rd: Register, rs2: Register);
+        c_addw(rd: Register, rs2: Register);
+        c_slli(rd: Register, uimm: u8);
+        c_fldsp(rd: FloatRegister, uimm: u16);
+        c_lwsp(rd: Register, uimm: u8);
+        c_flwsp(rd: FloatRegister, uimm: u8);
+        c_ldsp(rd: Register, uimm: u16);
+        c_mv(rs1: Register, rs2: Register);
+        c_add(rd: Register, rs2: Register);
+        c_fsdsp(rs2: FloatRegister, uimm: u16);
+        c_swsp(rs2: Register, uimm: u8);
+        c_fswsp(rs2: FloatRegister, uimm: u8);
+        c_sdsp(rs2: Register, uimm: u16);
+        c_nop();
+    ]
+}
+
+/// Straight-line code starting at `pc`, only the last instruction can jump
+#[derive(Debug, Clone)]
+pub struct Block {
+    pub pc: u64,
+    pub insts: Vec<DecodedInst>,
+}
+
+/// Read the instruction at `pc`, which must be readable and executable
+pub(crate) fn fetch(mem: &mut Mmu, pc: u64) -> Result<u32, MmuError> {
+    let perm = PermField::Read | PermField::Executable;
+    // read the first half so a compressed instruction at the end of
+    // a segment doesn't fault
+    let low: u16 = unsafe{mem.read_with_perm(VirtAddr(pc as usize), perm)}?;
+    if low & 0b11 != 0b11 {
+        return Ok(low as u32);
+    }
+    let high: u16 = unsafe{mem.read_with_perm(VirtAddr(pc as usize + 2), perm)}?;
+    Ok(low as u32 | ((high as u32) << 16))
+}
+
+/// The blocks decoded by an emulator, see the module documentation
+#[derive(Debug)]
+pub struct BlockCache {
+    /// All the blocks by pc, shared with the forks until one of them adds
+    /// or drops a block
+    blocks: Arc<BTreeMap<u64, Arc<Block>>>,
+    /// Direct mapped table of the last blocks looked up, empty until used
+    jump_table: Vec<Option<Arc<Block>>>,
+    /// The [`Mmu::code_version`] the blocks were decoded at
+    code_version: u64,
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        BlockCache {
+            blocks: Arc::default(),
+            jump_table: Vec::new(),
+            code_version: 0,
+        }
+    }
+}
+
+impl Clone for BlockCache {
+    /// Share the blocks, the jump table is rebuilt on demand
+    fn clone(&self) -> Self {
+        BlockCache {
+            blocks: self.blocks.clone(),
+            jump_table: Vec::new(),
+            code_version: self.code_version,
+        }
+    }
+}
+
+impl BlockCache {
+    /// Number of blocks in the cache
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Drop all the blocks, the forks sharing them are not affected
+    pub fn clear(&mut self) {
+        self.blocks = Arc::default();
+        self.jump_table.clear();
+    }
+
+    /// Check if the blocks were decoded from the current code in `mem`
+    #[inline(always)]
+    pub fn is_valid(&self, mem: &Mmu) -> bool {
+        self.code_version == mem.code_version
+    }
+
+    /// Get the block starting at `pc`, if it was already decoded from the
+    /// current code in `mem`
+    #[inline]
+    pub fn get(&mut self, mem: &Mmu, pc: u64) -> Option<Arc<Block>> {
+        if unlikely(!self.is_valid(mem)) {
+            self.clear();
+            self.code_version = mem.code_version;
+        }
+        if unlikely(self.jump_table.is_empty()) {
+            self.jump_table.resize(JUMP_TABLE_SIZE, None);
+        }
+
+        // instructions are at least 2 bytes aligned
+        let slot = (pc as usize >> 1) & (JUMP_TABLE_SIZE - 1);
+        if let Some(block) = &self.jump_table[slot] {
+            if block.pc == pc {
+                return Some(block.clone());
+            }
+        }
+
+        let block = self.blocks.get(&pc)?.clone();
+        self.jump_table[slot] = Some(block.clone());
+        Some(block)
+    }
+
+    /// Add a block decoded from the current code
+    pub fn insert(&mut self, block: Block) {
+        Arc::make_mut(&mut self.blocks).insert(block.pc, Arc::new(block));
+    }
+}
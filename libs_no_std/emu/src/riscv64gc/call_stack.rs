@@ -0,0 +1,79 @@
+//! Shadow call stack of the emulated program.
+//!
+//! The calls are the `jal`, `jalr` and `c.jalr` that write `ra`, and the
+//! returns are the jumps to `ra` that don't link (`ret`). A return pops the
+//! frames up to the one returning where it jumps, so the frames skipped by
+//! a `longjmp` are dropped too, while the returns to an address no frame
+//! returns to, e.g. from a function called before the tracking started,
+//! are ignored.
+use alloc::vec::Vec;
+
+/// The most frames kept, the deeper calls are only counted
+pub const MAX_CALL_DEPTH: usize = 1 << 16;
+
+/// A call that didn't return yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// pc of the call instruction
+    pub call_site: u64,
+    /// Address of the function called
+    pub function: u64,
+    /// Where the function returns
+    pub return_address: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CallStack {
+    /// The outermost first
+    frames: Vec<Frame>,
+    /// Calls deeper than [`MAX_CALL_DEPTH`]
+    overflow: usize,
+}
+
+impl CallStack {
+    /// Record a call from `call_site` to `function`
+    #[inline]
+    pub fn call(&mut self, call_site: u64, function: u64, return_address: u64) {
+        if self.frames.len() < MAX_CALL_DEPTH {
+            self.frames.push(Frame { call_site, function, return_address });
+        } else {
+            self.overflow += 1;
+        }
+    }
+
+    /// Record a return to `target`
+    #[inline]
+    pub fn ret(&mut self, target: u64) {
+        if self.overflow != 0 {
+            self.overflow -= 1;
+            return;
+        }
+        if let Some(idx) = self.frames.iter()
+            .rposition(|frame| frame.return_address == target) {
+            self.frames.truncate(idx);
+        }
+    }
+
+    /// The calls that didn't return yet, the outermost first
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// Number of calls that didn't return yet
+    pub fn depth(&self) -> usize {
+        self.frames.len() + self.overflow
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.overflow = 0;
+    }
+
+    /// The pcs of the backtrace of a program at `pc`: `pc` and then the
+    /// call sites, the innermost first
+    pub fn backtrace(&self, pc: u64) -> Vec<u64> {
+        core::iter::once(pc)
+            .chain(self.frames.iter().rev().map(|frame| frame.call_site))
+            .collect()
+    }
+}
@@ -2,6 +2,12 @@ use core::intrinsics::unlikely;
 use diss::riscv64gc::*;
 use mmu::{Mmu, VirtAddr, MmuError, PermField};
 use traits::{Word, Number};
+use super::softfloat::{self, F32, F64, FloatFormat};
+use super::{Block, BlockCache, CallStack, Coverage, DataRace, DecodedInst, MemoryAccess, RaceDetector, TaintTracker, UninitOrigin, UninitTracker, MAX_BLOCK_LEN};
+use super::block_cache::fetch;
+#[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+use super::Jit;
+use alloc::vec::Vec;
 
 #[derive(Debug)]
 pub enum CoreEmuError {
@@ -15,96 +21,192 @@ pub enum CoreEmuError {
     RegWrite,
     /// Yield execution, for multithreading mainly
     Yield,
+    /// The instruction can't be executed in the current state, e.g. a
+    /// dynamic rounding mode with a reserved value in `frm`
+    IllegalInstruction,
+    /// Access to a CSR that isn't implemented
+    UnknownCsr(u32),
+    /// An atomic memory operation on an address not aligned to its size
+    MisalignedAtomic(u64),
+    /// The instruction limit or the deadline was reached, the instruction at
+    /// `pc` wasn't executed so the execution can be resumed
+    Timeout,
+    /// The race detector found two conflicting accesses, the second one
+    /// was executed
+    DataRace(DataRace),
 }
 
+/// How many instructions are executed between two checks of the deadline,
+/// a power of two so that the check is a mask
+#[cfg(feature="std")]
+pub const DEADLINE_POLL_INTERVAL: usize = 1 << 16;
+
 impl From<MmuError> for CoreEmuError {
     fn from(value: MmuError) -> Self {
         CoreEmuError::MmuError(value)
     }
 } 
 
+/// How [`CoreEmu::run`] executes the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Execute the decoded blocks one instruction at a time
+    #[default]
+    Interpreter,
+    /// Translate the decoded blocks to x86_64, see [`Jit`]
+    #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+    Jit,
+}
+
 pub struct CoreEmu {
     pub regs: [u64; 32],
-    pub fregs: [f64; 32],
-//  pub csr: u64,
-//  pub fcsr: u64,
+    /// Raw bits of the float registers, singles are NaN-boxed
+    pub fregs: [u64; 32],
+    /// fflags in bits 0..5 and frm in bits 5..8
+    pub fcsr: u32,
+    /// Ticks added to the virtual clock read by `rdtime`, so that e.g. a
+    /// sleeping syscall can advance the time
+    pub time_offset: u64,
     pub pc: u64,
     pub mem: Mmu,
     pub instructions_executed: usize,
+    /// Reservation set registered by the last `lr`, as address and size.
+    /// It's per hart, so a scheduler running multiple `CoreEmu` on a shared
+    /// memory has to call `invalidate_reservation` on the other harts when
+    /// one of them writes to memory
+    pub reservation: Option<(u64, usize)>,
+    /// Stop with [`CoreEmuError::Timeout`] once `instructions_executed`
+    /// reaches this value
+    pub instruction_limit: usize,
+    /// Stop with [`CoreEmuError::Timeout`] after this instant, it's checked 
+    /// every [`DEADLINE_POLL_INTERVAL`] instructions. It's not restored by
+    /// `reset`, see [`CoreEmu::set_time_budget`]
+    #[cfg(feature="std")]
+    pub deadline: Option<std::time::Instant>,
+    /// The decoded code, shared with the forks. It's not restored by `reset`
+    /// as it's dropped by itself when the code changes
+    pub block_cache: BlockCache,
+    /// The edges taken by the branches and jumps, if set. It's not restored
+    /// by `reset` so that it accumulates across fuzz cases
+    pub coverage: Option<Coverage>,
+    /// The calls that didn't return yet, if set
+    pub call_stack: Option<CallStack>,
+    /// Stop with [`CoreEmuError::Yield`] after every instruction that
+    /// accesses memory, so that a scheduler can switch thread between any
+    /// two accesses. [`LinuxEmu`](super::LinuxEmu) sets it while the process
+    /// has more than one thread. The JIT is not used while it's set
+    pub memory_yields: bool,
+    /// Check the memory accesses for data races between the threads, if
+    /// set. The JIT is not used while it's set
+    pub race_detector: Option<RaceDetector>,
+    /// Track which bytes of the input flowed into each register and byte of
+    /// memory, if set. The JIT is not used while it's set
+    pub taint: Option<TaintTracker>,
+    /// Record the origin of the uninitialized memory, if set. The JIT is not
+    /// used while it's set
+    pub uninit: Option<UninitTracker>,
+    /// Can be changed between two runs, e.g. to diff the backends
+    pub backend: Backend,
+    /// The native code, forks start without it
+    #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+    pub jit: Jit,
 }
 
 impl CoreEmu {
     pub fn new(mem: Mmu) -> Self {
         CoreEmu {
             regs: [0; 32],
-            fregs: [0.0; 32],
+            fregs: [0; 32],
+            fcsr: 0,
+            time_offset: 0,
             pc: 0,
             mem,
             instructions_executed: 0,
+            reservation: None,
+            instruction_limit: usize::MAX,
+            #[cfg(feature="std")]
+            deadline: None,
+            block_cache: BlockCache::default(),
+            coverage: None,
+            call_stack: None,
+            memory_yields: false,
+            race_detector: None,
+            taint: None,
+            uninit: None,
+            backend: Backend::default(),
+            #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+            jit: Jit::default(),
         }
     }
 
     #[cfg(feature="std")]
     pub fn debug(&self) {
-        println!("PC: {:>16x} Zero: {:>16x}", 
+        let mut debug = alloc::string::String::new();
+        self.write_debug(&mut debug).unwrap();
+        print!("{}", debug);
+    }
+
+    /// Write the registers printed by [`CoreEmu::debug`] to `w`
+    pub fn write_debug<W: core::fmt::Write>(&self, w: &mut W) -> core::fmt::Result {
+        writeln!(w, "PC: {:>16x} Zero: {:>16x}", 
             self.pc, 
             self.read_reg(Register::Zero),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "Ra: {:>16x} Sp: {:>16x} Gp : {:>16x} Tp : {:>16x}", 
             self.read_reg(Register::Ra),
             self.read_reg(Register::Sp),
             self.read_reg(Register::Gp),
             self.read_reg(Register::Tp),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "T0: {:>16x} T1: {:>16x} T2 : {:>16x} T3 : {:>16x}", 
             self.read_reg(Register::T0),
             self.read_reg(Register::T1),
             self.read_reg(Register::T2),
             self.read_reg(Register::T3),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "T4: {:>16x} T5: {:>16x} T6 : {:>16x}", 
             self.read_reg(Register::T4),
             self.read_reg(Register::T5),
             self.read_reg(Register::T6),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "A0: {:>16x} A1: {:>16x} A2 : {:>16x} A3 : {:>16x}", 
             self.read_reg(Register::A0),
             self.read_reg(Register::A1),
             self.read_reg(Register::A2),
             self.read_reg(Register::A3),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "A4: {:>16x} A5: {:>16x} A6 : {:>16x} A7 : {:>16x}", 
             self.read_reg(Register::A4),
             self.read_reg(Register::A5),
             self.read_reg(Register::A6),
             self.read_reg(Register::A7),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "S0: {:>16x} S1: {:>16x} S2 : {:>16x} S3 : {:>16x}", 
             self.read_reg(Register::S0),
             self.read_reg(Register::S1),
             self.read_reg(Register::S2),
             self.read_reg(Register::S3),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "S4: {:>16x} S5: {:>16x} S6 : {:>16x} S7 : {:>16x}", 
             self.read_reg(Register::S4),
             self.read_reg(Register::S5),
             self.read_reg(Register::S6),
             self.read_reg(Register::S7),
-        );
-        println!(
+        )?;
+        writeln!(w,
             "S8: {:>16x} S9: {:>16x} S10: {:>16x} S11: {:>16x}", 
             self.read_reg(Register::S8),
             self.read_reg(Register::S9),
             self.read_reg(Register::S10),
             self.read_reg(Register::S11),
-        );
+        )
     }
 
     #[inline(always)]
@@ -123,51 +225,347 @@ impl CoreEmu {
     }
 
     #[inline(always)]
-    pub fn read_freg(&self, reg: FloatRegister) -> f64 {
+    pub fn read_freg(&self, reg: FloatRegister) -> u64 {
         self.fregs[reg as usize]
     }
 
     #[inline(always)]
-    pub fn write_freg(&mut self, reg: FloatRegister, value: f64) {
+    pub fn write_freg(&mut self, reg: FloatRegister, value: u64) {
         self.fregs[reg as usize] = value;
     }
 
+    /// Read a single precision value, if it's not properly NaN-boxed the
+    /// spec says we have to see it as the canonical NaN
+    #[inline(always)]
+    pub fn read_freg_s(&self, reg: FloatRegister) -> u64 {
+        let value = self.fregs[reg as usize];
+        if value >> 32 == 0xffff_ffff {
+            value & 0xffff_ffff
+        } else {
+            F32::CANONICAL_NAN
+        }
+    }
+
+    /// Write a single precision value NaN-boxing it
+    #[inline(always)]
+    pub fn write_freg_s(&mut self, reg: FloatRegister, value: u64) {
+        self.fregs[reg as usize] = 0xffff_ffff_0000_0000 | (value & 0xffff_ffff);
+    }
+
+    /// Drop the reservation set, so the next `sc` will fail
+    #[inline(always)]
+    pub fn clear_reservation(&mut self) {
+        self.reservation = None;
+    }
+
+    /// Drop the reservation set if it overlaps `size` bytes at `addr`,
+    /// this is how a store from another hart is observed
+    #[inline(always)]
+    pub fn invalidate_reservation(&mut self, addr: u64, size: usize) {
+        if let Some((res_addr, res_size)) = self.reservation {
+            if addr < res_addr + res_size as u64 && res_addr < addr + size as u64 {
+                self.reservation = None;
+            }
+        }
+    }
+
+    /// Compute the address of an atomic operation checking its alignment
+    #[inline(always)]
+    fn atomic_addr(&self, rs1: Register, size: usize) -> Result<u64, CoreEmuError> {
+        let addr = self.read_reg(rs1);
+        if unlikely(addr & (size as u64 - 1) != 0) {
+            return Err(CoreEmuError::MisalignedAtomic(addr));
+        }
+        Ok(addr)
+    }
+
+    /// Resolve the `DYN` rounding mode using `frm`
+    #[inline(always)]
+    fn rounding_mode(&self, rm: FloatRoundingMode) 
+        -> Result<FloatRoundingMode, CoreEmuError> {
+        match rm {
+            FloatRoundingMode::DYN => match FloatRoundingMode::try_from((self.fcsr >> 5) & 0b111) {
+                Ok(FloatRoundingMode::DYN) | Err(_) => Err(CoreEmuError::IllegalInstruction),
+                Ok(frm) => Ok(frm),
+            },
+            rm => Ok(rm),
+        }
+    }
+
     pub fn fork(&self) -> Self {
         Self {
             regs: self.regs,
             fregs: self.fregs,
+            fcsr: self.fcsr,
+            time_offset: self.time_offset,
             pc: self.pc,
             mem: self.mem.fork(),
             instructions_executed: self.instructions_executed,
+            reservation: self.reservation,
+            instruction_limit: self.instruction_limit,
+            #[cfg(feature="std")]
+            deadline: self.deadline,
+            block_cache: self.block_cache.clone(),
+            coverage: self.coverage.clone(),
+            call_stack: self.call_stack.clone(),
+            memory_yields: self.memory_yields,
+            race_detector: self.race_detector.clone(),
+            taint: self.taint.clone(),
+            uninit: self.uninit.clone(),
+            backend: self.backend,
+            #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+            jit: Jit::default(),
         }
     }
 
     pub fn reset(&mut self, other: &Self) {
         self.regs = other.regs;
         self.fregs = other.fregs;
+        self.fcsr = other.fcsr;
+        self.time_offset = other.time_offset;
+        self.instructions_executed = other.instructions_executed;
         self.pc = other.pc;
+        self.reservation = other.reservation;
+        self.instruction_limit = other.instruction_limit;
+        self.call_stack.clone_from(&other.call_stack);
+        self.race_detector.clone_from(&other.race_detector);
+        self.taint.clone_from(&other.taint);
+        self.uninit.clone_from(&other.uninit);
+        // the version goes back to the one of `other`, so the next write of
+        // the code could reuse the version of the blocks decoded since the fork
+        if self.mem.code_version != other.mem.code_version {
+            self.block_cache.clear();
+            #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+            self.jit.clear();
+        }
         self.mem.reset(&other.mem);
     }
 
+    /// The pcs of the backtrace, the current one and then the call sites,
+    /// only the current one if the call stack is not tracked
+    pub fn backtrace(&self) -> Vec<u64> {
+        match &self.call_stack {
+            Some(call_stack) => call_stack.backtrace(self.pc),
+            None => alloc::vec![self.pc],
+        }
+    }
 
-    pub fn run(&mut self) -> CoreEmuError {
-        loop {
-            let inst = self.mem.read(
-                VirtAddr(self.pc as usize)
-            );
-            
-            // TODO!: simplify this poopoo
-            if let Err(e) = inst {
-                return e.into();
+
+    /// Record the edge from `from` to the current pc, if the coverage is on
+    #[inline(always)]
+    fn record_edge(&mut self, from: u64) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(from, self.pc);
+        }
+    }
+
+    /// Update the call stack after a jump from `from` that linked in `rd`
+    /// to the address in `rs1`, `Zero` for the direct jumps
+    #[inline(always)]
+    fn record_jump(&mut self, from: u64, rd: Register, rs1: Register) {
+        if let Some(call_stack) = &mut self.call_stack {
+            if rd == Register::Ra {
+                call_stack.call(from, self.pc, self.regs[Register::Ra as usize]);
+            } else if rd == Register::Zero && rs1 == Register::Ra {
+                call_stack.ret(self.pc);
+            }
+        }
+    }
+
+    /// Record a comparison between `lhs` and `rhs` at the current pc, if the
+    /// comparison feedback is on
+    #[inline(always)]
+    fn record_cmp(&mut self, lhs: u64, rhs: u64) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record_cmp(self.pc, lhs, rhs);
+        }
+    }
+
+    /// The access the instruction `inst` is about to do, if the race
+    /// detector or the taint tracking is on
+    #[inline(always)]
+    fn pending_access(&self, inst: &DecodedInst) -> Option<MemoryAccess> {
+        if unlikely(self.race_detector.is_some() || self.taint.is_some())
+            && inst.accesses_memory() {
+            MemoryAccess::decode(inst.inst, &self.regs)
+        } else {
+            None
+        }
+    }
+
+    /// Check `access` done by the instruction at `pc` with the race detector
+    #[inline(never)]
+    fn record_access(&mut self, pc: u64, access: MemoryAccess) -> Result<(), CoreEmuError> {
+        match &mut self.race_detector {
+            Some(detector) => detector.record(&mut self.mem, pc, access)
+                .map_err(CoreEmuError::DataRace),
+            None => Ok(()),
+        }
+    }
+
+    /// Propagate the taint labels through the instruction `inst` at `pc`,
+    /// which did `access`
+    #[inline(never)]
+    fn propagate_taint(&mut self, pc: u64, inst: &DecodedInst, access: Option<MemoryAccess>) {
+        if let Some(taint) = &mut self.taint {
+            taint.propagate(pc, inst.inst, access);
+        }
+    }
+
+    /// Record the stack frame allocated by the instruction at `pc`, which
+    /// lowered `sp` from `old_sp`
+    #[inline(never)]
+    fn record_stack_frame(&mut self, pc: u64, old_sp: u64) {
+        let sp = self.regs[Register::Sp as usize];
+        if let Some(uninit) = &mut self.uninit {
+            uninit.record(sp, old_sp, UninitOrigin::StackFrame { pc });
+        }
+    }
+
+    /// Allow at most `budget` more instructions to execute
+    pub fn set_instruction_budget(&mut self, budget: usize) {
+        self.instruction_limit = self.instructions_executed.saturating_add(budget);
+    }
+
+    /// Stop the execution after `budget` from now, or never if `None`.
+    /// The deadline is not restored by `reset` so it has to be set for every
+    /// fuzz case
+    #[cfg(feature="std")]
+    pub fn set_time_budget(&mut self, budget: Option<std::time::Duration>) {
+        self.deadline = budget.map(|budget| std::time::Instant::now() + budget);
+    }
+
+    /// Check the instruction limit and, every [`DEADLINE_POLL_INTERVAL`]
+    /// instructions, the deadline
+    #[inline(always)]
+    pub(crate) fn budget_exhausted(&self) -> bool {
+        if unlikely(self.instructions_executed >= self.instruction_limit) {
+            return true;
+        }
+        #[cfg(feature="std")]
+        if unlikely(self.instructions_executed & (DEADLINE_POLL_INTERVAL - 1) == 0) {
+            if let Some(deadline) = self.deadline {
+                return std::time::Instant::now() >= deadline;
+            }
+        }
+        false
+    }
+
+    /// Execute the instructions from `pc` decoding them, until the end of
+    /// the block, and add it to the cache. The budget for the first 
+    /// instruction was already checked
+    #[cold]
+    pub(crate) fn run_and_decode(&mut self) -> Option<CoreEmuError> {
+        let mut block = Block { pc: self.pc, insts: Vec::new() };
+        let result = loop {
+            if !block.insts.is_empty() && self.budget_exhausted() {
+                break Some(CoreEmuError::Timeout);
             }
-            let inst: u32 = inst.unwrap();
+            let inst = match fetch(&mut self.mem, self.pc) {
+                Ok(inst) => inst,
+                Err(e) => break Some(e.into()),
+            };
             #[cfg(feature="dbg_prints")]
             {
                 println!("\n{:016x} {:02x?} {}", self.pc, &inst.to_le_bytes(), self.instructions_executed);
                 self.debug();
             }
+            let (inst, ends_block) = DecodedInst::decode(inst);
+            block.insts.push(inst);
             self.instructions_executed += 1;
-            if let Err(e) = diss_riscv64gc(self, inst) {
+            let pc = self.pc;
+            let next_pc = pc + inst.len();
+            let access = self.pending_access(&inst);
+            let sp = self.regs[Register::Sp as usize];
+            if let Err(e) = inst.execute(self) {
+                break Some(e);
+            }
+            if unlikely(self.uninit.is_some()) && self.regs[Register::Sp as usize] < sp {
+                self.record_stack_frame(pc, sp);
+            }
+            if unlikely(self.taint.is_some()) {
+                self.propagate_taint(pc, &inst, access);
+            }
+            if let Some(access) = access {
+                if let Err(e) = self.record_access(pc, access) {
+                    break Some(e);
+                }
+            }
+            if unlikely(self.memory_yields) && inst.accesses_memory() {
+                break Some(CoreEmuError::Yield);
+            }
+            if ends_block || self.pc != next_pc || block.insts.len() == MAX_BLOCK_LEN 
+                || !self.block_cache.is_valid(&self.mem) {
+                break None;
+            }
+        };
+        // don't cache code that was overwritten while running
+        if !block.insts.is_empty() && self.block_cache.is_valid(&self.mem) {
+            self.block_cache.insert(block);
+        }
+        result
+    }
+
+    /// Execute `insts`, the budget for the first one was already checked
+    #[inline(always)]
+    pub(crate) fn run_block(&mut self, insts: &[DecodedInst]) -> Option<CoreEmuError> {
+        for (i, inst) in insts.iter().enumerate() {
+            if i != 0 && self.budget_exhausted() {
+                return Some(CoreEmuError::Timeout);
+            }
+            #[cfg(feature="dbg_prints")]
+            {
+                println!("\n{:016x} {:02x?} {}", self.pc, &inst.inst.to_le_bytes(), self.instructions_executed);
+                self.debug();
+            }
+            self.instructions_executed += 1;
+            let pc = self.pc;
+            let next_pc = pc + inst.len();
+            let access = self.pending_access(inst);
+            let sp = self.regs[Register::Sp as usize];
+            if let Err(e) = inst.execute(self) {
+                return Some(e);
+            }
+            if unlikely(self.uninit.is_some()) && self.regs[Register::Sp as usize] < sp {
+                self.record_stack_frame(pc, sp);
+            }
+            if unlikely(self.taint.is_some()) {
+                self.propagate_taint(pc, inst, access);
+            }
+            if let Some(access) = access {
+                if let Err(e) = self.record_access(pc, access) {
+                    return Some(e);
+                }
+            }
+            if unlikely(self.memory_yields) && inst.accesses_memory() {
+                return Some(CoreEmuError::Yield);
+            }
+            // the rest of the block might have been overwritten
+            if self.pc != next_pc || !self.block_cache.is_valid(&self.mem) {
+                break;
+            }
+        }
+        None
+    }
+
+    pub fn run(&mut self) -> CoreEmuError {
+        #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+        if self.backend == Backend::Jit && !self.memory_yields && self.race_detector.is_none()
+            && self.taint.is_none() && self.uninit.is_none() {
+            return self.run_jit();
+        }
+        loop {
+            if self.budget_exhausted() {
+                return CoreEmuError::Timeout;
+            }
+            let block = match self.block_cache.get(&self.mem, self.pc) {
+                Some(block) => block,
+                None => match self.run_and_decode() {
+                    Some(e) => return e,
+                    None => continue,
+                },
+            };
+            if let Some(e) = self.run_block(&block.insts) {
                 return e;
             }
         }
@@ -200,7 +598,6 @@ impl RV64GCUser<()> for CoreEmu {
     fn auipc(&mut self, rd: Register, imm: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("auipc {:?} {}", rd, imm);
-        let imm = imm << 12;
         self.write_reg(rd, self.pc.wrapping_add_signed(imm as i32 as i64));
         self.pc += 4;
         Ok(())
@@ -217,13 +614,20 @@ impl RV64GCUser<()> for CoreEmu {
     fn slti(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("slti {:?} {:?} {}", rd, rs1, imm);
+        let (lhs, rhs) = (self.read_reg(rs1), imm as i64 as u64);
+        self.record_cmp(lhs, rhs);
+        self.write_reg(rd, ((lhs as i64) < (rhs as i64)) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sltiu(&mut self, rd: Register, rs1: Register, imm: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sltiu {:?} {:?} {}", rd, rs1, imm);
-        todo!();
+        let (lhs, rhs) = (self.read_reg(rs1), imm as i32 as i64 as u64);
+        self.record_cmp(lhs, rhs);
+        self.write_reg(rd, (lhs < rhs) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -294,21 +698,28 @@ impl RV64GCUser<()> for CoreEmu {
     fn sll(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sll {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, self.read_reg(rs1) << (self.read_reg(rs2) & 0b111111));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn slt(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("slt {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        self.write_reg(rd, ((lhs as i64) < (rhs as i64)) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sltu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sltu {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        self.write_reg(rd, (lhs < rhs) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -323,14 +734,16 @@ impl RV64GCUser<()> for CoreEmu {
     fn srl(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("srl {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, self.read_reg(rs1) >> (self.read_reg(rs2) & 0b111111));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sra(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sra {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, ((self.read_reg(rs1) as i64) >> (self.read_reg(rs2) & 0b111111)) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -350,45 +763,60 @@ impl RV64GCUser<()> for CoreEmu {
         Ok(())
     }
     #[inline(always)]
-    fn csrrw(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<(), Self::Error> {
+    fn csrrw(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrw {:?} {:?} {}", rd, rs1, offset);
-        todo!();
+        println!("csrrw {:?} {:?} {:#x}", rd, rs1, csr);
+        let value = self.read_reg(rs1);
+        let old = self.csr_rmw(csr, true, |_| value)?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn csrrs(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<(), Self::Error> {
+    fn csrrs(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrs {:?} {:?} {}", rd, rs1, offset);
-        todo!();
+        println!("csrrs {:?} {:?} {:#x}", rd, rs1, csr);
+        let value = self.read_reg(rs1);
+        let old = self.csr_rmw(csr, rs1 != Register::Zero, |old| old | value)?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn csrrc(&mut self, rd: Register, rs1: Register, offset: u32) -> Result<(), Self::Error> {
+    fn csrrc(&mut self, rd: Register, rs1: Register, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrc {:?} {:?} {}", rd, rs1, offset);
-        todo!();
+        println!("csrrc {:?} {:?} {:#x}", rd, rs1, csr);
+        let value = self.read_reg(rs1);
+        let old = self.csr_rmw(csr, rs1 != Register::Zero, |old| old & !value)?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn csrrwi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<(), Self::Error> {
+    fn csrrwi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrwi {:?} {} {}", rd, zimm, offset);
-        todo!();
+        println!("csrrwi {:?} {} {:#x}", rd, zimm, csr);
+        let old = self.csr_rmw(csr, true, |_| zimm as u64)?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn csrrsi(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<(), Self::Error> {
+    fn csrrsi(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrsi {:?} {} {}", rd, zimm, offset);
-        todo!();
+        println!("csrrsi {:?} {} {:#x}", rd, zimm, csr);
+        let old = self.csr_rmw(csr, zimm != 0, |old| old | zimm as u64)?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn csrrci(&mut self, rd: Register, zimm: u8, offset: u32) -> Result<(), Self::Error> {
+    fn csrrci(&mut self, rd: Register, zimm: u8, csr: u32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("csrrci {:?} {} {}", rd, zimm, offset);
-        todo!();
+        println!("csrrci {:?} {} {:#x}", rd, zimm, csr);
+        let old = self.csr_rmw(csr, zimm != 0, |old| old & !(zimm as u64))?;
+        self.write_reg(rd, old);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -501,88 +929,118 @@ impl RV64GCUser<()> for CoreEmu {
     fn jal(&mut self, rd: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("jal {:?} {}", rd, imm);
+        let from = self.pc;
         // ret addr
         self.write_reg(rd, self.pc.wrapping_add(4));
         // jmp
         self.pc = self.pc.wrapping_add_signed(imm as i64);
+        self.record_edge(from);
+        self.record_jump(from, rd, Register::Zero);
         Ok(())
     }
     #[inline(always)]
     fn jalr(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("jalr {:?} {:?} {}", rd, rs1, imm);
+        let from = self.pc;
         // ret addr
         let ret_addr = self.pc.wrapping_add(4);
         // jmp
         self.pc = self.read_reg(rs1).wrapping_add_signed(imm as i64);
         self.pc &= !1; // se the LSB to 0 for some reason TODO!: needed?
         self.write_reg(rd, ret_addr);
+        self.record_edge(from);
+        self.record_jump(from, rd, rs1);
         Ok(())
     }
     #[inline(always)]
     fn beq(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("beq {:?} {:?} {}", rs1, rs2, imm);
-        if self.read_reg(rs1) == self.read_reg(rs2) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if lhs == rhs {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn bne(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("bne {:?} {:?} {}", rs1, rs2, imm);
-        if self.read_reg(rs1) != self.read_reg(rs2) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if lhs != rhs {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn blt(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("blt {:?} {:?} {}", rs1, rs2, imm);
-        if (self.read_reg(rs1) as i64) < (self.read_reg(rs2) as i64) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if (lhs as i64) < (rhs as i64) {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn bge(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("bge {:?} {:?} {}", rs1, rs2, imm);
-        if (self.read_reg(rs1) as i64) > (self.read_reg(rs2) as i64) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if (lhs as i64) >= (rhs as i64) {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn bltu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("bltu {:?} {:?} {}", rs1, rs2, imm);
-        if self.read_reg(rs1) < self.read_reg(rs2) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if lhs < rhs {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn bgeu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("bgeu {:?} {:?} {}", rs1, rs2, imm);
-        if self.read_reg(rs1) > self.read_reg(rs2) {
+        let from = self.pc;
+        let (lhs, rhs) = (self.read_reg(rs1), self.read_reg(rs2));
+        self.record_cmp(lhs, rhs);
+        if lhs >= rhs {
             self.pc = self.pc.wrapping_add_signed(imm as i64);
         } else {
             self.pc += 4;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
@@ -599,56 +1057,74 @@ impl RV64GCUser<()> for CoreEmu {
     fn slliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("slliw {:?} {:?} {}", rd, rs1, shamt);
-        todo!();
+        self.write_reg(rd, ((self.read_reg(rs1) as u32) << (shamt & 0b11111)) as i32 as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn srliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("srliw {:?} {:?} {}", rd, rs1, shamt);
-        todo!();
+        self.write_reg(rd, ((self.read_reg(rs1) as u32) >> (shamt & 0b11111)) as i32 as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sraiw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sraiw {:?} {:?} {}", rd, rs1, shamt);
-        todo!();
+        self.write_reg(rd, ((self.read_reg(rs1) as i32) >> (shamt & 0b11111)) as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn addw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("addw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            (self.read_reg(rs1) as i32).wrapping_add(self.read_reg(rs2) as i32) as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn subw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("subw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            (self.read_reg(rs1) as i32).wrapping_sub(self.read_reg(rs2) as i32) as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sllw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sllw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            ((self.read_reg(rs1) as u32) << (self.read_reg(rs2) & 0b11111)) as i32 as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn srlw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("srlw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            ((self.read_reg(rs1) as u32) >> (self.read_reg(rs2) & 0b11111)) as i32 as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn sraw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("sraw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            ((self.read_reg(rs1) as i32) >> (self.read_reg(rs2) & 0b11111)) as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -663,28 +1139,38 @@ impl RV64GCUser<()> for CoreEmu {
     fn mulh(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("mulh {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let res = (self.read_reg(rs1) as i64 as i128) * (self.read_reg(rs2) as i64 as i128);
+        self.write_reg(rd, (res >> 64) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn mulhsu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("mulhsu {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let res = (self.read_reg(rs1) as i64 as i128) 
+            * (self.read_reg(rs2) as i128);
+        self.write_reg(rd, (res >> 64) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn mulhu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("mulhu {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let res = (self.read_reg(rs1) as u128) * (self.read_reg(rs2) as u128);
+        self.write_reg(rd, (res >> 64) as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn div(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("div {:?} {:?} {:?}", rd, rs1, rs2);
-        self.write_reg(rd, (self.read_reg(rs1) as i64).wrapping_div(self.read_reg(rs2) as i64) as u64);
+        let (a, b) = (self.read_reg(rs1) as i64, self.read_reg(rs2) as i64);
+        // division by zero gives all ones, overflow gives the most negative value
+        let res = a.checked_div(b).unwrap_or(if b == 0 { -1 } else { i64::MIN });
+        self.write_reg(rd, res as u64);
         self.pc += 4;
         Ok(())
     }
@@ -692,7 +1178,9 @@ impl RV64GCUser<()> for CoreEmu {
     fn divu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("divu {:?} {:?} {:?}", rd, rs1, rs2);
-        self.write_reg(rd, self.read_reg(rs1).wrapping_div(self.read_reg(rs2)));
+        let (a, b) = (self.read_reg(rs1), self.read_reg(rs2));
+        let res = a.checked_div(b).unwrap_or(u64::MAX);
+        self.write_reg(rd, res);
         self.pc += 4;
         Ok(())
     }
@@ -700,7 +1188,10 @@ impl RV64GCUser<()> for CoreEmu {
     fn rem(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("rem {:?} {:?} {:?}", rd, rs1, rs2);
-        self.write_reg(rd, (self.read_reg(rs1) as i64).wrapping_rem(self.read_reg(rs2) as i64) as u64);
+        let (a, b) = (self.read_reg(rs1) as i64, self.read_reg(rs2) as i64);
+        // the remainder of a division by zero is the dividend, overflow gives 0
+        let res = a.checked_rem(b).unwrap_or(if b == 0 { a } else { 0 });
+        self.write_reg(rd, res as u64);
         self.pc += 4;
         Ok(())
     }
@@ -708,7 +1199,9 @@ impl RV64GCUser<()> for CoreEmu {
     fn remu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("remu {:?} {:?} {:?}", rd, rs1, rs2);
-        self.write_reg(rd, self.read_reg(rs1).wrapping_rem(self.read_reg(rs2)));
+        let (a, b) = (self.read_reg(rs1), self.read_reg(rs2));
+        let res = a.checked_rem(b).unwrap_or(a);
+        self.write_reg(rd, res);
         self.pc += 4;
         Ok(())
     }
@@ -716,35 +1209,322 @@ impl RV64GCUser<()> for CoreEmu {
     fn mulw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("mulw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        self.write_reg(rd, 
+            (self.read_reg(rs1) as i32).wrapping_mul(self.read_reg(rs2) as i32) as i64 as u64
+        );
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn divw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("divw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (a, b) = (self.read_reg(rs1) as i32, self.read_reg(rs2) as i32);
+        // division by zero gives all ones, overflow gives the most negative value
+        let res = a.checked_div(b).unwrap_or(if b == 0 { -1 } else { i32::MIN });
+        self.write_reg(rd, res as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn divuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("divuw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (a, b) = (self.read_reg(rs1) as u32, self.read_reg(rs2) as u32);
+        let res = a.checked_div(b).unwrap_or(u32::MAX);
+        self.write_reg(rd, res as i32 as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn remw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("remw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (a, b) = (self.read_reg(rs1) as i32, self.read_reg(rs2) as i32);
+        // the remainder of a division by zero is the dividend, overflow gives 0
+        let res = a.checked_rem(b).unwrap_or(if b == 0 { a } else { 0 });
+        self.write_reg(rd, res as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn remuw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("remuw {:?} {:?} {:?}", rd, rs1, rs2);
-        todo!();
+        let (a, b) = (self.read_reg(rs1) as u32, self.read_reg(rs2) as u32);
+        let res = a.checked_rem(b).unwrap_or(a);
+        self.write_reg(rd, res as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn lr_w(&mut self, rd: Register, rs1: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("lr_w {:?} [{:?}] aq={} rl={}", rd, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.reservation = Some((addr, 4));
+        self.write_reg(rd, res as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn sc_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("sc_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        // the reservation is consumed whether the store succeeds or not
+        let res = if self.reservation.take() == Some((addr, 4)) {
+            self.mem.write(VirtAddr(addr as usize), self.read_reg(rs2) as u32)?;
+            0
+        } else {
+            1
+        };
+        self.write_reg(rd, res);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoswap_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoswap_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), b)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoadd_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoadd_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a.wrapping_add(b))?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoxor_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoxor_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a ^ b)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoand_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoand_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a & b)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoor_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoor_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a | b)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomin_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomin_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), (a as i32).min(b as i32) as u32)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomax_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomax_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), (a as i32).max(b as i32) as u32)?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amominu_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amominu_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a.min(b))?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomaxu_w(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomaxu_w {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 4)?;
+        let a: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2) as u32;
+        self.mem.write(VirtAddr(addr as usize), a.max(b))?;
+        self.write_reg(rd, a as i32 as i64 as u64);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn lr_d(&mut self, rd: Register, rs1: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("lr_d {:?} [{:?}] aq={} rl={}", rd, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let res: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        self.reservation = Some((addr, 8));
+        self.write_reg(rd, res);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn sc_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("sc_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        // the reservation is consumed whether the store succeeds or not
+        let res = if self.reservation.take() == Some((addr, 8)) {
+            self.mem.write(VirtAddr(addr as usize), self.read_reg(rs2))?;
+            0
+        } else {
+            1
+        };
+        self.write_reg(rd, res);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoswap_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoswap_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), b)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoadd_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoadd_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a.wrapping_add(b))?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoxor_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoxor_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a ^ b)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoand_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoand_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a & b)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amoor_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amoor_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a | b)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomin_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomin_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), (a as i64).min(b as i64) as u64)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomax_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomax_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), (a as i64).max(b as i64) as u64)?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amominu_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amominu_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a.min(b))?;
+        self.write_reg(rd, a);
+        self.pc += 4;
+        Ok(())
+    }
+    #[inline(always)]
+    fn amomaxu_d(&mut self, rd: Register, rs1: Register, rs2: Register, _aq: bool, _rl: bool) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("amomaxu_d {:?} {:?} [{:?}] aq={} rl={}", rd, rs2, rs1, _aq, _rl);
+        let addr = self.atomic_addr(rs1, 8)?;
+        let a: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        let b = self.read_reg(rs2);
+        self.mem.write(VirtAddr(addr as usize), a.max(b))?;
+        self.write_reg(rd, a);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -756,7 +1536,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmadd_s {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F32>(
+            self.read_freg_s(rs1),
+            self.read_freg_s(rs2),
+            self.read_freg_s(rs3),
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -768,7 +1560,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmsub_s {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F32>(
+            self.read_freg_s(rs1),
+            self.read_freg_s(rs2),
+            self.read_freg_s(rs3) ^ F32::SIGN_MASK,
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -780,7 +1584,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fnmsub_s {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F32>(
+            self.read_freg_s(rs1) ^ F32::SIGN_MASK,
+            self.read_freg_s(rs2),
+            self.read_freg_s(rs3),
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -792,7 +1608,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fnmadd_s {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F32>(
+            self.read_freg_s(rs1) ^ F32::SIGN_MASK,
+            self.read_freg_s(rs2),
+            self.read_freg_s(rs3) ^ F32::SIGN_MASK,
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -803,7 +1631,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fadd_s {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::add::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -814,7 +1651,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsub_s {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::sub::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -825,7 +1671,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmul_s {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::mul::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -836,7 +1691,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fdiv_s {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::div::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -846,7 +1710,14 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsqrt_s {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::sqrt::<F32>(self.read_freg_s(rs1), rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -856,7 +1727,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnj_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg_s(rs1);
+        let b = self.read_freg_s(rs2);
+        let res = (a & !F32::SIGN_MASK) | ((b) & F32::SIGN_MASK);
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -866,7 +1743,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnjn_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg_s(rs1);
+        let b = self.read_freg_s(rs2);
+        let res = (a & !F32::SIGN_MASK) | ((!b) & F32::SIGN_MASK);
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -876,7 +1759,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnjx_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg_s(rs1);
+        let b = self.read_freg_s(rs2);
+        let res = (a & !F32::SIGN_MASK) | ((a ^ b) & F32::SIGN_MASK);
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -886,7 +1775,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmin_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::min::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -896,7 +1793,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmax_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::max::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -906,7 +1811,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_w_s {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F32>(
+            self.read_freg_s(rs1), true, 32, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -916,7 +1830,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_wu_s {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F32>(
+            self.read_freg_s(rs1), false, 32, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -924,9 +1847,13 @@ impl RV64GCUser<()> for CoreEmu {
         &mut self,
         rd: Register,
         rs1: FloatRegister,
-        rm: FloatRoundingMode,
+        _rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmv_x_w {:?} {:?} {:?}", rd, rs1, _rm);
+        // raw bits, no NaN-boxing check
+        self.write_reg(rd, self.read_freg(rs1) as u32 as i32 as i64 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -936,7 +1863,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("feq_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::eq::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -946,7 +1881,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("flt_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::lt::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -956,12 +1899,27 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fle_s {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::le::<F32>(
+            self.read_freg_s(rs1), self.read_freg_s(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fclass_s(&mut self, rd: Register, rs1: FloatRegister) -> Result<(), Self::Error> {
-        todo!();
+    fn fclass_s(
+        &mut self,
+        rd: Register,
+        rs1: FloatRegister,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fclass_s {:?} {:?}", rd, rs1);
+        self.write_reg(rd, softfloat::classify::<F32>(self.read_freg_s(rs1)));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -971,7 +1929,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_s_w {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let value = self.read_reg(rs1) as i32 as i64;
+        let (negative, magnitude) = (value < 0, value.unsigned_abs());
+        let mut flags = 0;
+        let res = softfloat::from_int::<F32>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -981,12 +1948,27 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_s_wu {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let (negative, magnitude) = (false, self.read_reg(rs1) as u32 as u64);
+        let mut flags = 0;
+        let res = softfloat::from_int::<F32>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fmv_w_x(&mut self, rd: FloatRegister, rs1: Register) -> Result<(), Self::Error> {
-        todo!();
+    fn fmv_w_x(
+        &mut self,
+        rd: FloatRegister,
+        rs1: Register,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fmv_w_x {:?} {:?}", rd, rs1);
+        self.write_freg_s(rd, self.read_reg(rs1) as u32 as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -998,7 +1980,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmadd_d {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F64>(
+            self.read_freg(rs1),
+            self.read_freg(rs2),
+            self.read_freg(rs3),
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1010,7 +2004,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmsub_d {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F64>(
+            self.read_freg(rs1),
+            self.read_freg(rs2),
+            self.read_freg(rs3) ^ F64::SIGN_MASK,
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1022,7 +2028,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fnmsub_d {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F64>(
+            self.read_freg(rs1) ^ F64::SIGN_MASK,
+            self.read_freg(rs2),
+            self.read_freg(rs3),
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1034,7 +2052,19 @@ impl RV64GCUser<()> for CoreEmu {
         rs3: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fnmadd_d {:?} {:?} {:?} {:?} {:?}", rd, rs1, rs2, rs3, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::fma::<F64>(
+            self.read_freg(rs1) ^ F64::SIGN_MASK,
+            self.read_freg(rs2),
+            self.read_freg(rs3) ^ F64::SIGN_MASK,
+            rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1045,7 +2075,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fadd_d {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::add::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1056,7 +2095,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsub_d {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::sub::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1067,7 +2115,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmul_d {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::mul::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1078,7 +2135,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs2: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fdiv_d {:?} {:?} {:?} {:?}", rd, rs1, rs2, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::div::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1088,7 +2154,14 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsqrt_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::sqrt::<F64>(self.read_freg(rs1), rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1098,7 +2171,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnj_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg(rs1);
+        let b = self.read_freg(rs2);
+        let res = (a & !F64::SIGN_MASK) | ((b) & F64::SIGN_MASK);
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1108,7 +2187,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnjn_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg(rs1);
+        let b = self.read_freg(rs2);
+        let res = (a & !F64::SIGN_MASK) | ((!b) & F64::SIGN_MASK);
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1118,7 +2203,13 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsgnjx_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let a = self.read_freg(rs1);
+        let b = self.read_freg(rs2);
+        let res = (a & !F64::SIGN_MASK) | ((a ^ b) & F64::SIGN_MASK);
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1128,7 +2219,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmin_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::min::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1138,17 +2237,52 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmax_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::max::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fcvt_s_d(&mut self, rd: FloatRegister, rs1: FloatRegister) -> Result<(), Self::Error> {
-        todo!();
+    fn fcvt_s_d(
+        &mut self,
+        rd: FloatRegister,
+        rs1: FloatRegister,
+        rm: FloatRoundingMode,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_s_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::convert::<F64, F32>(
+            self.read_freg(rs1), rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fcvt_d_s(&mut self, rd: FloatRegister, rs1: FloatRegister) -> Result<(), Self::Error> {
-        todo!();
+    fn fcvt_d_s(
+        &mut self,
+        rd: FloatRegister,
+        rs1: FloatRegister,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_d_s {:?} {:?}", rd, rs1);
+        // widening is always exact so the rounding mode doesn't matter
+        let mut flags = 0;
+        let res = softfloat::convert::<F32, F64>(
+            self.read_freg_s(rs1), FloatRoundingMode::RNE, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1158,7 +2292,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("feq_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::eq::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1168,7 +2310,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("flt_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::lt::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1178,12 +2328,27 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rs2: FloatRegister,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fle_d {:?} {:?} {:?}", rd, rs1, rs2);
+        let mut flags = 0;
+        let res = softfloat::le::<F64>(
+            self.read_freg(rs1), self.read_freg(rs2), &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fclass_d(&mut self, rd: Register, rs1: FloatRegister) -> Result<(), Self::Error> {
-        todo!();
+    fn fclass_d(
+        &mut self,
+        rd: Register,
+        rs1: FloatRegister,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fclass_d {:?} {:?}", rd, rs1);
+        self.write_reg(rd, softfloat::classify::<F64>(self.read_freg(rs1)));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1193,7 +2358,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_w_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F64>(
+            self.read_freg(rs1), true, 32, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1203,7 +2377,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_wu_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F64>(
+            self.read_freg(rs1), false, 32, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1213,7 +2396,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_d_w {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let value = self.read_reg(rs1) as i32 as i64;
+        let (negative, magnitude) = (value < 0, value.unsigned_abs());
+        let mut flags = 0;
+        let res = softfloat::from_int::<F64>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1223,12 +2415,30 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_d_wu {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let (negative, magnitude) = (false, self.read_reg(rs1) as u32 as u64);
+        let mut flags = 0;
+        let res = softfloat::from_int::<F64>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn flw(&mut self, rd: FloatRegister, rs1: Register, imm: u32) -> Result<(), Self::Error> {
-        todo!();
+    fn flw(
+        &mut self,
+        rd: FloatRegister,
+        rs1: Register,
+        imm: i32,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("flw {:?} {:?} {}", rd, rs1, imm);
+        let addr = self.read_reg(rs1).wrapping_add_signed(imm as i64);
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg_s(rd, res as u64);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1236,14 +2446,28 @@ impl RV64GCUser<()> for CoreEmu {
         &mut self,
         rs1: Register,
         rs2: FloatRegister,
-        offset: u32,
+        imm: i32,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsw {:?} {:?} {}", rs1, rs2, imm);
+        let addr = VirtAddr(self.read_reg(rs1).wrapping_add_signed(imm as i64) as _);
+        self.mem.write(addr, self.read_freg(rs2) as u32)?;
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
-    fn fld(&mut self, rd: FloatRegister, rs1: Register, offset: u32) -> Result<(), Self::Error> {
-        todo!();
+    fn fld(
+        &mut self,
+        rd: FloatRegister,
+        rs1: Register,
+        imm: i32,
+    ) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("fld {:?} {:?} {}", rd, rs1, imm);
+        let addr = self.read_reg(rs1).wrapping_add_signed(imm as i64);
+        let res: u64 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1251,9 +2475,13 @@ impl RV64GCUser<()> for CoreEmu {
         &mut self,
         rs1: Register,
         rs2: FloatRegister,
-        offset: u32,
+        imm: i32,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fsd {:?} {:?} {}", rs1, rs2, imm);
+        let addr = VirtAddr(self.read_reg(rs1).wrapping_add_signed(imm as i64) as _);
+        self.mem.write(addr, self.read_freg(rs2))?;
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1263,17 +2491,35 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_l_s {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F32>(
+            self.read_freg_s(rs1), true, 64, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
     fn fcvt_lu_s(
         &mut self,
-        rd: FloatRegister,
-        rs1: Register,
+        rd: Register,
+        rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_lu_s {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F32>(
+            self.read_freg_s(rs1), false, 64, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1283,7 +2529,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_s_l {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let value = self.read_reg(rs1) as i64;
+        let (negative, magnitude) = (value < 0, value.unsigned_abs());
+        let mut flags = 0;
+        let res = softfloat::from_int::<F32>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1293,7 +2548,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_s_lu {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let (negative, magnitude) = (false, self.read_reg(rs1));
+        let mut flags = 0;
+        let res = softfloat::from_int::<F32>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg_s(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1303,7 +2566,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_l_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F64>(
+            self.read_freg(rs1), true, 64, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1313,7 +2585,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: FloatRegister,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_lu_d {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let mut flags = 0;
+        let res = softfloat::to_int::<F64>(
+            self.read_freg(rs1), false, 64, rm, &mut flags,
+        );
+        self.fcsr |= flags;
+        self.write_reg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1321,9 +2602,12 @@ impl RV64GCUser<()> for CoreEmu {
         &mut self,
         rd: Register,
         rs1: FloatRegister,
-        rm: FloatRoundingMode,
+        _rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmv_x_d {:?} {:?} {:?}", rd, rs1, _rm);
+        self.write_reg(rd, self.read_freg(rs1));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1333,7 +2617,16 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_d_l {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let value = self.read_reg(rs1) as i64;
+        let (negative, magnitude) = (value < 0, value.unsigned_abs());
+        let mut flags = 0;
+        let res = softfloat::from_int::<F64>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1343,7 +2636,15 @@ impl RV64GCUser<()> for CoreEmu {
         rs1: Register,
         rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fcvt_d_lu {:?} {:?} {:?}", rd, rs1, rm);
+        let rm = self.rounding_mode(rm)?;
+        let (negative, magnitude) = (false, self.read_reg(rs1));
+        let mut flags = 0;
+        let res = softfloat::from_int::<F64>(negative, magnitude, rm, &mut flags);
+        self.fcsr |= flags;
+        self.write_freg(rd, res);
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1351,9 +2652,12 @@ impl RV64GCUser<()> for CoreEmu {
         &mut self,
         rd: FloatRegister,
         rs1: Register,
-        rm: FloatRoundingMode,
+        _rm: FloatRoundingMode,
     ) -> Result<(), Self::Error> {
-        todo!();
+        #[cfg(feature="dbg_prints")]
+        println!("fmv_d_x {:?} {:?} {:?}", rd, rs1, _rm);
+        self.write_freg(rd, self.read_reg(rs1));
+        self.pc += 4;
         Ok(())
     }
     #[inline(always)]
@@ -1366,32 +2670,40 @@ impl RV64GCUser<()> for CoreEmu {
         Ok(())
     }
     #[inline(always)]
-    fn c_fld(&mut self, rd: Register, rs1: Register, imm: u16) -> Result<(), Self::Error> {
+    fn c_fld(&mut self, rd: FloatRegister, rs1: Register, imm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_fld {:?} {:?} {}", rd, rs1, imm);
-        todo!();
+        let addr = self.read_reg(rs1).wrapping_add(imm as u64);
+        let res = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg(rd, res);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_lw(&mut self, rd: Register, rs1: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_lw {:?} {:?} {}", rd, rs1, uimm);
-        todo!();
+        let addr = self.read_reg(rs1).wrapping_add(uimm as u64);
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_reg(rd, res as i32 as i64 as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_flw(&mut self, rd: FloatRegister, rs1: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_flw {:?} {:?} {}", rd, rs1, uimm);
-        todo!();
+        let addr = self.read_reg(rs1).wrapping_add(uimm as u64);
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg_s(rd, res as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_ld(&mut self, rd: Register, rs1: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_ld {:?} {:?} {}", rd, rs1, uimm);
-        // check
-        let addr = self.read_reg(rs1).wrapping_add(8 * uimm as u64);
+        let addr = self.read_reg(rs1).wrapping_add(uimm as u64);
         let res = self.mem.read(VirtAddr(addr as usize))?;
         self.write_reg(rd, res);
         self.pc += 2;
@@ -1406,29 +2718,35 @@ impl RV64GCUser<()> for CoreEmu {
     ) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_fsd {:?} {:?} {}", rs1, rs2, uimm);
-        todo!();
+        let addr = VirtAddr(self.read_reg(rs1).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_freg(rs2))?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_sw(&mut self, rs1: Register, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_sw {:?} {:?} {}", rs1, rs2, uimm);
-        todo!();
+        let addr = VirtAddr(self.read_reg(rs1).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_reg(rs2) as u32)?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_fsw(&mut self, rs1: Register, rs2: FloatRegister, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_fsw {:?} {:?} {}", rs1, rs2, uimm);
-        todo!();
+        let addr = VirtAddr(self.read_reg(rs1).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_freg(rs2) as u32)?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_sd(&mut self, rs1: Register, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_sd {:?} {:?} {}", rs1, rs2, uimm);
-        let addr = self.read_reg(rs1).wrapping_add(8 * uimm as u64);
-        self.mem.write(VirtAddr(addr as _), self.read_reg(rs2));
+        let addr = self.read_reg(rs1).wrapping_add(uimm as u64);
+        self.mem.write(VirtAddr(addr as _), self.read_reg(rs2))?;
         self.pc += 2;
         Ok(())
     }
@@ -1446,10 +2764,12 @@ impl RV64GCUser<()> for CoreEmu {
     fn c_jal(&mut self, imm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_jal {}", imm);
+        let from = self.pc;
         // ret addr
-        self.write_reg(Register::Ra, self.pc.wrapping_add(4));
+        self.write_reg(Register::Ra, self.pc.wrapping_add(2));
         // jmp
-        self.pc = self.pc.wrapping_add_signed(imm as i64);
+        self.pc = self.pc.wrapping_add_signed(imm as i16 as i64);
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
@@ -1483,8 +2803,7 @@ impl RV64GCUser<()> for CoreEmu {
     fn c_lui(&mut self, rd: Register, imm: i32) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_lui {:?} {}", rd, imm);
-        let value = self.read_reg(Register::Sp).wrapping_add_signed(imm as i64);
-        self.write_reg(rd, value);
+        self.write_reg(rd, imm as i64 as u64);
         self.pc += 2;
         Ok(())
     }
@@ -1492,21 +2811,24 @@ impl RV64GCUser<()> for CoreEmu {
     fn c_srli(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_srli {:?} {}", rd, uimm);
-        todo!();
+        self.write_reg(rd, self.read_reg(rd) >> (uimm & 0b111111));
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_srai(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_srai {:?} {}", rd, uimm);
-        todo!();
+        self.write_reg(rd, ((self.read_reg(rd) as i64) >> (uimm & 0b111111)) as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
-    fn c_andi(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+    fn c_andi(&mut self, rd: Register, imm: i8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("c_andi {:?} {}", rd, uimm);
-        todo!();
+        println!("c_andi {:?} {}", rd, imm);
+        self.write_reg(rd, self.read_reg(rd) & imm as i64 as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
@@ -1545,43 +2867,59 @@ impl RV64GCUser<()> for CoreEmu {
     fn c_subw(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_subw {:?} {:?}", rd, rs2);
-        todo!();
+        self.write_reg(rd, 
+            (self.read_reg(rd) as i32).wrapping_sub(self.read_reg(rs2) as i32) as i64 as u64
+        );
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_addw(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_addw {:?} {:?}", rd, rs2);
-        todo!();
+        self.write_reg(rd, 
+            (self.read_reg(rd) as i32).wrapping_add(self.read_reg(rs2) as i32) as i64 as u64
+        );
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_j(&mut self, imm: i16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_j {}", imm);
+        let from = self.pc;
         self.pc = self.pc.wrapping_add_signed(imm as i64);
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn c_beqz(&mut self, rs1: Register, offset: i16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_beqz {:?} {}", rs1, offset);
-        if self.read_reg(rs1) == 0 {
+        let from = self.pc;
+        let lhs = self.read_reg(rs1);
+        self.record_cmp(lhs, 0);
+        if lhs == 0 {
             self.pc = self.pc.wrapping_add_signed(offset as i64);
         } else {
             self.pc += 2;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
     fn c_bnez(&mut self, rs1: Register, offset: i16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_bnez {:?} {}", rs1, offset);
-        if self.read_reg(rs1) != 0 {
+        let from = self.pc;
+        let lhs = self.read_reg(rs1);
+        self.record_cmp(lhs, 0);
+        if lhs != 0 {
             self.pc = self.pc.wrapping_add_signed(offset as i64);
         } else {
             self.pc += 2;
         }
+        self.record_edge(from);
         Ok(())
     }
     #[inline(always)]
@@ -1594,28 +2932,37 @@ impl RV64GCUser<()> for CoreEmu {
         Ok(())
     }
     #[inline(always)]
-    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<(), Self::Error> {
+    fn c_fldsp(&mut self, rd: FloatRegister, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_fldsp {:?} {}", rd, uimm);
-        todo!();
+        let addr = self.read_reg(Register::Sp).wrapping_add(uimm as u64);
+        let res = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg(rd, res);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_lwsp(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_lwsp {:?} {}", rd, uimm);
-        todo!();
+        let addr = self.read_reg(Register::Sp).wrapping_add(uimm as u64);
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_reg(rd, res as i32 as i64 as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
-    fn c_flwsp(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+    fn c_flwsp(&mut self, rd: FloatRegister, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_flwsp {:?} {}", rd, uimm);
-        todo!();
+        let addr = self.read_reg(Register::Sp).wrapping_add(uimm as u64);
+        let res: u32 = self.mem.read(VirtAddr(addr as usize))?;
+        self.write_freg_s(rd, res as u64);
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
-    fn c_ldsp(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+    fn c_ldsp(&mut self, rd: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_ldsp {:?} {}", rd, uimm);
         let addr = self.read_reg(Register::Sp).wrapping_add(uimm as u64);
@@ -1633,7 +2980,10 @@ impl RV64GCUser<()> for CoreEmu {
                 println!("c_jr {:?}", rs1);
             }
         }
-        self.pc = self.read_reg(rs1);
+        let from = self.pc;
+        self.pc = self.read_reg(rs1) & !1;
+        self.record_edge(from);
+        self.record_jump(from, Register::Zero, rs1);
         Ok(())
     }
     #[inline(always)]
@@ -1648,7 +2998,13 @@ impl RV64GCUser<()> for CoreEmu {
     fn c_jalr(&mut self, rs1: Register) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_jalr {:?}", rs1);
-        todo!();
+        let from = self.pc;
+        // read the target first as rs1 might be ra
+        let target = self.read_reg(rs1) & !1;
+        self.write_reg(Register::Ra, self.pc.wrapping_add(2));
+        self.pc = target;
+        self.record_edge(from);
+        self.record_jump(from, Register::Ra, rs1);
         Ok(())
     }
     #[inline(always)]
@@ -1660,32 +3016,38 @@ impl RV64GCUser<()> for CoreEmu {
         Ok(())
     }
     #[inline(always)]
-    fn c_fsdsp(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+    fn c_fsdsp(&mut self, rs2: FloatRegister, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
-        println!("c_fsdsp {:?} {:?}", rd, rs2);
-        todo!();
+        println!("c_fsdsp {:?} {}", rs2, uimm);
+        let addr = VirtAddr(self.read_reg(Register::Sp).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_freg(rs2))?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_swsp(&mut self, rs2: Register, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_swsp {:?} {}", rs2, uimm);
-        todo!();
+        let addr = VirtAddr(self.read_reg(Register::Sp).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_reg(rs2) as u32)?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
     fn c_fswsp(&mut self, rs2: FloatRegister, uimm: u8) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_fswsp {:?} {}", rs2, uimm);
-        todo!();
+        let addr = VirtAddr(self.read_reg(Register::Sp).wrapping_add(uimm as u64) as _);
+        self.mem.write(addr, self.read_freg(rs2) as u32)?;
+        self.pc += 2;
         Ok(())
     }
     #[inline(always)]
-    fn c_sdsp(&mut self, rs2: Register, uimm: u8) -> Result<(), Self::Error> {
+    fn c_sdsp(&mut self, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("c_sdsp {:?} {}", rs2, uimm);
         let addr = VirtAddr(self.read_reg(Register::Sp).wrapping_add(uimm as u64) as _);
-        self.mem.write(addr, self.read_reg(rs2));
+        self.mem.write(addr, self.read_reg(rs2))?;
         self.pc += 2;
         Ok(())
     }
@@ -1693,6 +3055,9 @@ impl RV64GCUser<()> for CoreEmu {
     fn fence(&mut self) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("fence");
+        if let Some(detector) = &mut self.race_detector {
+            detector.fence();
+        }
         self.pc += 4;            
         Err(CoreEmuError::Yield)
     }
@@ -1700,7 +3065,7 @@ impl RV64GCUser<()> for CoreEmu {
     fn fence_i(&mut self) -> Result<(), Self::Error> {
         #[cfg(feature="dbg_prints")]
         println!("fence_i");
-        // CHECK
+        self.block_cache.clear();
         self.pc += 4;            
         Err(CoreEmuError::Yield)
     }
@@ -1732,4 +3097,10 @@ impl RV64GCUser<()> for CoreEmu {
         self.pc += 2;            
         Err(CoreEmuError::Breakpoint)
     }
+    #[inline(always)]
+    fn illegal(&mut self, _inst: u32) -> Result<(), Self::Error> {
+        #[cfg(feature="dbg_prints")]
+        println!("illegal {:#x}", _inst);
+        Err(CoreEmuError::IllegalInstruction)
+    }
 }
\ No newline at end of file
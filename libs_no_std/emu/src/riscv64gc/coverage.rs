@@ -0,0 +1,270 @@
+//! Edge coverage of [`CoreEmu`] for guided fuzzing.
+//!
+//! When [`CoreEmu::coverage`] is set, every branch (taken or not) and every
+//! jump records the [`Edge`] from its pc to the next one. The edges are
+//! counted in a hashed bitmap, AFL style, and the ones never seen before are
+//! also listed exactly, so a collision in the bitmap can't hide new code.
+//!
+//! The bitmap and the list of new edges are per case and are cleared by
+//! [`Coverage::clear_case`], while the set of the edges already seen is kept
+//! so that they are not reported again. The coverage is not part of the
+//! state restored by `reset`, and forks start with a copy of their parent's.
+//!
+//! With [`Coverage::set_cmpcov`] the conditional branches and the `slt`
+//! family also report how close their operands were: the number of equal
+//! low bytes is a [`CmpProgress`], counted in the same bitmap and listed
+//! when new like the edges, so that a fuzzer can guess a magic value one
+//! byte at a time. The operands that differ are logged too, as candidates
+//! to replace one with the other in the input.
+use alloc::collections::BTreeSet;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// log2 of the default number of entries of the bitmap
+pub const DEFAULT_COVERAGE_BITS: u32 = 16;
+
+/// A control flow transfer from the instruction at `from` to the one at `to`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Edge {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Can't be recorded as the pcs are always even
+const NO_EDGE: Edge = Edge { from: u64::MAX, to: u64::MAX };
+
+/// The lowest `bytes` bytes of the operands of the comparison at `pc` were
+/// equal, the comparisons record one for each number of bytes up to that
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CmpProgress {
+    pub pc: u64,
+    pub bytes: u8,
+}
+
+/// Can't be recorded as the pcs are always even
+const NO_CMP: CmpProgress = CmpProgress { pc: u64::MAX, bytes: 0 };
+
+/// The operands of a comparison at `pc` that were not equal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CmpOperands {
+    pub pc: u64,
+    pub lhs: u64,
+    pub rhs: u64,
+}
+
+/// The most [`CmpOperands`] logged per case
+pub const CMP_LOG_SIZE: usize = 1 << 12;
+
+#[derive(Debug, Clone)]
+pub struct Coverage {
+    /// `64 - log2(bitmap.len())`
+    shift: u32,
+    /// Hit counts of the current case, indexed by the hash of the edges and
+    /// of the [`CmpProgress`]
+    bitmap: Vec<u8>,
+    /// The non-zero entries of `bitmap`, so that clearing it is cheap
+    hits: Vec<usize>,
+    /// All the edges recorded since the creation or the last `clear`
+    seen: BTreeSet<Edge>,
+    /// The edges of `seen` recorded for the first time in the current case
+    new_edges: Vec<Edge>,
+    /// The last edge of each entry of the bitmap found in `seen`, so that
+    /// the set is only searched when the edge changes
+    last: Vec<Edge>,
+    /// Record the progress of the comparisons
+    cmpcov: bool,
+    /// Like `seen`, `new_edges` and `last` for the [`CmpProgress`], the
+    /// last is empty while `cmpcov` is off
+    seen_cmps: BTreeSet<CmpProgress>,
+    new_cmps: Vec<CmpProgress>,
+    last_cmps: Vec<CmpProgress>,
+    /// The operands of the comparisons of the current case
+    cmp_log: Vec<CmpOperands>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new(DEFAULT_COVERAGE_BITS)
+    }
+}
+
+impl Coverage {
+    /// Coverage with a bitmap of `1 << bits` entries
+    pub fn new(bits: u32) -> Self {
+        assert!((1..=32).contains(&bits), "The coverage bitmap must have between 2 and 2^32 entries");
+        Coverage {
+            shift: 64 - bits,
+            bitmap: vec![0; 1 << bits],
+            hits: Vec::new(),
+            seen: BTreeSet::new(),
+            new_edges: Vec::new(),
+            last: vec![NO_EDGE; 1 << bits],
+            cmpcov: false,
+            seen_cmps: BTreeSet::new(),
+            new_cmps: Vec::new(),
+            last_cmps: Vec::new(),
+            cmp_log: Vec::new(),
+        }
+    }
+
+    /// Turn the comparison feedback on or off
+    pub fn set_cmpcov(&mut self, enabled: bool) {
+        self.cmpcov = enabled;
+        if enabled && self.last_cmps.is_empty() {
+            self.last_cmps = vec![NO_CMP; self.bitmap.len()];
+        }
+    }
+
+    pub fn cmpcov(&self) -> bool {
+        self.cmpcov
+    }
+
+    /// Index of `edge` in the bitmap
+    #[inline(always)]
+    pub fn index(&self, edge: Edge) -> usize {
+        ((edge.from.rotate_left(32) ^ edge.to).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> self.shift) as usize
+    }
+
+    /// Record an execution of the edge from `from` to `to`
+    #[inline(always)]
+    pub fn record(&mut self, from: u64, to: u64) {
+        let edge = Edge { from, to };
+        let index = self.index(edge);
+        let count = &mut self.bitmap[index];
+        if *count == 0 {
+            self.hits.push(index);
+        }
+        *count = count.saturating_add(1);
+        if self.last[index] != edge {
+            self.lookup(index, edge);
+        }
+    }
+
+    #[cold]
+    fn lookup(&mut self, index: usize, edge: Edge) {
+        if self.seen.insert(edge) {
+            self.new_edges.push(edge);
+        }
+        self.last[index] = edge;
+    }
+
+    /// Index of `cmp` in the bitmap
+    #[inline(always)]
+    pub fn cmp_index(&self, cmp: CmpProgress) -> usize {
+        ((cmp.pc ^ (cmp.bytes as u64) << 56).wrapping_mul(0xc2b2_ae3d_27d4_eb4f) >> self.shift) as usize
+    }
+
+    /// Record a comparison at `pc` between `lhs` and `rhs`, if the
+    /// comparison feedback is on
+    #[inline(always)]
+    pub fn record_cmp(&mut self, pc: u64, lhs: u64, rhs: u64) {
+        if self.cmpcov {
+            self.record_cmp_progress(pc, lhs, rhs);
+        }
+    }
+
+    #[inline(never)]
+    fn record_cmp_progress(&mut self, pc: u64, lhs: u64, rhs: u64) {
+        if lhs != rhs && self.cmp_log.len() < CMP_LOG_SIZE
+            && self.cmp_log.last() != Some(&CmpOperands { pc, lhs, rhs }) {
+            self.cmp_log.push(CmpOperands { pc, lhs, rhs });
+        }
+        let equal = (lhs ^ rhs).trailing_zeros() / 8;
+        for bytes in 1..=equal as u8 {
+            let cmp = CmpProgress { pc, bytes };
+            let index = self.cmp_index(cmp);
+            let count = &mut self.bitmap[index];
+            if *count == 0 {
+                self.hits.push(index);
+            }
+            *count = count.saturating_add(1);
+            if self.last_cmps[index] != cmp {
+                if self.seen_cmps.insert(cmp) {
+                    self.new_cmps.push(cmp);
+                }
+                self.last_cmps[index] = cmp;
+            }
+        }
+    }
+
+    /// The hit counts of the current case, saturating at 255
+    pub fn bitmap(&self) -> &[u8] {
+        &self.bitmap
+    }
+
+    /// The indices of the entries of the bitmap hit in the current case, in
+    /// the order they were first hit
+    pub fn hits(&self) -> &[usize] {
+        &self.hits
+    }
+
+    /// The edges recorded in the current case that were never seen before
+    pub fn new_edges(&self) -> &[Edge] {
+        &self.new_edges
+    }
+
+    /// All the edges seen
+    pub fn edges(&self) -> &BTreeSet<Edge> {
+        &self.seen
+    }
+
+    /// The progress of the comparisons in the current case never seen before
+    pub fn new_cmps(&self) -> &[CmpProgress] {
+        &self.new_cmps
+    }
+
+    /// All the progress of the comparisons seen
+    pub fn cmps(&self) -> &BTreeSet<CmpProgress> {
+        &self.seen_cmps
+    }
+
+    /// The operands of the comparisons of the current case that were not
+    /// equal, in execution order and without consecutive duplicates, up to
+    /// [`CMP_LOG_SIZE`] of them
+    pub fn cmp_log(&self) -> &[CmpOperands] {
+        &self.cmp_log
+    }
+
+    /// Start a new case, the edges seen so far are not reported again
+    pub fn clear_case(&mut self) {
+        for index in self.hits.drain(..) {
+            self.bitmap[index] = 0;
+        }
+        self.new_edges.clear();
+        self.new_cmps.clear();
+        self.cmp_log.clear();
+    }
+
+    /// Forget the edges and the comparison progress first seen in the
+    /// current case, e.g. because its input is not kept, so that the next
+    /// case reaching them reports them as new
+    pub fn forget_case(&mut self) {
+        for idx in 0..self.new_edges.len() {
+            let edge = self.new_edges[idx];
+            self.seen.remove(&edge);
+            let index = self.index(edge);
+            if self.last[index] == edge {
+                self.last[index] = NO_EDGE;
+            }
+        }
+        for idx in 0..self.new_cmps.len() {
+            let cmp = self.new_cmps[idx];
+            self.seen_cmps.remove(&cmp);
+            let index = self.cmp_index(cmp);
+            if self.last_cmps[index] == cmp {
+                self.last_cmps[index] = NO_CMP;
+            }
+        }
+        self.new_edges.clear();
+        self.new_cmps.clear();
+    }
+
+    /// Forget everything, including the edges seen
+    pub fn clear(&mut self) {
+        self.clear_case();
+        self.seen.clear();
+        self.last.fill(NO_EDGE);
+        self.seen_cmps.clear();
+        self.last_cmps.fill(NO_CMP);
+    }
+}
@@ -0,0 +1,85 @@
+//! User-level CSR file (Zicsr), the float CSRs are views over
+//! [`CoreEmu::fcsr`] while the counters are derived from
+//! [`CoreEmu::instructions_executed`] so that they are deterministic.
+use super::{CoreEmu, CoreEmuError};
+
+/// Floating-Point Accrued Exceptions
+pub const FFLAGS:  u32 = 0x001;
+/// Floating-Point Dynamic Rounding Mode
+pub const FRM:     u32 = 0x002;
+/// Floating-Point Control and Status Register (frm + fflags)
+pub const FCSR:    u32 = 0x003;
+/// Cycle counter for RDCYCLE instruction
+pub const CYCLE:   u32 = 0xC00;
+/// Timer for RDTIME instruction
+pub const TIME:    u32 = 0xC01;
+/// Instructions-retired counter for RDINSTRET instruction
+pub const INSTRET: u32 = 0xC02;
+
+/// Number of virtual clock ticks for each executed instruction
+pub const TICKS_PER_INSTRUCTION: u64 = 1;
+
+/// CSRs with the two top bits of the address set are read-only
+#[inline(always)]
+pub fn is_read_only(csr: u32) -> bool {
+    (csr >> 10) & 0b11 == 0b11
+}
+
+impl CoreEmu {
+    /// Read the value of the CSR `csr`
+    #[inline]
+    pub fn read_csr(&self, csr: u32) -> Result<u64, CoreEmuError> {
+        Ok(match csr {
+            FFLAGS  => (self.fcsr & 0b11111) as u64,
+            FRM     => ((self.fcsr >> 5) & 0b111) as u64,
+            FCSR    => (self.fcsr & 0xff) as u64,
+            // we don't model the pipeline so every instruction is a cycle
+            CYCLE | INSTRET => self.instructions_executed as u64,
+            TIME    => self.time(),
+            _ => return Err(CoreEmuError::UnknownCsr(csr)),
+        })
+    }
+
+    /// Write `value` to the CSR `csr`, the bits that are not implemented
+    /// are ignored
+    #[inline]
+    pub fn write_csr(&mut self, csr: u32, value: u64) -> Result<(), CoreEmuError> {
+        match csr {
+            FFLAGS => self.fcsr = (self.fcsr & !0b11111) | (value as u32 & 0b11111),
+            FRM    => self.fcsr = (self.fcsr & 0b11111) | ((value as u32 & 0b111) << 5),
+            FCSR   => self.fcsr = value as u32 & 0xff,
+            CYCLE | TIME | INSTRET => return Err(CoreEmuError::IllegalInstruction),
+            _ => return Err(CoreEmuError::UnknownCsr(csr)),
+        }
+        Ok(())
+    }
+
+    /// Current value of the virtual clock, it only depends on the number of
+    /// executed instructions and on `time_offset`, so runs are reproducible
+    #[inline(always)]
+    pub fn time(&self) -> u64 {
+        self.time_offset.wrapping_add(
+            (self.instructions_executed as u64).wrapping_mul(TICKS_PER_INSTRUCTION)
+        )
+    }
+
+    /// Generic read-modify-write of a CSR, `write` is false when the
+    /// instruction must not write the CSR (csrrs / csrrc with x0 or a zero
+    /// immediate)
+    #[inline(always)]
+    pub(crate) fn csr_rmw(
+        &mut self,
+        csr: u32,
+        write: bool,
+        op: impl FnOnce(u64) -> u64,
+    ) -> Result<u64, CoreEmuError> {
+        let old = self.read_csr(csr)?;
+        if write {
+            if is_read_only(csr) {
+                return Err(CoreEmuError::IllegalInstruction);
+            }
+            self.write_csr(csr, op(old))?;
+        }
+        Ok(old)
+    }
+}
@@ -0,0 +1,65 @@
+//! Linux error numbers, from `include/uapi/asm-generic/errno-base.h` and
+//! `include/uapi/asm-generic/errno.h`.
+//! Syscalls return them negated in `a0`, see [`to_ret`].
+
+/// Operation not permitted
+pub const EPERM: u64 = 1;
+/// No such file or directory
+pub const ENOENT: u64 = 2;
+/// Bad file number
+pub const EBADF: u64 = 9;
+/// Try again
+pub const EAGAIN: u64 = 11;
+/// Out of memory
+pub const ENOMEM: u64 = 12;
+/// Permission denied
+pub const EACCES: u64 = 13;
+/// Bad address
+pub const EFAULT: u64 = 14;
+/// File exists
+pub const EEXIST: u64 = 17;
+/// Not a directory
+pub const ENOTDIR: u64 = 20;
+/// Invalid argument
+pub const EINVAL: u64 = 22;
+/// Not a typewriter
+pub const ENOTTY: u64 = 25;
+/// Illegal seek
+pub const ESPIPE: u64 = 29;
+/// Math result not representable
+pub const ERANGE: u64 = 34;
+/// File name too long
+pub const ENAMETOOLONG: u64 = 36;
+/// Invalid system call number
+pub const ENOSYS: u64 = 38;
+/// Connection timed out
+pub const ETIMEDOUT: u64 = 110;
+
+/// Convert an error number to the value returned by the syscall
+#[inline(always)]
+pub const fn to_ret(errno: u64) -> u64 {
+    (errno as i64).wrapping_neg() as u64
+}
+
+/// Name of an error number, like `ENOENT`
+pub fn errno_name(errno: u64) -> Option<&'static str> {
+    Some(match errno {
+        EPERM => "EPERM",
+        ENOENT => "ENOENT",
+        EBADF => "EBADF",
+        EAGAIN => "EAGAIN",
+        ENOMEM => "ENOMEM",
+        EACCES => "EACCES",
+        EFAULT => "EFAULT",
+        EEXIST => "EEXIST",
+        ENOTDIR => "ENOTDIR",
+        EINVAL => "EINVAL",
+        ENOTTY => "ENOTTY",
+        ESPIPE => "ESPIPE",
+        ERANGE => "ERANGE",
+        ENAMETOOLONG => "ENAMETOOLONG",
+        ENOSYS => "ENOSYS",
+        ETIMEDOUT => "ETIMEDOUT",
+        _ => return None,
+    })
+}
@@ -0,0 +1,384 @@
+//! Heap sanitizer replacing the allocator of the emulated program.
+//!
+//! [`LinuxEmu::enable_heap_sanitizer`] finds `malloc`, `calloc`, `realloc`
+//! and `free` in the symbol table and overwrites their first instruction
+//! with an `ebreak`, so that every call stops the core and is serviced by
+//! the [`HeapSanitizer`] instead, returning straight to the caller.
+//!
+//! The chunks are allocated in a segment of their own, each between two
+//! redzones with no permissions. The allocated bytes are readable only after
+//! they are written, except for `calloc`, and the freed chunks lose all their
+//! permissions and wait in a quarantine before they can be reused. So the
+//! mmu faults at the exact access that overflows a chunk or uses it after
+//! it was freed, and [`HeapSanitizer::classify`] tells which bug it was. The
+//! frees of pointers that are not live chunks stop with
+//! [`MmuError::InvalidFree`].
+//!
+//! The allocations bigger than [`HeapSanitizer::max_alloc`] return NULL, and
+//! so do the ones growing the segment past [`Mmu::memory_limit`].
+//!
+//! The functions must be in the symbol table when the sanitizer is enabled,
+//! and the memory allocated by the other functions of the allocator, e.g.
+//! `memalign`, can't be freed.
+//!
+//! [`LinuxEmu::enable_heap_sanitizer`]: super::LinuxEmu::enable_heap_sanitizer
+//! [`MmuError::InvalidFree`]: mmu::MmuError::InvalidFree
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::vec::Vec;
+use mmu::{Mmu, MmuError, Perm, PermField, SegmentMmu, VirtAddr};
+
+/// Where the segment of the chunks starts
+pub const DEFAULT_HEAP_BASE: u64 = 0x2000_0000_0000;
+/// How much the segment of the chunks can grow
+pub const DEFAULT_HEAP_SIZE: u64 = 1 << 36;
+/// Bytes with no permissions before and after each chunk
+pub const DEFAULT_REDZONE: u64 = 32;
+/// Bytes of the freed chunks kept in quarantine
+pub const DEFAULT_QUARANTINE: u64 = 1 << 20;
+/// Largest size of a chunk, bigger allocations fail like if out of memory
+pub const DEFAULT_MAX_ALLOC: u64 = 1 << 28;
+/// Alignment of the chunks, like the one of glibc
+pub const HEAP_ALIGN: u64 = 16;
+
+/// The functions of the allocator that are replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapFunction {
+    Malloc,
+    Calloc,
+    Realloc,
+    Free,
+}
+
+impl HeapFunction {
+    pub const ALL: [HeapFunction; 4] = [
+        HeapFunction::Malloc, HeapFunction::Calloc,
+        HeapFunction::Realloc, HeapFunction::Free,
+    ];
+
+    /// Name of the function in the symbol table
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            HeapFunction::Malloc => "malloc",
+            HeapFunction::Calloc => "calloc",
+            HeapFunction::Realloc => "realloc",
+            HeapFunction::Free => "free",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkState {
+    Live,
+    /// Freed and not reusable yet
+    Quarantined,
+    /// Freed and reusable by an allocation of the same capacity
+    Free,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Address returned to the program
+    pub addr: u64,
+    /// Size asked for by the program
+    pub size: u64,
+    pub state: ChunkState,
+    /// Return address of the allocation, in the caller
+    pub allocated_from: u64,
+    /// Return address of the last free, zero if live
+    pub freed_from: u64,
+}
+
+impl Chunk {
+    /// Bytes usable by the chunk, it's the size rounded up to the alignment
+    pub fn capacity(&self) -> u64 {
+        capacity(self.size)
+    }
+}
+
+#[inline(always)]
+fn capacity(size: u64) -> u64 {
+    size.max(1).next_multiple_of(HEAP_ALIGN)
+}
+
+/// The kinds of bugs found by the sanitizer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeapBug {
+    /// Access to the redzones or past the size of a chunk
+    Overflow,
+    /// Access to a freed chunk
+    UseAfterFree,
+    /// Free of a chunk already freed
+    DoubleFree,
+    /// Free of something that is not a chunk
+    InvalidFree,
+}
+
+#[derive(Debug, Clone)]
+pub struct HeapSanitizer {
+    /// Bytes with no permissions before and after each chunk, it can only
+    /// be changed before the first allocation
+    pub redzone: u64,
+    /// Bytes of the freed chunks kept in quarantine, the oldest ones are
+    /// reused first
+    pub quarantine_limit: u64,
+    /// Largest size of a chunk
+    pub max_alloc: u64,
+    /// Address of the segment of the chunks
+    base: u64,
+    /// Size the segment can grow to
+    max_size: u64,
+    /// The entry of each function replaced
+    hooks: BTreeMap<u64, HeapFunction>,
+    /// All the chunks ever allocated, by address
+    chunks: BTreeMap<u64, Chunk>,
+    /// The quarantined chunks, the oldest first
+    quarantine: VecDeque<u64>,
+    /// Sum of the capacities of the quarantined chunks
+    quarantined: u64,
+    /// The free chunks by capacity
+    free: BTreeMap<u64, Vec<u64>>,
+    /// Bytes of the segment used by the chunks and their redzones
+    top: u64,
+}
+
+impl Default for HeapSanitizer {
+    fn default() -> Self {
+        HeapSanitizer::new(DEFAULT_HEAP_BASE, DEFAULT_HEAP_SIZE)
+    }
+}
+
+impl HeapSanitizer {
+    /// A sanitizer whose chunks are in a segment at `base` that can grow up
+    /// to `max_size` bytes, it's mapped by [`HeapSanitizer::map`]
+    pub fn new(base: u64, max_size: u64) -> Self {
+        HeapSanitizer {
+            redzone: DEFAULT_REDZONE,
+            quarantine_limit: DEFAULT_QUARANTINE,
+            max_alloc: DEFAULT_MAX_ALLOC,
+            base,
+            max_size,
+            hooks: BTreeMap::new(),
+            chunks: BTreeMap::new(),
+            quarantine: VecDeque::new(),
+            quarantined: 0,
+            free: BTreeMap::new(),
+            top: 0,
+        }
+    }
+
+    /// Map the empty segment of the chunks in `mem`
+    pub fn map(&self, mem: &mut Mmu) -> Result<(), MmuError> {
+        let free = mem.is_free(VirtAddr(self.base as usize), self.max_size as usize, 0);
+        if !free || self.segment(mem).is_some() {
+            return Err(MmuError::CannotAllocate {
+                virtual_address: VirtAddr(self.base as usize),
+                mmu_length: self.max_size as usize,
+            });
+        }
+        mem.allocate_segment(Some(VirtAddr(self.base as usize)), 0, PermField::None.into())?;
+        Ok(())
+    }
+
+    /// The segment of the chunks, looked up by address as it can be empty
+    fn segment<'a>(&self, mem: &'a mut Mmu) -> Option<&'a mut SegmentMmu> {
+        mem.segments.iter_mut()
+            .find(|(addr, _)| addr.0 == self.base as usize)
+            .map(|(_, segment)| segment)
+    }
+
+    /// Replace the function whose first instruction is at `entry`
+    pub fn hook(&mut self, entry: u64, function: HeapFunction) {
+        self.hooks.insert(entry, function);
+    }
+
+    /// The function replaced whose first instruction is at `entry`
+    #[inline]
+    pub fn function(&self, entry: u64) -> Option<HeapFunction> {
+        if self.hooks.is_empty() {
+            return None;
+        }
+        self.hooks.get(&entry).copied()
+    }
+
+    /// The chunk whose bytes or redzones contain `addr`, the closest one
+    /// if it's in the redzones of two chunks
+    pub fn chunk(&self, addr: u64) -> Option<&Chunk> {
+        let after = self.chunks.range(addr.saturating_add(1)..).next()
+            .map(|(_, chunk)| chunk)
+            .filter(|chunk| chunk.addr - addr <= self.redzone);
+        let before = self.chunks.range(..=addr).next_back()
+            .map(|(_, chunk)| chunk)
+            .filter(|chunk| addr - chunk.addr < chunk.capacity() + self.redzone);
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                let end = before.addr + before.size;
+                if addr < end || addr - end < after.addr - addr {
+                    Some(before)
+                } else {
+                    Some(after)
+                }
+            }
+            (before, after) => before.or(after),
+        }
+    }
+
+    /// Why an access to `addr` faulted, if it's in a chunk or a redzone,
+    /// and the chunk
+    pub fn classify(&self, addr: u64) -> Option<(HeapBug, &Chunk)> {
+        let chunk = self.chunk(addr)?;
+        let bug = match chunk.state {
+            ChunkState::Live if (chunk.addr..chunk.addr + chunk.size).contains(&addr) => return None,
+            ChunkState::Live => HeapBug::Overflow,
+            ChunkState::Quarantined | ChunkState::Free => HeapBug::UseAfterFree,
+        };
+        Some((bug, chunk))
+    }
+
+    /// Why a free of `addr` failed
+    pub fn classify_free(&self, addr: u64) -> HeapBug {
+        match self.chunks.get(&addr) {
+            Some(chunk) if chunk.state != ChunkState::Live => HeapBug::DoubleFree,
+            _ => HeapBug::InvalidFree,
+        }
+    }
+
+    /// The live chunks
+    pub fn live(&self) -> impl Iterator<Item = &Chunk> {
+        self.chunks.values().filter(|chunk| chunk.state == ChunkState::Live)
+    }
+
+    /// Set the permissions of `len` bytes at `addr` of the segment, and zero
+    /// them if `zero`
+    fn set(&self, mem: &mut Mmu, addr: u64, len: u64, perm: Perm, zero: bool) {
+        let segment = self.segment(mem).unwrap();
+        let range = (addr - self.base) as usize..(addr - self.base + len) as usize;
+        segment.permissions[range.clone()].fill(perm);
+        if zero {
+            segment.memory[range.clone()].fill(0);
+        }
+        segment.dirty_range(range);
+    }
+
+    /// Address of a new chunk of `capacity` bytes, reusing a free one or
+    /// growing the segment, `None` if it's full or the segment can't grow
+    /// past the `memory_limit` of `mem`
+    fn place(&mut self, mem: &mut Mmu, capacity: u64) -> Option<u64> {
+        if let Some(addr) = self.free.get_mut(&capacity).and_then(Vec::pop) {
+            return Some(addr);
+        }
+        let slot = capacity.checked_add(2 * self.redzone)?;
+        let top = self.top.checked_add(slot)?;
+        if top > self.max_size {
+            return None;
+        }
+        let total = mem.len();
+        let limit = mem.memory_limit;
+        let segment = self.segment(mem)?;
+        if segment.len() < top as usize {
+            let size = (top as usize).next_multiple_of(0x1000).min(self.max_size as usize);
+            if total + (size - segment.len()) > limit {
+                return None;
+            }
+            segment.resize(size, PermField::None.into()).ok()?;
+        }
+        let addr = self.base + self.top + self.redzone;
+        self.top = top;
+        Some(addr)
+    }
+
+    /// `void *malloc(size_t size)` called from `caller`, the chunk is
+    /// readable after it's written. Zero if out of memory
+    pub fn malloc(&mut self, mem: &mut Mmu, size: u64, caller: u64) -> u64 {
+        self.allocate(mem, size, caller, false)
+    }
+
+    /// `void *calloc(size_t nmemb, size_t size)` called from `caller`, the
+    /// chunk is zeroed and readable. Zero if out of memory
+    pub fn calloc(&mut self, mem: &mut Mmu, nmemb: u64, size: u64, caller: u64) -> u64 {
+        match nmemb.checked_mul(size) {
+            Some(size) => self.allocate(mem, size, caller, true),
+            None => 0,
+        }
+    }
+
+    fn allocate(&mut self, mem: &mut Mmu, size: u64, caller: u64, zeroed: bool) -> u64 {
+        if size > self.max_alloc {
+            return 0;
+        }
+        let Some(addr) = self.place(mem, capacity(size)) else {
+            return 0;
+        };
+        self.chunks.insert(addr, Chunk {
+            addr,
+            size,
+            state: ChunkState::Live,
+            allocated_from: caller,
+            freed_from: 0,
+        });
+        let perm = match zeroed {
+            true => PermField::Read | PermField::Write,
+            false => PermField::ReadAfterWrite | PermField::Write,
+        };
+        self.set(mem, addr, size, perm, zeroed);
+        addr
+    }
+
+    /// `void free(void *ptr)` called from `caller`, the chunk loses all its
+    /// permissions and is quarantined. The error is why it can't be freed
+    pub fn free(&mut self, mem: &mut Mmu, addr: u64, caller: u64) -> Result<(), HeapBug> {
+        if addr == 0 {
+            return Ok(());
+        }
+        let chunk = match self.chunks.get_mut(&addr) {
+            Some(chunk) if chunk.state == ChunkState::Live => chunk,
+            _ => return Err(self.classify_free(addr)),
+        };
+        chunk.state = ChunkState::Quarantined;
+        chunk.freed_from = caller;
+        let capacity = chunk.capacity();
+        self.set(mem, addr, capacity, PermField::None.into(), false);
+
+        self.quarantine.push_back(addr);
+        self.quarantined += capacity;
+        while self.quarantined > self.quarantine_limit {
+            let Some(oldest) = self.quarantine.pop_front() else {
+                break;
+            };
+            let chunk = self.chunks.get_mut(&oldest).unwrap();
+            chunk.state = ChunkState::Free;
+            self.quarantined -= chunk.capacity();
+            self.free.entry(chunk.capacity()).or_default().push(oldest);
+        }
+        Ok(())
+    }
+
+    /// `void *realloc(void *ptr, size_t size)` called from `caller`, the
+    /// chunk is always moved so that the stale pointers fault. Zero if out
+    /// of memory, or if freed as `size` is zero
+    pub fn realloc(&mut self, mem: &mut Mmu, addr: u64, size: u64, caller: u64) -> Result<u64, HeapBug> {
+        if addr == 0 {
+            return Ok(self.malloc(mem, size, caller));
+        }
+        let old = match self.chunks.get(&addr) {
+            Some(chunk) if chunk.state == ChunkState::Live => *chunk,
+            _ => return Err(self.classify_free(addr)),
+        };
+        if size == 0 {
+            self.free(mem, addr, caller)?;
+            return Ok(0);
+        }
+        let new = self.malloc(mem, size, caller);
+        if new == 0 {
+            return Ok(0);
+        }
+        // the bytes never written stay unreadable
+        let len = old.size.min(size) as usize;
+        let segment = self.segment(mem).unwrap();
+        let (src, dst) = ((old.addr - self.base) as usize, (new - self.base) as usize);
+        segment.memory.copy_within(src..src + len, dst);
+        segment.permissions.copy_within(src..src + len, dst);
+        segment.dirty_range(dst..dst + len);
+        self.free(mem, addr, caller)?;
+        Ok(new)
+    }
+}
@@ -0,0 +1,334 @@
+//! x86_64 JIT backend of [`CoreEmu`], used by [`CoreEmu::run`] when
+//! [`CoreEmu::backend`] is [`Backend::Jit`].
+//!
+//! The blocks recorded in the [`BlockCache`](super::BlockCache) are
+//! translated to native code the first time they are found there, so the
+//! code that runs once is only interpreted. The guest registers stay in
+//! [`CoreEmu::regs`] and the memory accesses inline the checks of the
+//! [`Mmu`] for the last segments used, everything else (other segments,
+//! faults, read after write and tainted bytes, blocks not dirty yet, the
+//! instructions without a native translation, syscalls and breakpoints)
+//! goes back to the runtime that interprets that single instruction and
+//! resumes the native code after it. The stops are therefore the same [`CoreEmuError`] of the
+//! interpreter, at the same pc and instruction count, so the two backends
+//! can be diffed against each other.
+//!
+//! The translations are dropped with the blocks they come from, e.g. when
+//! the code changes, and all at once when the executable memory is full or
+//! the [`Coverage`](super::Coverage) is turned on or off, as the branches
+//! and jumps (and the `slt` family for the comparison feedback) are
+//! interpreted while it's on.
+//! Forks start without translations.
+mod x86;
+mod translate;
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use mmu::{Mmu, VirtAddr};
+use super::{Backend, Block, CoreEmu, CoreEmuError, DecodedInst, DEADLINE_POLL_INTERVAL};
+
+/// Number of segments whose accesses are inlined at the same time
+const SEGMENT_SLOTS: usize = 4;
+
+/// Size of the executable memory of a [`Jit`]
+const CODE_SIZE: usize = 16 << 20;
+
+/// Number of entries of the direct mapped table checked before the map of
+/// all the translations, must be a power of two
+const JUMP_TABLE_SIZE: usize = 1 << 12;
+
+/// Why the native code returned to the runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[repr(u64)]
+enum Exit {
+    /// Continue from `pc`
+    #[default]
+    Jump = 0,
+    /// Interpret the instruction at `pc`
+    Interpret = 1,
+    /// Interpret the memory access at `pc` to `addr`
+    SlowAccess = 2,
+}
+
+/// A segment of the [`Mmu`] whose accesses are inlined, an empty one
+/// matches no address
+#[derive(Debug, Default, Clone, Copy)]
+#[repr(C)]
+struct JitSegment {
+    start: u64,
+    len: u64,
+    memory: usize,
+    permissions: usize,
+    dirty: usize,
+}
+
+/// State shared between the native code and the runtime
+#[derive(Debug, Default)]
+#[repr(C)]
+struct JitContext {
+    pc: u64,
+    exit: Exit,
+    /// Number of instructions executed by the native code
+    count: u64,
+    /// Address of the last [`Exit::SlowAccess`]
+    addr: u64,
+    segments: [JitSegment; SEGMENT_SLOTS],
+}
+
+type JitFn = unsafe extern "sysv64" fn(regs: *mut u64, context: *mut JitContext);
+
+extern "C" {
+    fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+    fn munmap(addr: *mut u8, len: usize) -> i32;
+}
+
+/// Readable, writable and executable memory for the generated code
+struct CodeMemory {
+    ptr: *mut u8,
+    used: usize,
+}
+
+// the memory is only written through a `&mut`
+unsafe impl Send for CodeMemory {}
+unsafe impl Sync for CodeMemory {}
+
+impl CodeMemory {
+    fn new() -> Self {
+        const PROT_RWX: i32 = 0x7;
+        const MAP_PRIVATE_ANONYMOUS: i32 = 0x22;
+        let ptr = unsafe{mmap(
+            core::ptr::null_mut(), CODE_SIZE, PROT_RWX, MAP_PRIVATE_ANONYMOUS, -1, 0,
+        )};
+        if ptr as isize == -1 {
+            panic!("Cannot map {} bytes of executable memory for the JIT", CODE_SIZE);
+        }
+        CodeMemory { ptr, used: 0 }
+    }
+
+    /// Copy `code` in the memory, if there is space
+    fn push(&mut self, code: &[u8]) -> Option<JitFn> {
+        if self.used + code.len() > CODE_SIZE {
+            return None;
+        }
+        unsafe {
+            let dst = self.ptr.add(self.used);
+            core::ptr::copy_nonoverlapping(code.as_ptr(), dst, code.len());
+            // keep the functions aligned
+            self.used = (self.used + code.len() + 15) & !15;
+            Some(core::mem::transmute::<*mut u8, JitFn>(dst))
+        }
+    }
+}
+
+impl Drop for CodeMemory {
+    fn drop(&mut self) {
+        unsafe{munmap(self.ptr, CODE_SIZE)};
+    }
+}
+
+/// The native code of the instructions of `block` from `start`
+#[derive(Clone)]
+struct Translation {
+    block: Arc<Block>,
+    start: usize,
+    code: JitFn,
+}
+
+impl Translation {
+    #[inline(always)]
+    fn translates(&self, block: &Arc<Block>, start: usize) -> bool {
+        Arc::ptr_eq(&self.block, block) && self.start == start
+    }
+}
+
+/// The native code generated for a [`CoreEmu`], see the module documentation
+#[derive(Default)]
+pub struct Jit {
+    /// Allocated on the first translation
+    memory: Option<CodeMemory>,
+    /// All the translations by pc
+    translations: BTreeMap<u64, Translation>,
+    /// Direct mapped table of the last translations looked up
+    jump_table: Vec<Option<Translation>>,
+    context: JitContext,
+    /// The translations leave the branches and jumps to the interpreter, so
+    /// that the [`Coverage`](super::Coverage) records them
+    coverage: bool,
+    /// The translations leave the `slt` family to the interpreter too, for
+    /// the comparison feedback
+    cmpcov: bool,
+    /// The translations leave the calls and returns to the interpreter, so
+    /// that the [`CallStack`](super::CallStack) records them
+    call_stack: bool,
+    /// The next slot of [`JitContext::segments`] to replace
+    next_segment: usize,
+}
+
+impl Jit {
+    /// Number of translations
+    pub fn len(&self) -> usize {
+        self.translations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.translations.is_empty()
+    }
+
+    /// Drop all the translations
+    pub fn clear(&mut self) {
+        self.translations.clear();
+        self.jump_table.clear();
+        if let Some(memory) = &mut self.memory {
+            memory.used = 0;
+        }
+    }
+
+    /// Forget the pointers to the segments, the [`Mmu`] might have changed
+    /// since they were taken
+    fn clear_segments(&mut self) {
+        self.context.segments = Default::default();
+    }
+
+    /// Make the accesses to the segment with `addr` inline, if it's not
+    fn add_segment(&mut self, mem: &mut Mmu, addr: u64) {
+        let Some(idx) = mem.find_segment(VirtAddr(addr as usize)) else {
+            return;
+        };
+        let (start, segment) = &mut mem.segments[idx];
+        let start = start.0 as u64;
+        if self.context.segments.iter().any(|segment| segment.start == start && segment.len != 0) {
+            return;
+        }
+        let (memory, permissions, dirty) = segment.raw_parts();
+        self.context.segments[self.next_segment] = JitSegment {
+            start,
+            len: segment.len() as u64,
+            memory: memory as usize,
+            permissions: permissions as usize,
+            dirty: dirty as usize,
+        };
+        self.next_segment = (self.next_segment + 1) % SEGMENT_SLOTS;
+    }
+
+    /// Get the native code of the instructions of `block` from `start`,
+    /// which is at `pc`
+    #[inline]
+    fn get(&mut self, block: &Arc<Block>, start: usize, pc: u64) -> JitFn {
+        if self.jump_table.is_empty() {
+            self.jump_table.resize(JUMP_TABLE_SIZE, None);
+        }
+        let slot = (pc as usize >> 1) & (JUMP_TABLE_SIZE - 1);
+        if let Some(translation) = &self.jump_table[slot] {
+            if translation.translates(block, start) {
+                return translation.code;
+            }
+        }
+        let translation = match self.translations.get(&pc) {
+            Some(translation) if translation.translates(block, start) => translation.clone(),
+            _ => self.translate(block, start, pc),
+        };
+        let code = translation.code;
+        self.jump_table[slot] = Some(translation);
+        code
+    }
+
+    #[cold]
+    fn translate(&mut self, block: &Arc<Block>, start: usize, pc: u64) -> Translation {
+        let code = translate::translate(&block.insts[start..], pc, self.coverage, self.cmpcov,
+            self.call_stack);
+        let memory = self.memory.get_or_insert_with(CodeMemory::new);
+        let code = match memory.push(&code) {
+            Some(code) => code,
+            None => {
+                self.clear();
+                self.memory.as_mut().unwrap().push(&code)
+                    .expect("A block doesn't fit in the JIT memory")
+            }
+        };
+        let translation = Translation { block: block.clone(), start, code };
+        self.translations.insert(pc, translation.clone());
+        translation
+    }
+}
+
+impl CoreEmu {
+    /// Check if the next `len` instructions can run without checking the
+    /// budget, i.e. they don't reach the limit nor a deadline poll
+    #[inline(always)]
+    fn fits_budget(&self, len: usize) -> bool {
+        if self.instructions_executed + len > self.instruction_limit {
+            return false;
+        }
+        self.deadline.is_none()
+            || (self.instructions_executed & (DEADLINE_POLL_INTERVAL - 1)) + len <= DEADLINE_POLL_INTERVAL
+    }
+
+    /// [`CoreEmu::run`] with [`Backend::Jit`]
+    pub(crate) fn run_jit(&mut self) -> CoreEmuError {
+        debug_assert_eq!(self.backend, Backend::Jit);
+        // the segments might have been resized or dropped since the last run
+        self.jit.clear_segments();
+        // the coverage or the call stack might have been turned on or off
+        // since the last run
+        let cmpcov = self.coverage.as_ref().is_some_and(|coverage| coverage.cmpcov());
+        if self.jit.coverage != self.coverage.is_some() || self.jit.cmpcov != cmpcov
+            || self.jit.call_stack != self.call_stack.is_some() {
+            self.jit.clear();
+            self.jit.coverage = self.coverage.is_some();
+            self.jit.cmpcov = cmpcov;
+            self.jit.call_stack = self.call_stack.is_some();
+        }
+        // the rest of a block after an instruction interpreted in the middle
+        let mut resume: Option<(Arc<Block>, usize)> = None;
+        loop {
+            if self.budget_exhausted() {
+                return CoreEmuError::Timeout;
+            }
+            let (block, start) = match resume.take() {
+                Some(resume) => resume,
+                None => match self.block_cache.get(&self.mem, self.pc) {
+                    Some(block) => (block, 0),
+                    None => match self.run_and_decode() {
+                        Some(e) => return e,
+                        None => continue,
+                    },
+                },
+            };
+            // stop at the same instruction of the interpreter
+            if !self.fits_budget(block.insts.len() - start) {
+                match self.run_block(&block.insts[start..]) {
+                    Some(e) => return e,
+                    None => continue,
+                }
+            }
+
+            let code = self.jit.get(&block, start, self.pc);
+            unsafe{code(self.regs.as_mut_ptr(), &mut self.jit.context)};
+            let context = &self.jit.context;
+            let count = context.count as usize;
+            self.pc = context.pc;
+            self.instructions_executed += count;
+            let exit = context.exit;
+            let addr = context.addr;
+            if exit == Exit::Jump {
+                continue;
+            }
+            if exit == Exit::SlowAccess {
+                self.jit.add_segment(&mut self.mem, addr);
+            }
+
+            let index = start + count;
+            let inst: DecodedInst = block.insts[index];
+            self.instructions_executed += 1;
+            let next_pc = self.pc + inst.len();
+            if let Err(e) = inst.execute(self) {
+                return e;
+            }
+            if self.pc == next_pc && index + 1 < block.insts.len()
+                && self.block_cache.is_valid(&self.mem) {
+                resume = Some((block, index + 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,836 @@
+//! Translation of a run of [`DecodedInst`] to x86_64.
+//!
+//! The generated code is a `extern "sysv64" fn(regs, context)`, the guest
+//! registers are read and written in place through `rdi` and the
+//! [`JitContext`] is in `rsi`. Every exit stores the next pc and how many
+//! instructions were executed, so the code doesn't need a stack frame.
+use alloc::vec::Vec;
+use core::mem::{offset_of, size_of};
+use diss::riscv64gc::*;
+use mmu::{PermField, SegmentMmu};
+use super::x86::*;
+use super::{Exit, JitContext, JitSegment};
+use crate::riscv64gc::DecodedInst;
+
+/// Holds the pointer to the guest registers
+const REGS: Reg = Reg::Rdi;
+/// Holds the pointer to the [`JitContext`]
+const CONTEXT: Reg = Reg::Rsi;
+
+/// log2 of the size of the dirty blocks of the segments
+const DIRTY_BLOCK_SHIFT: u8 = <SegmentMmu>::DIRTY_BLOCK_SIZE.trailing_zeros() as u8;
+
+/// The instruction has no native translation, it's interpreted
+pub(super) struct Unsupported;
+
+/// The register of the guest `reg`
+fn guest(reg: Register) -> Mem {
+    Mem::new(REGS, reg as i32 * 8)
+}
+
+/// A field of the [`JitContext`]
+fn context(offset: usize) -> Mem {
+    Mem::new(CONTEXT, offset as i32)
+}
+
+/// A field of the `slot`-th [`JitSegment`] of the [`JitContext`]
+fn segment(slot: usize, offset: usize) -> usize {
+    offset_of!(JitContext, segments) + slot * size_of::<JitSegment>() + offset
+}
+
+/// Second operand of an ALU operation
+#[derive(Clone, Copy)]
+enum Src {
+    Reg(Register),
+    Imm(i32),
+}
+
+#[derive(Clone, Copy)]
+enum Access {
+    Load { rd: Register, signed: bool },
+    Store { rs2: Register },
+}
+
+/// A memory access that couldn't be done inline, it's interpreted
+struct SlowPath {
+    jumps: Vec<Fixup>,
+    pc: u64,
+    index: usize,
+}
+
+struct Translator {
+    asm: Assembler,
+    /// pc of the instruction being translated
+    pc: u64,
+    /// Size in bytes of the instruction being translated
+    len: u64,
+    /// Number of instructions before the one being translated
+    index: usize,
+    /// The instruction jumps, so nothing can follow it
+    ends_block: bool,
+    /// The branches and jumps are interpreted to record their edges
+    coverage: bool,
+    /// The `slt` family is interpreted to record the comparisons
+    cmpcov: bool,
+    /// The calls and returns are interpreted to update the call stack
+    call_stack: bool,
+    slow_paths: Vec<SlowPath>,
+}
+
+/// Translate `insts`, the first of which is at `pc`, leaving the branches
+/// and jumps to the interpreter if `coverage`, the `slt` family too if
+/// `cmpcov` and the calls and returns if `call_stack`
+pub(super) fn translate(insts: &[DecodedInst], pc: u64, coverage: bool, cmpcov: bool,
+    call_stack: bool) -> Vec<u8> {
+    let mut translator = Translator {
+        asm: Assembler::default(),
+        pc,
+        len: 0,
+        index: 0,
+        ends_block: false,
+        coverage,
+        cmpcov,
+        call_stack,
+        slow_paths: Vec::new(),
+    };
+    let mut done = false;
+    for inst in insts {
+        translator.len = inst.len();
+        if diss_riscv64gc(&mut translator, inst.inst).is_err() {
+            translator.exit(translator.pc, Exit::Interpret, translator.index);
+            done = true;
+            break;
+        }
+        translator.index += 1;
+        if translator.ends_block {
+            done = true;
+            break;
+        }
+        translator.pc = translator.pc.wrapping_add(translator.len);
+    }
+    if !done {
+        translator.exit(translator.pc, Exit::Jump, translator.index);
+    }
+
+    for slow_path in core::mem::take(&mut translator.slow_paths) {
+        for jump in slow_path.jumps {
+            translator.asm.bind(jump);
+        }
+        translator.asm.mov_store(context(offset_of!(JitContext, addr)), Reg::Rax);
+        translator.exit(slow_path.pc, Exit::SlowAccess, slow_path.index);
+    }
+    translator.asm.code
+}
+
+impl Translator {
+    /// Return to the runtime with `count` instructions executed
+    fn exit(&mut self, pc: u64, exit: Exit, count: usize) {
+        self.asm.mov_imm(Reg::Rax, pc);
+        self.exit_to_rax(exit, count);
+    }
+
+    /// Return to the runtime at the pc in `rax`
+    fn exit_to_rax(&mut self, exit: Exit, count: usize) {
+        self.asm.mov_store(context(offset_of!(JitContext, pc)), Reg::Rax);
+        self.asm.mov_store_imm(context(offset_of!(JitContext, exit)), exit as i32);
+        self.asm.mov_store_imm(context(offset_of!(JitContext, count)), count as i32);
+        self.asm.ret();
+    }
+
+    /// Write `rax` to `rd`, writes to the zero register are ignored
+    fn write_rd(&mut self, rd: Register, src: Reg) {
+        if rd != Register::Zero {
+            self.asm.mov_store(guest(rd), src);
+        }
+    }
+
+    /// `rd = rs1 op src`, if not `wide` it's done on 32 bits and sign extended
+    fn alu(&mut self, op: Alu, wide: bool, rd: Register, rs1: Register, src: Src) {
+        if rd == Register::Zero {
+            return;
+        }
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        match src {
+            Src::Reg(rs2) => self.asm.alu(op, wide, Reg::Rax, Rm::Mem(guest(rs2))),
+            Src::Imm(imm) => self.asm.alu_imm(op, wide, Rm::Reg(Reg::Rax), imm),
+        }
+        if !wide {
+            self.asm.movsxd(Reg::Rax, Reg::Rax);
+        }
+        self.write_rd(rd, Reg::Rax);
+    }
+
+    /// `rd = (rs1 cmp src) as u64`
+    fn set(&mut self, cond: Cond, rd: Register, rs1: Register, src: Src) -> Result<(), Unsupported> {
+        if self.cmpcov {
+            return Err(Unsupported);
+        }
+        if rd == Register::Zero {
+            return Ok(());
+        }
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        match src {
+            Src::Reg(rs2) => self.asm.alu(Alu::Cmp, true, Reg::Rax, Rm::Mem(guest(rs2))),
+            Src::Imm(imm) => self.asm.alu_imm(Alu::Cmp, true, Rm::Reg(Reg::Rax), imm),
+        }
+        self.asm.set(cond, Reg::Rax);
+        self.write_rd(rd, Reg::Rax);
+        Ok(())
+    }
+
+    /// `rd = rs1 op src`, the shift amount is masked like x86 does, i.e.
+    /// like riscv
+    fn shift(&mut self, op: Shift, wide: bool, rd: Register, rs1: Register, src: Src) {
+        if rd == Register::Zero {
+            return;
+        }
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        match src {
+            Src::Reg(rs2) => {
+                self.asm.mov(Reg::Rcx, Rm::Mem(guest(rs2)));
+                self.asm.shift_cl(op, wide, Reg::Rax);
+            }
+            Src::Imm(shamt) => {
+                let mask = if wide { 0b111111 } else { 0b11111 };
+                self.asm.shift_imm(op, wide, Reg::Rax, (shamt & mask) as u8);
+            }
+        }
+        if !wide {
+            self.asm.movsxd(Reg::Rax, Reg::Rax);
+        }
+        self.write_rd(rd, Reg::Rax);
+    }
+
+    /// `rd = imm`
+    fn load_imm(&mut self, rd: Register, imm: u64) {
+        if rd != Register::Zero {
+            self.asm.mov_imm(Reg::Rax, imm);
+            self.write_rd(rd, Reg::Rax);
+        }
+    }
+
+    /// The high 64 bits of the product of `rs1` and `rs2`
+    fn mul_high(&mut self, signed: bool, rd: Register, rs1: Register, rs2: Register) {
+        if rd == Register::Zero {
+            return;
+        }
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        self.asm.mul_wide(signed, Rm::Mem(guest(rs2)));
+        self.write_rd(rd, Reg::Rdx);
+    }
+
+    /// Jump to `target` setting `rd` to the return address
+    fn jump(&mut self, rd: Register, target: u64) -> Result<(), Unsupported> {
+        if self.coverage || (self.call_stack && rd == Register::Ra) {
+            return Err(Unsupported);
+        }
+        self.load_imm(rd, self.pc.wrapping_add(self.len));
+        self.exit(target, Exit::Jump, self.index + 1);
+        self.ends_block = true;
+        Ok(())
+    }
+
+    /// Jump to `rs1 + imm` setting `rd` to the return address
+    fn jump_indirect(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Unsupported> {
+        let ret = rd == Register::Zero && rs1 == Register::Ra;
+        if self.coverage || (self.call_stack && (rd == Register::Ra || ret)) {
+            return Err(Unsupported);
+        }
+        // read the target first as rs1 might be rd
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        if imm != 0 {
+            self.asm.alu_imm(Alu::Add, true, Rm::Reg(Reg::Rax), imm);
+        }
+        self.asm.alu_imm(Alu::And, true, Rm::Reg(Reg::Rax), !1);
+        if rd != Register::Zero {
+            self.asm.mov_imm(Reg::Rcx, self.pc.wrapping_add(self.len));
+            self.write_rd(rd, Reg::Rcx);
+        }
+        self.exit_to_rax(Exit::Jump, self.index + 1);
+        self.ends_block = true;
+        Ok(())
+    }
+
+    /// Jump to `pc + offset` if `rs1 cond src`
+    fn branch(&mut self, cond: Cond, rs1: Register, src: Src, offset: i64) -> Result<(), Unsupported> {
+        if self.coverage {
+            return Err(Unsupported);
+        }
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+        match src {
+            Src::Reg(rs2) => self.asm.alu(Alu::Cmp, true, Reg::Rax, Rm::Mem(guest(rs2))),
+            Src::Imm(imm) => self.asm.alu_imm(Alu::Cmp, true, Rm::Reg(Reg::Rax), imm),
+        }
+        let taken = self.asm.jcc(cond);
+        self.exit(self.pc.wrapping_add(self.len), Exit::Jump, self.index + 1);
+        self.asm.bind(taken);
+        self.exit(self.pc.wrapping_add_signed(offset), Exit::Jump, self.index + 1);
+        self.ends_block = true;
+        Ok(())
+    }
+
+    /// Inline the fast path of [`mmu::Mmu::read`] and [`mmu::Mmu::write`]
+    /// of `size` bytes at `base + offset`: the address must be in one of the
+    /// segments of the [`JitContext`], the bytes must be readable (and not
+    /// to taint) or writable (and not read after write nor executable) and
+    /// the dirty blocks touched must already be dirty. Everything else is
+    /// left to the interpreter
+    fn access(&mut self, size: usize, base: Register, offset: i32, access: Access) {
+        let mut slow = Vec::new();
+        self.asm.mov(Reg::Rax, Rm::Mem(guest(base)));
+        if offset != 0 {
+            self.asm.alu_imm(Alu::Add, true, Rm::Reg(Reg::Rax), offset);
+        }
+
+        // r8 = the segment, rdx = the offset in it
+        let mut found = Vec::new();
+        for slot in 0..super::SEGMENT_SLOTS {
+            self.asm.mov(Reg::Rdx, Rm::Reg(Reg::Rax));
+            self.asm.alu(Alu::Sub, true, Reg::Rdx, 
+                Rm::Mem(context(segment(slot, offset_of!(JitSegment, start)))));
+            self.asm.alu(Alu::Cmp, true, Reg::Rdx, 
+                Rm::Mem(context(segment(slot, offset_of!(JitSegment, len)))));
+            self.asm.lea(Reg::R8, context(segment(slot, 0)));
+            found.push(self.asm.jcc(Cond::B));
+        }
+        slow.push(self.asm.jmp());
+        for jump in found {
+            self.asm.bind(jump);
+        }
+        self.asm.lea(Reg::Rcx, Mem::new(Reg::Rdx, size as i32));
+        self.asm.alu(Alu::Cmp, true, Reg::Rcx, 
+            Rm::Mem(Mem::new(Reg::R8, offset_of!(JitSegment, len) as i32)));
+        slow.push(self.asm.jcc(Cond::A));
+
+        // the permissions of all the bytes
+        let (mask, expected) = match access {
+            Access::Load { .. } => (
+                PermField::Read | PermField::ToTaint, 
+                u8::from(PermField::Read),
+            ),
+            Access::Store { .. } => (
+                PermField::Write | PermField::ReadAfterWrite | PermField::Executable, 
+                u8::from(PermField::Write),
+            ),
+        };
+        let broadcast = |byte: u8| u64::from_ne_bytes([byte; 8]) >> (64 - 8 * size);
+        self.asm.mov(Reg::R9, Rm::Mem(Mem::new(Reg::R8, offset_of!(JitSegment, permissions) as i32)));
+        self.asm.load_zx(size, Reg::Rcx, Mem::indexed(Reg::R9, Reg::Rdx));
+        if size == 8 {
+            self.asm.mov_imm(Reg::R10, broadcast(mask.0));
+            self.asm.alu(Alu::And, true, Reg::Rcx, Rm::Reg(Reg::R10));
+            self.asm.mov_imm(Reg::R10, broadcast(expected));
+            self.asm.alu(Alu::Cmp, true, Reg::Rcx, Rm::Reg(Reg::R10));
+        } else {
+            self.asm.alu_imm(Alu::And, false, Rm::Reg(Reg::Rcx), broadcast(mask.0) as i32);
+            self.asm.alu_imm(Alu::Cmp, false, Rm::Reg(Reg::Rcx), broadcast(expected) as i32);
+        }
+        slow.push(self.asm.jcc(Cond::Ne));
+
+        // reads dirty the first block as they might taint it, writes the
+        // first and the last
+        self.asm.mov(Reg::R9, Rm::Mem(Mem::new(Reg::R8, offset_of!(JitSegment, dirty) as i32)));
+        let mut last_bytes = alloc::vec![0];
+        if matches!(access, Access::Store { .. }) && size > 1 {
+            last_bytes.push(size - 1);
+        }
+        for byte in last_bytes {
+            self.asm.lea(Reg::Rcx, Mem::new(Reg::Rdx, byte as i32));
+            self.asm.shift_imm(Shift::Shr, true, Reg::Rcx, DIRTY_BLOCK_SHIFT);
+            self.asm.bt(Mem::new(Reg::R9, 0), Reg::Rcx);
+            slow.push(self.asm.jcc(Cond::Ae));
+        }
+
+        self.asm.mov(Reg::R9, Rm::Mem(Mem::new(Reg::R8, offset_of!(JitSegment, memory) as i32)));
+        let data = Mem::indexed(Reg::R9, Reg::Rdx);
+        match access {
+            Access::Load { rd, signed: true } => {
+                self.asm.load_sx(size, Reg::Rax, data);
+                self.write_rd(rd, Reg::Rax);
+            }
+            Access::Load { rd, signed: false } => {
+                self.asm.load_zx(size, Reg::Rax, data);
+                self.write_rd(rd, Reg::Rax);
+            }
+            Access::Store { rs2 } => {
+                self.asm.mov(Reg::Rcx, Rm::Mem(guest(rs2)));
+                self.asm.store(size, data, Reg::Rcx);
+            }
+        }
+        self.slow_paths.push(SlowPath { jumps: slow, pc: self.pc, index: self.index });
+    }
+
+    fn load(&mut self, size: usize, signed: bool, rd: Register, rs1: Register, offset: i32) {
+        self.access(size, rs1, offset, Access::Load { rd, signed });
+    }
+
+    fn store(&mut self, size: usize, rs1: Register, rs2: Register, offset: i32) {
+        self.access(size, rs1, offset, Access::Store { rs2 });
+    }
+}
+
+/// Implement the instructions without a native translation
+macro_rules! unsupported {
+    ($($name:ident($($arg:ident: $ty:ty),*);)*) => {
+$(
+    fn $name(&mut self, $(_: $ty),*) -> Result<(), Self::Error> {
+        Err(Unsupported)
+    }
+)*
+    };
+}
+
+impl RV64GCUser<()> for Translator {
+    type Error = Unsupported;
+
+    fn lui(&mut self, rd: Register, imm: u32) -> Result<(), Self::Error> {
+        self.load_imm(rd, imm as i32 as i64 as u64);
+        Ok(())
+    }
+    fn auipc(&mut self, rd: Register, imm: u32) -> Result<(), Self::Error> {
+        self.load_imm(rd, self.pc.wrapping_add_signed(imm as i32 as i64));
+        Ok(())
+    }
+    fn addi(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rd, rs1, Src::Imm(imm));
+        Ok(())
+    }
+    fn slti(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.set(Cond::L, rd, rs1, Src::Imm(imm))
+    }
+    fn sltiu(&mut self, rd: Register, rs1: Register, imm: u32) -> Result<(), Self::Error> {
+        self.set(Cond::B, rd, rs1, Src::Imm(imm as i32))
+    }
+    fn xori(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.alu(Alu::Xor, true, rd, rs1, Src::Imm(imm));
+        Ok(())
+    }
+    fn ori(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.alu(Alu::Or, true, rd, rs1, Src::Imm(imm));
+        Ok(())
+    }
+    fn andi(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.alu(Alu::And, true, rd, rs1, Src::Imm(imm));
+        Ok(())
+    }
+    fn slli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Shl, true, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn srli(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Shr, true, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn srai(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Sar, true, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn add(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn sub(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Sub, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn sll(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Shl, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn slt(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.set(Cond::L, rd, rs1, Src::Reg(rs2))
+    }
+    fn sltu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.set(Cond::B, rd, rs1, Src::Reg(rs2))
+    }
+    fn xor(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Xor, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn srl(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Shr, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn sra(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Sar, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn or(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Or, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn and(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::And, true, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn lb(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(1, true, rd, rs1, imm);
+        Ok(())
+    }
+    fn lh(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(2, true, rd, rs1, imm);
+        Ok(())
+    }
+    fn lw(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(4, true, rd, rs1, imm);
+        Ok(())
+    }
+    fn ld(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(8, true, rd, rs1, imm);
+        Ok(())
+    }
+    fn lbu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(1, false, rd, rs1, imm);
+        Ok(())
+    }
+    fn lhu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(2, false, rd, rs1, imm);
+        Ok(())
+    }
+    fn lwu(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load(4, false, rd, rs1, imm);
+        Ok(())
+    }
+    fn sb(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.store(1, rs1, rs2, imm);
+        Ok(())
+    }
+    fn sh(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.store(2, rs1, rs2, imm);
+        Ok(())
+    }
+    fn sw(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.store(4, rs1, rs2, imm);
+        Ok(())
+    }
+    fn sd(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.store(8, rs1, rs2, imm);
+        Ok(())
+    }
+    fn jal(&mut self, rd: Register, imm: i32) -> Result<(), Self::Error> {
+        self.jump(rd, self.pc.wrapping_add_signed(imm as i64))
+    }
+    fn jalr(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.jump_indirect(rd, rs1, imm)
+    }
+    fn beq(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::E, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn bne(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::Ne, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn blt(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::L, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn bge(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::Ge, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn bltu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::B, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn bgeu(&mut self, rs1: Register, rs2: Register, imm: i32) -> Result<(), Self::Error> {
+        self.branch(Cond::Ae, rs1, Src::Reg(rs2), imm as i64)
+    }
+    fn addiw(&mut self, rd: Register, rs1: Register, imm: i32) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, false, rd, rs1, Src::Imm(imm));
+        Ok(())
+    }
+    fn slliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Shl, false, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn srliw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Shr, false, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn sraiw(&mut self, rd: Register, rs1: Register, shamt: i32) -> Result<(), Self::Error> {
+        self.shift(Shift::Sar, false, rd, rs1, Src::Imm(shamt));
+        Ok(())
+    }
+    fn addw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, false, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn subw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Sub, false, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn sllw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Shl, false, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn srlw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Shr, false, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn sraw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.shift(Shift::Sar, false, rd, rs1, Src::Reg(rs2));
+        Ok(())
+    }
+    fn mul(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        if rd != Register::Zero {
+            self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+            self.asm.imul(true, Reg::Rax, Rm::Mem(guest(rs2)));
+            self.write_rd(rd, Reg::Rax);
+        }
+        Ok(())
+    }
+    fn mulh(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.mul_high(true, rd, rs1, rs2);
+        Ok(())
+    }
+    fn mulhu(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.mul_high(false, rd, rs1, rs2);
+        Ok(())
+    }
+    fn mulw(&mut self, rd: Register, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        if rd != Register::Zero {
+            self.asm.mov(Reg::Rax, Rm::Mem(guest(rs1)));
+            self.asm.imul(false, Reg::Rax, Rm::Mem(guest(rs2)));
+            self.asm.movsxd(Reg::Rax, Reg::Rax);
+            self.write_rd(rd, Reg::Rax);
+        }
+        Ok(())
+    }
+    fn c_addi4spn(&mut self, rd: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rd, Register::Sp, Src::Imm(uimm as i32));
+        Ok(())
+    }
+    fn c_lw(&mut self, rd: Register, rs1: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.load(4, true, rd, rs1, uimm as i32);
+        Ok(())
+    }
+    fn c_ld(&mut self, rd: Register, rs1: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.load(8, true, rd, rs1, uimm as i32);
+        Ok(())
+    }
+    fn c_sw(&mut self, rs1: Register, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.store(4, rs1, rs2, uimm as i32);
+        Ok(())
+    }
+    fn c_sd(&mut self, rs1: Register, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.store(8, rs1, rs2, uimm as i32);
+        Ok(())
+    }
+    fn c_addi(&mut self, rd: Register, imm: i8) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rd, rd, Src::Imm(imm as i32));
+        Ok(())
+    }
+    fn c_jal(&mut self, imm: u16) -> Result<(), Self::Error> {
+        self.jump(Register::Ra, self.pc.wrapping_add_signed(imm as i16 as i64))
+    }
+    fn c_addiw(&mut self, rd: Register, imm: i8) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, false, rd, rd, Src::Imm(imm as i32));
+        Ok(())
+    }
+    fn c_li(&mut self, rd: Register, imm: i8) -> Result<(), Self::Error> {
+        self.load_imm(rd, imm as i64 as u64);
+        Ok(())
+    }
+    fn c_addi16sp(&mut self, imm: i16) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, Register::Sp, Register::Sp, Src::Imm(imm as i32));
+        Ok(())
+    }
+    fn c_lui(&mut self, rd: Register, imm: i32) -> Result<(), Self::Error> {
+        self.load_imm(rd, imm as i64 as u64);
+        Ok(())
+    }
+    fn c_srli(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+        self.shift(Shift::Shr, true, rd, rd, Src::Imm(uimm as i32));
+        Ok(())
+    }
+    fn c_srai(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+        self.shift(Shift::Sar, true, rd, rd, Src::Imm(uimm as i32));
+        Ok(())
+    }
+    fn c_andi(&mut self, rd: Register, imm: i8) -> Result<(), Self::Error> {
+        self.alu(Alu::And, true, rd, rd, Src::Imm(imm as i32));
+        Ok(())
+    }
+    fn c_sub(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Sub, true, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_xor(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Xor, true, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_or(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Or, true, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_and(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::And, true, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_subw(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Sub, false, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_addw(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, false, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_j(&mut self, imm: i16) -> Result<(), Self::Error> {
+        self.jump(Register::Zero, self.pc.wrapping_add_signed(imm as i64))
+    }
+    fn c_beqz(&mut self, rs1: Register, offset: i16) -> Result<(), Self::Error> {
+        self.branch(Cond::E, rs1, Src::Imm(0), offset as i64)
+    }
+    fn c_bnez(&mut self, rs1: Register, offset: i16) -> Result<(), Self::Error> {
+        self.branch(Cond::Ne, rs1, Src::Imm(0), offset as i64)
+    }
+    fn c_slli(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+        self.shift(Shift::Shl, true, rd, rd, Src::Imm(uimm as i32));
+        Ok(())
+    }
+    fn c_lwsp(&mut self, rd: Register, uimm: u8) -> Result<(), Self::Error> {
+        self.load(4, true, rd, Register::Sp, uimm as i32);
+        Ok(())
+    }
+    fn c_ldsp(&mut self, rd: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.load(8, true, rd, Register::Sp, uimm as i32);
+        Ok(())
+    }
+    fn c_jr(&mut self, rs1: Register) -> Result<(), Self::Error> {
+        self.jump_indirect(Register::Zero, rs1, 0)
+    }
+    fn c_mv(&mut self, rs1: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rs1, rs2, Src::Imm(0));
+        Ok(())
+    }
+    fn c_jalr(&mut self, rs1: Register) -> Result<(), Self::Error> {
+        self.jump_indirect(Register::Ra, rs1, 0)
+    }
+    fn c_add(&mut self, rd: Register, rs2: Register) -> Result<(), Self::Error> {
+        self.alu(Alu::Add, true, rd, rd, Src::Reg(rs2));
+        Ok(())
+    }
+    fn c_swsp(&mut self, rs2: Register, uimm: u8) -> Result<(), Self::Error> {
+        self.store(4, Register::Sp, rs2, uimm as i32);
+        Ok(())
+    }
+    fn c_sdsp(&mut self, rs2: Register, uimm: u16) -> Result<(), Self::Error> {
+        self.store(8, Register::Sp, rs2, uimm as i32);
+        Ok(())
+    }
+    fn c_nop(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    unsupported!{
+        csrrw(rd: Register, rs1: Register, csr: u32);
+        csrrs(rd: Register, rs1: Register, csr: u32);
+        csrrc(rd: Register, rs1: Register, csr: u32);
+        csrrwi(rd: Register, zimm: u8, csr: u32);
+        csrrsi(rd: Register, zimm: u8, csr: u32);
+        csrrci(rd: Register, zimm: u8, csr: u32);
+        mulhsu(rd: Register, rs1: Register, rs2: Register);
+        div(rd: Register, rs1: Register, rs2: Register);
+        divu(rd: Register, rs1: Register, rs2: Register);
+        rem(rd: Register, rs1: Register, rs2: Register);
+        remu(rd: Register, rs1: Register, rs2: Register);
+        divw(rd: Register, rs1: Register, rs2: Register);
+        divuw(rd: Register, rs1: Register, rs2: Register);
+        remw(rd: Register, rs1: Register, rs2: Register);
+        remuw(rd: Register, rs1: Register, rs2: Register);
+        lr_w(rd: Register, rs1: Register, aq: bool, rl: bool);
+        sc_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoswap_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoadd_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoxor_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoand_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoor_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomin_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomax_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amominu_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomaxu_w(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        lr_d(rd: Register, rs1: Register, aq: bool, rl: bool);
+        sc_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoswap_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoadd_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoxor_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoand_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amoor_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomin_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomax_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amominu_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        amomaxu_d(rd: Register, rs1: Register, rs2: Register, aq: bool, rl: bool);
+        fmadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fmsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fadd_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsub_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fmul_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fdiv_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsqrt_s(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fsgnj_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjn_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjx_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmin_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmax_s(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fcvt_w_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_wu_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fmv_x_w(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        feq_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        flt_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fle_s(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fclass_s(rd: Register, rs1: FloatRegister);
+        fcvt_s_w(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_s_wu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fmv_w_x(rd: FloatRegister, rs1: Register);
+        fmadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fmsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fnmadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rs3: FloatRegister, rm: FloatRoundingMode);
+        fadd_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsub_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fmul_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fdiv_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister, rm: FloatRoundingMode);
+        fsqrt_d(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fsgnj_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjn_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fsgnjx_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmin_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fmax_d(rd: FloatRegister, rs1: FloatRegister, rs2: FloatRegister);
+        fcvt_s_d(rd: FloatRegister, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_s(rd: FloatRegister, rs1: FloatRegister);
+        feq_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        flt_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fle_d(rd: Register, rs1: FloatRegister, rs2: FloatRegister);
+        fclass_d(rd: Register, rs1: FloatRegister);
+        fcvt_w_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_wu_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_w(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_d_wu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        flw(rd: FloatRegister, rs1: Register, imm: i32);
+        fsw(rs1: Register, rs2: FloatRegister, offset: i32);
+        fld(rd: FloatRegister, rs1: Register, offset: i32);
+        fsd(rs1: Register, rs2: FloatRegister, offset: i32);
+        fcvt_l_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_lu_s(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_s_l(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_s_lu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_l_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_lu_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fmv_x_d(rd: Register, rs1: FloatRegister, rm: FloatRoundingMode);
+        fcvt_d_l(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fcvt_d_lu(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        fmv_d_x(rd: FloatRegister, rs1: Register, rm: FloatRoundingMode);
+        c_fld(rd: FloatRegister, rs1: Register, imm: u16);
+        c_flw(rd: FloatRegister, rs1: Register, uimm: u16);
+        c_fsd(rs1: Register, rs2: FloatRegister, uimm: u16);
+        c_fsw(rs1: Register, rs2: FloatRegister, uimm: u8);
+        c_fldsp(rd: FloatRegister, uimm: u16);
+        c_flwsp(rd: FloatRegister, uimm: u8);
+        c_fsdsp(rs2: FloatRegister, uimm: u16);
+        c_fswsp(rs2: FloatRegister, uimm: u8);
+        fence();
+        fence_i();
+        ecall();
+        ebreak();
+        c_ebreak();
+        illegal(inst: u32);
+    }
+}
@@ -0,0 +1,292 @@
+//! A tiny x86_64 assembler with only the instructions the translator needs
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Reg {
+    Rax = 0,
+    Rcx = 1,
+    Rdx = 2,
+    Rsi = 6,
+    Rdi = 7,
+    R8 = 8,
+    R9 = 9,
+    R10 = 10,
+}
+
+/// A memory operand `[base + index + disp]`
+#[derive(Debug, Clone, Copy)]
+pub struct Mem {
+    pub base: Reg,
+    pub index: Option<Reg>,
+    pub disp: i32,
+}
+
+impl Mem {
+    pub fn new(base: Reg, disp: i32) -> Self {
+        Mem { base, index: None, disp }
+    }
+
+    pub fn indexed(base: Reg, index: Reg) -> Self {
+        Mem { base, index: Some(index), disp: 0 }
+    }
+}
+
+/// Operand that can be either a register or memory
+#[derive(Debug, Clone, Copy)]
+pub enum Rm {
+    Reg(Reg),
+    Mem(Mem),
+}
+
+/// Condition codes of `jcc` and `setcc`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Cond {
+    B = 0x2,
+    Ae = 0x3,
+    E = 0x4,
+    Ne = 0x5,
+    A = 0x7,
+    L = 0xc,
+    Ge = 0xd,
+}
+
+/// The ALU operations with the `op r, r/m` and `op r/m, imm` forms, the
+/// value is the `/digit` of the immediate form
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Alu {
+    Add = 0,
+    Or = 1,
+    And = 4,
+    Sub = 5,
+    Xor = 6,
+    Cmp = 7,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Shift {
+    Shl = 4,
+    Shr = 5,
+    Sar = 7,
+}
+
+/// Position of the rel32 of a jump, to patch once the target is known
+#[derive(Debug, Clone, Copy)]
+pub struct Fixup(usize);
+
+#[derive(Debug, Default)]
+pub struct Assembler {
+    pub code: Vec<u8>,
+}
+
+impl Assembler {
+    /// Emit the optional prefix, the REX, the opcode and the ModRM (plus SIB
+    /// and displacement) for `reg` and `rm`
+    fn emit(&mut self, prefix: Option<u8>, wide: bool, opcode: &[u8], reg: u8, rm: Rm) {
+        if let Some(prefix) = prefix {
+            self.code.push(prefix);
+        }
+        let (index, base) = match rm {
+            Rm::Reg(reg) => (0, reg as u8),
+            Rm::Mem(mem) => (mem.index.map(|r| r as u8).unwrap_or(0), mem.base as u8),
+        };
+        let rex = 0x40 | (wide as u8) << 3 | (reg >> 3) << 2 | (index >> 3) << 1 | base >> 3;
+        if rex != 0x40 {
+            self.code.push(rex);
+        }
+        self.code.extend_from_slice(opcode);
+
+        let mem = match rm {
+            Rm::Reg(rm) => {
+                self.code.push(0xc0 | (reg & 7) << 3 | (rm as u8 & 7));
+                return;
+            }
+            Rm::Mem(mem) => mem,
+        };
+        // rsp and r12 as base need a SIB, which we never use
+        debug_assert!(mem.base as u8 & 7 != 4);
+        // rbp and r13 can't be encoded without displacement
+        let mode = if mem.disp == 0 && mem.base as u8 & 7 != 5 {
+            0b00
+        } else if mem.disp as i8 as i32 == mem.disp {
+            0b01
+        } else {
+            0b10
+        };
+        match mem.index {
+            None => self.code.push(mode << 6 | (reg & 7) << 3 | (mem.base as u8 & 7)),
+            Some(index) => {
+                self.code.push(mode << 6 | (reg & 7) << 3 | 0b100);
+                self.code.push((index as u8 & 7) << 3 | (mem.base as u8 & 7));
+            }
+        }
+        match mode {
+            0b01 => self.code.push(mem.disp as i8 as u8),
+            0b10 => self.code.extend_from_slice(&mem.disp.to_le_bytes()),
+            _ => {}
+        }
+    }
+
+    /// `mov dst, src` on 64 bits
+    pub fn mov(&mut self, dst: Reg, src: Rm) {
+        self.emit(None, true, &[0x8b], dst as u8, src);
+    }
+
+    /// `mov [dst], src` on 64 bits
+    pub fn mov_store(&mut self, dst: Mem, src: Reg) {
+        self.emit(None, true, &[0x89], src as u8, Rm::Mem(dst));
+    }
+
+    /// Store the low `size` bytes of `src`
+    pub fn store(&mut self, size: usize, dst: Mem, src: Reg) {
+        match size {
+            1 => self.emit(None, false, &[0x88], src as u8, Rm::Mem(dst)),
+            2 => self.emit(Some(0x66), false, &[0x89], src as u8, Rm::Mem(dst)),
+            4 => self.emit(None, false, &[0x89], src as u8, Rm::Mem(dst)),
+            8 => self.mov_store(dst, src),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Load `size` bytes zero extending them to 64 bits
+    pub fn load_zx(&mut self, size: usize, dst: Reg, src: Mem) {
+        match size {
+            1 => self.emit(None, false, &[0x0f, 0xb6], dst as u8, Rm::Mem(src)),
+            2 => self.emit(None, false, &[0x0f, 0xb7], dst as u8, Rm::Mem(src)),
+            4 => self.emit(None, false, &[0x8b], dst as u8, Rm::Mem(src)),
+            8 => self.mov(dst, Rm::Mem(src)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Load `size` bytes sign extending them to 64 bits
+    pub fn load_sx(&mut self, size: usize, dst: Reg, src: Mem) {
+        match size {
+            1 => self.emit(None, true, &[0x0f, 0xbe], dst as u8, Rm::Mem(src)),
+            2 => self.emit(None, true, &[0x0f, 0xbf], dst as u8, Rm::Mem(src)),
+            4 => self.emit(None, true, &[0x63], dst as u8, Rm::Mem(src)),
+            8 => self.mov(dst, Rm::Mem(src)),
+            _ => unreachable!(),
+        }
+    }
+
+    /// `mov dst, imm` with the shortest encoding
+    pub fn mov_imm(&mut self, dst: Reg, imm: u64) {
+        if imm as i32 as i64 as u64 == imm {
+            self.emit(None, true, &[0xc7], 0, Rm::Reg(dst));
+            self.code.extend_from_slice(&(imm as u32).to_le_bytes());
+            return;
+        }
+        // the register is in the opcode, zero extended if 32 bits
+        let wide = imm >> 32 != 0;
+        let rex = 0x40 | (wide as u8) << 3 | dst as u8 >> 3;
+        if rex != 0x40 {
+            self.code.push(rex);
+        }
+        self.code.push(0xb8 | (dst as u8 & 7));
+        if wide {
+            self.code.extend_from_slice(&imm.to_le_bytes());
+        } else {
+            self.code.extend_from_slice(&(imm as u32).to_le_bytes());
+        }
+    }
+
+    /// `mov qword [dst], imm` sign extending `imm`
+    pub fn mov_store_imm(&mut self, dst: Mem, imm: i32) {
+        self.emit(None, true, &[0xc7], 0, Rm::Mem(dst));
+        self.code.extend_from_slice(&imm.to_le_bytes());
+    }
+
+    /// `op dst, src`, on 32 bits if not `wide`
+    pub fn alu(&mut self, op: Alu, wide: bool, dst: Reg, src: Rm) {
+        let opcode = match op {
+            Alu::Add => 0x03,
+            Alu::Or => 0x0b,
+            Alu::And => 0x23,
+            Alu::Sub => 0x2b,
+            Alu::Xor => 0x33,
+            Alu::Cmp => 0x3b,
+        };
+        self.emit(None, wide, &[opcode], dst as u8, src);
+    }
+
+    /// `op dst, imm`, on 32 bits if not `wide`
+    pub fn alu_imm(&mut self, op: Alu, wide: bool, dst: Rm, imm: i32) {
+        if imm as i8 as i32 == imm {
+            self.emit(None, wide, &[0x83], op as u8, dst);
+            self.code.push(imm as u8);
+        } else {
+            self.emit(None, wide, &[0x81], op as u8, dst);
+            self.code.extend_from_slice(&imm.to_le_bytes());
+        }
+    }
+
+    /// `op dst, imm`, on 32 bits if not `wide`
+    pub fn shift_imm(&mut self, op: Shift, wide: bool, dst: Reg, imm: u8) {
+        self.emit(None, wide, &[0xc1], op as u8, Rm::Reg(dst));
+        self.code.push(imm);
+    }
+
+    /// `op dst, cl`, on 32 bits if not `wide`
+    pub fn shift_cl(&mut self, op: Shift, wide: bool, dst: Reg) {
+        self.emit(None, wide, &[0xd3], op as u8, Rm::Reg(dst));
+    }
+
+    /// `imul dst, src`, on 32 bits if not `wide`
+    pub fn imul(&mut self, wide: bool, dst: Reg, src: Rm) {
+        self.emit(None, wide, &[0x0f, 0xaf], dst as u8, src);
+    }
+
+    /// `mul src` or `imul src`, `rdx:rax = rax * src`
+    pub fn mul_wide(&mut self, signed: bool, src: Rm) {
+        self.emit(None, true, &[0xf7], if signed { 5 } else { 4 }, src);
+    }
+
+    /// `movsxd dst, src`
+    pub fn movsxd(&mut self, dst: Reg, src: Reg) {
+        self.emit(None, true, &[0x63], dst as u8, Rm::Reg(src));
+    }
+
+    /// `setcc dst; movzx dst, dst`, `dst` must be one of the first four
+    /// registers as we never emit the REX to use the low byte of the others
+    pub fn set(&mut self, cond: Cond, dst: Reg) {
+        debug_assert!((dst as u8) < 4);
+        self.emit(None, false, &[0x0f, 0x90 | cond as u8], 0, Rm::Reg(dst));
+        self.emit(None, false, &[0x0f, 0xb6], dst as u8, Rm::Reg(dst));
+    }
+
+    /// `lea dst, [src]`
+    pub fn lea(&mut self, dst: Reg, src: Mem) {
+        self.emit(None, true, &[0x8d], dst as u8, Rm::Mem(src));
+    }
+
+    /// `bt [base], bit`, CF is the bit of index `bit` of the bitmap at `base`
+    pub fn bt(&mut self, base: Mem, bit: Reg) {
+        self.emit(None, true, &[0x0f, 0xa3], bit as u8, Rm::Mem(base));
+    }
+
+    /// `jcc rel32` to be patched
+    pub fn jcc(&mut self, cond: Cond) -> Fixup {
+        self.code.extend_from_slice(&[0x0f, 0x80 | cond as u8, 0, 0, 0, 0]);
+        Fixup(self.code.len() - 4)
+    }
+
+    /// `jmp rel32` to be patched
+    pub fn jmp(&mut self) -> Fixup {
+        self.code.extend_from_slice(&[0xe9, 0, 0, 0, 0]);
+        Fixup(self.code.len() - 4)
+    }
+
+    /// Make the jump at `fixup` go to the current position
+    pub fn bind(&mut self, fixup: Fixup) {
+        let rel = (self.code.len() - (fixup.0 + 4)) as i32;
+        self.code[fixup.0..fixup.0 + 4].copy_from_slice(&rel.to_le_bytes());
+    }
+
+    pub fn ret(&mut self) {
+        self.code.push(0xc3);
+    }
+}
@@ -1,11 +1,23 @@
-use super::{CoreEmu, CoreEmuError, LinuxSyscall};
-use mmu::{Mmu, MmuError, VirtAddr};
+use super::{Backtrace, CoreEmu, CoreEmuError, DataRace, ChunkState, HeapFunction, HeapSanitizer, Label, LinuxSyscall, Symbol, Symbols, UninitOrigin, UninitRead, UninitTracker};
+use super::errno::*;
+use super::mman::*;
+use super::vfs::*;
+use super::syscall_hook::*;
+use super::tracer::*;
+use super::linux_emu_threading::*;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use mmu::{Mmu, MmuError, VirtAddr, MapPlacement, Perm, PermField};
+use core::mem::size_of;
 use diss::riscv64gc::*;
 
 #[derive(Debug)]
 pub enum LinuxEmuError {
-    /// the system called a syscall that isn't bussing:)
+    /// the system called a syscall that isn't bussing:) it has neither a hook
+    /// nor a built-in implementation, see [`UnknownSyscallPolicy::Stop`]
     BadSyscall(u64),
+    /// A syscall hook stopped the emulation with this reason
+    HookStop(u64),
     /// The execution hitted a breakpoint
     Breakpoint,
     /// Oh-oh a memory error! yays
@@ -14,105 +26,1209 @@ pub enum LinuxEmuError {
     RegWrite,
     /// We are done!
     Exit(u64),
+    /// The guest executed an instruction it wasn't allowed to
+    IllegalInstruction,
+    /// The guest accessed a CSR that isn't implemented
+    UnknownCsr(u32),
+    /// The guest executed an atomic operation on a misaligned address
+    MisalignedAtomic(u64),
+    /// The guest ran out of its instruction or time budget, it's probably
+    /// stuck in a loop
+    Timeout,
+    /// Every thread is blocked in a `futex` wait that can't time out
+    Deadlock,
+    /// Two threads accessed the same memory without synchronizing, see
+    /// [`RaceDetector`](super::RaceDetector)
+    DataRace(DataRace),
+    /// The guest read memory it never wrote, see
+    /// [`UninitTracker`](super::UninitTracker)
+    UninitRead(UninitRead),
 }
 
+/// Process id of the emulated process, and thread id of its main thread
+pub const GUEST_PID: u64 = 1000;
+
+/// Longest path accepted by the filesystem syscalls, including the null
+pub const PATH_MAX: usize = 4096;
+
+/// Bytes copied at a time from the guest buffers, so that the host never
+/// allocates the lengths chosen by the guest
+pub const COPY_CHUNK_SIZE: usize = 4096;
+
 pub struct LinuxEmu {
     pub core: CoreEmu,
+    /// Files and file descriptors of the process
+    pub vfs: Vfs,
+    /// State of the generator used by `getrandom`, so that runs are 
+    /// reproducible
+    pub random_state: u64,
+    /// Handlers that run before the built-in syscalls
+    pub syscall_hooks: SyscallHooks,
+    /// What to do with the syscalls that are not implemented
+    pub unknown_syscall_policy: UnknownSyscallPolicy,
+    /// Records the syscalls if set, by default only in debug builds
+    pub tracer: Option<SyscallTracer>,
+    /// The symbols of the loaded objects, shared with the forks
+    pub symbols: Arc<Symbols>,
+    /// The threads of the process, the running one is in `core`
+    pub threading: LinuxEmuThreading,
+    /// Services the allocations of the program if set, see
+    /// [`LinuxEmu::enable_heap_sanitizer`]
+    pub heap_sanitizer: Option<HeapSanitizer>,
 }
 
 impl LinuxEmu {
     pub fn new(mem: Mmu) -> Self {
         LinuxEmu{
-            core:CoreEmu::new(mem)
+            core: CoreEmu::new(mem),
+            vfs: Vfs::new(),
+            random_state: 0x6f77_6f20_7577_7521,
+            syscall_hooks: SyscallHooks::default(),
+            unknown_syscall_policy: UnknownSyscallPolicy::default(),
+            tracer: (cfg!(debug_assertions) || cfg!(feature="dbg_prints"))
+                .then(SyscallTracer::default),
+            symbols: Arc::default(),
+            threading: LinuxEmuThreading::default(),
+            heap_sanitizer: None,
         }
     }
 
     pub fn reset(&mut self, other: &Self) {
         self.core.reset(&other.core);
+        self.vfs.reset(&other.vfs);
+        self.random_state = other.random_state;
+        self.threading.clone_from(&other.threading);
+        self.heap_sanitizer.clone_from(&other.heap_sanitizer);
+        match (&mut self.tracer, &other.tracer) {
+            (Some(tracer), Some(other)) => tracer.reset(other),
+            (tracer, other) => *tracer = other.clone(),
+        }
     }
 
     pub fn fork(&self) -> Self {
-        LinuxEmu { core: self.core.fork() }
+        LinuxEmu { 
+            core: self.core.fork(),
+            vfs: self.vfs.clone(),
+            random_state: self.random_state,
+            syscall_hooks: self.syscall_hooks.clone(),
+            unknown_syscall_policy: self.unknown_syscall_policy,
+            tracer: self.tracer.clone(),
+            symbols: self.symbols.clone(),
+            threading: self.threading.clone(),
+            heap_sanitizer: self.heap_sanitizer.clone(),
+        }
+    }
+
+    /// Add `symbols` to the ones of the loaded objects, e.g. the
+    /// `LoadingInfo::symbols` of the loader
+    pub fn add_symbols(&mut self, symbols: impl IntoIterator<Item = Symbol>) {
+        Arc::make_mut(&mut self.symbols).extend(symbols);
+    }
+
+    /// The symbolized backtrace of the current pc, see
+    /// [`CoreEmu::backtrace`]
+    pub fn backtrace(&self) -> Backtrace {
+        self.symbols.symbolize(&self.core.backtrace())
+    }
+
+    /// Replace the allocator of the program with `sanitizer`, mapping its
+    /// segment and hooking the functions found in [`LinuxEmu::symbols`].
+    /// Return how many functions were hooked
+    pub fn enable_heap_sanitizer(&mut self, mut sanitizer: HeapSanitizer) -> Result<usize, MmuError> {
+        sanitizer.map(&mut self.core.mem)?;
+        let mut hooked = 0;
+        for function in HeapFunction::ALL {
+            let Some(symbol) = self.symbols.find(function.symbol()) else {
+                continue;
+            };
+            // the calls stop before the function runs
+            let ebreak = AssemblerRV64GC.ebreak().unwrap().to_le_bytes();
+            unsafe{self.core.mem.write_from_slice(VirtAddr(symbol.addr as usize), &ebreak)}?;
+            sanitizer.hook(symbol.addr, function);
+            hooked += 1;
+        }
+        self.heap_sanitizer = Some(sanitizer);
+        Ok(hooked)
+    }
+
+    /// Service the call to a function of the allocator if the core stopped
+    /// on one of the `ebreak` of [`LinuxEmu::enable_heap_sanitizer`], and
+    /// return to the caller. `None` if it's another breakpoint
+    fn heap_call(&mut self) -> Option<Result<(), LinuxEmuError>> {
+        let sanitizer = self.heap_sanitizer.as_mut()?;
+        let entry = self.core.pc.wrapping_sub(4);
+        let function = sanitizer.function(entry)?;
+
+        let caller = self.core.read_reg(Register::Ra);
+        let a0 = self.core.read_reg(Register::A0);
+        let a1 = self.core.read_reg(Register::A1);
+        let mem = &mut self.core.mem;
+        let result = match function {
+            HeapFunction::Malloc => Ok(sanitizer.malloc(mem, a0, caller)),
+            HeapFunction::Calloc => Ok(sanitizer.calloc(mem, a0, a1, caller)),
+            HeapFunction::Realloc => sanitizer.realloc(mem, a0, a1, caller),
+            HeapFunction::Free => sanitizer.free(mem, a0, caller).map(|_| 0),
+        };
+        let Ok(ret) = result else {
+            // stop in the function, with the caller in the backtrace
+            self.core.pc = entry;
+            return Some(Err(LinuxEmuError::MmuError(MmuError::InvalidFree(VirtAddr(a0 as usize)))));
+        };
+        self.core.write_reg(Register::A0, ret);
+        if let Some(taint) = &mut self.core.taint {
+            taint.set_reg(Register::A0, Label::CLEAN);
+        }
+        if let Some(chunk) = self.heap_sanitizer.as_ref().and_then(|sanitizer| sanitizer.chunk(ret)) {
+            if chunk.state == ChunkState::Live {
+                let end = chunk.addr + chunk.size;
+                self.record_uninit(ret, end, UninitOrigin::HeapAlloc { pc: caller });
+            }
+        }
+        self.core.pc = caller;
+        if let Some(call_stack) = &mut self.core.call_stack {
+            call_stack.ret(caller);
+        }
+        Some(Ok(()))
+    }
+
+    /// Handle `error` if it's a read of uninitialized memory and the origins
+    /// are tracked, either logging it and continuing or stopping with its
+    /// origin
+    fn uninit_read(&mut self, error: &MmuError) -> Option<Result<(), LinuxEmuError>> {
+        let uninit = self.core.uninit.as_mut()?;
+        let read = uninit.read(error, self.core.pc)?;
+        if !uninit.keep_going {
+            return Some(Err(LinuxEmuError::UninitRead(read)));
+        }
+        uninit.reads.push(read);
+        let MmuError::PermissionsFault { virtual_address, size, .. } = error else {
+            unreachable!("only the permission faults are uninitialized reads");
+        };
+        // the instruction runs again, reading the current content
+        Some(UninitTracker::initialize(&mut self.core.mem, virtual_address.0 as u64, *size)
+            .map_err(LinuxEmuError::MmuError))
+    }
+
+    /// pc of the `ecall` of the syscall being handled, the pc is already
+    /// after it
+    fn syscall_pc(&self) -> u64 {
+        self.core.pc.wrapping_sub(4)
+    }
+
+    /// Record that `origin` created the bytes `start..end`, if the origins
+    /// are tracked
+    fn record_uninit(&mut self, start: u64, end: u64, origin: UninitOrigin) {
+        if let Some(uninit) = &mut self.core.uninit {
+            uninit.record(start, end, origin);
+        }
+    }
+
+    /// Apply the [`UnknownSyscallPolicy`] to the syscall `number`, the
+    /// result is either the value to return to the guest or why to stop
+    fn unknown_syscall(&self, number: u64) -> Result<u64, LinuxEmuError> {
+        match self.unknown_syscall_policy {
+            UnknownSyscallPolicy::Enosys => Ok(to_ret(ENOSYS)),
+            UnknownSyscallPolicy::Stop => Err(LinuxEmuError::BadSyscall(number)),
+        }
+    }
+
+    /// Handle the syscall `syscall_number` with arguments `args`, the result 
+    /// is either the value to return in `a0` or why to stop
+    fn syscall(&mut self, syscall_number: u64, args: [u64; 6]) 
+        -> Result<u64, LinuxEmuError> {
+        if let Some(hook) = self.syscall_hooks.get(syscall_number) {
+            match hook(&mut self.core, args) {
+                SyscallAction::Continue => {},
+                SyscallAction::Return(ret) => return Ok(ret),
+                SyscallAction::Stop(reason) => {
+                    return Err(LinuxEmuError::HookStop(reason));
+                }
+            }
+        }
+
+        let syscall_variant: LinuxSyscall = match syscall_number.try_into() {
+            Ok(syscall_variant) => syscall_variant,
+            Err(_) => return self.unknown_syscall(syscall_number),
+        };
+
+        let ret = match syscall_variant {
+            // the last thread exiting ends the process
+            LinuxSyscall::exit if self.threading.is_multithreaded() => self.sys_exit(),
+            LinuxSyscall::exit | LinuxSyscall::exit_group => {
+                return Err(LinuxEmuError::Exit(self.core.read_reg(Register::A0)));
+            }
+            LinuxSyscall::read            => self.sys_read(),
+            LinuxSyscall::write           => self.sys_write(),
+            LinuxSyscall::pread64         => self.sys_pread64(),
+            LinuxSyscall::readv           => self.sys_readv(),
+            LinuxSyscall::writev          => self.sys_writev(),
+            LinuxSyscall::lseek           => self.sys_lseek(),
+            LinuxSyscall::openat          => self.sys_openat(),
+            LinuxSyscall::close           => self.sys_close(),
+            LinuxSyscall::newfstat        => self.sys_newfstat(),
+            LinuxSyscall::newfstatat      => self.sys_newfstatat(),
+            LinuxSyscall::faccessat       => self.sys_faccessat(),
+            LinuxSyscall::readlinkat      => self.sys_readlinkat(),
+            LinuxSyscall::ioctl           => self.sys_ioctl(),
+            // on 64-bit targets this is the plain ppoll
+            LinuxSyscall::ppoll_time32    => self.sys_ppoll(),
+            LinuxSyscall::brk             => self.sys_brk(),
+            LinuxSyscall::mmap            => self.sys_mmap(),
+            LinuxSyscall::munmap          => self.sys_munmap(),
+            LinuxSyscall::mremap          => self.sys_mremap(),
+            LinuxSyscall::mprotect        => self.sys_mprotect(),
+            LinuxSyscall::set_tid_address => self.sys_set_tid_address(),
+            LinuxSyscall::set_robust_list => self.sys_set_robust_list(),
+            LinuxSyscall::rseq            => self.sys_rseq(),
+            LinuxSyscall::prlimit64       => self.sys_prlimit64(),
+            LinuxSyscall::getrandom       => self.sys_getrandom(),
+            LinuxSyscall::newuname        => self.sys_uname(),
+            LinuxSyscall::getpid          => GUEST_PID,
+            LinuxSyscall::gettid          => self.threading.current().tid,
+            LinuxSyscall::clone           => self.sys_clone()?,
+            // glibc falls back to clone
+            LinuxSyscall::clone3          => to_ret(ENOSYS),
+            LinuxSyscall::futex           => self.sys_futex(),
+            // the scheduler runs after the syscall
+            LinuxSyscall::sched_yield     => 0,
+            LinuxSyscall::rt_sigaction    => self.sys_rt_sigaction(),
+            LinuxSyscall::rt_sigprocmask  => self.sys_rt_sigprocmask(),
+            LinuxSyscall::sigaltstack     => self.sys_sigaltstack(),
+            // TODO!: fork and execve
+            _ => return self.unknown_syscall(syscall_number),
+        };
+        Ok(ret)
+    }
+
+    /// Let the scheduler pick the thread to run, see [`LinuxEmuThreading`]
+    fn schedule(&mut self) -> Result<(), LinuxEmuError> {
+        if self.threading.schedule(&mut self.core) {
+            if let Some(detector) = &mut self.core.race_detector {
+                detector.set_current(self.threading.current().tid);
+            }
+            Ok(())
+        } else {
+            Err(LinuxEmuError::Deadlock)
+        }
+    }
+
+    /// Run the current thread until it stops, or until the end of its 
+    /// quantum if there are other threads, which is a yield
+    fn run_thread(&mut self) -> CoreEmuError {
+        if !self.threading.is_multithreaded() {
+            self.core.memory_yields = false;
+            return self.core.run();
+        }
+        self.core.memory_yields = self.threading.memory_yields;
+        let limit = self.core.instruction_limit;
+        let preemption = self.core.instructions_executed
+            .saturating_add(self.threading.quantum)
+            .min(limit);
+        self.core.instruction_limit = preemption;
+        let stop = self.core.run();
+        self.core.instruction_limit = limit;
+        match stop {
+            CoreEmuError::Timeout if preemption < limit
+                && self.core.instructions_executed >= preemption => CoreEmuError::Yield,
+            stop => stop,
+        }
     }
 
     pub fn run(&mut self) -> LinuxEmuError {
         loop {
-            match self.core.run() {
-                // useful only on multithreaded systems
-                CoreEmuError::Yield => {},
+            match self.run_thread() {
+                CoreEmuError::Yield => {
+                    if self.threading.is_multithreaded() {
+                        if let Err(error) = self.schedule() {
+                            return error;
+                        }
+                    }
+                },
                 CoreEmuError::Syscall => {
                     // https://github.com/riscv-collab/riscv-gnu-toolchain/blob/master/linux-headers/include/asm-generic/unistd.h#L183
                     let syscall_number = self.core.read_reg(Register::A7);
-                    let syscall_variant = syscall_number.try_into();
-                    // TODO!: make this cleaner
-                    if syscall_variant.is_err() {
-                        return LinuxEmuError::BadSyscall(syscall_number);
+                    // the arguments are in a0..a5 and the result goes in a0
+                    let args = [
+                        self.core.read_reg(Register::A0),
+                        self.core.read_reg(Register::A1),
+                        self.core.read_reg(Register::A2),
+                        self.core.read_reg(Register::A3),
+                        self.core.read_reg(Register::A4),
+                        self.core.read_reg(Register::A5),
+                    ];
+
+                    let pc = self.syscall_pc();
+                    if let Some(taint) = &mut self.core.taint {
+                        taint.record_syscall(pc, syscall_number);
                     }
-                    let syscall_variant = syscall_variant.unwrap();
+                    let result = self.syscall(syscall_number, args);
 
-                    #[cfg(feature="dbg_prints")]
-                    println!("syscall {:?}", syscall_variant);
-                    match syscall_variant {
-                        LinuxSyscall::exit => {
-                            return LinuxEmuError::Exit(self.core.read_reg(Register::A0));
-                        }
-                        LinuxSyscall::read => {
-                            let fd = self.core.read_reg(Register::A1);
-                            let buf = self.core.read_reg(Register::A2);
-                            let fcount= self.core.read_reg(Register::A3);
-                            todo!("read");
-                        }
-                        LinuxSyscall::write => {
-                            let fd = self.core.read_reg(Register::A1);
-                            let buf = self.core.read_reg(Register::A2);
-                            let fcount= self.core.read_reg(Register::A3);
-                            todo!("write");
-                        }
-                        LinuxSyscall::close => {
-                            let fd = self.core.read_reg(Register::A1);
-                            todo!("close");
-                        }
-                        LinuxSyscall::brk => {
-                            let brk = self.core.read_reg(Register::A1);
-                            todo!("brk");
-                        }
-                        LinuxSyscall::mmap => {
-                            todo!("mmap");
-                        }
-                        LinuxSyscall::munmap => {
-                            todo!("munmap");
-                        }
-                        LinuxSyscall::mremap => {
-                            todo!("mremap");
-                        }
-                        LinuxSyscall::clone => {
-                            let flags = self.core.read_reg(Register::A1);
-                            let newsp = self.core.read_reg(Register::A2);
-                            let parent_tid = self.core.read_reg(Register::A3);
-                            let child_tid = self.core.read_reg(Register::A4);
-                            todo!("clone");
-                        }
-                        LinuxSyscall::execve => {
-                            let filename = self.core.read_reg(Register::A1);
-                            let argv = self.core.read_reg(Register::A2);
-                            let envp = self.core.read_reg(Register::A3);
-                            todo!("execve");
+                    if let Some(tracer) = &mut self.tracer {
+                        // the pc is already after the ecall
+                        let pc = self.core.pc.wrapping_sub(4);
+                        let ret = result.as_ref().ok().copied();
+                        tracer.push(SyscallEvent::new(
+                            &mut self.core.mem, pc, syscall_number, args, ret,
+                        ));
+                    }
+
+                    match result {
+                        Ok(ret) => {
+                            self.core.write_reg(Register::A0, ret);
+                            if let Some(taint) = &mut self.core.taint {
+                                taint.set_reg(Register::A0, Label::CLEAN);
+                            }
                         }
-                        syscall_variant => {
-                            todo!("handle syscall {:?}", syscall_variant)
+                        Err(error) => return error,
+                    }
+                    // the thread blocked, exited or gave up the cpu
+                    if !self.threading.current().is_runnable()
+                        || syscall_number == LinuxSyscall::sched_yield as u64 {
+                        if let Err(error) = self.schedule() {
+                            return error;
                         }
                     }
                 },
                 CoreEmuError::Breakpoint => {
-                    return LinuxEmuError::Breakpoint;
+                    match self.heap_call() {
+                        Some(Ok(())) => {},
+                        Some(Err(error)) => return error,
+                        None => return LinuxEmuError::Breakpoint,
+                    }
                 },
                 CoreEmuError::RegWrite => {
                     return LinuxEmuError::RegWrite;
                 },
                 CoreEmuError::MmuError(mmu_error) => {
-                    return LinuxEmuError::MmuError(mmu_error);
+                    match self.uninit_read(&mmu_error) {
+                        Some(Ok(())) => {},
+                        Some(Err(error)) => return error,
+                        None => return LinuxEmuError::MmuError(mmu_error),
+                    }
+                },
+                CoreEmuError::IllegalInstruction => {
+                    return LinuxEmuError::IllegalInstruction;
+                },
+                CoreEmuError::UnknownCsr(csr) => {
+                    return LinuxEmuError::UnknownCsr(csr);
                 },
+                CoreEmuError::MisalignedAtomic(addr) => {
+                    return LinuxEmuError::MisalignedAtomic(addr);
+                },
+                CoreEmuError::Timeout => {
+                    return LinuxEmuError::Timeout;
+                },
+                CoreEmuError::DataRace(race) => {
+                    return LinuxEmuError::DataRace(race);
+                },
+            }
+        }
+    }
+}
+
+/// Round `size` up to a multiple of the page size, `None` on overflow
+#[inline(always)]
+fn page_align(size: u64) -> Option<u64> {
+    size.checked_next_multiple_of(PAGE_SIZE)
+}
+
+/// Convert the `PROT_*` flags to the permissions of the mmu
+fn prot_to_perm(prot: u64) -> Perm {
+    let mut perm = Perm::default();
+    if prot & PROT_READ != 0 {
+        perm |= PermField::Read;
+    }
+    if prot & PROT_WRITE != 0 {
+        perm |= PermField::Write;
+    }
+    if prot & PROT_EXEC != 0 {
+        perm |= PermField::Executable;
+    }
+    perm
+}
+
+/// Memory management syscalls, they return the value to put in `a0`
+impl LinuxEmu {
+    /// `unsigned long brk(unsigned long brk)`
+    fn sys_brk(&mut self) -> u64 {
+        let addr = self.core.read_reg(Register::A0);
+        let old = self.core.mem.brk(VirtAddr(0)).map(|brk| brk.0 as u64).unwrap_or(0);
+        // on failure the current break is returned, which is what brk(0) does
+        let brk = self.core.mem.brk(VirtAddr(addr as usize))
+            .or_else(|_| self.core.mem.brk(VirtAddr(0)))
+            .map(|brk| brk.0 as u64)
+            .unwrap_or(0);
+        self.record_uninit(old, brk, UninitOrigin::Brk { pc: self.syscall_pc() });
+        brk
+    }
+
+    /// `void *mmap(void *addr, size_t length, int prot, int flags, int fd, 
+    /// off_t offset)`
+    fn sys_mmap(&mut self) -> u64 {
+        let addr   = self.core.read_reg(Register::A0);
+        let length = self.core.read_reg(Register::A1);
+        let prot   = self.core.read_reg(Register::A2);
+        let flags  = self.core.read_reg(Register::A3);
+        let fd     = self.core.read_reg(Register::A4) as i32;
+        let offset = self.core.read_reg(Register::A5);
+
+        // exactly one of shared and private (or MAP_SHARED_VALIDATE)
+        if length == 0 || !offset.is_multiple_of(PAGE_SIZE) 
+            || flags & (MAP_SHARED | MAP_PRIVATE) == 0 {
+            return to_ret(EINVAL);
+        }
+        let size = match page_align(length) {
+            Some(size) => size,
+            None => return to_ret(ENOMEM),
+        };
+        let placement = if flags & (MAP_FIXED | MAP_FIXED_NOREPLACE) != 0 {
+            if !addr.is_multiple_of(PAGE_SIZE) {
+                return to_ret(EINVAL);
+            }
+            if flags & MAP_FIXED_NOREPLACE != 0 {
+                MapPlacement::FixedNoReplace(VirtAddr(addr as usize))
+            } else {
+                MapPlacement::Fixed(VirtAddr(addr as usize))
+            }
+        } else if addr != 0 {
+            MapPlacement::Hint(VirtAddr((addr & !(PAGE_SIZE - 1)) as usize))
+        } else {
+            MapPlacement::Any
+        };
+
+        // the content of file-backed mappings, the bytes after the end of 
+        // the file are zero
+        let input_offset = if flags & MAP_ANONYMOUS == 0 {
+            self.vfs.input_offset(fd, Some(offset as usize))
+        } else {
+            None
+        };
+        let content = if flags & MAP_ANONYMOUS == 0 {
+            match self.vfs.content(fd) {
+                Ok(content) => Some(content),
+                Err(errno) => return to_ret(errno),
+            }
+        } else {
+            None
+        };
+
+        let addr = match self.core.mem.mmap(placement, size as usize, prot_to_perm(prot)) {
+            Ok(addr) => addr,
+            Err(MmuError::AddressInUse { .. }) => return to_ret(EEXIST),
+            Err(_) => return to_ret(ENOMEM),
+        };
+        if let Some(content) = content {
+            let start = (offset as usize).min(content.len());
+            let end = start.saturating_add(length as usize).min(content.len());
+            // Safety: the mapping was just created so it can hold the data
+            unsafe{self.core.mem.write_from_slice(addr, &content[start..end])}
+                .expect("the new mapping is big enough");
+            if let (Some(taint), Some(input_offset)) = (&mut self.core.taint, input_offset) {
+                taint.taint_input(addr.0 as u64, input_offset, end - start);
+            }
+        }
+        let start = addr.0 as u64;
+        self.record_uninit(start, start + size, UninitOrigin::Mmap { pc: self.syscall_pc() });
+        start
+    }
+
+    /// `int munmap(void *addr, size_t length)`
+    fn sys_munmap(&mut self) -> u64 {
+        let addr   = self.core.read_reg(Register::A0);
+        let length = self.core.read_reg(Register::A1);
+
+        if length == 0 || !addr.is_multiple_of(PAGE_SIZE) {
+            return to_ret(EINVAL);
+        }
+        let size = match page_align(length) {
+            Some(size) => size,
+            None => return to_ret(EINVAL),
+        };
+        match self.core.mem.munmap(VirtAddr(addr as usize), size as usize) {
+            Ok(()) => 0,
+            Err(_) => to_ret(EINVAL),
+        }
+    }
+
+    /// `void *mremap(void *old_address, size_t old_size, size_t new_size,
+    /// int flags, ... /* void *new_address */)`
+    fn sys_mremap(&mut self) -> u64 {
+        let old_addr = self.core.read_reg(Register::A0);
+        let old_size = self.core.read_reg(Register::A1);
+        let new_size = self.core.read_reg(Register::A2);
+        let flags    = self.core.read_reg(Register::A3);
+        let new_addr = self.core.read_reg(Register::A4);
+
+        // old_size == 0 duplicates shared mappings, which we don't have
+        if !old_addr.is_multiple_of(PAGE_SIZE) || old_size == 0 || new_size == 0
+            || flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0 
+            || (flags & MREMAP_FIXED != 0 && flags & MREMAP_MAYMOVE == 0) {
+            return to_ret(EINVAL);
+        }
+        let (old_size, new_size) = match (page_align(old_size), page_align(new_size)) {
+            (Some(old_size), Some(new_size)) => (old_size, new_size),
+            _ => return to_ret(EINVAL),
+        };
+
+        let old_end = match old_addr.checked_add(old_size) {
+            Some(old_end) => old_end,
+            None => return to_ret(EFAULT),
+        };
+
+        let new_addr = if flags & MREMAP_FIXED != 0 {
+            let new_end = match new_addr.checked_add(new_size) {
+                Some(new_end) => new_end,
+                None => return to_ret(EINVAL),
+            };
+            // the two ranges can't overlap
+            if !new_addr.is_multiple_of(PAGE_SIZE) 
+                || (new_addr < old_end && old_addr < new_end) {
+                return to_ret(EINVAL);
+            }
+            Some(VirtAddr(new_addr as usize))
+        } else {
+            None
+        };
+
+        match self.core.mem.mremap(
+            VirtAddr(old_addr as usize), old_size as usize, new_size as usize,
+            flags & MREMAP_MAYMOVE != 0, new_addr,
+        ) {
+            Ok(addr) => {
+                // the bytes added get the permissions of the last one
+                let (start, end) = (addr.0 as u64 + old_size, addr.0 as u64 + new_size);
+                self.record_uninit(start, end, UninitOrigin::Mmap { pc: self.syscall_pc() });
+                addr.0 as u64
+            }
+            Err(MmuError::SegmentNotFound { .. }) => to_ret(EFAULT),
+            Err(_) => to_ret(ENOMEM),
+        }
+    }
+}
+
+// Constants of the riscv64 ABI used by the process syscalls
+/// `newfstatat` flag to stat the fd itself when the path is empty
+pub const AT_EMPTY_PATH: u64 = 0x1000;
+/// Size of `struct stat`
+pub const STAT_SIZE: usize = 128;
+/// Character device file type in `st_mode`
+pub const S_IFCHR: u32 = 0o020000;
+/// Regular file type in `st_mode`
+pub const S_IFREG: u32 = 0o100000;
+/// Size of `struct robust_list_head`
+pub const ROBUST_LIST_HEAD_SIZE: u64 = 24;
+/// `prlimit64` resource of the stack size
+pub const RLIMIT_STACK: u64 = 3;
+/// `prlimit64` resource of the number of open files
+pub const RLIMIT_NOFILE: u64 = 7;
+/// No limit
+pub const RLIM_INFINITY: u64 = u64::MAX;
+/// Length of each field of `struct new_utsname`
+pub const UTSNAME_LENGTH: usize = 65;
+/// `sigaltstack` flag of a disabled alternate stack
+pub const SS_DISABLE: u32 = 2;
+/// `ppoll` event of an invalid fd
+pub const POLLNVAL: u16 = 0x20;
+
+/// Process and file syscalls, they return the value to put in `a0`
+impl LinuxEmu {
+    /// Read the null-terminated path at `addr`
+    fn read_path(&mut self, addr: u64) -> Result<Vec<u8>, u64> {
+        match self.core.mem.read_cstr(VirtAddr(addr as usize), PATH_MAX) {
+            Ok(path) => Ok(path),
+            Err(MmuError::CStrTooLong { .. }) => Err(ENAMETOOLONG),
+            Err(_) => Err(EFAULT),
+        }
+    }
+
+    /// Write `data` to the guest buffer at `addr`
+    fn write_buffer(&mut self, addr: u64, data: &[u8]) -> Result<(), u64> {
+        self.core.mem.write_slice(VirtAddr(addr as usize), data)
+            .map_err(|_| EFAULT)
+    }
+
+    /// Read at most `count` bytes from `fd` into the guest buffer at `buf`,
+    /// at `offset` or at the offset of the fd
+    fn read_to_guest(&mut self, fd: i32, buf: u64, count: u64, offset: Option<usize>)
+        -> Result<u64, u64> {
+        let input_offset = self.vfs.input_offset(fd, offset);
+        let data = self.vfs.read(fd, count as usize, offset)?;
+        self.core.mem.write_slice(VirtAddr(buf as usize), data)
+            .map_err(|_| EFAULT)?;
+        if let Some(taint) = &mut self.core.taint {
+            match input_offset {
+                Some(input_offset) => taint.taint_input(buf, input_offset, data.len()),
+                None => taint.set_memory(buf, data.len() as u64, Label::CLEAN),
+            }
+        }
+        Ok(data.len() as u64)
+    }
+
+    /// Write `count` bytes from the guest buffer at `buf` to `fd`, in chunks.
+    /// Like Linux, a fault after the first chunk is a short write
+    fn write_from_guest(&mut self, fd: i32, buf: u64, count: u64) -> Result<u64, u64> {
+        let mut chunk = [0_u8; COPY_CHUNK_SIZE];
+        let mut written = 0;
+        loop {
+            let len = (count - written).min(COPY_CHUNK_SIZE as u64) as usize;
+            let result = self.core.mem
+                .read_slice(VirtAddr(buf.wrapping_add(written) as usize), &mut chunk[..len])
+                .map_err(|_| EFAULT)
+                .and_then(|()| self.vfs.write(fd, &chunk[..len]));
+            match result {
+                Ok(chunk_written) => written += chunk_written as u64,
+                Err(errno) if written == 0 => return Err(errno),
+                Err(_) => break,
+            }
+            if written == count {
+                break;
+            }
+        }
+        Ok(written)
+    }
+
+    /// Read the `struct iovec { void *iov_base; size_t iov_len; }` array
+    /// at `iov`
+    fn read_iovec(&mut self, iov: u64, iovcnt: u64) -> Result<Vec<(u64, u64)>, u64> {
+        // UIO_MAXIOV
+        if iovcnt > 1024 {
+            return Err(EINVAL);
+        }
+        (0..iovcnt).map(|i| {
+            let entry = VirtAddr((iov + i * 16) as usize);
+            let base = self.core.mem.read::<u64>(entry).map_err(|_| EFAULT)?;
+            let len = self.core.mem.read::<u64>(entry + 8).map_err(|_| EFAULT)?;
+            Ok((base, len))
+        }).collect()
+    }
+
+    /// `ssize_t read(int fd, void *buf, size_t count)`
+    fn sys_read(&mut self) -> u64 {
+        let fd    = self.core.read_reg(Register::A0) as i32;
+        let buf   = self.core.read_reg(Register::A1);
+        let count = self.core.read_reg(Register::A2);
+
+        self.read_to_guest(fd, buf, count, None)
+            .unwrap_or_else(to_ret)
+    }
+
+    /// `ssize_t pread64(int fd, void *buf, size_t count, off_t offset)`
+    fn sys_pread64(&mut self) -> u64 {
+        let fd     = self.core.read_reg(Register::A0) as i32;
+        let buf    = self.core.read_reg(Register::A1);
+        let count  = self.core.read_reg(Register::A2);
+        let offset = self.core.read_reg(Register::A3);
+
+        if (offset as i64) < 0 {
+            return to_ret(EINVAL);
+        }
+        // pipes and terminals can't be read at an offset
+        if let Ok(FileKind::CharDevice) = self.vfs.fd_kind(fd) {
+            return to_ret(ESPIPE);
+        }
+        self.read_to_guest(fd, buf, count, Some(offset as usize))
+            .unwrap_or_else(to_ret)
+    }
+
+    /// `ssize_t readv(int fd, const struct iovec *iov, int iovcnt)`
+    fn sys_readv(&mut self) -> u64 {
+        let fd     = self.core.read_reg(Register::A0) as i32;
+        let iov    = self.core.read_reg(Register::A1);
+        let iovcnt = self.core.read_reg(Register::A2);
+
+        let iov = match self.read_iovec(iov, iovcnt) {
+            Ok(iov) => iov,
+            Err(errno) => return to_ret(errno),
+        };
+        let mut total = 0;
+        for (base, len) in iov {
+            match self.read_to_guest(fd, base, len, None) {
+                Ok(read) => {
+                    total += read;
+                    // short read, the file is over
+                    if read < len {
+                        break;
+                    }
+                }
+                Err(errno) => return to_ret(errno),
+            }
+        }
+        total
+    }
+
+    /// `ssize_t write(int fd, const void *buf, size_t count)`
+    fn sys_write(&mut self) -> u64 {
+        let fd    = self.core.read_reg(Register::A0) as i32;
+        let buf   = self.core.read_reg(Register::A1);
+        let count = self.core.read_reg(Register::A2);
+
+        self.write_from_guest(fd, buf, count)
+            .unwrap_or_else(to_ret)
+    }
+
+    /// `ssize_t writev(int fd, const struct iovec *iov, int iovcnt)`
+    fn sys_writev(&mut self) -> u64 {
+        let fd     = self.core.read_reg(Register::A0) as i32;
+        let iov    = self.core.read_reg(Register::A1);
+        let iovcnt = self.core.read_reg(Register::A2);
+
+        let iov = match self.read_iovec(iov, iovcnt) {
+            Ok(iov) => iov,
+            Err(errno) => return to_ret(errno),
+        };
+        let mut total = 0;
+        for (base, len) in iov {
+            match self.write_from_guest(fd, base, len) {
+                Ok(written) => total += written,
+                Err(errno) => return to_ret(errno),
+            }
+        }
+        total
+    }
+
+    /// `off_t lseek(int fd, off_t offset, int whence)`
+    fn sys_lseek(&mut self) -> u64 {
+        let fd     = self.core.read_reg(Register::A0) as i32;
+        let offset = self.core.read_reg(Register::A1) as i64;
+        let whence = self.core.read_reg(Register::A2);
+
+        match self.vfs.lseek(fd, offset, whence) {
+            Ok(offset) => offset as u64,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int openat(int dirfd, const char *pathname, int flags, mode_t mode)`
+    fn sys_openat(&mut self) -> u64 {
+        let dirfd = self.core.read_reg(Register::A0) as i32;
+        let path  = self.core.read_reg(Register::A1);
+        let flags = self.core.read_reg(Register::A2);
+
+        let path = match self.read_path(path) {
+            Ok(path) => path,
+            Err(errno) => return to_ret(errno),
+        };
+        // there are no directories so only absolute paths can be resolved
+        if dirfd != AT_FDCWD && path.first() != Some(&b'/') {
+            return to_ret(ENOTDIR);
+        }
+        match self.vfs.open(&path, flags) {
+            Ok(fd) => fd as u64,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int close(int fd)`
+    fn sys_close(&mut self) -> u64 {
+        let fd = self.core.read_reg(Register::A0) as i32;
+        match self.vfs.close(fd) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// Write the `struct stat` of a file of kind `kind` at `buf`
+    fn write_stat(&mut self, buf: u64, kind: FileKind) -> Result<(), u64> {
+        let (mode, size, rdev) = match kind {
+            // /dev/pts/0
+            FileKind::CharDevice => (S_IFCHR | 0o620, 0, 0x8800_u64),
+            FileKind::Regular(size) => (S_IFREG | 0o755, size as u64, 0),
+        };
+        let mut stat = [0_u8; STAT_SIZE];
+        // st_ino
+        stat[8..16].copy_from_slice(&1_u64.to_le_bytes());
+        stat[16..20].copy_from_slice(&mode.to_le_bytes());
+        // st_nlink
+        stat[20..24].copy_from_slice(&1_u32.to_le_bytes());
+        stat[32..40].copy_from_slice(&rdev.to_le_bytes());
+        stat[48..56].copy_from_slice(&size.to_le_bytes());
+        // st_blksize
+        stat[56..60].copy_from_slice(&4096_u32.to_le_bytes());
+        // st_blocks, in 512 bytes units
+        stat[64..72].copy_from_slice(&size.div_ceil(512).to_le_bytes());
+        self.write_buffer(buf, &stat)
+    }
+
+    /// `int fstat(int fd, struct stat *statbuf)`
+    fn sys_newfstat(&mut self) -> u64 {
+        let fd  = self.core.read_reg(Register::A0) as i32;
+        let buf = self.core.read_reg(Register::A1);
+
+        match self.vfs.fd_kind(fd) {
+            Ok(kind) => self.write_stat(buf, kind)
+                .map(|()| 0)
+                .unwrap_or_else(to_ret),
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int newfstatat(int dirfd, const char *pathname, struct stat *statbuf,
+    /// int flags)`
+    fn sys_newfstatat(&mut self) -> u64 {
+        let dirfd = self.core.read_reg(Register::A0) as i32;
+        let path  = self.core.read_reg(Register::A1);
+        let buf   = self.core.read_reg(Register::A2);
+        let flags = self.core.read_reg(Register::A3);
+
+        let path = match self.read_path(path) {
+            Ok(path) => path,
+            Err(errno) => return to_ret(errno),
+        };
+        let kind = if path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+            self.vfs.fd_kind(dirfd)
+        } else {
+            self.vfs.path_kind(&path)
+        };
+        let kind = match kind {
+            Ok(kind) => kind,
+            Err(errno) => return to_ret(errno),
+        };
+
+        self.write_stat(buf, kind)
+            .map(|()| 0)
+            .unwrap_or_else(to_ret)
+    }
+
+    /// `int faccessat(int dirfd, const char *pathname, int mode)`
+    fn sys_faccessat(&mut self) -> u64 {
+        let path = self.core.read_reg(Register::A1);
+        match self.read_path(path) {
+            Ok(path) if self.vfs.exists(&path) => 0,
+            Ok(_) => to_ret(ENOENT),
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `ssize_t readlinkat(int dirfd, const char *pathname, char *buf,
+    /// size_t bufsiz)`
+    fn sys_readlinkat(&mut self) -> u64 {
+        let path   = self.core.read_reg(Register::A1);
+        let buf    = self.core.read_reg(Register::A2);
+        let bufsiz = self.core.read_reg(Register::A3);
+
+        let path = match self.read_path(path) {
+            Ok(path) => path,
+            Err(errno) => return to_ret(errno),
+        };
+        let target = match self.vfs.readlink(&path) {
+            // the target is truncated and not null-terminated
+            Ok(target) => target[..target.len().min(bufsiz as usize)].to_vec(),
+            Err(errno) => return to_ret(errno),
+        };
+        match self.write_buffer(buf, &target) {
+            Ok(()) => target.len() as u64,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int ioctl(int fd, unsigned long request, ...)`
+    fn sys_ioctl(&mut self) -> u64 {
+        let fd = self.core.read_reg(Register::A0) as i32;
+        // no fd is a terminal, so TCGETS fails and the output is buffered
+        match self.vfs.get(fd) {
+            Ok(_) => to_ret(ENOTTY),
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int ppoll(struct pollfd *fds, nfds_t nfds, 
+    /// const struct timespec *tmo_p, const sigset_t *sigmask)`
+    fn sys_ppoll(&mut self) -> u64 {
+        let fds  = self.core.read_reg(Register::A0);
+        let nfds = self.core.read_reg(Register::A1);
+
+        // struct pollfd { int fd; short events; short revents; }
+        let mut ready = 0;
+        for i in 0..nfds {
+            let pollfd = VirtAddr((fds + i * 8) as usize);
+            let fd = match self.core.mem.read::<u32>(pollfd) {
+                Ok(fd) => fd as i32,
+                Err(_) => return to_ret(EFAULT),
+            };
+            // negative fds are ignored
+            let revents = if fd >= 0 && self.vfs.get(fd).is_err() {
+                ready += 1;
+                POLLNVAL
+            } else {
+                0
+            };
+            if self.core.mem.write::<u16>(pollfd + 6, revents).is_err() {
+                return to_ret(EFAULT);
+            }
+        }
+        ready
+    }
+
+    /// `int mprotect(void *addr, size_t len, int prot)`
+    fn sys_mprotect(&mut self) -> u64 {
+        let addr   = self.core.read_reg(Register::A0);
+        let length = self.core.read_reg(Register::A1);
+        let prot   = self.core.read_reg(Register::A2);
+
+        if !addr.is_multiple_of(PAGE_SIZE) || prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+            return to_ret(EINVAL);
+        }
+        let size = match page_align(length) {
+            Some(size) => size,
+            None => return to_ret(ENOMEM),
+        };
+        if size == 0 {
+            return 0;
+        }
+        match self.core.mem.mprotect(VirtAddr(addr as usize), size as usize, prot_to_perm(prot)) {
+            Ok(()) => 0,
+            Err(_) => to_ret(ENOMEM),
+        }
+    }
+
+    /// `pid_t set_tid_address(int *tidptr)`
+    fn sys_set_tid_address(&mut self) -> u64 {
+        let thread = self.threading.current_mut();
+        thread.clear_child_tid = self.core.read_reg(Register::A0);
+        thread.tid
+    }
+
+    /// `long set_robust_list(struct robust_list_head *head, size_t len)`
+    fn sys_set_robust_list(&mut self) -> u64 {
+        // the list is only walked when a thread dies, so it's not stored
+        if self.core.read_reg(Register::A1) != ROBUST_LIST_HEAD_SIZE {
+            return to_ret(EINVAL);
+        }
+        0
+    }
+
+    /// `int rseq(struct rseq *rseq, u32 rseq_len, int flags, u32 sig)`
+    fn sys_rseq(&mut self) -> u64 {
+        // glibc works without restartable sequences
+        to_ret(ENOSYS)
+    }
+
+    /// `int prlimit64(pid_t pid, int resource, const struct rlimit64 *new_limit,
+    /// struct rlimit64 *old_limit)`
+    fn sys_prlimit64(&mut self) -> u64 {
+        let resource  = self.core.read_reg(Register::A1);
+        let old_limit = self.core.read_reg(Register::A3);
+
+        // the limits can't be changed
+        if old_limit == 0 {
+            return 0;
+        }
+        let limit = match resource {
+            RLIMIT_STACK => 8 << 20,
+            RLIMIT_NOFILE => 1024,
+            _ => RLIM_INFINITY,
+        };
+        let mut rlimit = [0_u8; 2 * size_of::<u64>()];
+        // the soft and hard limits are the same
+        rlimit[..8].copy_from_slice(&limit.to_le_bytes());
+        rlimit[8..].copy_from_slice(&limit.to_le_bytes());
+        match self.write_buffer(old_limit, &rlimit) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `ssize_t getrandom(void *buf, size_t buflen, unsigned int flags)`
+    fn sys_getrandom(&mut self) -> u64 {
+        let buf    = self.core.read_reg(Register::A0);
+        let buflen = self.core.read_reg(Register::A1);
+
+        // xorshift64, the bytes only need to look random
+        let mut chunk = [0_u8; COPY_CHUNK_SIZE];
+        let mut written = 0;
+        while written < buflen {
+            let len = (buflen - written).min(COPY_CHUNK_SIZE as u64) as usize;
+            for word in chunk[..len.next_multiple_of(8)].chunks_exact_mut(8) {
+                self.random_state ^= self.random_state << 13;
+                self.random_state ^= self.random_state >> 7;
+                self.random_state ^= self.random_state << 17;
+                word.copy_from_slice(&self.random_state.to_le_bytes());
+            }
+            match self.write_buffer(buf.wrapping_add(written), &chunk[..len]) {
+                Ok(()) => written += len as u64,
+                Err(errno) if written == 0 => return to_ret(errno),
+                Err(_) => break,
             }
         }
+        written
     }
-}
\ No newline at end of file
+
+    /// `int uname(struct utsname *buf)`
+    fn sys_uname(&mut self) -> u64 {
+        let buf = self.core.read_reg(Register::A0);
+
+        let fields: [&[u8]; 6] = [
+            b"Linux", b"folpetti", b"6.1.0", b"#1", b"riscv64", b"(none)",
+        ];
+        let mut utsname = [0_u8; 6 * UTSNAME_LENGTH];
+        for (i, field) in fields.iter().enumerate() {
+            let start = i * UTSNAME_LENGTH;
+            utsname[start..start + field.len()].copy_from_slice(field);
+        }
+        match self.write_buffer(buf, &utsname) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int rt_sigaction(int signum, const struct sigaction *act,
+    /// struct sigaction *oldact, size_t sigsetsize)`
+    fn sys_rt_sigaction(&mut self) -> u64 {
+        let oldact = self.core.read_reg(Register::A2);
+        // signals are never delivered, so every handler is SIG_DFL
+        if oldact == 0 {
+            return 0;
+        }
+        match self.write_buffer(oldact, &[0; 3 * size_of::<u64>()]) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int rt_sigprocmask(int how, const sigset_t *set, sigset_t *oldset,
+    /// size_t sigsetsize)`
+    fn sys_rt_sigprocmask(&mut self) -> u64 {
+        let oldset = self.core.read_reg(Register::A2);
+        if oldset == 0 {
+            return 0;
+        }
+        match self.write_buffer(oldset, &[0; size_of::<u64>()]) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+
+    /// `int sigaltstack(const stack_t *ss, stack_t *old_ss)`
+    fn sys_sigaltstack(&mut self) -> u64 {
+        let old_ss = self.core.read_reg(Register::A1);
+        if old_ss == 0 {
+            return 0;
+        }
+        // stack_t { void *ss_sp; int ss_flags; size_t ss_size; }
+        let mut stack = [0_u8; 3 * size_of::<u64>()];
+        stack[8..12].copy_from_slice(&SS_DISABLE.to_le_bytes());
+        match self.write_buffer(old_ss, &stack) {
+            Ok(()) => 0,
+            Err(errno) => to_ret(errno),
+        }
+    }
+}
+
+/// Thread syscalls, they return the value to put in `a0`. The scheduler
+/// runs after them if the thread is not runnable anymore
+impl LinuxEmu {
+    /// `long clone(unsigned long flags, void *stack, int *parent_tid,
+    /// unsigned long tls, int *child_tid)`
+    fn sys_clone(&mut self) -> Result<u64, LinuxEmuError> {
+        let flags      = self.core.read_reg(Register::A0);
+        let stack      = self.core.read_reg(Register::A1);
+        let parent_tid = self.core.read_reg(Register::A2);
+        let tls        = self.core.read_reg(Register::A3);
+        let child_tid  = self.core.read_reg(Register::A4);
+
+        // there is a single address space, so only threads can be created
+        if flags & CLONE_THREAD == 0 {
+            return self.unknown_syscall(LinuxSyscall::clone as u64);
+        }
+        if flags & (CLONE_VM | CLONE_SIGHAND) != CLONE_VM | CLONE_SIGHAND {
+            return Ok(to_ret(EINVAL));
+        }
+
+        let tid = self.threading.next_tid();
+        for (flag, addr) in [(CLONE_PARENT_SETTID, parent_tid), (CLONE_CHILD_SETTID, child_tid)] {
+            if flags & flag != 0 
+                && self.core.mem.write::<u32>(VirtAddr(addr as usize), tid as u32).is_err() {
+                return Ok(to_ret(EFAULT));
+            }
+        }
+        if let Some(detector) = &mut self.core.race_detector {
+            detector.spawn(tid);
+        }
+        let thread = self.threading.spawn(&self.core);
+        if stack != 0 {
+            thread.context.regs[Register::Sp as usize] = stack;
+        }
+        if flags & CLONE_SETTLS != 0 {
+            thread.context.regs[Register::Tp as usize] = tls;
+        }
+        if flags & CLONE_CHILD_CLEARTID != 0 {
+            thread.clear_child_tid = child_tid;
+        }
+        Ok(tid)
+    }
+
+    /// `void exit(int status)` of a thread that is not the last one
+    fn sys_exit(&mut self) -> u64 {
+        let thread = self.threading.current_mut();
+        thread.state = ThreadState::Exited;
+        let clear_child_tid = thread.clear_child_tid;
+        // the tid is cleared with a release, that the joiner can acquire
+        // with a fence if it doesn't wait
+        if let Some(detector) = &mut self.core.race_detector {
+            detector.fence();
+        }
+        // how pthread_join waits for the thread
+        if clear_child_tid != 0 && self.core.mem
+            .write::<u32>(VirtAddr(clear_child_tid as usize), 0).is_ok() {
+            self.futex_wake(clear_child_tid, 1, FUTEX_BITSET_MATCH_ANY);
+        }
+        0
+    }
+
+    /// Wake the waiters of `addr`, which synchronize with the running
+    /// thread, see [`LinuxEmuThreading::futex_wake`]
+    fn futex_wake(&mut self, addr: u64, count: u64, bitset: u32) -> u64 {
+        match &mut self.core.race_detector {
+            Some(detector) => {
+                detector.release(addr);
+                self.threading.futex_wake(addr, count, bitset, |tid| detector.acquire_for(tid, addr))
+            }
+            None => self.threading.futex_wake(addr, count, bitset, |_| {}),
+        }
+    }
+
+    /// `long futex(u32 *uaddr, int futex_op, u32 val,
+    /// const struct timespec *timeout, u32 *uaddr2, u32 val3)`
+    fn sys_futex(&mut self) -> u64 {
+        let addr    = self.core.read_reg(Register::A0);
+        let op      = self.core.read_reg(Register::A1);
+        let val     = self.core.read_reg(Register::A2);
+        let timeout = self.core.read_reg(Register::A3);
+        let val3    = self.core.read_reg(Register::A5) as u32;
+
+        if !addr.is_multiple_of(4) {
+            return to_ret(EINVAL);
+        }
+        let (wait, bitset) = match op & !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME) {
+            FUTEX_WAIT => (true, FUTEX_BITSET_MATCH_ANY),
+            FUTEX_WAKE => (false, FUTEX_BITSET_MATCH_ANY),
+            FUTEX_WAIT_BITSET => (true, val3),
+            FUTEX_WAKE_BITSET => (false, val3),
+            _ => return to_ret(ENOSYS),
+        };
+        if bitset == 0 {
+            return to_ret(EINVAL);
+        }
+        if !wait {
+            // the count is an int
+            return self.futex_wake(addr, val as u32 as i32 as u64, bitset);
+        }
+
+        // the waiter synchronizes with the wakes, even if it doesn't block
+        if let Some(detector) = &mut self.core.race_detector {
+            detector.acquire(addr);
+        }
+
+        match self.core.mem.read::<u32>(VirtAddr(addr as usize)) {
+            Ok(word) if word != val as u32 => to_ret(EAGAIN),
+            Ok(_) => {
+                self.threading.current_mut().state = ThreadState::FutexWait {
+                    addr, bitset, timed: timeout != 0,
+                };
+                // overwritten by the scheduler if the wait times out
+                0
+            }
+            Err(_) => to_ret(EFAULT),
+        }
+    }
+}
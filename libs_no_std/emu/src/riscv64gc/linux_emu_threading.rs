@@ -1,10 +1,279 @@
+//! Threads of the emulated process and their deterministic scheduler.
+//!
+//! Only one thread runs at a time, in [`LinuxEmu::core`], while the others
+//! wait in a [`ThreadContext`]. The scheduler switches thread at every
+//! [`CoreEmuError::Yield`], at the end of every quantum of instructions and
+//! when the running thread blocks or exits, picking the next one among the
+//! runnable ones with a generator seeded by [`LinuxEmuThreading::set_seed`].
+//! With [`LinuxEmuThreading::memory_yields`] the core yields after every
+//! memory access, so that the data races are much more likely to show up.
+//!
+//! The switches only depend on the instructions executed and on the seed,
+//! so an input always replays the same way with the same seed, and to fuzz
+//! for data races the same input can be run with multiple seeds.
+//!
+//! [`LinuxEmu::core`]: super::LinuxEmu::core
+//! [`CoreEmuError::Yield`]: super::CoreEmuError::Yield
+use super::{CallStack, CoreEmu, GUEST_PID};
+use super::errno::*;
+use alloc::vec::Vec;
+use diss::riscv64gc::Register;
 
+/// Instructions a thread runs before the scheduler can preempt it
+pub const DEFAULT_QUANTUM: usize = 1 << 12;
+
+// Constants of the riscv64 ABI used by the thread syscalls
+/// `clone` flag to share the address space
+pub const CLONE_VM: u64 = 0x100;
+/// `clone` flag to share the signal handlers
+pub const CLONE_SIGHAND: u64 = 0x800;
+/// `clone` flag to create a thread in the same thread group
+pub const CLONE_THREAD: u64 = 0x1_0000;
+/// `clone` flag to set `tp` to the `tls` argument
+pub const CLONE_SETTLS: u64 = 0x8_0000;
+/// `clone` flag to write the tid at `parent_tid`
+pub const CLONE_PARENT_SETTID: u64 = 0x10_0000;
+/// `clone` flag to zero and wake `child_tid` when the thread exits
+pub const CLONE_CHILD_CLEARTID: u64 = 0x20_0000;
+/// `clone` flag to write the tid at `child_tid`
+pub const CLONE_CHILD_SETTID: u64 = 0x100_0000;
+/// `futex` operation that blocks if the word still has the expected value
+pub const FUTEX_WAIT: u64 = 0;
+/// `futex` operation that wakes the waiters of a word
+pub const FUTEX_WAKE: u64 = 1;
+/// [`FUTEX_WAIT`] with a bitset and an absolute timeout
+pub const FUTEX_WAIT_BITSET: u64 = 9;
+/// [`FUTEX_WAKE`] with a bitset
+pub const FUTEX_WAKE_BITSET: u64 = 10;
+/// `futex` flags that don't change the operation, there is one process
+/// and time is emulated
+pub const FUTEX_PRIVATE_FLAG: u64 = 128;
+pub const FUTEX_CLOCK_REALTIME: u64 = 256;
+/// `futex` bitset that matches every waiter
+pub const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+/// The registers of a thread that is not running
+#[derive(Debug, Clone, Default)]
+pub struct ThreadContext {
+    pub regs: [u64; 32],
+    pub fregs: [u64; 32],
+    pub fcsr: u32,
+    pub pc: u64,
+    pub call_stack: Option<CallStack>,
+}
+
+impl ThreadContext {
+    /// Move the registers of the thread running in `core` to the context
+    pub fn save(&mut self, core: &mut CoreEmu) {
+        self.regs = core.regs;
+        self.fregs = core.fregs;
+        self.fcsr = core.fcsr;
+        self.pc = core.pc;
+        self.call_stack = core.call_stack.take();
+    }
+
+    /// Move the registers of the context to `core`. The reservation is
+    /// dropped, like Linux does on every context switch
+    pub fn restore(&mut self, core: &mut CoreEmu) {
+        core.regs = self.regs;
+        core.fregs = self.fregs;
+        core.fcsr = self.fcsr;
+        core.pc = self.pc;
+        core.call_stack = self.call_stack.take();
+        core.clear_reservation();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThreadState {
+    Runnable,
+    /// Blocked in `futex` on the word at `addr` until a wake with a bitset
+    /// that matches `bitset`. If `timed` it times out when no thread can
+    /// run anymore, as the emulated time only moves forward when told to
+    FutexWait { addr: u64, bitset: u32, timed: bool },
+    /// Dropped at the next switch
+    Exited,
+}
+
+#[derive(Debug, Clone)]
+pub struct Thread {
+    pub tid: u64,
+    /// Address registered by `set_tid_address` or `CLONE_CHILD_CLEARTID`,
+    /// it's zeroed and woken when the thread exits
+    pub clear_child_tid: u64,
+    pub state: ThreadState,
+    /// The registers, stale for the running thread
+    pub context: ThreadContext,
+}
+
+impl Thread {
+    pub fn new(tid: u64) -> Self {
+        Thread {
+            tid,
+            clear_child_tid: 0,
+            state: ThreadState::Runnable,
+            context: ThreadContext::default(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn is_runnable(&self) -> bool {
+        self.state == ThreadState::Runnable
+    }
+}
+
+/// The threads of a [`LinuxEmu`](super::LinuxEmu) and their scheduler
+#[derive(Debug, Clone)]
 pub struct LinuxEmuThreading {
-    pub threads: Vec<CoreEmu>,
+    /// The live threads in creation order, the first one is the main one
+    /// until it exits
+    pub threads: Vec<Thread>,
+    /// Index in `threads` of the running thread
+    pub current: usize,
+    /// Instructions a thread runs before the scheduler can preempt it
+    pub quantum: usize,
+    /// Yield after every memory access while there is more than one
+    /// thread, see [`CoreEmu::memory_yields`]
+    pub memory_yields: bool,
+    /// Number of times a thread was switched for another
+    pub switches: u64,
+    /// Tid of the next thread created
+    next_tid: u64,
+    /// State of the splitmix64 generator used to pick the threads
+    rng: u64,
 }
 
-/// add yields before every mem read and after every mem write
-/// to amplify data-races, the thread selection algorithm might change but
-/// it has to be deterministic. 
-/// To fuzz for data-races we can also re-run the same inputs but with multiple
-/// scheduler seeds 
\ No newline at end of file
+impl Default for LinuxEmuThreading {
+    fn default() -> Self {
+        LinuxEmuThreading {
+            threads: alloc::vec![Thread::new(GUEST_PID)],
+            current: 0,
+            quantum: DEFAULT_QUANTUM,
+            memory_yields: true,
+            switches: 0,
+            next_tid: GUEST_PID + 1,
+            rng: 0,
+        }
+    }
+}
+
+impl LinuxEmuThreading {
+    /// Seed the generator that picks the threads, the same seed always
+    /// gives the same schedule
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = seed;
+    }
+
+    #[inline(always)]
+    pub fn current(&self) -> &Thread {
+        &self.threads[self.current]
+    }
+
+    #[inline(always)]
+    pub fn current_mut(&mut self) -> &mut Thread {
+        &mut self.threads[self.current]
+    }
+
+    /// If more than one thread is alive, so the scheduler has to run
+    #[inline(always)]
+    pub fn is_multithreaded(&self) -> bool {
+        self.threads.len() > 1
+    }
+
+    /// Tid the next thread will have
+    pub fn next_tid(&self) -> u64 {
+        self.next_tid
+    }
+
+    /// Create a runnable thread with the registers of the one running in
+    /// `core`, as if it returned 0 from the syscall
+    pub fn spawn(&mut self, core: &CoreEmu) -> &mut Thread {
+        let mut thread = Thread::new(self.next_tid);
+        self.next_tid += 1;
+        thread.context = ThreadContext {
+            regs: core.regs,
+            fregs: core.fregs,
+            fcsr: core.fcsr,
+            pc: core.pc,
+            call_stack: core.call_stack.as_ref().map(|_| CallStack::default()),
+        };
+        thread.context.regs[Register::A0 as usize] = 0;
+        self.threads.push(thread);
+        self.threads.last_mut().unwrap()
+    }
+
+    /// Wake at most `count` threads waiting on `addr` with a bitset that
+    /// matches `bitset`, in creation order, calling `on_wake` with their
+    /// tids, and return how many were woken
+    pub fn futex_wake(&mut self, addr: u64, count: u64, bitset: u32, mut on_wake: impl FnMut(u64)) -> u64 {
+        let mut woken = 0;
+        for thread in &mut self.threads {
+            if woken == count {
+                break;
+            }
+            if let ThreadState::FutexWait { addr: wait_addr, bitset: wait_bitset, .. } = thread.state {
+                if wait_addr == addr && wait_bitset & bitset != 0 {
+                    // the syscall already returned 0 in its context
+                    thread.state = ThreadState::Runnable;
+                    on_wake(thread.tid);
+                    woken += 1;
+                }
+            }
+        }
+        woken
+    }
+
+    #[inline(always)]
+    fn next_random(&mut self) -> u64 {
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Drop the running thread if it exited, and switch `core` to a thread
+    /// picked among the runnable ones, possibly the same. If none is
+    /// runnable the timed waits time out, and if there are none of those
+    /// either it's a deadlock and `false` is returned
+    pub fn schedule(&mut self, core: &mut CoreEmu) -> bool {
+        let mut current = Some(self.current);
+        if self.current().state == ThreadState::Exited {
+            self.threads.remove(self.current);
+            current = None;
+        }
+
+        if !self.threads.iter().any(Thread::is_runnable) {
+            for (idx, thread) in self.threads.iter_mut().enumerate() {
+                if let ThreadState::FutexWait { timed: true, .. } = thread.state {
+                    thread.state = ThreadState::Runnable;
+                    let regs = if Some(idx) == current {
+                        &mut core.regs
+                    } else {
+                        &mut thread.context.regs
+                    };
+                    regs[Register::A0 as usize] = to_ret(ETIMEDOUT);
+                }
+            }
+        }
+
+        let runnable = self.threads.iter().filter(|thread| thread.is_runnable()).count();
+        if runnable == 0 {
+            return false;
+        }
+        let pick = (self.next_random() % runnable as u64) as usize;
+        let (next, _) = self.threads.iter().enumerate()
+            .filter(|(_, thread)| thread.is_runnable())
+            .nth(pick)
+            .unwrap();
+        if Some(next) != current {
+            if let Some(current) = current {
+                self.threads[current].context.save(core);
+            }
+            self.threads[next].context.restore(core);
+            self.switches += 1;
+        }
+        self.current = next;
+        true
+    }
+}
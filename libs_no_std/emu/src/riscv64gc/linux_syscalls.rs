@@ -216,7 +216,7 @@ pub enum LinuxSyscall {
 	/// long sys_unshare(unsigned long unshare_flags);
 	unshare = 97,
 	/// long sys_futex(u32 __user *uaddr, int op, u32 val, struct __kernel_timespec __user *utime, u32 __user *uaddr2, u32 val3);
-	futex = 422,
+	futex = 98,
 	/// long sys_set_robust_list(struct robust_list_head __user *head, size_t len);
 	set_robust_list = 99,
 	/// long sys_get_robust_list(int pid, struct robust_list_head __user * __user *head_ptr, size_t __user *len_ptr);
@@ -715,7 +715,7 @@ impl TryFrom<u64> for LinuxSyscall {
            95 => Ok(LinuxSyscall::waitid),
            96 => Ok(LinuxSyscall::set_tid_address),
            97 => Ok(LinuxSyscall::unshare),
-          422 => Ok(LinuxSyscall::futex),
+           98 => Ok(LinuxSyscall::futex),
            99 => Ok(LinuxSyscall::set_robust_list),
           100 => Ok(LinuxSyscall::get_robust_list),
           101 => Ok(LinuxSyscall::nanosleep),
@@ -0,0 +1,28 @@
+//! Flags of the memory mapping syscalls, from
+//! `include/uapi/asm-generic/mman-common.h` and `include/uapi/linux/mman.h`.
+
+/// Page can be read
+pub const PROT_READ: u64 = 0x1;
+/// Page can be written
+pub const PROT_WRITE: u64 = 0x2;
+/// Page can be executed
+pub const PROT_EXEC: u64 = 0x4;
+
+/// Share changes
+pub const MAP_SHARED: u64 = 0x01;
+/// Changes are private
+pub const MAP_PRIVATE: u64 = 0x02;
+/// Interpret addr exactly
+pub const MAP_FIXED: u64 = 0x10;
+/// Don't use a file
+pub const MAP_ANONYMOUS: u64 = 0x20;
+/// MAP_FIXED which doesn't unmap underlying mapping
+pub const MAP_FIXED_NOREPLACE: u64 = 0x100000;
+
+/// mremap can move the mapping
+pub const MREMAP_MAYMOVE: u64 = 1;
+/// mremap moves the mapping to the given address
+pub const MREMAP_FIXED: u64 = 2;
+
+/// Size of a page, mappings are rounded to it
+pub const PAGE_SIZE: u64 = 0x1000;
@@ -1,10 +1,56 @@
 pub use diss::riscv64gc::*;
 
+pub mod softfloat;
+pub mod csr;
+pub mod errno;
+pub mod mman;
+
+mod vfs;
+pub use vfs::*;
+
 mod core_emu;
 pub use core_emu::*;
 
+mod block_cache;
+pub use block_cache::*;
+
+mod coverage;
+pub use coverage::*;
+
+mod call_stack;
+pub use call_stack::*;
+
+mod symbols;
+pub use symbols::*;
+
+mod race_detector;
+pub use race_detector::*;
+
+mod heap_sanitizer;
+pub use heap_sanitizer::*;
+
+mod taint;
+pub use taint::*;
+
+mod uninit;
+pub use uninit::*;
+
+#[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+mod jit;
+#[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+pub use jit::Jit;
+
 mod linux_emu;
 pub use linux_emu::*;
 
+mod linux_emu_threading;
+pub use linux_emu_threading::*;
+
+mod syscall_hook;
+pub use syscall_hook::*;
+
+mod tracer;
+pub use tracer::*;
+
 mod linux_syscalls;
 pub use linux_syscalls::LinuxSyscall;
\ No newline at end of file
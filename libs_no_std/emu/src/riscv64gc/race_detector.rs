@@ -0,0 +1,338 @@
+//! Happens-before data race detector for the threads of the emulated process.
+//!
+//! Every thread has a vector clock, and every memory access of the core is
+//! recorded in the shadow of its segment, see [`mmu::ShadowWord`], with the
+//! thread and its own clock at the time. An access races with the previous
+//! write to the same bytes, and a write also with the previous read, if they
+//! are from different threads and the previous one does not happen before
+//! it, i.e. its clock is above the one the current thread knows about for
+//! that thread.
+//!
+//! The clocks only move forward through synchronization:
+//! - the `lr`, `sc` and AMO instructions acquire and release their address,
+//!   so they are not checked themselves;
+//! - `fence` acquires and releases a single global clock;
+//! - `futex` waits acquire their word, wakes release it and the woken
+//!   threads acquire it, which includes the exit of a thread waking its
+//!   `clear_child_tid`. The exit is also a release of the fences, for the
+//!   joiners that see the tid cleared without waiting;
+//! - a thread created by `clone` starts knowing everything its parent did.
+//!
+//! Only the last write and the last read of each word are kept, so a race
+//! with an older read or with other bytes of the same word written before
+//! the last write can be missed, but every race reported is a real one.
+//! The accesses done by the syscalls are not recorded.
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use diss::riscv64gc::Register;
+use mmu::{Mmu, ShadowAccess, ShadowWord, VirtAddr, SHADOW_WORD_SIZE};
+
+/// Two unsynchronized accesses to the same bytes, at least one a write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DataRace {
+    /// First byte of the current access, the instruction was executed
+    pub addr: u64,
+    pub size: u64,
+    pub pc: u64,
+    pub tid: u64,
+    pub is_write: bool,
+    /// The previous access it conflicts with
+    pub previous_pc: u64,
+    pub previous_tid: u64,
+    pub previous_is_write: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    /// `lr`, `sc` and the AMOs
+    Atomic,
+}
+
+/// The memory accessed by an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub addr: u64,
+    pub size: u64,
+    pub kind: AccessKind,
+}
+
+impl MemoryAccess {
+    /// The access the raw instruction `inst` will do with the registers
+    /// `regs`, `None` if it does not access memory. It has to be called
+    /// before the instruction executes, as it might overwrite its base
+    pub fn decode(inst: u32, regs: &[u64; 32]) -> Option<Self> {
+        let reg = |idx: u32| regs[idx as usize & 0x1f];
+        let bits = |lo: u32, len: u32| (inst >> lo) & ((1 << len) - 1);
+        // the compressed ones use x8..x15 or sp as base
+        let creg = || reg(8 + bits(7, 3));
+        let sp = regs[Register::Sp as usize];
+        let funct3 = bits(13, 3);
+
+        let (addr, size, kind) = match inst & 0b11 {
+            0b11 => {
+                let size = 1 << (bits(12, 3) & 0b11);
+                let base = reg(bits(15, 5));
+                match inst & 0x7f {
+                    0b000_0011 | 0b000_0111 => {
+                        let imm = (inst as i32 >> 20) as i64;
+                        (base.wrapping_add_signed(imm), size, AccessKind::Read)
+                    }
+                    0b010_0011 | 0b010_0111 => {
+                        let imm = ((inst as i32 >> 25) << 5) as i64 | bits(7, 5) as i64;
+                        (base.wrapping_add_signed(imm), size, AccessKind::Write)
+                    }
+                    0b010_1111 => (base, size, AccessKind::Atomic),
+                    _ => return None,
+                }
+            }
+            0b00 => {
+                // c.fld, c.lw, c.ld and the stores with the same offsets
+                let word = bits(10, 3) << 3 | bits(6, 1) << 2 | bits(5, 1) << 6;
+                let double = bits(10, 3) << 3 | bits(5, 2) << 6;
+                match funct3 {
+                    1 | 3 => (creg().wrapping_add(double as u64), 8, AccessKind::Read),
+                    2 => (creg().wrapping_add(word as u64), 4, AccessKind::Read),
+                    5 | 7 => (creg().wrapping_add(double as u64), 8, AccessKind::Write),
+                    6 => (creg().wrapping_add(word as u64), 4, AccessKind::Write),
+                    _ => return None,
+                }
+            }
+            0b10 => match funct3 {
+                1 | 3 => {
+                    let imm = bits(12, 1) << 5 | bits(5, 2) << 3 | bits(2, 3) << 6;
+                    (sp.wrapping_add(imm as u64), 8, AccessKind::Read)
+                }
+                2 => {
+                    let imm = bits(12, 1) << 5 | bits(4, 3) << 2 | bits(2, 2) << 6;
+                    (sp.wrapping_add(imm as u64), 4, AccessKind::Read)
+                }
+                5 | 7 => {
+                    let imm = bits(10, 3) << 3 | bits(7, 3) << 6;
+                    (sp.wrapping_add(imm as u64), 8, AccessKind::Write)
+                }
+                6 => {
+                    let imm = bits(9, 4) << 2 | bits(7, 2) << 6;
+                    (sp.wrapping_add(imm as u64), 4, AccessKind::Write)
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(MemoryAccess { addr, size, kind })
+    }
+}
+
+/// The clock of each thread, by index, as known by someone. The missing
+/// entries are zero
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct VectorClock(Vec<u32>);
+
+impl VectorClock {
+    #[inline(always)]
+    fn get(&self, thread: usize) -> u32 {
+        self.0.get(thread).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, thread: usize, clock: u32) {
+        if self.0.len() <= thread {
+            self.0.resize(thread + 1, 0);
+        }
+        self.0[thread] = clock;
+    }
+
+    /// Learn everything `other` knows
+    fn join(&mut self, other: &Self) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (clock, other) in self.0.iter_mut().zip(&other.0) {
+            *clock = (*clock).max(*other);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RaceDetector {
+    /// The tid of each thread index, the shadow stores the indices. The
+    /// exited threads keep theirs, as their accesses are still there
+    tids: Vec<u64>,
+    /// The vector clock of each thread index
+    clocks: Vec<VectorClock>,
+    /// Index of the running thread
+    current: usize,
+    /// What was released at each address by the atomics and the futexes
+    syncs: BTreeMap<u64, VectorClock>,
+    /// What was released by the fences
+    fence: VectorClock,
+}
+
+impl RaceDetector {
+    /// A detector whose running thread is `tid`
+    pub fn new(tid: u64) -> Self {
+        let mut detector = RaceDetector {
+            tids: Vec::new(),
+            clocks: Vec::new(),
+            current: 0,
+            syncs: BTreeMap::new(),
+            fence: VectorClock::default(),
+        };
+        detector.current = detector.add_thread(tid, VectorClock::default());
+        detector
+    }
+
+    /// Tid of the running thread
+    pub fn current_tid(&self) -> u64 {
+        self.tids[self.current]
+    }
+
+    /// Index of a new thread knowing what `clock` does
+    fn add_thread(&mut self, tid: u64, mut clock: VectorClock) -> usize {
+        let thread = self.tids.len();
+        assert!(thread <= u16::MAX as usize, "the race detector supports at most 2^16 threads");
+        // start from one so that its accesses are not known by the others
+        clock.set(thread, 1);
+        self.tids.push(tid);
+        self.clocks.push(clock);
+        thread
+    }
+
+    /// Index of the thread `tid`, the last one with that tid. A thread
+    /// never seen is created without any synchronization, e.g. if the
+    /// detector was enabled after it was
+    fn thread(&mut self, tid: u64) -> usize {
+        match self.tids.iter().rposition(|other| *other == tid) {
+            Some(thread) => thread,
+            None => self.add_thread(tid, VectorClock::default()),
+        }
+    }
+
+    /// Switch to the thread `tid`
+    pub fn set_current(&mut self, tid: u64) {
+        self.current = self.thread(tid);
+    }
+
+    /// The running thread created the thread `tid`, which starts knowing
+    /// everything it did so far
+    pub fn spawn(&mut self, tid: u64) {
+        let clock = self.clocks[self.current].clone();
+        self.add_thread(tid, clock);
+        self.tick();
+    }
+
+    /// Advance the clock of the running thread, so that what it does next
+    /// is not known by who synchronized with it
+    fn tick(&mut self) {
+        let clock = &mut self.clocks[self.current];
+        clock.set(self.current, clock.get(self.current) + 1);
+    }
+
+    /// The thread `tid` learns what was released at `addr`
+    pub fn acquire_for(&mut self, tid: u64, addr: u64) {
+        let thread = self.thread(tid);
+        if let Some(sync) = self.syncs.get(&addr) {
+            self.clocks[thread].join(sync);
+        }
+    }
+
+    /// The running thread learns what was released at `addr`
+    pub fn acquire(&mut self, addr: u64) {
+        self.acquire_for(self.current_tid(), addr);
+    }
+
+    /// The running thread publishes what it did so far at `addr`
+    pub fn release(&mut self, addr: u64) {
+        self.syncs.entry(addr).or_default().join(&self.clocks[self.current]);
+        self.tick();
+    }
+
+    /// A `fence` of the running thread
+    pub fn fence(&mut self) {
+        self.clocks[self.current].join(&self.fence);
+        self.fence.join(&self.clocks[self.current]);
+        self.tick();
+    }
+
+    /// Record that the instruction at `pc` of the running thread did
+    /// `access`, returning the first race it has with a previous access.
+    /// The atomics only synchronize
+    pub fn record(&mut self, mem: &mut Mmu, pc: u64, access: MemoryAccess)
+        -> Result<(), DataRace> {
+        let is_write = match access.kind {
+            AccessKind::Atomic => {
+                self.acquire(access.addr);
+                self.release(access.addr);
+                return Ok(());
+            }
+            AccessKind::Read => false,
+            AccessKind::Write => true,
+        };
+        let Ok((segment_addr, segment)) = mem.resolve_segment(VirtAddr(access.addr as usize)) else {
+            return Ok(());
+        };
+        let start = access.addr as usize - segment_addr.0;
+        let end = (start + access.size as usize).min(segment.len());
+        let words = segment.shadow_words(start..end);
+
+        let clock = &self.clocks[self.current];
+        let current = ShadowAccess {
+            pc,
+            clock: clock.get(self.current),
+            thread: self.current as u16,
+            mask: 0,
+        };
+        // if `previous` touched some of `mask` without happening before
+        let races = |previous: &ShadowAccess, mask: u8| previous.mask & mask != 0
+            && previous.thread != current.thread
+            && previous.clock > clock.get(previous.thread as usize);
+
+        let first_word = start / SHADOW_WORD_SIZE;
+        for (idx, word) in words.iter_mut().enumerate() {
+            let word_start = (first_word + idx) * SHADOW_WORD_SIZE;
+            let mut mask = 0_u8;
+            for byte in start.max(word_start)..end.min(word_start + SHADOW_WORD_SIZE) {
+                mask |= 1 << (byte - word_start);
+            }
+
+            let previous = match (races(&word.write, mask), is_write && races(&word.read, mask)) {
+                (true, _) => Some((word.write, true)),
+                (false, true) => Some((word.read, false)),
+                (false, false) => None,
+            };
+            if let Some((previous, previous_is_write)) = previous {
+                return Err(DataRace {
+                    addr: access.addr,
+                    size: access.size,
+                    pc,
+                    tid: self.tids[self.current],
+                    is_write,
+                    previous_pc: previous.pc,
+                    previous_tid: self.tids[previous.thread as usize],
+                    previous_is_write,
+                });
+            }
+
+            update(word, ShadowAccess { mask, ..current }, is_write);
+        }
+        Ok(())
+    }
+}
+
+/// Record `access` as the last one of its kind to `word`, it does not race
+/// with the previous ones
+#[inline]
+fn update(word: &mut ShadowWord, mut access: ShadowAccess, is_write: bool) {
+    let last = if is_write { &mut word.write } else { &mut word.read };
+    // keep the other bytes only if they were accessed at the same time by
+    // the same instruction, otherwise they would get the wrong clock
+    if (last.thread, last.clock, last.pc) == (access.thread, access.clock, access.pc) {
+        access.mask |= last.mask;
+    }
+    *last = access;
+    // the write happens after the reads of its bytes, so it's enough to
+    // check the next accesses against it
+    if is_write {
+        word.read.mask &= !access.mask;
+    }
+}
@@ -0,0 +1,820 @@
+//! Software IEEE-754 arithmetic for the F and D extensions.
+//!
+//! The host FPU can't be used directly: it has no RMM rounding, it doesn't
+//! expose the exception flags and it propagates NaN payloads while RISC-V
+//! always returns the canonical NaN. So every operation is done on the raw
+//! bits, with the whole computation done exactly on `u128` and a single
+//! rounding step at the end. This also makes the results the same on every
+//! host, which is nice for a fuzzer.
+//!
+//! Single precision values are passed around as the low 32 bits of a u64,
+//! the NaN-boxing is handled by the emulator, not here.
+use diss::riscv64gc::FloatRoundingMode;
+
+/// Inexact
+pub const FFLAGS_NX: u32 = 1 << 0;
+/// Underflow
+pub const FFLAGS_UF: u32 = 1 << 1;
+/// Overflow
+pub const FFLAGS_OF: u32 = 1 << 2;
+/// Divide by Zero
+pub const FFLAGS_DZ: u32 = 1 << 3;
+/// Invalid Operation
+pub const FFLAGS_NV: u32 = 1 << 4;
+
+/// Description of a binary IEEE-754 format
+pub trait FloatFormat {
+    /// How many bits the exponent has
+    const EXP_BITS: u32;
+    /// How many bits the fraction has (without the implicit one)
+    const FRAC_BITS: u32;
+    /// The NaN that RISC-V returns from every operation that produces a NaN
+    const CANONICAL_NAN: u64;
+
+    const BIAS: i32 = (1 << (Self::EXP_BITS - 1)) - 1;
+    const SIGN_MASK: u64 = 1 << (Self::EXP_BITS + Self::FRAC_BITS);
+    const EXP_MASK: u64 = ((1 << Self::EXP_BITS) - 1) << Self::FRAC_BITS;
+    const FRAC_MASK: u64 = (1 << Self::FRAC_BITS) - 1;
+    const INFINITY: u64 = Self::EXP_MASK;
+    const MAX_FINITE: u64 = Self::EXP_MASK - 1;
+}
+
+/// IEEE-754 binary32
+pub struct F32;
+
+impl FloatFormat for F32 {
+    const EXP_BITS: u32 = 8;
+    const FRAC_BITS: u32 = 23;
+    const CANONICAL_NAN: u64 = 0x7fc0_0000;
+}
+
+/// IEEE-754 binary64
+pub struct F64;
+
+impl FloatFormat for F64 {
+    const EXP_BITS: u32 = 11;
+    const FRAC_BITS: u32 = 52;
+    const CANONICAL_NAN: u64 = 0x7ff8_0000_0000_0000;
+}
+
+/// A decoded float, finite values are normalized so that the msb of `sig` is
+/// at bit `FRAC_BITS` and the value is `sig * 2^exp`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Class {
+    Zero,
+    Infinity,
+    NaN { signaling: bool },
+    Finite { exp: i32, sig: u128 },
+}
+
+#[inline]
+fn unpack<F: FloatFormat>(bits: u64) -> (bool, Class) {
+    let sign = bits & F::SIGN_MASK != 0;
+    let biased = ((bits & F::EXP_MASK) >> F::FRAC_BITS) as i32;
+    let frac = bits & F::FRAC_MASK;
+    let emin = 1 - F::BIAS;
+
+    let class = if biased == (1 << F::EXP_BITS) - 1 {
+        if frac == 0 {
+            Class::Infinity
+        } else {
+            Class::NaN { signaling: frac & (1 << (F::FRAC_BITS - 1)) == 0 }
+        }
+    } else if biased == 0 {
+        if frac == 0 {
+            Class::Zero
+        } else {
+            // subnormal, normalize it so all the math can assume the msb
+            // position
+            let shift = F::FRAC_BITS - (63 - frac.leading_zeros());
+            Class::Finite {
+                exp: emin - F::FRAC_BITS as i32 - shift as i32,
+                sig: (frac as u128) << shift,
+            }
+        }
+    } else {
+        Class::Finite {
+            exp: biased - F::BIAS - F::FRAC_BITS as i32,
+            sig: (frac | (1 << F::FRAC_BITS)) as u128,
+        }
+    };
+    (sign, class)
+}
+
+#[inline]
+fn sign_bit<F: FloatFormat>(sign: bool) -> u64 {
+    if sign { F::SIGN_MASK } else { 0 }
+}
+
+/// Shift right `sig` by `shift` bits rounding the result.
+/// Returns the rounded value and if the result is inexact.
+#[inline]
+fn round_shift(
+    sig: u128, shift: i32, sign: bool, rm: FloatRoundingMode
+) -> (u128, bool) {
+    if shift <= 0 {
+        return (sig << (-shift) as u32, false);
+    }
+
+    let (kept, round, sticky) = if shift > 128 {
+        (0, false, sig != 0)
+    } else if shift == 128 {
+        (0, sig >> 127 != 0, sig & (u128::MAX >> 1) != 0)
+    } else {
+        let shift = shift as u32;
+        (
+            sig >> shift,
+            (sig >> (shift - 1)) & 1 != 0,
+            sig & ((1 << (shift - 1)) - 1) != 0,
+        )
+    };
+
+    let increment = match rm {
+        FloatRoundingMode::RNE => round && (sticky || kept & 1 != 0),
+        FloatRoundingMode::RMM => round,
+        FloatRoundingMode::RTZ => false,
+        FloatRoundingMode::RDN => sign && (round || sticky),
+        FloatRoundingMode::RUP => !sign && (round || sticky),
+        FloatRoundingMode::DYN => unreachable!("DYN must be resolved by the caller"),
+    };
+
+    (kept + increment as u128, round || sticky)
+}
+
+/// Shift right keeping track if any set bit was lost in the lsb
+#[inline]
+fn shift_right_jam(value: u128, shift: u32) -> u128 {
+    if shift == 0 {
+        value
+    } else if shift >= 128 {
+        (value != 0) as u128
+    } else {
+        (value >> shift) | ((value & ((1 << shift) - 1)) != 0) as u128
+    }
+}
+
+/// Round the exact value `(-1)^sign * sig * 2^exp` to the format `F`
+/// accumulating the exception flags. `sig` must not be zero.
+fn round_pack<F: FloatFormat>(
+    sign: bool, exp: i32, sig: u128, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    debug_assert!(sig != 0);
+    let msb = 127 - sig.leading_zeros() as i32;
+    let frac_bits = F::FRAC_BITS as i32;
+    let emin = 1 - F::BIAS;
+    let emax = F::BIAS;
+    // unbiased exponent of the msb
+    let mut e = exp + msb;
+
+    if e >= emin {
+        let (mut kept, inexact) = round_shift(sig, msb - frac_bits, sign, rm);
+        // the rounding carried to the next power of two
+        if kept >> (F::FRAC_BITS + 1) != 0 {
+            kept >>= 1;
+            e += 1;
+        }
+
+        if e > emax {
+            *flags |= FFLAGS_OF | FFLAGS_NX;
+            let to_inf = match rm {
+                FloatRoundingMode::RNE | FloatRoundingMode::RMM => true,
+                FloatRoundingMode::RTZ => false,
+                FloatRoundingMode::RDN => sign,
+                FloatRoundingMode::RUP => !sign,
+                FloatRoundingMode::DYN => unreachable!(),
+            };
+            return sign_bit::<F>(sign) | if to_inf {
+                F::INFINITY
+            } else {
+                F::MAX_FINITE
+            };
+        }
+
+        if inexact {
+            *flags |= FFLAGS_NX;
+        }
+        sign_bit::<F>(sign)
+            | (((e + F::BIAS) as u64) << F::FRAC_BITS)
+            | (kept as u64 & F::FRAC_MASK)
+    } else {
+        // subnormal range, the lsb is fixed at 2^(emin - frac_bits)
+        let (kept, inexact) = round_shift(
+            sig, emin - frac_bits - exp, sign, rm
+        );
+
+        if inexact {
+            *flags |= FFLAGS_NX;
+            // RISC-V detects tininess after rounding, so check if rounding
+            // with an unbounded exponent would have reached the min normal
+            let (unbounded, _) = round_shift(sig, msb - frac_bits, sign, rm);
+            let carried = unbounded >> (F::FRAC_BITS + 1) != 0;
+            if !(carried && e + 1 >= emin) {
+                *flags |= FFLAGS_UF;
+            }
+        }
+        // if the rounding reached 1 << FRAC_BITS, this becomes the min normal
+        sign_bit::<F>(sign) | kept as u64
+    }
+}
+
+/// Handle the NaN inputs, returning the canonical NaN and raising the
+/// invalid flag if any input is signaling.
+#[inline]
+fn propagate_nan<F: FloatFormat>(classes: &[Class], flags: &mut u32) -> u64 {
+    if classes.iter().any(|c| matches!(c, Class::NaN { signaling: true })) {
+        *flags |= FFLAGS_NV;
+    }
+    F::CANONICAL_NAN
+}
+
+#[inline]
+fn is_nan(class: &Class) -> bool {
+    matches!(class, Class::NaN { .. })
+}
+
+/// Exactly add two finite non-zero values and round the result
+fn add_finite<F: FloatFormat>(
+    sa: bool, ea: i32, siga: u128,
+    sb: bool, eb: i32, sigb: u128,
+    rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    // normalize both to have the msb at bit 124, this leaves at least a
+    // couple of guard bits even for the 106 bits products of the fma, and
+    // a bit of headroom for the carry
+    let na = siga.leading_zeros() - 3;
+    let nb = sigb.leading_zeros() - 3;
+    let (mut ea, mut siga) = (ea - na as i32, siga << na);
+    let (mut eb, mut sigb) = (eb - nb as i32, sigb << nb);
+
+    // align the smaller to the bigger
+    if ea >= eb {
+        sigb = shift_right_jam(sigb, (ea - eb) as u32);
+        eb = ea;
+    } else {
+        siga = shift_right_jam(siga, (eb - ea) as u32);
+        ea = eb;
+    }
+    debug_assert_eq!(ea, eb);
+
+    let (sign, sig) = if sa == sb {
+        (sa, siga + sigb)
+    } else if siga >= sigb {
+        (sa, siga - sigb)
+    } else {
+        (sb, sigb - siga)
+    };
+
+    if sig == 0 {
+        // exact cancellation is +0 in every rounding mode but RDN
+        return sign_bit::<F>(rm == FloatRoundingMode::RDN);
+    }
+    round_pack::<F>(sign, ea, sig, rm, flags)
+}
+
+/// `a + b`
+pub fn add<F: FloatFormat>(
+    a: u64, b: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sa, ca) = unpack::<F>(a);
+    let (sb, cb) = unpack::<F>(b);
+
+    match (ca, cb) {
+        _ if is_nan(&ca) || is_nan(&cb) => propagate_nan::<F>(&[ca, cb], flags),
+        (Class::Infinity, Class::Infinity) if sa != sb => {
+            *flags |= FFLAGS_NV;
+            F::CANONICAL_NAN
+        }
+        (Class::Infinity, _) => a,
+        (_, Class::Infinity) => b,
+        (Class::Zero, Class::Zero) => {
+            if sa == sb {
+                a
+            } else {
+                sign_bit::<F>(rm == FloatRoundingMode::RDN)
+            }
+        }
+        (Class::Zero, _) => b,
+        (_, Class::Zero) => a,
+        (
+            Class::Finite { exp: ea, sig: siga },
+            Class::Finite { exp: eb, sig: sigb },
+        ) => add_finite::<F>(sa, ea, siga, sb, eb, sigb, rm, flags),
+        _ => unreachable!(),
+    }
+}
+
+/// `a - b`
+pub fn sub<F: FloatFormat>(
+    a: u64, b: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    add::<F>(a, b ^ F::SIGN_MASK, rm, flags)
+}
+
+/// `a * b`
+pub fn mul<F: FloatFormat>(
+    a: u64, b: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sa, ca) = unpack::<F>(a);
+    let (sb, cb) = unpack::<F>(b);
+    let sign = sa ^ sb;
+
+    match (ca, cb) {
+        _ if is_nan(&ca) || is_nan(&cb) => propagate_nan::<F>(&[ca, cb], flags),
+        (Class::Infinity, Class::Zero) | (Class::Zero, Class::Infinity) => {
+            *flags |= FFLAGS_NV;
+            F::CANONICAL_NAN
+        }
+        (Class::Infinity, _) | (_, Class::Infinity) => {
+            sign_bit::<F>(sign) | F::INFINITY
+        }
+        (Class::Zero, _) | (_, Class::Zero) => sign_bit::<F>(sign),
+        (
+            Class::Finite { exp: ea, sig: siga },
+            Class::Finite { exp: eb, sig: sigb },
+        ) => round_pack::<F>(sign, ea + eb, siga * sigb, rm, flags),
+        _ => unreachable!(),
+    }
+}
+
+/// `a / b`
+pub fn div<F: FloatFormat>(
+    a: u64, b: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sa, ca) = unpack::<F>(a);
+    let (sb, cb) = unpack::<F>(b);
+    let sign = sa ^ sb;
+
+    match (ca, cb) {
+        _ if is_nan(&ca) || is_nan(&cb) => propagate_nan::<F>(&[ca, cb], flags),
+        (Class::Infinity, Class::Infinity) | (Class::Zero, Class::Zero) => {
+            *flags |= FFLAGS_NV;
+            F::CANONICAL_NAN
+        }
+        (Class::Infinity, _) => sign_bit::<F>(sign) | F::INFINITY,
+        (_, Class::Infinity) => sign_bit::<F>(sign),
+        (Class::Zero, _) => sign_bit::<F>(sign),
+        (_, Class::Zero) => {
+            *flags |= FFLAGS_DZ;
+            sign_bit::<F>(sign) | F::INFINITY
+        }
+        (
+            Class::Finite { exp: ea, sig: siga },
+            Class::Finite { exp: eb, sig: sigb },
+        ) => {
+            // both are normalized so the quotient has at least 69 bits,
+            // way more than needed, and the reminder goes in the sticky bit
+            let num = siga << 70;
+            let quot = num / sigb;
+            let sticky = (num % sigb != 0) as u128;
+            round_pack::<F>(sign, ea - eb - 70, quot | sticky, rm, flags)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Integer square root of a u128
+fn isqrt(value: u128) -> u128 {
+    let mut rem = value;
+    let mut res = 0;
+    let mut bit = 1 << 126;
+    while bit > value {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rem >= res + bit {
+            rem -= res + bit;
+            res = (res >> 1) + bit;
+        } else {
+            res >>= 1;
+        }
+        bit >>= 2;
+    }
+    res
+}
+
+/// `sqrt(a)`
+pub fn sqrt<F: FloatFormat>(
+    a: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sa, ca) = unpack::<F>(a);
+
+    match ca {
+        Class::NaN { .. } => propagate_nan::<F>(&[ca], flags),
+        // sqrt(-0) = -0
+        Class::Zero => a,
+        _ if sa => {
+            *flags |= FFLAGS_NV;
+            F::CANONICAL_NAN
+        }
+        Class::Infinity => a,
+        Class::Finite { mut exp, mut sig } => {
+            // make the exponent even so it can be halved
+            if exp & 1 != 0 {
+                sig <<= 1;
+                exp -= 1;
+            }
+            let num = sig << 72;
+            let root = isqrt(num);
+            let sticky = (root * root != num) as u128;
+            round_pack::<F>(false, (exp - 72) / 2, root | sticky, rm, flags)
+        }
+    }
+}
+
+/// `a * b + c` with a single rounding
+pub fn fma<F: FloatFormat>(
+    a: u64, b: u64, c: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sa, ca) = unpack::<F>(a);
+    let (sb, cb) = unpack::<F>(b);
+    let (sc, cc) = unpack::<F>(c);
+    let sp = sa ^ sb;
+
+    let invalid_product = matches!(
+        (ca, cb),
+        (Class::Infinity, Class::Zero) | (Class::Zero, Class::Infinity)
+    );
+
+    // RISC-V raises invalid on inf * 0 even if the addend is a quiet NaN
+    if invalid_product {
+        *flags |= FFLAGS_NV;
+        if is_nan(&cc) {
+            propagate_nan::<F>(&[cc], flags);
+        }
+        return F::CANONICAL_NAN;
+    }
+    if is_nan(&ca) || is_nan(&cb) || is_nan(&cc) {
+        return propagate_nan::<F>(&[ca, cb, cc], flags);
+    }
+
+    let product_inf = matches!(ca, Class::Infinity)
+        || matches!(cb, Class::Infinity);
+    if product_inf {
+        if matches!(cc, Class::Infinity) && sc != sp {
+            *flags |= FFLAGS_NV;
+            return F::CANONICAL_NAN;
+        }
+        return sign_bit::<F>(sp) | F::INFINITY;
+    }
+    if matches!(cc, Class::Infinity) {
+        return c;
+    }
+
+    match (ca, cb, cc) {
+        (Class::Zero, _, Class::Zero) | (_, Class::Zero, Class::Zero) => {
+            if sp == sc {
+                sign_bit::<F>(sp)
+            } else {
+                sign_bit::<F>(rm == FloatRoundingMode::RDN)
+            }
+        }
+        (Class::Zero, _, _) | (_, Class::Zero, _) => c,
+        (
+            Class::Finite { exp: ea, sig: siga },
+            Class::Finite { exp: eb, sig: sigb },
+            Class::Zero,
+        ) => round_pack::<F>(sp, ea + eb, siga * sigb, rm, flags),
+        (
+            Class::Finite { exp: ea, sig: siga },
+            Class::Finite { exp: eb, sig: sigb },
+            Class::Finite { exp: ec, sig: sigc },
+        ) => add_finite::<F>(
+            sp, ea + eb, siga * sigb, sc, ec, sigc, rm, flags
+        ),
+        _ => unreachable!(),
+    }
+}
+
+/// Ordering on non NaN values where -0 < +0
+#[inline]
+fn lt_total<F: FloatFormat>(a: u64, b: u64) -> bool {
+    let sa = a & F::SIGN_MASK != 0;
+    let sb = b & F::SIGN_MASK != 0;
+    let ma = a & !F::SIGN_MASK;
+    let mb = b & !F::SIGN_MASK;
+    match (sa, sb) {
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => ma < mb,
+        (true, true) => ma > mb,
+    }
+}
+
+#[inline]
+fn is_zero<F: FloatFormat>(a: u64) -> bool {
+    a & !F::SIGN_MASK == 0
+}
+
+/// `a == b`, quiet comparison so only signaling NaNs raise invalid
+pub fn eq<F: FloatFormat>(a: u64, b: u64, flags: &mut u32) -> bool {
+    let (_, ca) = unpack::<F>(a);
+    let (_, cb) = unpack::<F>(b);
+    if is_nan(&ca) || is_nan(&cb) {
+        propagate_nan::<F>(&[ca, cb], flags);
+        return false;
+    }
+    a == b || (is_zero::<F>(a) && is_zero::<F>(b))
+}
+
+/// `a < b`, signaling comparison so any NaN raises invalid
+pub fn lt<F: FloatFormat>(a: u64, b: u64, flags: &mut u32) -> bool {
+    let (_, ca) = unpack::<F>(a);
+    let (_, cb) = unpack::<F>(b);
+    if is_nan(&ca) || is_nan(&cb) {
+        *flags |= FFLAGS_NV;
+        return false;
+    }
+    if is_zero::<F>(a) && is_zero::<F>(b) {
+        return false;
+    }
+    lt_total::<F>(a, b)
+}
+
+/// `a <= b`, signaling comparison so any NaN raises invalid
+pub fn le<F: FloatFormat>(a: u64, b: u64, flags: &mut u32) -> bool {
+    let (_, ca) = unpack::<F>(a);
+    let (_, cb) = unpack::<F>(b);
+    if is_nan(&ca) || is_nan(&cb) {
+        *flags |= FFLAGS_NV;
+        return false;
+    }
+    a == b || (is_zero::<F>(a) && is_zero::<F>(b)) || lt_total::<F>(a, b)
+}
+
+/// Shared logic of fmin and fmax, a single NaN input is ignored
+#[inline]
+fn min_max<F: FloatFormat>(a: u64, b: u64, max: bool, flags: &mut u32) -> u64 {
+    let (_, ca) = unpack::<F>(a);
+    let (_, cb) = unpack::<F>(b);
+    match (is_nan(&ca), is_nan(&cb)) {
+        (true, true) => propagate_nan::<F>(&[ca, cb], flags),
+        (true, false) => {
+            propagate_nan::<F>(&[ca], flags);
+            b
+        }
+        (false, true) => {
+            propagate_nan::<F>(&[cb], flags);
+            a
+        }
+        (false, false) => {
+            if lt_total::<F>(a, b) ^ max { a } else { b }
+        }
+    }
+}
+
+/// `min(a, b)` following the 2.2 semantic
+pub fn min<F: FloatFormat>(a: u64, b: u64, flags: &mut u32) -> u64 {
+    min_max::<F>(a, b, false, flags)
+}
+
+/// `max(a, b)` following the 2.2 semantic
+pub fn max<F: FloatFormat>(a: u64, b: u64, flags: &mut u32) -> u64 {
+    min_max::<F>(a, b, true, flags)
+}
+
+/// Classify the value in the 10 bits mask returned by `fclass`
+pub fn classify<F: FloatFormat>(a: u64) -> u64 {
+    let (sign, class) = unpack::<F>(a);
+    let subnormal = a & F::EXP_MASK == 0;
+    let bit = match (class, sign) {
+        (Class::Infinity, true) => 0,
+        (Class::Finite { .. }, true) if !subnormal => 1,
+        (Class::Finite { .. }, true) => 2,
+        (Class::Zero, true) => 3,
+        (Class::Zero, false) => 4,
+        (Class::Finite { .. }, false) if subnormal => 5,
+        (Class::Finite { .. }, false) => 6,
+        (Class::Infinity, false) => 7,
+        (Class::NaN { signaling: true }, _) => 8,
+        (Class::NaN { signaling: false }, _) => 9,
+    };
+    1 << bit
+}
+
+/// Convert a float to an integer of `bits` bits, saturating on overflow
+/// like RISC-V does. 32 bits results are sign-extended to 64 bits, as
+/// `fcvt.w[u]` requires.
+pub fn to_int<F: FloatFormat>(
+    a: u64, signed: bool, bits: u32, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    debug_assert!(bits == 32 || bits == 64);
+    let (sign, class) = unpack::<F>(a);
+
+    let max: u128 = if signed { (1 << (bits - 1)) - 1 } else { (1 << bits) - 1 };
+    let min_mag: u128 = if signed { 1 << (bits - 1) } else { 0 };
+
+    let saturate = |negative: bool, flags: &mut u32| -> u64 {
+        *flags |= FFLAGS_NV;
+        let res = if negative {
+            (min_mag as u64).wrapping_neg()
+        } else {
+            max as u64
+        };
+        sext_result(res, bits)
+    };
+
+    match class {
+        Class::NaN { .. } => saturate(false, flags),
+        Class::Infinity => saturate(sign, flags),
+        Class::Zero => 0,
+        Class::Finite { exp, sig } => {
+            // anything with more than 64 integer bits is out of range anyway
+            if exp > 64 {
+                return saturate(sign, flags);
+            }
+            let (mag, inexact) = round_shift(sig, -exp, sign, rm);
+            let in_range = if sign { mag <= min_mag } else { mag <= max };
+            if !in_range {
+                return saturate(sign, flags);
+            }
+            if inexact {
+                *flags |= FFLAGS_NX;
+            }
+            let res = if sign { (mag as u64).wrapping_neg() } else { mag as u64 };
+            sext_result(res, bits)
+        }
+    }
+}
+
+#[inline]
+fn sext_result(value: u64, bits: u32) -> u64 {
+    if bits == 32 {
+        value as i32 as i64 as u64
+    } else {
+        value
+    }
+}
+
+/// Convert an integer, given as sign and magnitude, to a float
+pub fn from_int<F: FloatFormat>(
+    negative: bool, magnitude: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    if magnitude == 0 {
+        return 0;
+    }
+    round_pack::<F>(negative, 0, magnitude as u128, rm, flags)
+}
+
+/// Convert between float formats
+pub fn convert<From: FloatFormat, To: FloatFormat>(
+    a: u64, rm: FloatRoundingMode, flags: &mut u32,
+) -> u64 {
+    let (sign, class) = unpack::<From>(a);
+    match class {
+        Class::NaN { .. } => propagate_nan::<To>(&[class], flags),
+        Class::Infinity => sign_bit::<To>(sign) | To::INFINITY,
+        Class::Zero => sign_bit::<To>(sign),
+        Class::Finite { exp, sig } => round_pack::<To>(sign, exp, sig, rm, flags),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use FloatRoundingMode::*;
+
+    fn d(x: f64) -> u64 { x.to_bits() }
+    fn s(x: f32) -> u64 { x.to_bits() as u64 }
+
+    fn check_f64(ours: u64, host: f64) {
+        if host.is_nan() {
+            assert_eq!(ours, F64::CANONICAL_NAN);
+        } else {
+            assert_eq!(ours, d(host));
+        }
+    }
+
+    fn check_f32(ours: u64, host: f32) {
+        if host.is_nan() {
+            assert_eq!(ours, F32::CANONICAL_NAN);
+        } else {
+            assert_eq!(ours, s(host));
+        }
+    }
+
+    #[test]
+    fn test_matches_host() {
+        let values = [
+            0.0, -0.0, 1.0, -1.5, 3.25, 1e300, -1e300, 1e-310, 5e-324,
+            0.1, 0.2, 123456.789, f64::MAX, f64::MIN_POSITIVE, f64::INFINITY,
+        ];
+        let mut flags = 0;
+        for a in values {
+            for b in values {
+                check_f64(add::<F64>(d(a), d(b), RNE, &mut flags), a + b);
+                check_f64(sub::<F64>(d(a), d(b), RNE, &mut flags), a - b);
+                check_f64(mul::<F64>(d(a), d(b), RNE, &mut flags), a * b);
+                check_f64(div::<F64>(d(a), d(b), RNE, &mut flags), a / b);
+                let (fa, fb) = (a as f32, b as f32);
+                check_f32(add::<F32>(s(fa), s(fb), RNE, &mut flags), fa + fb);
+                check_f32(sub::<F32>(s(fa), s(fb), RNE, &mut flags), fa - fb);
+                check_f32(mul::<F32>(s(fa), s(fb), RNE, &mut flags), fa * fb);
+                check_f32(div::<F32>(s(fa), s(fb), RNE, &mut flags), fa / fb);
+            }
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut flags = 0;
+        add::<F64>(d(0.1), d(0.2), RNE, &mut flags);
+        assert_eq!(flags, FFLAGS_NX);
+
+        let mut flags = 0;
+        assert_eq!(add::<F64>(d(1.0), d(2.0), RNE, &mut flags), d(3.0));
+        assert_eq!(flags, 0);
+
+        let mut flags = 0;
+        assert_eq!(div::<F64>(d(1.0), d(0.0), RNE, &mut flags), d(f64::INFINITY));
+        assert_eq!(flags, FFLAGS_DZ);
+
+        let mut flags = 0;
+        assert_eq!(sqrt::<F64>(d(-1.0), RNE, &mut flags), F64::CANONICAL_NAN);
+        assert_eq!(flags, FFLAGS_NV);
+
+        let mut flags = 0;
+        assert_eq!(mul::<F64>(d(f64::MAX), d(2.0), RTZ, &mut flags), d(f64::MAX));
+        assert_eq!(flags, FFLAGS_OF | FFLAGS_NX);
+
+        let mut flags = 0;
+        mul::<F64>(d(f64::MIN_POSITIVE), d(0.3), RNE, &mut flags);
+        assert_eq!(flags, FFLAGS_UF | FFLAGS_NX);
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let mut flags = 0;
+        // 1 + 2^-53 is exactly halfway between 1 and the next double
+        let half_ulp = d(f64::EPSILON / 2.0);
+        assert_eq!(add::<F64>(d(1.0), half_ulp, RNE, &mut flags), d(1.0));
+        assert_eq!(add::<F64>(d(1.0), half_ulp, RMM, &mut flags), d(1.0 + f64::EPSILON));
+        assert_eq!(add::<F64>(d(1.0), half_ulp, RUP, &mut flags), d(1.0 + f64::EPSILON));
+        assert_eq!(add::<F64>(d(1.0), half_ulp, RDN, &mut flags), d(1.0));
+        assert_eq!(add::<F64>(d(-1.0), half_ulp ^ F64::SIGN_MASK, RDN, &mut flags), d(-1.0 - f64::EPSILON));
+        // x - x is -0 only when rounding down
+        assert_eq!(sub::<F64>(d(1.0), d(1.0), RNE, &mut flags), d(0.0));
+        assert_eq!(sub::<F64>(d(1.0), d(1.0), RDN, &mut flags), d(-0.0));
+    }
+
+    #[test]
+    fn test_sqrt_fma() {
+        let mut flags = 0;
+        for x in [0.0, 1.0, 2.0, 4.0, 1e-310, 12345.678, 1e300] {
+            assert_eq!(sqrt::<F64>(d(x), RNE, &mut flags), d(x.sqrt()));
+            let x = x as f32;
+            assert_eq!(sqrt::<F32>(s(x), RNE, &mut flags), s(x.sqrt()));
+        }
+        for (a, b, c) in [(0.1, 10.0, -1.0), (1e300, 1e10, -1e308), (3.0, 7.0, 0.5), (-2.0, 0.5, 1.0)] {
+            assert_eq!(fma::<F64>(d(a), d(b), d(c), RNE, &mut flags), d(f64::mul_add(a, b, c)));
+        }
+        // inf * 0 + qNaN is still invalid
+        let mut flags = 0;
+        fma::<F64>(d(f64::INFINITY), d(0.0), F64::CANONICAL_NAN, RNE, &mut flags);
+        assert_eq!(flags, FFLAGS_NV);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut flags = 0;
+        assert_eq!(to_int::<F64>(d(-1.5), true, 32, RNE, &mut flags), -2i64 as u64);
+        assert_eq!(to_int::<F64>(d(-1.5), true, 32, RMM, &mut flags), -2i64 as u64);
+        assert_eq!(to_int::<F64>(d(2.5), true, 64, RNE, &mut flags), 2);
+        assert_eq!(to_int::<F64>(d(2.5), true, 64, RMM, &mut flags), 3);
+        assert_eq!(to_int::<F64>(d(2.5), true, 64, RTZ, &mut flags), 2);
+        assert_eq!(to_int::<F64>(d(-2.5), true, 64, RDN, &mut flags), -3i64 as u64);
+        assert_eq!(flags, FFLAGS_NX);
+
+        let mut flags = 0;
+        assert_eq!(to_int::<F64>(d(1e20), true, 32, RNE, &mut flags), i32::MAX as u64);
+        assert_eq!(to_int::<F64>(d(-1e20), true, 64, RNE, &mut flags), i64::MIN as u64);
+        assert_eq!(to_int::<F64>(F64::CANONICAL_NAN, true, 64, RNE, &mut flags), i64::MAX as u64);
+        assert_eq!(to_int::<F64>(d(-3.0), false, 64, RNE, &mut flags), 0);
+        // wu results are sign-extended too
+        assert_eq!(to_int::<F64>(d(4e9), false, 32, RNE, &mut flags), 4_000_000_000u32 as i32 as i64 as u64);
+        assert_eq!(flags, FFLAGS_NV);
+
+        let mut flags = 0;
+        assert_eq!(from_int::<F64>(true, 7, RNE, &mut flags), d(-7.0));
+        assert_eq!(from_int::<F32>(false, u64::MAX, RNE, &mut flags), s(u64::MAX as f32));
+        assert_eq!(convert::<F64, F32>(d(0.1), RNE, &mut flags), s(0.1f32));
+        assert_eq!(convert::<F32, F64>(s(0.1f32), RNE, &mut flags), d(0.1f32 as f64));
+    }
+
+    #[test]
+    fn test_compare_min_max() {
+        let mut flags = 0;
+        assert!(eq::<F64>(d(0.0), d(-0.0), &mut flags));
+        assert!(!lt::<F64>(d(-0.0), d(0.0), &mut flags));
+        assert!(le::<F64>(d(-1.0), d(-1.0), &mut flags));
+        assert_eq!(flags, 0);
+        assert_eq!(min::<F64>(d(0.0), d(-0.0), &mut flags), d(-0.0));
+        assert_eq!(max::<F64>(d(0.0), d(-0.0), &mut flags), d(0.0));
+        assert_eq!(min::<F64>(F64::CANONICAL_NAN, d(3.0), &mut flags), d(3.0));
+        assert_eq!(flags, 0);
+        assert!(!lt::<F64>(F64::CANONICAL_NAN, d(3.0), &mut flags));
+        assert_eq!(flags, FFLAGS_NV);
+        assert_eq!(classify::<F64>(d(-0.0)), 1 << 3);
+        assert_eq!(classify::<F32>(s(f32::NAN)), 1 << 9);
+        assert_eq!(classify::<F64>(d(5e-324)), 1 << 5);
+    }
+}
@@ -0,0 +1,96 @@
+//! Symbol tables of the emulated programs, used to give names to the pcs of
+//! the backtraces.
+use alloc::string::String;
+use alloc::vec::Vec;
+
+/// A function or object of the program
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Symbol {
+    pub addr: u64,
+    /// Zero if unknown, then the symbol extends up to the next one
+    pub size: u64,
+    pub name: String,
+}
+
+/// The symbols of all the objects loaded, sorted by address
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    symbols: Vec<Symbol>,
+}
+
+impl Symbols {
+    pub fn add(&mut self, symbol: Symbol) {
+        let idx = self.symbols.partition_point(|other| *other <= symbol);
+        self.symbols.insert(idx, symbol);
+    }
+
+    /// The symbol containing `addr` and the offset of `addr` in it
+    pub fn lookup(&self, addr: u64) -> Option<(&Symbol, u64)> {
+        let idx = self.symbols.partition_point(|symbol| symbol.addr <= addr);
+        // the sized symbols win over the unsized ones at the same address
+        self.symbols[..idx].iter().rev()
+            .take_while(|symbol| symbol.addr == self.symbols[idx - 1].addr)
+            .find(|symbol| symbol.size == 0 || addr - symbol.addr < symbol.size)
+            .map(|symbol| (symbol, addr - symbol.addr))
+    }
+
+    /// The first symbol called `name`, by address
+    pub fn find(&self, name: &str) -> Option<&Symbol> {
+        self.symbols.iter().find(|symbol| symbol.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Give names to the pcs of a backtrace, e.g. from
+    /// [`CallStack::backtrace`](super::CallStack::backtrace)
+    pub fn symbolize(&self, pcs: &[u64]) -> Backtrace {
+        Backtrace {
+            frames: pcs.iter().map(|&pc| BacktraceFrame {
+                pc,
+                symbol: self.lookup(pc)
+                    .map(|(symbol, offset)| (symbol.name.clone(), offset)),
+            }).collect(),
+        }
+    }
+}
+
+impl Extend<Symbol> for Symbols {
+    fn extend<T: IntoIterator<Item = Symbol>>(&mut self, iter: T) {
+        self.symbols.extend(iter);
+        self.symbols.sort();
+    }
+}
+
+/// A pc of a [`Backtrace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BacktraceFrame {
+    pub pc: u64,
+    /// Name of the symbol containing the pc and offset of the pc in it
+    pub symbol: Option<(String, u64)>,
+}
+
+/// The pcs of the call stack with their symbols, the innermost first
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Backtrace {
+    pub frames: Vec<BacktraceFrame>,
+}
+
+impl core::fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (idx, frame) in self.frames.iter().enumerate() {
+            write!(f, "#{:<3} {:016x}", idx, frame.pc)?;
+            match &frame.symbol {
+                Some((name, 0)) => writeln!(f, " {}", name)?,
+                Some((name, offset)) => writeln!(f, " {}+{:#x}", name, offset)?,
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+//! Hooks that let a harness override or stub the syscalls of [`LinuxEmu`].
+//!
+//! A hook is registered for a [`LinuxSyscall`] and runs before the built-in
+//! implementation, which is used as fallback when the hook returns
+//! [`SyscallAction::Continue`].
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use super::{CoreEmu, LinuxEmu, LinuxSyscall};
+
+/// What to do after a syscall hook ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyscallAction {
+    /// Run the built-in implementation. The hook can modify the argument
+    /// registers before that.
+    Continue,
+    /// Skip the built-in implementation and return this value in `a0`
+    Return(u64),
+    /// Stop the emulation with [`LinuxEmuError::HookStop`] with this reason
+    ///
+    /// [`LinuxEmuError::HookStop`]: super::LinuxEmuError::HookStop
+    Stop(u64),
+}
+
+/// What to do with syscalls that have neither a hook nor a built-in
+/// implementation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownSyscallPolicy {
+    /// Return `-ENOSYS` to the guest, like an old kernel would
+    #[default]
+    Enosys,
+    /// Stop the emulation with [`LinuxEmuError::BadSyscall`]
+    ///
+    /// [`LinuxEmuError::BadSyscall`]: super::LinuxEmuError::BadSyscall
+    Stop,
+}
+
+/// A syscall handler, it gets the core and the arguments `a0..a5`
+pub type SyscallHook = Arc<
+    dyn Fn(&mut CoreEmu, [u64; 6]) -> SyscallAction + Send + Sync
+>;
+
+/// The hooks registered on an emulator, indexed by syscall number
+#[derive(Clone, Default)]
+pub struct SyscallHooks {
+    hooks: Vec<Option<SyscallHook>>,
+}
+
+impl core::fmt::Debug for SyscallHooks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_list()
+            .entries(self.hooks.iter().enumerate()
+                .filter(|(_, hook)| hook.is_some())
+                .map(|(number, _)| number))
+            .finish()
+    }
+}
+
+impl SyscallHooks {
+    /// Get the hook of the syscall with number `number`
+    #[inline(always)]
+    pub fn get(&self, number: u64) -> Option<&SyscallHook> {
+        self.hooks.get(number as usize).and_then(Option::as_ref)
+    }
+
+    /// Set the hook of `syscall` and return the old one
+    pub fn insert(&mut self, syscall: LinuxSyscall, hook: SyscallHook) -> Option<SyscallHook> {
+        let idx = syscall as usize;
+        if self.hooks.len() <= idx {
+            self.hooks.resize(idx + 1, None);
+        }
+        self.hooks[idx].replace(hook)
+    }
+
+    /// Remove the hook of `syscall` and return it
+    pub fn remove(&mut self, syscall: LinuxSyscall) -> Option<SyscallHook> {
+        self.hooks.get_mut(syscall as usize).and_then(Option::take)
+    }
+}
+
+impl LinuxEmu {
+    /// Run `hook` whenever the guest calls `syscall`, replacing the previous
+    /// hook of `syscall` if any.
+    ///
+    /// ```ignore
+    /// // fake the pid
+    /// emu.hook_syscall(LinuxSyscall::getpid, |_core, _args| {
+    ///     SyscallAction::Return(1337)
+    /// });
+    /// ```
+    pub fn hook_syscall<F>(&mut self, syscall: LinuxSyscall, hook: F)
+    where
+        F: Fn(&mut CoreEmu, [u64; 6]) -> SyscallAction + Send + Sync + 'static
+    {
+        self.syscall_hooks.insert(syscall, Arc::new(hook));
+    }
+
+    /// Remove the hook of `syscall`, so the built-in implementation is used
+    pub fn unhook_syscall(&mut self, syscall: LinuxSyscall) {
+        self.syscall_hooks.remove(syscall);
+    }
+}
@@ -0,0 +1,439 @@
+//! Byte-granular taint tracking of the fuzz input.
+//!
+//! Every byte of memory and every register has a [`Label`], the set of
+//! offsets of the input its value was computed from, unlike the
+//! [`mmu::PermField::Tainted`] bit which only tells that a byte was touched.
+//! The bytes read from the input get the label of their offset, see
+//! [`TaintTracker::taint_input`], and the instructions executed by the core
+//! propagate them:
+//! - the result of an operation has the union of the labels of its
+//!   operands, while the immediates, `lui`, `auipc`, the links of the jumps
+//!   and the CSRs are clean;
+//! - a load gets the union of the labels of the bytes it reads, a store
+//!   gives the label of its register to every byte it writes;
+//! - the AMOs store the union of the old value and of their register.
+//!
+//! The addresses don't propagate, so a load from a table indexed by the
+//! input is clean. The labels of the operands of the branches and of the
+//! `slt` family are accumulated by pc, see [`TaintTracker::comparison`], and
+//! [`LinuxEmu`](super::LinuxEmu) does the same with the arguments of the
+//! syscalls, see [`TaintTracker::syscall`]. The memory written by the
+//! syscalls is clean, but the one read from the input. The labels of the
+//! registers are not switched with the threads, so the tracking is only
+//! exact for single-threaded programs.
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use diss::riscv64gc::{FloatRegister, Register};
+use super::{AccessKind, MemoryAccess};
+
+/// A set of offsets of the input, interned by a [`TaintTracker`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Label(u32);
+
+impl Label {
+    /// The empty set, of the values that don't depend on the input
+    pub const CLEAN: Label = Label(0);
+
+    #[inline(always)]
+    pub fn is_clean(&self) -> bool {
+        *self == Label::CLEAN
+    }
+}
+
+/// The labels of the arguments of a syscall, see [`TaintTracker::syscall`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaintedSyscall {
+    pub number: u64,
+    pub args: [Label; 6],
+}
+
+#[derive(Debug, Clone)]
+pub struct TaintTracker {
+    /// The sorted offsets of each label, by index
+    sets: Vec<Vec<u32>>,
+    /// The label of each set
+    labels: BTreeMap<Vec<u32>, Label>,
+    /// The union of each pair of labels computed so far, the smaller first
+    unions: BTreeMap<(Label, Label), Label>,
+    regs: [Label; 32],
+    fregs: [Label; 32],
+    /// The label of each byte of memory, the missing ones are clean
+    memory: BTreeMap<u64, Label>,
+    /// The labels of the operands of the comparisons, by pc
+    comparisons: BTreeMap<u64, (Label, Label)>,
+    /// The labels of the arguments of the syscalls, by pc of the `ecall`
+    syscalls: BTreeMap<u64, TaintedSyscall>,
+}
+
+impl Default for TaintTracker {
+    fn default() -> Self {
+        let mut labels = BTreeMap::new();
+        labels.insert(Vec::new(), Label::CLEAN);
+        TaintTracker {
+            sets: alloc::vec![Vec::new()],
+            labels,
+            unions: BTreeMap::new(),
+            regs: [Label::CLEAN; 32],
+            fregs: [Label::CLEAN; 32],
+            memory: BTreeMap::new(),
+            comparisons: BTreeMap::new(),
+            syscalls: BTreeMap::new(),
+        }
+    }
+}
+
+impl TaintTracker {
+    /// The offsets of the input in `label`, sorted
+    pub fn offsets(&self, label: Label) -> &[u32] {
+        &self.sets[label.0 as usize]
+    }
+
+    /// The label of the sorted `offsets`
+    fn intern(&mut self, offsets: Vec<u32>) -> Label {
+        if let Some(label) = self.labels.get(&offsets) {
+            return *label;
+        }
+        let label = Label(u32::try_from(self.sets.len()).expect("too many taint labels"));
+        self.sets.push(offsets.clone());
+        self.labels.insert(offsets, label);
+        label
+    }
+
+    /// The label of the byte of the input at `offset`
+    pub fn input(&mut self, offset: u32) -> Label {
+        self.intern(alloc::vec![offset])
+    }
+
+    /// The label with the offsets of both `a` and `b`
+    pub fn union(&mut self, a: Label, b: Label) -> Label {
+        if a == b || b.is_clean() {
+            return a;
+        }
+        if a.is_clean() {
+            return b;
+        }
+        let key = (a.min(b), a.max(b));
+        if let Some(label) = self.unions.get(&key) {
+            return *label;
+        }
+        let (lhs, rhs) = (self.offsets(a), self.offsets(b));
+        let mut offsets = Vec::with_capacity(lhs.len() + rhs.len());
+        let (mut i, mut j) = (0, 0);
+        while i < lhs.len() && j < rhs.len() {
+            let next = lhs[i].min(rhs[j]);
+            i += (lhs[i] == next) as usize;
+            j += (rhs[j] == next) as usize;
+            offsets.push(next);
+        }
+        offsets.extend_from_slice(&lhs[i..]);
+        offsets.extend_from_slice(&rhs[j..]);
+        let label = self.intern(offsets);
+        self.unions.insert(key, label);
+        label
+    }
+
+    pub fn reg(&self, reg: Register) -> Label {
+        self.regs[reg as usize]
+    }
+
+    /// Set the label of `reg`, the zero register stays clean
+    pub fn set_reg(&mut self, reg: Register, label: Label) {
+        self.set_x(reg as u32, label);
+    }
+
+    pub fn freg(&self, reg: FloatRegister) -> Label {
+        self.fregs[reg as usize]
+    }
+
+    pub fn set_freg(&mut self, reg: FloatRegister, label: Label) {
+        self.fregs[reg as usize] = label;
+    }
+
+    #[inline(always)]
+    fn x(&self, idx: u32) -> Label {
+        self.regs[idx as usize & 0x1f]
+    }
+
+    #[inline(always)]
+    fn set_x(&mut self, idx: u32, label: Label) {
+        if idx != 0 {
+            self.regs[idx as usize & 0x1f] = label;
+        }
+    }
+
+    #[inline(always)]
+    fn f(&self, idx: u32) -> Label {
+        self.fregs[idx as usize & 0x1f]
+    }
+
+    #[inline(always)]
+    fn set_f(&mut self, idx: u32, label: Label) {
+        self.fregs[idx as usize & 0x1f] = label;
+    }
+
+    /// The union of the labels of the `size` bytes at `addr`
+    pub fn memory(&mut self, addr: u64, size: u64) -> Label {
+        let mut label = Label::CLEAN;
+        let end = addr.saturating_add(size);
+        // collect them first as the unions borrow mutably
+        let bytes: Vec<Label> = self.memory.range(addr..end).map(|(_, label)| *label).collect();
+        for byte in bytes {
+            label = self.union(label, byte);
+        }
+        label
+    }
+
+    /// Give `label` to the `size` bytes at `addr`
+    pub fn set_memory(&mut self, addr: u64, size: u64, label: Label) {
+        let end = addr.saturating_add(size);
+        if label.is_clean() {
+            let bytes: Vec<u64> = self.memory.range(addr..end).map(|(byte, _)| *byte).collect();
+            for byte in bytes {
+                self.memory.remove(&byte);
+            }
+        } else {
+            for byte in addr..end {
+                self.memory.insert(byte, label);
+            }
+        }
+    }
+
+    /// The `len` bytes at `addr` were read from the input at `offset`,
+    /// each gets the label of its own offset
+    pub fn taint_input(&mut self, addr: u64, offset: usize, len: usize) {
+        for i in 0..len {
+            let label = self.input((offset + i) as u32);
+            self.memory.insert(addr + i as u64, label);
+        }
+    }
+
+    /// The labels of the operands of the comparison at `pc`, accumulated
+    /// over all its executions, `None` if they were always clean
+    pub fn comparison(&self, pc: u64) -> Option<(Label, Label)> {
+        self.comparisons.get(&pc).copied()
+    }
+
+    /// The pcs of the tainted comparisons with the labels of their operands
+    pub fn comparisons(&self) -> impl Iterator<Item = (u64, Label, Label)> + '_ {
+        self.comparisons.iter().map(|(pc, (lhs, rhs))| (*pc, *lhs, *rhs))
+    }
+
+    /// The labels of the arguments of the syscall at `pc`, accumulated over
+    /// all its executions, `None` if they were always clean
+    pub fn syscall(&self, pc: u64) -> Option<&TaintedSyscall> {
+        self.syscalls.get(&pc)
+    }
+
+    pub fn syscalls(&self) -> impl Iterator<Item = (u64, &TaintedSyscall)> + '_ {
+        self.syscalls.iter().map(|(pc, syscall)| (*pc, syscall))
+    }
+
+    /// Record the comparison at `pc` if one of the operands is tainted
+    fn compare(&mut self, pc: u64, lhs: Label, rhs: Label) {
+        if lhs.is_clean() && rhs.is_clean() {
+            return;
+        }
+        let (old_lhs, old_rhs) = self.comparisons.get(&pc).copied().unwrap_or_default();
+        let labels = (self.union(old_lhs, lhs), self.union(old_rhs, rhs));
+        self.comparisons.insert(pc, labels);
+    }
+
+    /// Record the syscall `number` at `pc`, with the arguments in `a0..a5`,
+    /// if one of them is tainted
+    pub fn record_syscall(&mut self, pc: u64, number: u64) {
+        let args: [Label; 6] = core::array::from_fn(|i| self.regs[Register::A0 as usize + i]);
+        if args.iter().all(Label::is_clean) {
+            return;
+        }
+        let mut syscall = self.syscalls.get(&pc).copied()
+            .filter(|syscall| syscall.number == number)
+            .unwrap_or(TaintedSyscall { number, args: [Label::CLEAN; 6] });
+        for (old, new) in syscall.args.iter_mut().zip(args) {
+            *old = self.union(*old, new);
+        }
+        self.syscalls.insert(pc, syscall);
+    }
+
+    /// Propagate the labels through the raw instruction `inst` at `pc`,
+    /// which was just executed and did `access`, see
+    /// [`MemoryAccess::decode`]
+    pub fn propagate(&mut self, pc: u64, inst: u32, access: Option<MemoryAccess>) {
+        let bits = |lo: u32, len: u32| (inst >> lo) & ((1 << len) - 1);
+        // of the compressed ones, the others have it at bit 12
+        let funct3 = bits(13, 3);
+        match inst & 0b11 {
+            0b11 => {
+                let (rd, rs1, rs2, rs3) = (bits(7, 5), bits(15, 5), bits(20, 5), bits(27, 5));
+                let funct3 = bits(12, 3);
+                match inst & 0x7f {
+                    // lui, auipc, jal, jalr and the CSRs
+                    0b011_0111 | 0b001_0111 | 0b110_1111 | 0b110_0111 | 0b111_0011 => {
+                        self.set_x(rd, Label::CLEAN);
+                    }
+                    0b110_0011 => self.compare(pc, self.x(rs1), self.x(rs2)),
+                    0b000_0011 => {
+                        let label = self.load(access);
+                        self.set_x(rd, label);
+                    }
+                    0b000_0111 => {
+                        let label = self.load(access);
+                        self.set_f(rd, label);
+                    }
+                    0b010_0011 => self.store(access, self.x(rs2)),
+                    0b010_0111 => self.store(access, self.f(rs2)),
+                    0b001_0011 | 0b001_1011 => {
+                        // slti and sltiu
+                        if inst & 0x7f == 0b001_0011 && (funct3 == 2 || funct3 == 3) {
+                            self.compare(pc, self.x(rs1), Label::CLEAN);
+                        }
+                        self.set_x(rd, self.x(rs1));
+                    }
+                    0b011_0011 | 0b011_1011 => {
+                        // slt and sltu, not the M extension
+                        if inst & 0x7f == 0b011_0011 && bits(25, 7) == 0
+                            && (funct3 == 2 || funct3 == 3) {
+                            self.compare(pc, self.x(rs1), self.x(rs2));
+                        }
+                        let label = self.union(self.x(rs1), self.x(rs2));
+                        self.set_x(rd, label);
+                    }
+                    0b010_1111 => self.atomic(inst, rd, rs2, access),
+                    // the fused multiply-adds
+                    0b100_0011 | 0b100_0111 | 0b100_1011 | 0b100_1111 => {
+                        let label = self.union(self.f(rs1), self.f(rs2));
+                        let label = self.union(label, self.f(rs3));
+                        self.set_f(rd, label);
+                    }
+                    0b101_0011 => match bits(27, 5) {
+                        // comparisons
+                        0b10100 => {
+                            let label = self.union(self.f(rs1), self.f(rs2));
+                            self.set_x(rd, label);
+                        }
+                        // conversions to integers, moves and fclass
+                        0b11000 | 0b11100 => self.set_x(rd, self.f(rs1)),
+                        // conversions from integers and moves
+                        0b11010 | 0b11110 => self.set_f(rd, self.x(rs1)),
+                        // conversions between formats and sqrt
+                        0b01000 | 0b01011 => self.set_f(rd, self.f(rs1)),
+                        _ => {
+                            let label = self.union(self.f(rs1), self.f(rs2));
+                            self.set_f(rd, label);
+                        }
+                    },
+                    // fences
+                    _ => {}
+                }
+            }
+            0b00 => {
+                // rd' or rs2', in x8..x15 or f8..f15
+                let low = 8 + bits(2, 3);
+                match funct3 {
+                    // c.addi4spn
+                    0 => self.set_x(low, self.x(Register::Sp as u32)),
+                    1 => {
+                        let label = self.load(access);
+                        self.set_f(low, label);
+                    }
+                    2 | 3 => {
+                        let label = self.load(access);
+                        self.set_x(low, label);
+                    }
+                    5 => self.store(access, self.f(low)),
+                    6 | 7 => self.store(access, self.x(low)),
+                    _ => {}
+                }
+            }
+            0b01 => {
+                let (rd, low, high) = (bits(7, 5), 8 + bits(2, 3), 8 + bits(7, 3));
+                match funct3 {
+                    // c.li, and c.lui but not c.addi16sp
+                    2 => self.set_x(rd, Label::CLEAN),
+                    3 if rd != Register::Sp as u32 => self.set_x(rd, Label::CLEAN),
+                    // c.sub, c.xor, c.or, c.and, c.subw and c.addw
+                    4 if bits(10, 2) == 0b11 => {
+                        let label = self.union(self.x(high), self.x(low));
+                        self.set_x(high, label);
+                    }
+                    // c.beqz and c.bnez
+                    6 | 7 => self.compare(pc, self.x(high), Label::CLEAN),
+                    // the ones with an immediate keep the label of rd
+                    _ => {}
+                }
+            }
+            _ => {
+                let (rd, rs2) = (bits(7, 5), bits(2, 5));
+                match funct3 {
+                    1 => {
+                        let label = self.load(access);
+                        self.set_f(rd, label);
+                    }
+                    2 | 3 => {
+                        let label = self.load(access);
+                        self.set_x(rd, label);
+                    }
+                    4 => match (bits(12, 1), rd, rs2) {
+                        // c.jr and c.ebreak
+                        (0, _, 0) | (1, 0, 0) => {}
+                        // c.mv
+                        (0, _, _) => self.set_x(rd, self.x(rs2)),
+                        // c.jalr
+                        (_, _, 0) => self.set_x(Register::Ra as u32, Label::CLEAN),
+                        // c.add
+                        _ => {
+                            let label = self.union(self.x(rd), self.x(rs2));
+                            self.set_x(rd, label);
+                        }
+                    },
+                    5 => self.store(access, self.f(rs2)),
+                    6 | 7 => self.store(access, self.x(rs2)),
+                    // c.slli keeps the label of rd
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    #[inline]
+    fn load(&mut self, access: Option<MemoryAccess>) -> Label {
+        match access {
+            Some(access) => self.memory(access.addr, access.size),
+            None => Label::CLEAN,
+        }
+    }
+
+    #[inline]
+    fn store(&mut self, access: Option<MemoryAccess>, label: Label) {
+        if let Some(access) = access {
+            self.set_memory(access.addr, access.size, label);
+        }
+    }
+
+    /// `lr`, `sc` and the AMOs. A failed `sc` is treated as if it stored
+    fn atomic(&mut self, inst: u32, rd: u32, rs2: u32, access: Option<MemoryAccess>) {
+        let Some(access) = access.filter(|access| access.kind == AccessKind::Atomic) else {
+            return;
+        };
+        let old = self.memory(access.addr, access.size);
+        let value = self.x(rs2);
+        match inst >> 27 {
+            // lr
+            0b00010 => self.set_x(rd, old),
+            // sc
+            0b00011 => {
+                self.set_memory(access.addr, access.size, value);
+                self.set_x(rd, Label::CLEAN);
+            }
+            // amoswap
+            0b00001 => {
+                self.set_memory(access.addr, access.size, value);
+                self.set_x(rd, old);
+            }
+            _ => {
+                let new = self.union(old, value);
+                self.set_memory(access.addr, access.size, new);
+                self.set_x(rd, old);
+            }
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! strace-like tracing of the syscalls done by a [`LinuxEmu`].
+//!
+//! Every syscall is recorded as a [`SyscallEvent`] with its arguments decoded
+//! through the [`Mmu`] once it returned, so the buffers filled by the kernel
+//! (e.g. by `read`) hold the data the guest got. The events implement
+//! [`core::fmt::Display`] which renders them like `strace -i` does:
+//! ```text
+//! [0000000000010004] openat(AT_FDCWD, "/etc/ld.so.cache", 0x80000) = -1 ENOENT
+//! ```
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::fmt;
+use mmu::{Mmu, VirtAddr};
+use super::LinuxSyscall;
+use super::errno::errno_name;
+use super::vfs::AT_FDCWD;
+
+/// How many bytes of strings and buffers are kept, like `strace -s 32`
+pub const STRING_LIMIT: usize = 32;
+
+/// How many events are kept by default, the older ones are dropped
+pub const DEFAULT_EVENT_LIMIT: usize = 1024;
+
+/// A decoded syscall argument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyscallArg {
+    /// A number, printed in decimal
+    Int(u64),
+    /// Flags or sizes, printed in hex
+    Hex(u64),
+    /// A file descriptor
+    Fd(i32),
+    /// A pointer that wasn't decoded or that couldn't be read
+    Ptr(u64),
+    /// A null-terminated string
+    Str {
+        data: Vec<u8>,
+        /// The string is longer than [`STRING_LIMIT`]
+        truncated: bool,
+    },
+    /// A buffer with an explicit length
+    Buf {
+        data: Vec<u8>,
+        /// The buffer is longer than [`STRING_LIMIT`]
+        truncated: bool,
+    },
+}
+
+/// A syscall done by the guest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallEvent {
+    /// Address of the `ecall`
+    pub pc: u64,
+    /// Value of `a7`
+    pub number: u64,
+    /// The decoded arguments
+    pub args: Vec<SyscallArg>,
+    /// Value returned in `a0`, `None` if the syscall didn't return, like
+    /// `exit` or a syscall that stopped the emulation
+    pub ret: Option<u64>,
+}
+
+/// How to decode an argument
+#[derive(Debug, Clone, Copy)]
+enum ArgKind {
+    Int,
+    Hex,
+    Fd,
+    Ptr,
+    /// A null-terminated string
+    Str,
+    /// A buffer read by the kernel, its length is the argument with this index
+    InBuf(usize),
+    /// A buffer written by the kernel, its length is the return value
+    OutBuf,
+}
+
+/// The arguments of the syscalls, the ones not listed have their six
+/// argument registers printed as hex
+fn signature(syscall: LinuxSyscall) -> Option<&'static [ArgKind]> {
+    use ArgKind::*;
+    Some(match syscall {
+        LinuxSyscall::read | LinuxSyscall::pread64 => &[Fd, OutBuf, Int, Int],
+        LinuxSyscall::write => &[Fd, InBuf(2), Int],
+        LinuxSyscall::readv | LinuxSyscall::writev => &[Fd, Ptr, Int],
+        LinuxSyscall::openat => &[Fd, Str, Hex, Hex],
+        LinuxSyscall::close => &[Fd],
+        LinuxSyscall::lseek => &[Fd, Int, Int],
+        LinuxSyscall::newfstat => &[Fd, Ptr],
+        LinuxSyscall::newfstatat => &[Fd, Str, Ptr, Hex],
+        LinuxSyscall::faccessat => &[Fd, Str, Hex],
+        LinuxSyscall::readlinkat => &[Fd, Str, OutBuf, Int],
+        LinuxSyscall::ioctl => &[Fd, Hex, Ptr],
+        LinuxSyscall::ppoll_time32 => &[Ptr, Int, Ptr, Ptr],
+        LinuxSyscall::brk => &[Ptr],
+        LinuxSyscall::mmap => &[Ptr, Hex, Hex, Hex, Fd, Hex],
+        LinuxSyscall::munmap | LinuxSyscall::mprotect => &[Ptr, Hex, Hex],
+        LinuxSyscall::mremap => &[Ptr, Hex, Hex, Hex, Ptr],
+        LinuxSyscall::exit | LinuxSyscall::exit_group => &[Int],
+        LinuxSyscall::set_tid_address => &[Ptr],
+        LinuxSyscall::set_robust_list => &[Ptr, Int],
+        LinuxSyscall::rseq => &[Ptr, Hex, Hex, Hex],
+        LinuxSyscall::prlimit64 => &[Int, Int, Ptr, Ptr],
+        LinuxSyscall::getrandom => &[Ptr, Int, Hex],
+        LinuxSyscall::newuname => &[Ptr],
+        LinuxSyscall::rt_sigaction => &[Int, Ptr, Ptr, Int],
+        LinuxSyscall::rt_sigprocmask => &[Int, Ptr, Ptr, Int],
+        LinuxSyscall::sigaltstack => &[Ptr, Ptr],
+        LinuxSyscall::getpid | LinuxSyscall::gettid => &[],
+        _ => return None,
+    })
+}
+
+/// Read at most [`STRING_LIMIT`] bytes at `addr`, stopping at the null
+/// terminator if `cstr`
+fn read_bytes(mem: &mut Mmu, addr: u64, len: usize, cstr: bool) 
+    -> Option<(Vec<u8>, bool)> {
+    let mut data = Vec::new();
+    for i in 0..len.min(STRING_LIMIT) {
+        let byte: u8 = mem.read(VirtAddr(addr as usize + i)).ok()?;
+        if cstr && byte == 0 {
+            return Some((data, false));
+        }
+        data.push(byte);
+    }
+    if cstr {
+        // check if the string ends right after the limit
+        let next = mem.read::<u8>(VirtAddr(addr as usize + STRING_LIMIT)).ok();
+        return Some((data, next != Some(0)));
+    }
+    Some((data, len > STRING_LIMIT))
+}
+
+impl SyscallEvent {
+    /// Decode the syscall `number` with arguments `args` that returned `ret`
+    pub fn new(mem: &mut Mmu, pc: u64, number: u64, args: [u64; 6], ret: Option<u64>)
+        -> Self {
+        let signature = LinuxSyscall::try_from(number).ok().and_then(signature);
+        let args = match signature {
+            None => args.iter().map(|arg| SyscallArg::Hex(*arg)).collect(),
+            Some(signature) => signature.iter().zip(args).map(|(kind, arg)| {
+                match kind {
+                    ArgKind::Int => SyscallArg::Int(arg),
+                    ArgKind::Hex => SyscallArg::Hex(arg),
+                    ArgKind::Fd => SyscallArg::Fd(arg as i32),
+                    ArgKind::Ptr => SyscallArg::Ptr(arg),
+                    ArgKind::Str => read_bytes(mem, arg, STRING_LIMIT, true)
+                        .map(|(data, truncated)| SyscallArg::Str { data, truncated })
+                        .unwrap_or(SyscallArg::Ptr(arg)),
+                    ArgKind::InBuf(_) | ArgKind::OutBuf => {
+                        let len = match kind {
+                            ArgKind::InBuf(idx) => Some(args[*idx]),
+                            // errors are between -4095 and -1
+                            _ => ret.filter(|ret| *ret < (-4095_i64) as u64),
+                        };
+                        len.and_then(|len| read_bytes(mem, arg, len as usize, false))
+                            .map(|(data, truncated)| SyscallArg::Buf { data, truncated })
+                            .unwrap_or(SyscallArg::Ptr(arg))
+                    }
+                }
+            }).collect(),
+        };
+        SyscallEvent { pc, number, args, ret }
+    }
+
+    /// The syscall, if the number is valid
+    pub fn syscall(&self) -> Option<LinuxSyscall> {
+        LinuxSyscall::try_from(self.number).ok()
+    }
+}
+
+/// Print `data` as a C string literal, escaping the non-printable bytes
+fn fmt_escaped(f: &mut fmt::Formatter<'_>, data: &[u8], truncated: bool) -> fmt::Result {
+    f.write_str("\"")?;
+    for byte in data {
+        match byte {
+            b'\n' => f.write_str("\\n")?,
+            b'\t' => f.write_str("\\t")?,
+            b'\r' => f.write_str("\\r")?,
+            b'"' => f.write_str("\\\"")?,
+            b'\\' => f.write_str("\\\\")?,
+            0x20..=0x7e => write!(f, "{}", *byte as char)?,
+            _ => write!(f, "\\x{:02x}", byte)?,
+        }
+    }
+    f.write_str("\"")?;
+    if truncated {
+        f.write_str("...")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for SyscallArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyscallArg::Int(value) => write!(f, "{}", *value as i64),
+            SyscallArg::Hex(value) => write!(f, "{:#x}", value),
+            SyscallArg::Fd(AT_FDCWD) => f.write_str("AT_FDCWD"),
+            SyscallArg::Fd(fd) => write!(f, "{}", fd),
+            SyscallArg::Ptr(0) => f.write_str("NULL"),
+            SyscallArg::Ptr(ptr) => write!(f, "{:#x}", ptr),
+            SyscallArg::Str { data, truncated }
+            | SyscallArg::Buf { data, truncated } => fmt_escaped(f, data, *truncated),
+        }
+    }
+}
+
+impl fmt::Display for SyscallEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{:016x}] ", self.pc)?;
+        match self.syscall() {
+            Some(syscall) => write!(f, "{:?}(", syscall)?,
+            None => write!(f, "syscall_{:#x}(", self.number)?,
+        }
+        for (i, arg) in self.args.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", arg)?;
+        }
+        f.write_str(") = ")?;
+        match self.ret {
+            None => f.write_str("?"),
+            Some(ret) if ret >= (-4095_i64) as u64 => {
+                let errno = (ret as i64).wrapping_neg() as u64;
+                match errno_name(errno) {
+                    Some(name) => write!(f, "-1 {}", name),
+                    None => write!(f, "-1 errno {}", errno),
+                }
+            }
+            Some(ret) if ret > 0xffff => write!(f, "{:#x}", ret),
+            Some(ret) => write!(f, "{}", ret),
+        }
+    }
+}
+
+/// Records the last syscalls of an emulator
+#[derive(Debug, Clone)]
+pub struct SyscallTracer {
+    /// The recorded events, from the oldest
+    pub events: VecDeque<SyscallEvent>,
+    /// How many events to keep
+    pub limit: usize,
+}
+
+impl Default for SyscallTracer {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_LIMIT)
+    }
+}
+
+impl SyscallTracer {
+    /// A tracer that keeps the last `limit` events
+    pub fn new(limit: usize) -> Self {
+        SyscallTracer {
+            events: VecDeque::new(),
+            limit,
+        }
+    }
+
+    /// Record `event`, dropping the oldest one if the tracer is full
+    pub fn push(&mut self, event: SyscallEvent) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.events.len() == self.limit {
+            self.events.pop_front();
+        }
+        #[cfg(feature="dbg_prints")]
+        println!("{}", event);
+        self.events.push_back(event);
+    }
+
+    /// Drop all the events
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+
+    /// Restore the events of `other` reusing the allocation
+    pub fn reset(&mut self, other: &Self) {
+        self.events.clone_from(&other.events);
+        self.limit = other.limit;
+    }
+}
+
+impl fmt::Display for SyscallTracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
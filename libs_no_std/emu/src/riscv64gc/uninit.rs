@@ -0,0 +1,132 @@
+//! Provenance of the uninitialized memory.
+//!
+//! The bytes with [`PermField::ReadAfterWrite`] can't be read before they
+//! are written, but the fault alone doesn't say where they came from. The
+//! [`UninitTracker`] records the origin of the regions that can hold such
+//! bytes, the newer regions replacing the older ones where they overlap:
+//! - the stack frames, when an instruction lowers `sp`;
+//! - the program break raised by `brk`;
+//! - the mappings created or grown by `mmap` and `mremap`;
+//! - the chunks of the [`HeapSanitizer`](super::HeapSanitizer).
+//!
+//! [`LinuxEmu`](super::LinuxEmu) turns the reads of uninitialized bytes
+//! into a [`UninitRead`] with the origin of the first one, or, with
+//! [`UninitTracker::keep_going`], logs them and continues as if they were
+//! initialized with their current content. The regions created before the
+//! tracker, e.g. by the loader, have no origin.
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use mmu::{Mmu, MmuError, PermField, VirtAddr};
+
+/// What created a region of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UninitOrigin {
+    /// The stack frame allocated by the instruction at `pc` lowering `sp`
+    StackFrame { pc: u64 },
+    /// The program break raised by the `brk` at `pc`
+    Brk { pc: u64 },
+    /// The mapping created or grown by the `mmap` or `mremap` at `pc`
+    Mmap { pc: u64 },
+    /// The chunk of the heap sanitizer allocated from `pc`
+    HeapAlloc { pc: u64 },
+}
+
+/// The bytes `start..end` and what created them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRegion {
+    pub start: u64,
+    pub end: u64,
+    pub origin: UninitOrigin,
+}
+
+/// A read of bytes never written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UninitRead {
+    /// The first uninitialized byte read
+    pub addr: u64,
+    pub pc: u64,
+    /// The region of `addr`, if it was recorded
+    pub region: Option<UninitRegion>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UninitTracker {
+    /// Log the reads in `reads` and continue instead of stopping
+    pub keep_going: bool,
+    /// The reads found with `keep_going`, each byte is reported once
+    pub reads: Vec<UninitRead>,
+    /// Non-overlapping regions by start, with their end and origin
+    regions: BTreeMap<u64, (u64, UninitOrigin)>,
+}
+
+impl UninitTracker {
+    /// Record that `origin` created the bytes `start..end`
+    pub fn record(&mut self, start: u64, end: u64, origin: UninitOrigin) {
+        if start >= end {
+            return;
+        }
+        // cut the region that starts before and overlaps
+        if let Some((&prev_start, &(prev_end, prev_origin))) = self.regions.range(..start).next_back() {
+            if prev_end > start {
+                self.regions.insert(prev_start, (start, prev_origin));
+                if prev_end > end {
+                    self.regions.insert(end, (prev_end, prev_origin));
+                }
+            }
+        }
+        // and the ones that start inside
+        let inside: Vec<u64> = self.regions.range(start..end).map(|(start, _)| *start).collect();
+        for inner in inside {
+            let (inner_end, inner_origin) = self.regions.remove(&inner).unwrap();
+            if inner_end > end {
+                self.regions.insert(end, (inner_end, inner_origin));
+            }
+        }
+        self.regions.insert(start, (end, origin));
+    }
+
+    /// The region that contains `addr`, if any
+    pub fn region(&self, addr: u64) -> Option<UninitRegion> {
+        let (&start, &(end, origin)) = self.regions.range(..=addr).next_back()?;
+        (addr < end).then_some(UninitRegion { start, end, origin })
+    }
+
+    /// Everything recorded, sorted by address
+    pub fn regions(&self) -> impl Iterator<Item = UninitRegion> + '_ {
+        self.regions.iter()
+            .map(|(&start, &(end, origin))| UninitRegion { start, end, origin })
+    }
+
+    /// The read of uninitialized bytes that caused `error` at `pc`, if it
+    /// was one
+    pub fn read(&self, error: &MmuError, pc: u64) -> Option<UninitRead> {
+        let MmuError::PermissionsFault { is_read: true, virtual_address, permissions, size } = error else {
+            return None;
+        };
+        let idx = permissions[..(*size).min(8)].iter().position(|perm| {
+            perm.is_superset_of(PermField::ReadAfterWrite)
+                && !perm.is_superset_of(PermField::Read)
+        })?;
+        let addr = (virtual_address.0 + idx) as u64;
+        // the fetch of the instruction itself
+        if virtual_address.0 as u64 == pc {
+            return None;
+        }
+        Some(UninitRead { addr, pc, region: self.region(addr) })
+    }
+
+    /// Make readable the uninitialized bytes among the `size` at `addr`,
+    /// with their current content
+    pub fn initialize(mem: &mut Mmu, addr: u64, size: usize) -> Result<(), MmuError> {
+        let (start, segment) = mem.resolve_segment(VirtAddr(addr as usize))?;
+        let offset = addr as usize - start.0;
+        let range = offset..(offset + size).min(segment.len());
+        for perm in &mut segment.permissions[range.clone()] {
+            if perm.is_superset_of(PermField::ReadAfterWrite) {
+                *perm |= PermField::Read;
+            }
+        }
+        segment.dirty_range(range);
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+//! Deterministic in-memory filesystem of the emulated process, the guest can
+//! only read the files registered by the user while the writes to stdout and
+//! stderr are captured into buffers.
+//!
+//! The fuzz input can be exposed as a file, at a path chosen with
+//! [`Vfs::set_input_path`], or directly on an fd with [`Vfs::set_input_fd`].
+//! Its content is replaced for every case with [`Vfs::set_input`].
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use super::errno::*;
+
+/// Use the current working directory in the `*at` syscalls
+pub const AT_FDCWD: i32 = -100;
+
+/// Mask of the access mode in the `openat` flags
+pub const O_ACCMODE: u64 = 0o3;
+/// Open for reading only
+pub const O_RDONLY: u64 = 0o0;
+
+/// `lseek` from the start of the file
+pub const SEEK_SET: u64 = 0;
+/// `lseek` from the current offset
+pub const SEEK_CUR: u64 = 1;
+/// `lseek` from the end of the file
+pub const SEEK_END: u64 = 2;
+
+/// An open file description
+#[derive(Debug, Clone)]
+pub enum FileDescription {
+    /// Standard input, always at end of file
+    Stdin,
+    /// Standard output, captured in [`Vfs::stdout`]
+    Stdout,
+    /// Standard error, captured in [`Vfs::stderr`]
+    Stderr,
+    /// A registered read-only file
+    File {
+        content: Arc<Vec<u8>>,
+        offset: usize,
+    },
+    /// The fuzz input, see [`Vfs::set_input`]
+    Input {
+        offset: usize,
+    },
+}
+
+/// Kind of an entry, used to fill `st_mode` in the `stat` syscalls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// A character device, like the terminal
+    CharDevice,
+    /// A regular file of the given size
+    Regular(usize),
+}
+
+/// The filesystem and the fd table of a process.
+///
+/// The registered files are shared between the forks of an emulator so
+/// [`Vfs::reset`] only has to restore the fd table and the output buffers.
+#[derive(Debug, Clone)]
+pub struct Vfs {
+    /// Files the guest can open, indexed by absolute path
+    pub files: Arc<BTreeMap<Vec<u8>, Arc<Vec<u8>>>>,
+    /// Symbolic links the guest can read, e.g. `/proc/self/exe`
+    pub links: Arc<BTreeMap<Vec<u8>, Vec<u8>>>,
+    /// Path at which the guest can open the fuzz input
+    pub input_path: Option<Vec<u8>>,
+    /// Content of the fuzz input
+    pub input: Vec<u8>,
+    /// Open file descriptors, indexed by fd number
+    pub fds: Vec<Option<FileDescription>>,
+    /// What the guest wrote to fd 1
+    pub stdout: Vec<u8>,
+    /// What the guest wrote to fd 2
+    pub stderr: Vec<u8>,
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Vfs {
+    /// A new filesystem with just the standard file descriptors
+    pub fn new() -> Self {
+        Vfs {
+            files: Arc::new(BTreeMap::new()),
+            links: Arc::new(BTreeMap::new()),
+            input_path: None,
+            input: Vec::new(),
+            fds: alloc::vec![
+                Some(FileDescription::Stdin),
+                Some(FileDescription::Stdout),
+                Some(FileDescription::Stderr),
+            ],
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+
+    /// Restore the state of `other`, which this filesystem was forked from.
+    /// The fuzz input is kept, so it can be set before or after the reset.
+    pub fn reset(&mut self, other: &Self) {
+        // these are just reference counts unless a file was registered
+        // after the fork
+        if !Arc::ptr_eq(&self.files, &other.files) {
+            self.files = other.files.clone();
+        }
+        if !Arc::ptr_eq(&self.links, &other.links) {
+            self.links = other.links.clone();
+        }
+        self.input_path.clone_from(&other.input_path);
+        // reuse the allocations
+        self.fds.clone_from(&other.fds);
+        self.stdout.clear();
+        self.stdout.extend_from_slice(&other.stdout);
+        self.stderr.clear();
+        self.stderr.extend_from_slice(&other.stderr);
+    }
+
+    /// Register a file at `path` with content `content`
+    pub fn add_file(&mut self, path: &[u8], content: Vec<u8>) {
+        Arc::make_mut(&mut self.files).insert(path.to_vec(), Arc::new(content));
+    }
+
+    /// Register a symbolic link at `path` pointing to `target`
+    pub fn add_link(&mut self, path: &[u8], target: &[u8]) {
+        Arc::make_mut(&mut self.links).insert(path.to_vec(), target.to_vec());
+    }
+
+    /// Make the fuzz input readable at `path`
+    pub fn set_input_path(&mut self, path: &[u8]) {
+        self.input_path = Some(path.to_vec());
+    }
+
+    /// Open the fuzz input on `fd`, replacing whatever was there.
+    /// Use fd 0 for targets that read their input from stdin.
+    pub fn set_input_fd(&mut self, fd: i32) {
+        let fd = usize::try_from(fd).expect("the fd must be positive");
+        if self.fds.len() <= fd {
+            self.fds.resize(fd + 1, None);
+        }
+        self.fds[fd] = Some(FileDescription::Input { offset: 0 });
+    }
+
+    /// Set the content of the fuzz input
+    pub fn set_input(&mut self, input: &[u8]) {
+        self.input.clear();
+        self.input.extend_from_slice(input);
+    }
+
+    /// Get the description of an open fd from the table `fds`, this doesn't
+    /// borrow the whole filesystem
+    fn slot(fds: &mut [Option<FileDescription>], fd: i32)
+        -> Result<&mut FileDescription, u64> {
+        usize::try_from(fd).ok()
+            .and_then(|fd| fds.get_mut(fd))
+            .and_then(|desc| desc.as_mut())
+            .ok_or(EBADF)
+    }
+
+    /// Get the description of an open fd
+    pub fn get(&mut self, fd: i32) -> Result<&mut FileDescription, u64> {
+        Self::slot(&mut self.fds, fd)
+    }
+
+    /// Open the file at `path` and return the lowest free fd
+    pub fn open(&mut self, path: &[u8], flags: u64) -> Result<i32, u64> {
+        let desc = if self.input_path.as_deref() == Some(path) {
+            FileDescription::Input { offset: 0 }
+        } else {
+            let content = self.files.get(path).ok_or(ENOENT)?.clone();
+            FileDescription::File { content, offset: 0 }
+        };
+        // all the files are read-only
+        if flags & O_ACCMODE != O_RDONLY {
+            return Err(EACCES);
+        }
+        let fd = match self.fds.iter().position(Option::is_none) {
+            Some(fd) => {
+                self.fds[fd] = Some(desc);
+                fd
+            }
+            None => {
+                self.fds.push(Some(desc));
+                self.fds.len() - 1
+            }
+        };
+        Ok(fd as i32)
+    }
+
+    /// Close the fd `fd`
+    pub fn close(&mut self, fd: i32) -> Result<(), u64> {
+        self.get(fd)?;
+        self.fds[fd as usize] = None;
+        Ok(())
+    }
+
+    /// Read at most `count` bytes from `fd` at `offset`, or at the offset of
+    /// the fd advancing it if `offset` is `None`
+    pub fn read(&mut self, fd: i32, count: usize, offset: Option<usize>)
+        -> Result<&[u8], u64> {
+        let (content, fd_offset): (&[u8], _) = match Self::slot(&mut self.fds, fd)? {
+            FileDescription::Stdin => return Ok(&[]),
+            FileDescription::Stdout | FileDescription::Stderr => return Err(EBADF),
+            FileDescription::File { content, offset } => (content, offset),
+            FileDescription::Input { offset } => (&self.input, offset),
+        };
+        let position = offset.unwrap_or(*fd_offset);
+        let start = position.min(content.len());
+        let end = start.saturating_add(count).min(content.len());
+        if offset.is_none() {
+            // an offset past the end is kept
+            *fd_offset = position + (end - start);
+        }
+        Ok(&content[start..end])
+    }
+
+    /// Offset in the fuzz input of the next byte read from `fd`, at `offset`
+    /// or at the offset of the fd, `None` if `fd` is not the input
+    pub fn input_offset(&mut self, fd: i32, offset: Option<usize>) -> Option<usize> {
+        match self.get(fd) {
+            Ok(FileDescription::Input { offset: fd_offset }) => Some(offset.unwrap_or(*fd_offset)),
+            _ => None,
+        }
+    }
+
+    /// Write `data` to `fd` and return the number of written bytes
+    pub fn write(&mut self, fd: i32, data: &[u8]) -> Result<usize, u64> {
+        match self.get(fd)? {
+            FileDescription::Stdout => self.stdout.extend_from_slice(data),
+            FileDescription::Stderr => self.stderr.extend_from_slice(data),
+            _ => return Err(EBADF),
+        }
+        Ok(data.len())
+    }
+
+    /// Move the offset of `fd` and return the new one
+    pub fn lseek(&mut self, fd: i32, offset: i64, whence: u64) -> Result<usize, u64> {
+        let (len, fd_offset) = match Self::slot(&mut self.fds, fd)? {
+            FileDescription::File { content, offset } => (content.len(), offset),
+            FileDescription::Input { offset } => (self.input.len(), offset),
+            _ => return Err(ESPIPE),
+        };
+        let base = match whence {
+            SEEK_SET => 0,
+            SEEK_CUR => *fd_offset,
+            SEEK_END => len,
+            _ => return Err(EINVAL),
+        };
+        // seeking past the end is fine, the reads will just return nothing
+        let new_offset = (base as i64).checked_add(offset)
+            .filter(|new_offset| *new_offset >= 0)
+            .ok_or(EINVAL)?;
+        *fd_offset = new_offset as usize;
+        Ok(new_offset as usize)
+    }
+
+    /// Content of the file open on `fd`, used for file-backed mappings
+    pub fn content(&self, fd: i32) -> Result<&[u8], u64> {
+        let desc = usize::try_from(fd).ok()
+            .and_then(|fd| self.fds.get(fd))
+            .and_then(|desc| desc.as_ref())
+            .ok_or(EBADF)?;
+        match desc {
+            FileDescription::File { content, .. } => Ok(content),
+            FileDescription::Input { .. } => Ok(&self.input),
+            _ => Err(EACCES),
+        }
+    }
+
+    /// Kind of the file open on `fd`
+    pub fn fd_kind(&mut self, fd: i32) -> Result<FileKind, u64> {
+        Ok(match self.get(fd)? {
+            FileDescription::File { content, .. } => FileKind::Regular(content.len()),
+            FileDescription::Input { .. } => FileKind::Regular(self.input.len()),
+            _ => FileKind::CharDevice,
+        })
+    }
+
+    /// Kind of the file at `path`
+    pub fn path_kind(&self, path: &[u8]) -> Result<FileKind, u64> {
+        if self.input_path.as_deref() == Some(path) {
+            return Ok(FileKind::Regular(self.input.len()));
+        }
+        self.files.get(path)
+            .map(|content| FileKind::Regular(content.len()))
+            .ok_or(ENOENT)
+    }
+
+    /// Check if something exists at `path`
+    pub fn exists(&self, path: &[u8]) -> bool {
+        self.files.contains_key(path) || self.links.contains_key(path)
+            || self.input_path.as_deref() == Some(path)
+    }
+
+    /// Target of the symbolic link at `path`
+    pub fn readlink(&self, path: &[u8]) -> Result<&[u8], u64> {
+        match self.links.get(path) {
+            Some(target) => Ok(target),
+            // exists but it's not a link
+            None if self.exists(path) => Err(EINVAL),
+            None => Err(ENOENT),
+        }
+    }
+}
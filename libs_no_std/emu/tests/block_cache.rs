@@ -0,0 +1,126 @@
+//! Tests of the cache of decoded blocks of [`CoreEmu`]
+use emu::riscv64gc::*;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+fn addi_a0(imm: i32) -> u32 {
+    AssemblerRV64GC.addi(Register::A0, Register::A0, imm).unwrap()
+}
+
+/// A core with writable code that stores `t0` at `t1`, increments `a0` and
+/// stops on a breakpoint, with a second block at `CODE + 0x10`
+fn self_modifying_core() -> CoreEmu {
+    let code = [
+        AssemblerRV64GC.sw(Register::T1, Register::T0, 0).unwrap(),
+        addi_a0(1),
+        AssemblerRV64GC.ebreak().unwrap(),
+        AssemblerRV64GC.fence_i().unwrap(),
+        addi_a0(0x10),
+        AssemblerRV64GC.ebreak().unwrap(),
+    ];
+    let code: Vec<u8> = code.iter().copied().flat_map(u32::to_le_bytes).collect();
+    let mut core = core_with_code(
+        &code, PermField::Read | PermField::Write | PermField::Executable,
+    );
+    core.mem.allocate_segment(
+        Some(VirtAddr(DATA as usize)), 0x1000,
+        PermField::Read | PermField::Write,
+    ).unwrap();
+    core.write_reg(Register::T1, DATA);
+    core
+}
+
+/// Run from `pc` with `a0` cleared and return it
+fn run_at(core: &mut CoreEmu, pc: u64) -> u64 {
+    core.pc = pc;
+    core.write_reg(Register::A0, 0);
+    assert!(matches!(core.run(), CoreEmuError::Breakpoint));
+    core.read_reg(Register::A0)
+}
+
+#[test]
+fn test_blocks_are_reused() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.addi(Register::S0, Register::Zero, 10))
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::A0, 3))
+        .inst(AssemblerRV64GC.addi(Register::S0, Register::S0, -1))
+        .inst(AssemblerRV64GC.bne(Register::S0, Register::Zero, -8))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::A0), 30);
+    assert_eq!(core.instructions_executed, 32);
+    // the entry, the loop body and the final ecall
+    assert_eq!(core.block_cache.len(), 3);
+}
+
+#[test]
+fn test_written_code() {
+    let mut core = self_modifying_core();
+    assert_eq!(run_at(&mut core, CODE), 1);
+    assert_eq!(core.block_cache.len(), 1);
+
+    // written by the harness
+    unsafe{core.mem.write_from_slice(VirtAddr(CODE as usize + 4), &addi_a0(2).to_le_bytes())}.unwrap();
+    assert_eq!(run_at(&mut core, CODE), 2);
+
+    // written by the guest in the middle of the block
+    let reference = core.fork();
+    core.write_reg(Register::T0, addi_a0(3) as u64);
+    core.write_reg(Register::T1, CODE + 4);
+    assert_eq!(run_at(&mut core, CODE), 3);
+
+    // restoring the code drops the blocks decoded from the modified one
+    core.reset(&reference);
+    assert_eq!(run_at(&mut core, CODE + 4), 2);
+}
+
+#[test]
+fn test_written_again_after_reset() {
+    let mut core = self_modifying_core();
+    let reference = core.fork();
+    let write = |core: &mut CoreEmu, imm| {
+        unsafe{core.mem.write_from_slice(VirtAddr(CODE as usize + 4), &addi_a0(imm).to_le_bytes())}.unwrap();
+    };
+
+    write(&mut core, 3);
+    assert_eq!(run_at(&mut core, CODE + 4), 3);
+    // the same version as the first write, but different code
+    core.reset(&reference);
+    write(&mut core, 4);
+    assert_eq!(run_at(&mut core, CODE + 4), 4);
+}
+
+#[test]
+fn test_unmapped_and_protected_code() {
+    let mut core = self_modifying_core();
+    assert_eq!(run_at(&mut core, CODE), 1);
+
+    core.mem.mprotect(VirtAddr(CODE as usize), 0x1000, PermField::Read.into()).unwrap();
+    core.pc = CODE;
+    assert!(matches!(core.run(), CoreEmuError::MmuError(_)));
+
+    core.mem.munmap(VirtAddr(CODE as usize), 0x1000).unwrap();
+    core.pc = CODE;
+    assert!(matches!(core.run(), CoreEmuError::MmuError(_)));
+}
+
+#[test]
+fn test_forks_and_fence_i() {
+    let mut core = self_modifying_core();
+    assert_eq!(run_at(&mut core, CODE), 1);
+
+    // the fork sees the blocks of its parent, but not the other way around
+    let mut fork = core.fork();
+    assert_eq!(fork.block_cache.len(), 1);
+    assert_eq!(run_at(&mut fork, CODE + 0x10), 0x10);
+    assert_eq!(fork.block_cache.len(), 2);
+    assert_eq!(core.block_cache.len(), 1);
+
+    // fence.i drops everything, the block it's in is cached again
+    fork.pc = CODE + 0xc;
+    assert!(matches!(fork.run(), CoreEmuError::Yield));
+    assert_eq!(fork.block_cache.len(), 1);
+    assert_eq!(core.block_cache.len(), 1);
+    assert_eq!(run_at(&mut core, CODE), 1);
+}
@@ -0,0 +1,154 @@
+//! Tests of the shadow call stack of [`CoreEmu`] and of the symbolized
+//! backtraces
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+
+mod common;
+use common::*;
+
+/// The call stack as (call site, function, return address) relative to the
+/// start of the code
+fn frames(core: &CoreEmu) -> Vec<(u64, u64, u64)> {
+    core.call_stack.as_ref().unwrap().frames().iter()
+        .map(|frame| (frame.call_site - CODE, frame.function - CODE, frame.return_address - CODE))
+        .collect()
+}
+
+fn relative(pcs: Vec<u64>) -> Vec<u64> {
+    pcs.into_iter().map(|pc| pc - CODE).collect()
+}
+
+/// `main` calls `f` with `jal`, which calls `g` with `c.jalr`, which stops
+/// with an `ecall` and returns with `c.jr`
+fn nested_program() -> CoreEmu {
+    let mut core = Program::default()
+        // 0: main
+        .inst(AssemblerRV64GC.jal(Ra, 8))
+        .inst(AssemblerRV64GC.ecall())
+        // 8: f
+        .inst(AssemblerRV64GC.addi(S0, Ra, 0))
+        .inst(AssemblerRV64GC.auipc(T0, 0))
+        .inst(AssemblerRV64GC.addi(T0, T0, 18))
+        .c_inst(AssemblerRV64GC.c_jalr(T0))
+        .inst(AssemblerRV64GC.addi(Ra, S0, 0))
+        .c_inst(AssemblerRV64GC.c_jr(Ra))
+        .c_inst(AssemblerRV64GC.c_nop())
+        // 30: g
+        .inst(AssemblerRV64GC.ecall())
+        .c_inst(AssemblerRV64GC.c_jr(Ra))
+        .build();
+    core.call_stack = Some(CallStack::default());
+    core
+}
+
+#[test]
+fn test_calls_and_returns() {
+    let mut core = nested_program();
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert_eq!(core.pc, CODE + 34);
+    assert_eq!(frames(&core), [(0, 8, 4), (20, 30, 22)]);
+    assert_eq!(relative(core.backtrace()), [34, 20, 0]);
+    assert_eq!(core.call_stack.as_ref().unwrap().depth(), 2);
+
+    // both return
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert_eq!(core.pc, CODE + 8);
+    assert!(frames(&core).is_empty());
+    assert_eq!(relative(core.backtrace()), [8]);
+
+    // without the call stack there are no callers
+    let mut core = nested_program();
+    core.call_stack = None;
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert_eq!(relative(core.backtrace()), [34]);
+}
+
+#[test]
+fn test_unwinding() {
+    let mut core = Program::default()
+        // 0: a return nothing called, ignored
+        .inst(AssemblerRV64GC.auipc(Ra, 0))
+        .inst(AssemblerRV64GC.addi(Ra, Ra, 10))
+        .c_inst(AssemblerRV64GC.c_jr(Ra))
+        // 10: call f with jalr
+        .inst(AssemblerRV64GC.auipc(T0, 0))
+        .inst(AssemblerRV64GC.jalr(Ra, T0, 12))
+        .inst(AssemblerRV64GC.ecall())
+        // 22: f calls g
+        .inst(AssemblerRV64GC.jal(Ra, 8))
+        .c_inst(AssemblerRV64GC.c_nop())
+        .c_inst(AssemblerRV64GC.c_nop())
+        // 30: g returns straight to the caller of f, like a longjmp
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.auipc(Ra, 0))
+        .inst(AssemblerRV64GC.addi(Ra, Ra, -16))
+        .c_inst(AssemblerRV64GC.c_jr(Ra))
+        .build();
+    core.call_stack = Some(CallStack::default());
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert_eq!(frames(&core), [(14, 22, 18), (22, 30, 26)]);
+
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert_eq!(core.pc, CODE + 22);
+    assert!(frames(&core).is_empty());
+}
+
+#[test]
+fn test_forks() {
+    let mut core = nested_program();
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    let snapshot = core.fork();
+    assert_eq!(frames(&snapshot), [(0, 8, 4), (20, 30, 22)]);
+
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert!(frames(&core).is_empty());
+    core.reset(&snapshot);
+    assert_eq!(frames(&core), [(0, 8, 4), (20, 30, 22)]);
+}
+
+#[test]
+fn test_max_depth() {
+    let mut call_stack = CallStack::default();
+    for idx in 0..MAX_CALL_DEPTH as u64 + 2 {
+        call_stack.call(idx, 0, idx + 4);
+    }
+    assert_eq!(call_stack.frames().len(), MAX_CALL_DEPTH);
+    assert_eq!(call_stack.depth(), MAX_CALL_DEPTH + 2);
+
+    // the calls that were only counted return first
+    call_stack.ret(0);
+    call_stack.ret(0);
+    assert_eq!(call_stack.depth(), MAX_CALL_DEPTH);
+    call_stack.ret(4);
+    assert_eq!(call_stack.depth(), 0);
+}
+
+#[test]
+fn test_symbols() {
+    let mut symbols = Symbols::default();
+    symbols.extend([
+        Symbol { addr: CODE + 30, size: 6, name: "g".into() },
+        Symbol { addr: CODE, size: 0, name: "main".into() },
+    ]);
+    symbols.add(Symbol { addr: CODE + 8, size: 22, name: "f".into() });
+    assert_eq!(symbols.len(), 3);
+
+    let lookup = |addr| symbols.lookup(addr).map(|(symbol, offset)| (symbol.name.as_str(), offset));
+    assert_eq!(lookup(CODE - 1), None);
+    assert_eq!(lookup(CODE + 6), Some(("main", 6)));
+    assert_eq!(lookup(CODE + 20), Some(("f", 12)));
+    assert_eq!(lookup(CODE + 30), Some(("g", 0)));
+    assert_eq!(lookup(CODE + 34), Some(("g", 4)));
+    // past the end of g
+    assert_eq!(lookup(CODE + 36), None);
+
+    let mut emu = LinuxEmu::new(mmu::Mmu::new());
+    emu.core = nested_program();
+    emu.symbols = symbols.into();
+    assert!(matches!(emu.core.run(), CoreEmuError::Syscall));
+    assert_eq!(emu.backtrace().to_string(), concat!(
+        "#0   0000000000010022 g+0x4\n",
+        "#1   0000000000010014 f+0xc\n",
+        "#2   0000000000010000 main\n",
+    ));
+}
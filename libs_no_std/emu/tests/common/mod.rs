@@ -0,0 +1,116 @@
+//! Helpers shared by the integration tests, a [`Program`] is assembled with
+//! [`AssemblerRV64GC`] and terminated by an `ecall`
+#![allow(dead_code)]
+use emu::riscv64gc::*;
+use emu::riscv64gc::mman::*;
+use mmu::{Mmu, Perm, PermField, VirtAddr};
+
+pub const CODE: u64 = 0x1_0000;
+pub const DATA: u64 = 0x2_0000;
+pub const BRK: u64 = 0x4_0000;
+
+#[derive(Default)]
+pub struct Program {
+    pub code: Vec<u8>,
+}
+
+impl Program {
+    pub fn inst(&mut self, inst: Result<u32, &str>) -> &mut Self {
+        self.code.extend_from_slice(&inst.unwrap().to_le_bytes());
+        self
+    }
+
+    pub fn c_inst(&mut self, inst: Result<u16, &str>) -> &mut Self {
+        self.code.extend_from_slice(&inst.unwrap().to_le_bytes());
+        self
+    }
+
+    /// Terminate the program with an `ecall` and load it in a core with
+    /// a data segment, `sp` points to its middle
+    pub fn build(&mut self) -> CoreEmu {
+        self.inst(AssemblerRV64GC.ecall());
+
+        let mut core = core_with_code(&self.code, PermField::Read | PermField::Executable);
+        core.mem.allocate_segment(
+            Some(VirtAddr(DATA as usize)), 0x1000,
+            PermField::Read | PermField::Write,
+        ).unwrap();
+        core.write_reg(Register::Sp, DATA + 0x800);
+        core
+    }
+
+    /// Run the program until the final `ecall`, `setup` can initialize the
+    /// registers before the execution starts
+    pub fn run(&mut self, setup: impl FnOnce(&mut CoreEmu)) -> CoreEmu {
+        let mut core = self.build();
+        setup(&mut core);
+
+        match core.run() {
+            CoreEmuError::Syscall => {},
+            e => panic!("unexpected stop {:?} at pc {:x}", e, core.pc),
+        }
+        assert_eq!(core.pc, CODE + self.code.len() as u64);
+        core
+    }
+}
+
+/// A core about to run `code`, alone in the pages at [`CODE`] with the
+/// permissions `perm`
+pub fn core_with_code(code: &[u8], perm: Perm) -> CoreEmu {
+    let mut mem = Mmu::new();
+    let size = code.len().next_multiple_of(0x1000);
+    mem.allocate_segment(Some(VirtAddr(CODE as usize)), size, perm).unwrap();
+    unsafe{mem.write_from_slice(VirtAddr(CODE as usize), code)}.unwrap();
+    let mut core = CoreEmu::new(mem);
+    core.pc = CODE;
+    core
+}
+
+/// A [`LinuxEmu`] with an empty brk segment and the code to do a syscall and stop on a breakpoint
+pub fn new_emu() -> LinuxEmu {
+    let mut code = Vec::new();
+    code.extend_from_slice(&AssemblerRV64GC.ecall().unwrap().to_le_bytes());
+    code.extend_from_slice(&AssemblerRV64GC.ebreak().unwrap().to_le_bytes());
+
+    let mut mem = Mmu::new();
+    mem.allocate_segment(
+        Some(VirtAddr(CODE as usize)), 0x1000,
+        PermField::Read | PermField::Executable,
+    ).unwrap();
+    unsafe{mem.write_from_slice(VirtAddr(CODE as usize), &code)}.unwrap();
+    let (brk_idx, _) = mem.allocate_segment(
+        Some(VirtAddr(BRK as usize)), 0,
+        PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    mem.brk_idx = brk_idx;
+    LinuxEmu::new(mem)
+}
+
+/// Do the syscall `nr` with arguments `args` and return `a0`
+pub fn syscall(emu: &mut LinuxEmu, nr: LinuxSyscall, args: &[u64]) -> u64 {
+    let regs = [Register::A0, Register::A1, Register::A2, Register::A3, Register::A4, Register::A5];
+    for (reg, arg) in regs.iter().zip(args) {
+        emu.core.write_reg(*reg, *arg);
+    }
+    emu.core.write_reg(Register::A7, nr as u64);
+    emu.core.pc = CODE;
+    match emu.run() {
+        LinuxEmuError::Breakpoint => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+    emu.core.read_reg(Register::A0)
+}
+
+/// Map a read-write buffer of a page
+pub fn buffer(emu: &mut LinuxEmu) -> u64 {
+    let fd = (-1_i64) as u64;
+    syscall(emu, LinuxSyscall::mmap,
+        &[0, 0x1000, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, fd, 0])
+}
+
+/// The `len` bytes at `addr`
+pub fn read_bytes(emu: &mut LinuxEmu, addr: u64, len: usize) -> Vec<u8> {
+    let mut data = vec![0; len];
+    emu.core.mem.read_slice(VirtAddr(addr as usize), &mut data).unwrap();
+    data
+}
@@ -0,0 +1,224 @@
+//! Tests of the edge coverage of [`CoreEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+
+mod common;
+use common::*;
+
+/// The new edges of the current case, relative to the start of the code
+fn new_edges(core: &CoreEmu) -> Vec<(u64, u64)> {
+    core.coverage.as_ref().unwrap().new_edges().iter()
+        .map(|edge| (edge.from - CODE, edge.to - CODE))
+        .collect()
+}
+
+/// Run until the final `ecall` with `a0` set to `a0`
+fn run_case(core: &mut CoreEmu, a0: u64) {
+    core.coverage.as_mut().unwrap().clear_case();
+    core.write_reg(A0, a0);
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+}
+
+/// A call to a function that returns with `c.jr`, skipped if `a0` is zero
+fn call_program() -> CoreEmu {
+    let mut core = Program::default()
+        .c_inst(AssemblerRV64GC.c_beqz(A0, 6))
+        .inst(AssemblerRV64GC.addi(A1, A1, 1))
+        .inst(AssemblerRV64GC.jal(Ra, 10))
+        .c_inst(AssemblerRV64GC.c_j(10))
+        .c_inst(AssemblerRV64GC.c_nop())
+        .c_inst(AssemblerRV64GC.c_nop())
+        // called
+        .c_inst(AssemblerRV64GC.c_jr(Ra))
+        .c_inst(AssemblerRV64GC.c_nop())
+        .build();
+    core.coverage = Some(Coverage::default());
+    core
+}
+
+#[test]
+fn test_new_edges() {
+    let mut core = call_program();
+    let snapshot = core.fork();
+    run_case(&mut core, 0);
+    assert_eq!(new_edges(&core), [(0, 6), (6, 16), (16, 10), (10, 20)]);
+
+    // the same path is not new, but it's still in the bitmap
+    core.reset(&snapshot);
+    run_case(&mut core, 0);
+    assert!(new_edges(&core).is_empty());
+    let coverage = core.coverage.as_ref().unwrap();
+    assert_eq!(coverage.hits().len(), 4);
+    for edge in coverage.edges() {
+        assert_eq!(coverage.bitmap()[coverage.index(*edge)], 1);
+    }
+
+    core.reset(&snapshot);
+    run_case(&mut core, 1);
+    assert_eq!(new_edges(&core), [(0, 2)]);
+    assert_eq!(core.coverage.as_ref().unwrap().edges().len(), 5);
+
+    // everything is new again after clearing
+    core.coverage.as_mut().unwrap().clear();
+    core.reset(&snapshot);
+    run_case(&mut core, 1);
+    assert_eq!(new_edges(&core), [(0, 2), (6, 16), (16, 10), (10, 20)]);
+}
+
+#[test]
+fn test_forget_case() {
+    let mut core = call_program();
+    let snapshot = core.fork();
+    run_case(&mut core, 0);
+    core.coverage.as_mut().unwrap().forget_case();
+    assert!(core.coverage.as_ref().unwrap().edges().is_empty());
+
+    // reported again by the next case
+    core.reset(&snapshot);
+    run_case(&mut core, 1);
+    assert_eq!(new_edges(&core), [(0, 2), (6, 16), (16, 10), (10, 20)]);
+}
+
+#[test]
+fn test_forks() {
+    let mut core = call_program();
+    run_case(&mut core, 0);
+
+    // the fork knows the edges of its parent, but not the other way around
+    let mut fork = core.fork();
+    fork.pc = CODE;
+    run_case(&mut fork, 1);
+    assert_eq!(new_edges(&fork), [(0, 2)]);
+    core.pc = CODE;
+    run_case(&mut core, 1);
+    assert_eq!(new_edges(&core), [(0, 2)]);
+}
+
+#[test]
+fn test_hit_counts() {
+    // the loop branch is taken 299 times, the counts saturate
+    let mut program = Program::default();
+    program
+        .inst(AssemblerRV64GC.addi(S0, Zero, 300))
+        .inst(AssemblerRV64GC.addi(S0, S0, -1))
+        .inst(AssemblerRV64GC.bne(S0, Zero, -4));
+    let mut core = program.build();
+    core.coverage = Some(Coverage::new(8));
+    run_case(&mut core, 0);
+    let coverage = core.coverage.as_ref().unwrap();
+    assert_eq!(new_edges(&core), [(8, 4), (8, 12)]);
+    let taken = coverage.index(coverage.new_edges()[0]);
+    let not_taken = coverage.index(coverage.new_edges()[1]);
+    assert_eq!(coverage.bitmap()[taken], 255);
+    assert_eq!(coverage.bitmap()[not_taken], 1);
+
+    core.coverage.as_mut().unwrap().clear_case();
+    assert!(core.coverage.as_ref().unwrap().bitmap().iter().all(|count| *count == 0));
+}
+
+#[test]
+fn test_branches_and_jumps() {
+    // every branch skips the following instruction when taken
+    let branches: &[fn(&mut AssemblerRV64GC, Register, Register, i32) -> Result<u32, &str>] = &[
+        AssemblerRV64GC::beq, AssemblerRV64GC::bne, AssemblerRV64GC::blt,
+        AssemblerRV64GC::bge, AssemblerRV64GC::bltu, AssemblerRV64GC::bgeu,
+    ];
+    let mut program = Program::default();
+    for branch in branches {
+        program
+            .inst(branch(&mut AssemblerRV64GC, A0, A1, 8))
+            .inst(AssemblerRV64GC.addi(Zero, Zero, 0));
+    }
+    program
+        .c_inst(AssemblerRV64GC.c_beqz(A0, 4))
+        .c_inst(AssemblerRV64GC.c_nop())
+        .c_inst(AssemblerRV64GC.c_bnez(A0, 4))
+        .c_inst(AssemblerRV64GC.c_nop())
+        // 56: call 72 with jalr and c.jalr
+        .inst(AssemblerRV64GC.auipc(T0, 0))
+        .inst(AssemblerRV64GC.jalr(Ra, T0, 16))
+        .inst(AssemblerRV64GC.addi(T0, T0, 16))
+        .c_inst(AssemblerRV64GC.c_jalr(T0))
+        .c_inst(AssemblerRV64GC.c_j(4))
+        // 72: return
+        .c_inst(AssemblerRV64GC.c_jr(Ra));
+    let mut core = program.build();
+    core.write_reg(A1, -1i64 as u64);
+    core.coverage = Some(Coverage::default());
+    let snapshot = core.fork();
+
+    let calls = [(60, 72), (72, 64), (68, 72), (72, 70), (70, 74)];
+    // a0 = 1 and a1 = -1: bne, bge, bltu and c.bnez are taken
+    run_case(&mut core, 1);
+    let mut expected = vec![(0, 4), (8, 16), (16, 20), (24, 32), (32, 40), (40, 44), (48, 50), (52, 56)];
+    expected.extend_from_slice(&calls);
+    assert_eq!(new_edges(&core), expected);
+
+    // a0 = 0 and a1 = -1: bne, bge, bltu and c.beqz are taken
+    core.reset(&snapshot);
+    run_case(&mut core, 0);
+    assert_eq!(new_edges(&core), [(48, 52), (52, 54)]);
+    assert_eq!(core.coverage.as_ref().unwrap().edges().len(), expected.len() + 2);
+}
+
+/// The new progress of the comparisons of the current case, relative to the
+/// start of the code
+fn new_cmps(core: &CoreEmu) -> Vec<(u64, u8)> {
+    core.coverage.as_ref().unwrap().new_cmps().iter()
+        .map(|cmp| (cmp.pc - CODE, cmp.bytes))
+        .collect()
+}
+
+#[test]
+fn test_cmpcov() {
+    // a magic value checked by a branch and by sltu, and a slti
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.bne(A0, T0, 8))
+        .inst(AssemblerRV64GC.addi(A1, A1, 1))
+        .inst(AssemblerRV64GC.sltu(T1, A0, T0))
+        .inst(AssemblerRV64GC.slti(T2, A0, 0x123))
+        .build();
+    core.write_reg(T0, 0xdead_beef);
+    core.coverage = Some(Coverage::default());
+    let snapshot = core.fork();
+
+    // off by default
+    run_case(&mut core, 0x1234_beef);
+    assert!(new_cmps(&core).is_empty());
+    assert!(core.coverage.as_ref().unwrap().cmp_log().is_empty());
+
+    core.coverage.as_mut().unwrap().set_cmpcov(true);
+    core.reset(&snapshot);
+    run_case(&mut core, 0x1111_1111);
+    assert!(new_cmps(&core).is_empty());
+    let log: Vec<_> = core.coverage.as_ref().unwrap().cmp_log().iter()
+        .map(|cmp| (cmp.pc - CODE, cmp.lhs, cmp.rhs))
+        .collect();
+    assert_eq!(log, [(0, 0x1111_1111, 0xdead_beef), (8, 0x1111_1111, 0xdead_beef), (12, 0x1111_1111, 0x123)]);
+
+    // one byte at a time
+    core.reset(&snapshot);
+    run_case(&mut core, 0xef);
+    assert_eq!(new_cmps(&core), [(0, 1), (8, 1)]);
+    core.reset(&snapshot);
+    run_case(&mut core, 0xbeef);
+    assert_eq!(new_cmps(&core), [(0, 2), (8, 2)]);
+    core.reset(&snapshot);
+    run_case(&mut core, 0x1111_beef);
+    assert!(new_cmps(&core).is_empty());
+    assert!(core.coverage.as_ref().unwrap().new_edges().is_empty());
+
+    // the progress is in the bitmap with the edges
+    let coverage = core.coverage.as_ref().unwrap();
+    assert_eq!(coverage.hits().len(), 1 + 4);
+    for cmp in coverage.cmps() {
+        assert_eq!(coverage.bitmap()[coverage.cmp_index(*cmp)], 1);
+    }
+
+    // the equal operands are not logged
+    core.reset(&snapshot);
+    run_case(&mut core, 0xdead_beef);
+    assert_eq!(new_cmps(&core), [(0, 3), (0, 4), (0, 5), (0, 6), (0, 7), (0, 8), (8, 3), (8, 4), (8, 5), (8, 6), (8, 7), (8, 8)]);
+    assert_eq!(new_edges(&core), [(0, 4)]);
+    assert_eq!(core.coverage.as_ref().unwrap().cmp_log().len(), 1);
+}
@@ -0,0 +1,256 @@
+//! Tests of the [`HeapSanitizer`] and of the hooks of the allocator of
+//! [`LinuxEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use mmu::{Mmu, MmuError, VirtAddr};
+use std::sync::Arc;
+
+mod common;
+use common::*;
+
+/// The allocator functions, each a `ret` replaced by an `ebreak`
+const MALLOC: u64 = 4;
+const CALLOC: u64 = 8;
+const REALLOC: u64 = 12;
+const FREE: u64 = 16;
+
+/// The first chunk allocated
+const CHUNK: u64 = DEFAULT_HEAP_BASE + DEFAULT_REDZONE;
+
+/// A call from the end of `program` to the function at `offset`
+fn call(program: &mut Program, offset: u64) {
+    let pc = program.code.len() as i32;
+    program.inst(AssemblerRV64GC.jal(Ra, offset as i32 - pc));
+}
+
+/// A process running `main`, that exits with `a0`, with the sanitizer on
+fn sanitized(main: impl FnOnce(&mut Program)) -> LinuxEmu {
+    let mut program = Program::default();
+    program.inst(AssemblerRV64GC.jal(Zero, 20));
+    for _ in 0..4 {
+        program.inst(AssemblerRV64GC.jalr(Zero, Ra, 0));
+    }
+    main(&mut program);
+    program.inst(AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit_group as i32));
+
+    let mut emu = LinuxEmu::new(Mmu::new());
+    emu.core = program.build();
+    emu.core.call_stack = Some(CallStack::default());
+    let symbols = Arc::make_mut(&mut emu.symbols);
+    for (offset, name) in [(MALLOC, "malloc"), (CALLOC, "calloc"), (REALLOC, "realloc"), (FREE, "free")] {
+        symbols.add(Symbol { addr: CODE + offset, size: 4, name: name.into() });
+    }
+    assert_eq!(emu.enable_heap_sanitizer(HeapSanitizer::default()).unwrap(), 4);
+    emu
+}
+
+/// The address of the fault that stopped `emu`
+fn fault_address(stop: &LinuxEmuError) -> u64 {
+    match stop {
+        LinuxEmuError::MmuError(MmuError::PermissionsFault { virtual_address, .. })
+        | LinuxEmuError::MmuError(MmuError::InvalidFree(virtual_address)) => virtual_address.0 as u64,
+        stop => panic!("unexpected stop {:?}", stop),
+    }
+}
+
+#[test]
+fn test_hooks() {
+    let mut emu = sanitized(|program| {
+        program.inst(AssemblerRV64GC.addi(A0, Zero, 24));
+        call(program, MALLOC);
+        program
+            .inst(AssemblerRV64GC.addi(S0, A0, 0))
+            .inst(AssemblerRV64GC.addi(T0, Zero, 0x55))
+            .inst(AssemblerRV64GC.sd(S0, T0, 16));
+        program
+            .inst(AssemblerRV64GC.addi(A0, Zero, 2))
+            .inst(AssemblerRV64GC.addi(A1, Zero, 8));
+        call(program, CALLOC);
+        program
+            .inst(AssemblerRV64GC.ld(T1, A0, 8))
+            .inst(AssemblerRV64GC.addi(A0, S0, 0));
+        call(program, FREE);
+        program.inst(AssemblerRV64GC.addi(A0, T1, 0x55));
+    });
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(0x55)));
+    assert_eq!(emu.core.read_reg(S0), CHUNK);
+    // the calls returned to their callers
+    assert!(emu.core.call_stack.as_ref().unwrap().frames().is_empty());
+
+    let sanitizer = emu.heap_sanitizer.as_ref().unwrap();
+    let live: Vec<_> = sanitizer.live().copied().collect();
+    assert_eq!(live.len(), 1);
+    assert_eq!(live[0].size, 16);
+    assert_eq!(live[0].addr % HEAP_ALIGN, 0);
+    assert_eq!(live[0].allocated_from, CODE + 52);
+    let freed = sanitizer.chunk(CHUNK).unwrap();
+    assert_eq!((freed.state, freed.freed_from), (ChunkState::Quarantined, CODE + 64));
+}
+
+#[test]
+fn test_faults() {
+    // overflow, after the size even if within the alignment
+    for (offset, expected) in [(24, Some(HeapBug::Overflow)), (-8, Some(HeapBug::Overflow)), (0, None)] {
+        let mut emu = sanitized(|program| {
+            program.inst(AssemblerRV64GC.addi(A0, Zero, 20));
+            call(program, MALLOC);
+            program.inst(AssemblerRV64GC.lw(T0, A0, offset));
+        });
+        let stop = emu.run();
+        let address = fault_address(&stop);
+        assert_eq!(address, (CHUNK as i64 + offset as i64) as u64);
+        // the access itself faulted
+        assert_eq!(emu.core.pc, CODE + 28);
+        // the bytes never written are not readable either
+        let sanitizer = emu.heap_sanitizer.as_ref().unwrap();
+        assert_eq!(sanitizer.classify(address).map(|(bug, _)| bug), expected);
+    }
+
+    // use after free
+    let mut emu = sanitized(|program| {
+        program.inst(AssemblerRV64GC.addi(A0, Zero, 8));
+        call(program, MALLOC);
+        program.inst(AssemblerRV64GC.addi(S0, A0, 0));
+        call(program, FREE);
+        program.inst(AssemblerRV64GC.sb(S0, Zero, 3));
+    });
+    let address = fault_address(&emu.run());
+    let (bug, chunk) = emu.heap_sanitizer.as_ref().unwrap().classify(address).unwrap();
+    assert_eq!((bug, chunk.addr), (HeapBug::UseAfterFree, CHUNK));
+
+    // double and invalid frees stop in free
+    for (offset, expected) in [(0, HeapBug::DoubleFree), (8, HeapBug::InvalidFree)] {
+        let mut emu = sanitized(|program| {
+            program.inst(AssemblerRV64GC.addi(A0, Zero, 8));
+            call(program, MALLOC);
+            program.inst(AssemblerRV64GC.addi(S0, A0, 0));
+            call(program, FREE);
+            program.inst(AssemblerRV64GC.addi(A0, S0, offset));
+            call(program, FREE);
+        });
+        let address = fault_address(&emu.run());
+        assert_eq!(address, CHUNK + offset as u64);
+        assert_eq!(emu.core.pc, CODE + FREE);
+        assert_eq!(emu.backtrace().frames.len(), 2);
+        assert_eq!(emu.heap_sanitizer.as_ref().unwrap().classify_free(address), expected);
+    }
+}
+
+#[test]
+fn test_allocator() {
+    let mut mem = Mmu::new();
+    let mut sanitizer = HeapSanitizer::default();
+    sanitizer.map(&mut mem).unwrap();
+    // the segment is mapped only once
+    assert!(sanitizer.map(&mut mem).is_err());
+
+    // calloc zeroes and checks the overflow of the size
+    let a = sanitizer.calloc(&mut mem, 3, 8, 0x100);
+    assert_eq!(mem.read::<u64>(VirtAddr(a as usize + 16)).unwrap(), 0);
+    assert_eq!(sanitizer.calloc(&mut mem, u64::MAX, 2, 0x100), 0);
+    assert_eq!(sanitizer.malloc(&mut mem, DEFAULT_HEAP_SIZE, 0x100), 0);
+    let b = sanitizer.malloc(&mut mem, 0, 0x100);
+    assert!(b > a && b % HEAP_ALIGN == 0);
+    assert!(mem.read::<u8>(VirtAddr(b as usize)).is_err());
+
+    // realloc moves the chunk keeping the contents and what's uninitialized
+    let c = sanitizer.malloc(&mut mem, 16, 0x100);
+    mem.write::<u32>(VirtAddr(c as usize), 0x1337).unwrap();
+    let d = sanitizer.realloc(&mut mem, c, 64, 0x200).unwrap();
+    assert_ne!(c, d);
+    assert_eq!(mem.read::<u32>(VirtAddr(d as usize)).unwrap(), 0x1337);
+    assert!(mem.read::<u32>(VirtAddr(d as usize + 4)).is_err());
+    mem.write::<u64>(VirtAddr(d as usize + 56), 1).unwrap();
+    assert!(mem.read::<u32>(VirtAddr(c as usize)).is_err());
+    assert_eq!(sanitizer.realloc(&mut mem, c, 8, 0x200), Err(HeapBug::DoubleFree));
+    assert_eq!(sanitizer.realloc(&mut mem, d, 0, 0x200), Ok(0));
+    let e = sanitizer.realloc(&mut mem, 0, 8, 0x200).unwrap();
+    assert!(e > d);
+    assert_eq!(sanitizer.free(&mut mem, 0, 0x300), Ok(()));
+
+    // the chunks are reused only after the quarantine
+    sanitizer.free(&mut mem, e, 0x300).unwrap();
+    assert!(sanitizer.malloc(&mut mem, 8, 0x100) > e);
+    sanitizer.quarantine_limit = 0;
+    let f = sanitizer.malloc(&mut mem, 16, 0x100);
+    sanitizer.free(&mut mem, f, 0x300).unwrap();
+    assert_eq!(sanitizer.chunk(f).unwrap().state, ChunkState::Free);
+    let g = sanitizer.malloc(&mut mem, 9, 0x100);
+    assert_eq!(g, f);
+    assert_eq!(sanitizer.chunk(g).unwrap().state, ChunkState::Live);
+    assert!(mem.read::<u8>(VirtAddr(g as usize)).is_err());
+    assert!(mem.read::<u8>(VirtAddr(g as usize + 9)).is_err());
+    mem.write::<u8>(VirtAddr(g as usize + 8), 1).unwrap();
+}
+
+#[test]
+fn test_reset() {
+    let mut emu = sanitized(|program| {
+        program.inst(AssemblerRV64GC.addi(A0, Zero, 8));
+        call(program, MALLOC);
+        program.inst(AssemblerRV64GC.addi(S0, A0, 0));
+        call(program, FREE);
+        program.inst(AssemblerRV64GC.addi(A0, Zero, 8));
+        call(program, MALLOC);
+        program.inst(AssemblerRV64GC.ld(A0, S0, 0));
+    });
+    let snapshot = emu.fork();
+    for _ in 0..2 {
+        let address = fault_address(&emu.run());
+        assert_eq!(address, CHUNK);
+        let sanitizer = emu.heap_sanitizer.as_ref().unwrap();
+        assert_eq!(sanitizer.live().count(), 1);
+        emu.reset(&snapshot);
+        assert_eq!(emu.heap_sanitizer.as_ref().unwrap().live().count(), 0);
+    }
+}
+
+#[test]
+fn test_uninit_origin() {
+    let mut emu = sanitized(|program| {
+        program.inst(AssemblerRV64GC.addi(A0, Zero, 16));
+        call(program, MALLOC);
+        program
+            .inst(AssemblerRV64GC.sd(A0, Zero, 0))
+            .inst(AssemblerRV64GC.ld(T0, A0, 8));
+    });
+    emu.core.uninit = Some(UninitTracker::default());
+    let LinuxEmuError::UninitRead(read) = emu.run() else {
+        panic!("unexpected stop");
+    };
+    assert_eq!((read.addr, read.pc), (CHUNK + 8, CODE + 32));
+    assert_eq!(read.region, Some(UninitRegion {
+        start: CHUNK,
+        end: CHUNK + 16,
+        origin: UninitOrigin::HeapAlloc { pc: CODE + 28 },
+    }));
+}
+
+#[test]
+fn test_limits() {
+    // a huge malloc returns NULL to the guest
+    let mut emu = sanitized(|program| {
+        program
+            .inst(AssemblerRV64GC.addi(A0, Zero, 1))
+            .inst(AssemblerRV64GC.slli(A0, A0, 35));
+        call(program, MALLOC);
+    });
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(0)));
+    assert_eq!(emu.heap_sanitizer.as_ref().unwrap().live().count(), 0);
+
+    let mut mem = Mmu::new();
+    let mut sanitizer = HeapSanitizer::default();
+    sanitizer.map(&mut mem).unwrap();
+    assert_eq!(sanitizer.malloc(&mut mem, DEFAULT_MAX_ALLOC + 1, 0x100), 0);
+    assert_eq!(sanitizer.calloc(&mut mem, 1, u64::MAX, 0x100), 0);
+    sanitizer.max_alloc = 0x100;
+    assert_eq!(sanitizer.realloc(&mut mem, 0, 0x101, 0x100), Ok(0));
+    assert_ne!(sanitizer.malloc(&mut mem, 0x100, 0x100), 0);
+
+    // the segment doesn't grow past the limit of the memory
+    sanitizer.max_alloc = DEFAULT_MAX_ALLOC;
+    mem.memory_limit = mem.len() + 0x4000;
+    assert_eq!(sanitizer.malloc(&mut mem, 0x8000, 0x100), 0);
+    assert_ne!(sanitizer.malloc(&mut mem, 0x1000, 0x100), 0);
+    assert!(mem.len() <= mem.memory_limit);
+}
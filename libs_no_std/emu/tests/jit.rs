@@ -0,0 +1,448 @@
+//! Tests of the JIT backend, the programs are run by both backends which
+//! have to stop in the same state
+#![cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+type RegReg = fn(&mut AssemblerRV64GC, Register, Register, Register) -> Result<u32, &str>;
+type RegImm = fn(&mut AssemblerRV64GC, Register, Register, i32) -> Result<u32, &str>;
+
+/// A program running `body` `iterations` times, counting with `s0`
+fn looped(iterations: i32, body: impl Fn(&mut Program)) -> Program {
+    let mut program = Program::default();
+    program.inst(AssemblerRV64GC.addi(S0, Zero, iterations));
+    let start = program.code.len();
+    body(&mut program);
+    program.inst(AssemblerRV64GC.addi(S0, S0, -1));
+    let offset = start as i32 - program.code.len() as i32;
+    program.inst(AssemblerRV64GC.bne(S0, Zero, offset));
+    program
+}
+
+/// Seed the registers with some values that are not all small
+fn seed(core: &mut CoreEmu) {
+    core.write_reg(A0, 0x9e37_79b9_7f4a_7c15);
+    core.write_reg(A1, 0xffff_ffff_8000_0001);
+    core.write_reg(A2, 0x7fff_ffff);
+    core.write_reg(A3, 63);
+}
+
+/// Run `core` with the interpreter and a fork of it with the JIT, check that
+/// they stop in the same state and return the stop and the JIT core
+fn diff(mut core: CoreEmu) -> (CoreEmuError, CoreEmu) {
+    let mut jit = core.fork();
+    jit.backend = Backend::Jit;
+    let expected = core.run();
+    let stop = jit.run();
+    assert_eq!(format!("{:?}", stop), format!("{:?}", expected));
+    assert_eq!(jit.pc, core.pc);
+    assert_eq!(jit.regs, core.regs);
+    assert_eq!(jit.instructions_executed, core.instructions_executed);
+    for ((_, segment), (_, expected)) in jit.mem.segments.iter().zip(core.mem.segments.iter()) {
+        assert_eq!(segment.memory, expected.memory);
+        assert_eq!(segment.permissions, expected.permissions);
+    }
+    if let (Some(coverage), Some(expected)) = (&jit.coverage, &core.coverage) {
+        assert_eq!(coverage.new_edges(), expected.new_edges());
+        assert_eq!(coverage.new_cmps(), expected.new_cmps());
+        assert_eq!(coverage.cmp_log(), expected.cmp_log());
+        assert_eq!(coverage.bitmap(), expected.bitmap());
+    }
+    if let (Some(call_stack), Some(expected)) = (&jit.call_stack, &core.call_stack) {
+        assert_eq!(call_stack.frames(), expected.frames());
+        assert_eq!(call_stack.depth(), expected.depth());
+    }
+    (stop, jit)
+}
+
+/// Run `program` on both backends with seeded registers, it must get to
+/// the final `ecall` after translating some code
+fn diff_program(program: &mut Program) -> CoreEmu {
+    let mut core = program.build();
+    seed(&mut core);
+    let (stop, jit) = diff(core);
+    assert!(matches!(stop, CoreEmuError::Syscall));
+    assert!(!jit.jit.is_empty());
+    jit
+}
+
+#[test]
+fn test_integer() {
+    let reg_reg: &[RegReg] = &[
+        AssemblerRV64GC::add, AssemblerRV64GC::sub, AssemblerRV64GC::sll,
+        AssemblerRV64GC::slt, AssemblerRV64GC::sltu, AssemblerRV64GC::xor,
+        AssemblerRV64GC::srl, AssemblerRV64GC::sra, AssemblerRV64GC::or,
+        AssemblerRV64GC::and, AssemblerRV64GC::addw, AssemblerRV64GC::subw,
+        AssemblerRV64GC::sllw, AssemblerRV64GC::srlw, AssemblerRV64GC::sraw,
+        AssemblerRV64GC::mul, AssemblerRV64GC::mulh, AssemblerRV64GC::mulhu,
+        AssemblerRV64GC::mulw,
+        // interpreted
+        AssemblerRV64GC::mulhsu, AssemblerRV64GC::divu, AssemblerRV64GC::remw,
+    ];
+    let reg_imm: &[(RegImm, i32)] = &[
+        (AssemblerRV64GC::addi, -2048),
+        (AssemblerRV64GC::slti, -1), (AssemblerRV64GC::xori, -1),
+        (AssemblerRV64GC::ori, 0xf0), (AssemblerRV64GC::andi, -256),
+        (AssemblerRV64GC::slli, 17), (AssemblerRV64GC::srli, 63),
+        (AssemblerRV64GC::srai, 33), (AssemblerRV64GC::addiw, 2047),
+        (AssemblerRV64GC::slliw, 31), (AssemblerRV64GC::srliw, 1),
+        (AssemblerRV64GC::sraiw, 5),
+    ];
+    diff_program(&mut looped(200, |program| {
+        // xorshift a0
+        program
+            .inst(AssemblerRV64GC.slli(T0, A0, 13))
+            .inst(AssemblerRV64GC.xor(A0, A0, T0))
+            .inst(AssemblerRV64GC.srli(T0, A0, 7))
+            .inst(AssemblerRV64GC.xor(A0, A0, T0))
+            .inst(AssemblerRV64GC.slli(T0, A0, 17))
+            .inst(AssemblerRV64GC.xor(A0, A0, T0));
+        // accumulate everything in a1 and a2
+        for op in reg_reg {
+            program
+                .inst(op(&mut AssemblerRV64GC, T1, A0, A1))
+                .inst(op(&mut AssemblerRV64GC, T2, A2, A3))
+                .inst(AssemblerRV64GC.add(A1, A1, T1))
+                .inst(AssemblerRV64GC.xor(A2, A2, T2));
+        }
+        for (op, imm) in reg_imm {
+            program
+                .inst(op(&mut AssemblerRV64GC, T1, A0, *imm))
+                .inst(AssemblerRV64GC.add(A1, A1, T1));
+        }
+        program
+            .inst(AssemblerRV64GC.sltiu(T1, A0, 100))
+            .inst(AssemblerRV64GC.add(A2, A2, T1))
+            .inst(AssemblerRV64GC.lui(T1, 0x8000_0000))
+            .inst(AssemblerRV64GC.add(A2, A2, T1))
+            .inst(AssemblerRV64GC.auipc(T1, 0xfff0_0000))
+            .inst(AssemblerRV64GC.add(A2, A2, T1))
+            // writes to zero are dropped
+            .inst(AssemblerRV64GC.add(Zero, A0, A1))
+            .inst(AssemblerRV64GC.lui(Zero, 0x1000));
+    }));
+}
+
+#[test]
+fn test_compressed() {
+    diff_program(&mut looped(100, |program| {
+        program
+            .c_inst(AssemblerRV64GC.c_addi(A0, -7))
+            .c_inst(AssemblerRV64GC.c_addiw(A1, 31))
+            .c_inst(AssemblerRV64GC.c_li(A2, -32))
+            .c_inst(AssemblerRV64GC.c_lui(A4, -0x1000))
+            .c_inst(AssemblerRV64GC.c_srli(A0, 3))
+            .c_inst(AssemblerRV64GC.c_srai(A1, 63))
+            .c_inst(AssemblerRV64GC.c_andi(A2, 0x1f))
+            .c_inst(AssemblerRV64GC.c_slli(A3, 1))
+            .c_inst(AssemblerRV64GC.c_sub(A0, A4))
+            .c_inst(AssemblerRV64GC.c_xor(A1, A0))
+            .c_inst(AssemblerRV64GC.c_or(A2, A1))
+            .c_inst(AssemblerRV64GC.c_and(A3, A0))
+            .c_inst(AssemblerRV64GC.c_subw(A4, A1))
+            .c_inst(AssemblerRV64GC.c_addw(A5, A4))
+            .c_inst(AssemblerRV64GC.c_mv(T0, A5))
+            .c_inst(AssemblerRV64GC.c_add(A0, T0))
+            .c_inst(AssemblerRV64GC.c_addi4spn(S1, 0x3fc))
+            .c_inst(AssemblerRV64GC.c_addi16sp(-16))
+            .c_inst(AssemblerRV64GC.c_addi16sp(16))
+            .c_inst(AssemblerRV64GC.c_nop());
+    }));
+}
+
+#[test]
+fn test_control_flow() {
+    // calls, jumps and branches, a0 is incremented only by the path taken
+    // when the counter is odd
+    let mut program = looped(64, |program| {
+        program
+            .inst(AssemblerRV64GC.andi(A5, S0, 1))
+            .c_inst(AssemblerRV64GC.c_beqz(A5, 12))
+            .inst(AssemblerRV64GC.addi(A0, A0, 1))
+            .inst(AssemblerRV64GC.jal(Ra, 14))
+            .c_inst(AssemblerRV64GC.c_j(22))
+            // even
+            .inst(AssemblerRV64GC.addi(A1, A1, 1))
+            .c_inst(AssemblerRV64GC.c_j(16))
+            .c_inst(AssemblerRV64GC.c_nop())
+            // called
+            .inst(AssemblerRV64GC.addi(A2, A2, 1))
+            .c_inst(AssemblerRV64GC.c_jr(Ra))
+            .c_inst(AssemblerRV64GC.c_nop())
+            .c_inst(AssemblerRV64GC.c_nop())
+            .c_inst(AssemblerRV64GC.c_nop())
+            // both
+            .inst(AssemblerRV64GC.blt(A1, A0, 8))
+            .inst(AssemblerRV64GC.addi(A3, A3, 1))
+            .inst(AssemblerRV64GC.bgeu(A1, A0, 8))
+            .inst(AssemblerRV64GC.addi(A4, A4, 1))
+            .inst(AssemblerRV64GC.auipc(T1, 0))
+            // the lowest bit of the target is ignored
+            .inst(AssemblerRV64GC.jalr(T2, T1, 13))
+            .inst(AssemblerRV64GC.addi(A4, A4, 100));
+    });
+    let jit = diff_program(&mut program);
+    assert_eq!(jit.read_reg(A0), 0x9e37_79b9_7f4a_7c15 + 32);
+}
+
+#[test]
+fn test_coverage() {
+    let program = || looped(100, |program| {
+        program
+            .inst(AssemblerRV64GC.andi(A5, S0, 3))
+            .c_inst(AssemblerRV64GC.c_beqz(A5, 6))
+            .inst(AssemblerRV64GC.addi(A0, A0, 1))
+            .inst(AssemblerRV64GC.bltu(A5, A1, 8))
+            .inst(AssemblerRV64GC.addi(A0, A0, 2));
+    });
+    let mut core = program().build();
+    seed(&mut core);
+    core.coverage = Some(Coverage::default());
+    let (stop, mut jit) = diff(core.fork());
+    assert!(matches!(stop, CoreEmuError::Syscall));
+    assert!(!jit.jit.is_empty());
+    assert_eq!(jit.coverage.as_ref().unwrap().new_edges().len(), 5);
+
+    // the translations without coverage jump natively
+    jit.coverage = None;
+    jit.reset(&core);
+    jit.run();
+    assert_eq!(jit.regs, diff_program(&mut program()).regs);
+}
+
+#[test]
+fn test_cmpcov() {
+    let mut program = looped(300, |program| {
+        program
+            .inst(AssemblerRV64GC.slli(T0, S0, 4))
+            .inst(AssemblerRV64GC.sltu(T1, T0, A2))
+            .inst(AssemblerRV64GC.slt(T2, T0, A1))
+            .inst(AssemblerRV64GC.slti(T3, T0, 0x100))
+            .inst(AssemblerRV64GC.sltiu(T4, T0, 0x7f0))
+            .inst(AssemblerRV64GC.add(A0, A0, T1))
+            .inst(AssemblerRV64GC.add(A0, A0, T4))
+            .inst(AssemblerRV64GC.blt(T2, T3, 8))
+            .inst(AssemblerRV64GC.addi(A0, A0, 1));
+    });
+    let mut core = program.build();
+    seed(&mut core);
+    let mut coverage = Coverage::default();
+    coverage.set_cmpcov(true);
+    core.coverage = Some(coverage);
+    let (stop, jit) = diff(core);
+    assert!(matches!(stop, CoreEmuError::Syscall));
+    assert!(!jit.jit.is_empty());
+    assert!(!jit.coverage.as_ref().unwrap().new_cmps().is_empty());
+}
+
+#[test]
+fn test_call_stack() {
+    // a call and a return in every iteration, then a call to the final ecall
+    let mut program = looped(100, |program| {
+        program
+            .inst(AssemblerRV64GC.jal(Ra, 8))
+            .inst(AssemblerRV64GC.jal(Zero, 10))
+            .inst(AssemblerRV64GC.addi(A0, A0, 1))
+            .c_inst(AssemblerRV64GC.c_jr(Ra));
+    });
+    program.inst(AssemblerRV64GC.jal(Ra, 4));
+    let mut core = program.build();
+    seed(&mut core);
+    core.call_stack = Some(CallStack::default());
+    let (stop, mut jit) = diff(core.fork());
+    assert!(matches!(stop, CoreEmuError::Syscall));
+    assert!(!jit.jit.is_empty());
+    assert_eq!(jit.call_stack.as_ref().unwrap().depth(), 1);
+
+    // the translations are redone without the call stack
+    let expected = jit.regs;
+    jit.reset(&core);
+    jit.call_stack = None;
+    assert!(matches!(jit.run(), CoreEmuError::Syscall));
+    assert_eq!(jit.regs, expected);
+}
+
+#[test]
+fn test_memory() {
+    let mut program = looped(100, |program| {
+        program
+            .inst(AssemblerRV64GC.addi(A0, A0, 0x77))
+            .inst(AssemblerRV64GC.andi(T0, A0, 0x3f8))
+            .inst(AssemblerRV64GC.add(T0, T0, A4))
+            // the stack and the data segment
+            .inst(AssemblerRV64GC.sd(Sp, A0, -8))
+            .inst(AssemblerRV64GC.sw(Sp, A0, -12))
+            .inst(AssemblerRV64GC.sh(Sp, A0, -14))
+            .inst(AssemblerRV64GC.sb(Sp, A0, -15))
+            .inst(AssemblerRV64GC.lb(T1, Sp, -16))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.lh(T1, Sp, -15))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.lw(T1, Sp, -13))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.ld(T1, Sp, -9))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.sd(T0, A1, 0))
+            .inst(AssemblerRV64GC.lbu(T1, T0, 7))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.lhu(T1, T0, 6))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.lwu(T1, T0, 3))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            // a third segment
+            .inst(AssemblerRV64GC.sw(A5, A1, 0xfe))
+            .inst(AssemblerRV64GC.lw(Zero, A5, 0xfe))
+            .c_inst(AssemblerRV64GC.c_sd(A5, A0, 8))
+            .c_inst(AssemblerRV64GC.c_ld(A2, A5, 8))
+            .c_inst(AssemblerRV64GC.c_sw(A5, A1, 4))
+            .c_inst(AssemblerRV64GC.c_lw(A3, A5, 4))
+            .c_inst(AssemblerRV64GC.c_sdsp(A2, 0x1f8))
+            .c_inst(AssemblerRV64GC.c_ldsp(T2, 0x1f8))
+            .c_inst(AssemblerRV64GC.c_swsp(A3, 0xfc))
+            .c_inst(AssemblerRV64GC.c_lwsp(T3, 0xfc));
+    });
+    let mut core = program.build();
+    let rw = PermField::Read | PermField::Write;
+    core.mem.allocate_segment(Some(VirtAddr(BRK as usize)), 0x1000, rw).unwrap();
+    core.write_reg(A4, DATA);
+    core.write_reg(A5, BRK + 0x100);
+    let reference = core.fork();
+    let (stop, mut jit) = diff(core);
+    assert!(matches!(stop, CoreEmuError::Syscall));
+
+    // the JIT dirtied all the memory it wrote
+    jit.reset(&reference);
+    for ((_, segment), (_, expected)) in jit.mem.segments.iter().zip(reference.mem.segments.iter()) {
+        assert_eq!(segment.memory, expected.memory);
+    }
+}
+
+#[test]
+fn test_read_after_write() {
+    let mut program = looped(50, |program| {
+        program
+            .inst(AssemblerRV64GC.lw(T1, A4, 0))
+            .inst(AssemblerRV64GC.add(A1, A1, T1))
+            .inst(AssemblerRV64GC.sh(A4, A1, 8))
+            .inst(AssemblerRV64GC.lhu(A2, A4, 8))
+            .inst(AssemblerRV64GC.addi(A4, A4, 4));
+    });
+    let mut core = program.build();
+    let raw = PermField::Write | PermField::ReadAfterWrite;
+    core.mem.allocate_segment(Some(VirtAddr(BRK as usize)), 0x1000, raw).unwrap();
+    core.mem.write::<u64>(VirtAddr(BRK as usize), 0x1337).unwrap();
+    core.write_reg(A4, BRK);
+    // the loads get past the initialized bytes on the fourth iteration
+    let (stop, _) = diff(core);
+    assert!(matches!(stop, CoreEmuError::MmuError(_)));
+}
+
+#[test]
+fn test_faults() {
+    // out of the segment
+    let mut program = looped(1000, |program| {
+        program
+            .inst(AssemblerRV64GC.ld(T1, A4, 0))
+            .inst(AssemblerRV64GC.addi(A4, A4, 0xf8));
+    });
+    let mut core = program.build();
+    core.write_reg(A4, DATA);
+    let (stop, jit) = diff(core);
+    assert!(matches!(stop, CoreEmuError::MmuError(_)));
+    assert!(!jit.jit.is_empty());
+
+    // write to a read only segment right after the data
+    let mut program = looped(1000, |program| {
+        program
+            .inst(AssemblerRV64GC.sw(A4, A0, 0))
+            .inst(AssemblerRV64GC.addi(A4, A4, 0x40));
+    });
+    let mut core = program.build();
+    core.mem.allocate_segment(
+        Some(VirtAddr(DATA as usize + 0x1000)), 0x1000, PermField::Read.into(),
+    ).unwrap();
+    core.write_reg(A4, DATA + 0xe00);
+    let (stop, jit) = diff(core);
+    assert!(matches!(stop, CoreEmuError::MmuError(_)));
+    assert_eq!(jit.read_reg(A4), DATA + 0x1000);
+}
+
+#[test]
+fn test_budget() {
+    let mut program = looped(1000, |program| {
+        program
+            .inst(AssemblerRV64GC.addi(A0, A0, 1))
+            .inst(AssemblerRV64GC.sd(Sp, A0, 0))
+            .inst(AssemblerRV64GC.divu(A1, A0, S0))
+            .inst(AssemblerRV64GC.addi(A2, A2, 1));
+    });
+    for budget in [1, 2, 5, 12, 13, 100, 1001, 3333] {
+        let mut core = program.build();
+        core.set_instruction_budget(budget);
+        let (stop, mut jit) = diff(core);
+        assert!(matches!(stop, CoreEmuError::Timeout));
+        // and resumes where it stopped
+        jit.set_instruction_budget(budget);
+        jit.run();
+        let mut core = program.build();
+        core.set_instruction_budget(2 * budget);
+        core.run();
+        assert_eq!(jit.regs, core.regs);
+        assert_eq!(jit.pc, core.pc);
+    }
+}
+
+#[test]
+fn test_self_modifying_code() {
+    // the loop body is rewritten by the loop itself, from `addi a0, a0, 1`
+    // to `addi a0, a0, 2` in the middle of the iterations
+    let mut program = looped(100, |program| {
+        program
+            .inst(AssemblerRV64GC.addi(A0, A0, 1))
+            .inst(AssemblerRV64GC.addi(A5, S0, -50))
+            .c_inst(AssemblerRV64GC.c_bnez(A5, 10))
+            .inst(AssemblerRV64GC.sw(S1, A2, 0))
+            .inst(AssemblerRV64GC.fence_i());
+    });
+    let mut core = program.build();
+    let rwx = PermField::Read | PermField::Write | PermField::Executable;
+    core.mem.mprotect(VirtAddr(CODE as usize), 0x1000, rwx).unwrap();
+    core.write_reg(S1, CODE + 4);
+    core.write_reg(A2, AssemblerRV64GC.addi(A0, A0, 2).unwrap() as u64);
+    core.write_reg(A0, 0);
+    let mut jit = core.fork();
+    jit.backend = Backend::Jit;
+    loop {
+        match (core.run(), jit.run()) {
+            (CoreEmuError::Yield, CoreEmuError::Yield) => {},
+            (CoreEmuError::Syscall, CoreEmuError::Syscall) => break,
+            stops => panic!("{:?}", stops),
+        }
+        assert_eq!(jit.regs, core.regs);
+        assert_eq!(jit.pc, core.pc);
+    }
+    assert_eq!(jit.read_reg(A0), 51 + 2 * 49);
+    assert_eq!(jit.regs, core.regs);
+}
+
+#[test]
+fn test_code_written_again_after_reset() {
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.addi(A0, Zero, 0))
+        .build();
+    let rwx = PermField::Read | PermField::Write | PermField::Executable;
+    core.mem.mprotect(VirtAddr(CODE as usize), 0x1000, rwx).unwrap();
+    core.backend = Backend::Jit;
+    let reference = core.fork();
+    for imm in [1, 2] {
+        let inst = AssemblerRV64GC.addi(A0, Zero, imm).unwrap();
+        unsafe{core.mem.write_from_slice(VirtAddr(CODE as usize), &inst.to_le_bytes())}.unwrap();
+        assert!(matches!(core.run(), CoreEmuError::Syscall));
+        assert_eq!(core.read_reg(A0), imm as u64);
+        core.reset(&reference);
+    }
+}
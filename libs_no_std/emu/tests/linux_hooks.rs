@@ -0,0 +1,99 @@
+//! Tests of the syscall hooks of [`LinuxEmu`]
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+
+mod common;
+use common::*;
+
+/// Do the syscall `nr` and return why the emulator stopped
+fn syscall_stop(emu: &mut LinuxEmu, nr: u64) -> LinuxEmuError {
+    emu.core.write_reg(Register::A7, nr);
+    emu.core.pc = CODE;
+    emu.run()
+}
+
+#[test]
+fn test_return() {
+    let mut emu = new_emu();
+    emu.hook_syscall(LinuxSyscall::getpid, |_core, _args| SyscallAction::Return(1337));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getpid, &[]), 1337);
+    // the other syscalls are not affected
+    assert_eq!(syscall(&mut emu, LinuxSyscall::gettid, &[]), GUEST_PID);
+
+    // the built-in implementation is used again
+    emu.unhook_syscall(LinuxSyscall::getpid);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getpid, &[]), GUEST_PID);
+}
+
+#[test]
+fn test_continue() {
+    let mut emu = new_emu();
+    let calls = Arc::new(AtomicU64::new(0));
+    let counter = calls.clone();
+    // count the calls and redirect stderr to stdout
+    emu.hook_syscall(LinuxSyscall::write, move |core, args| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        if args[0] == 2 {
+            core.write_reg(Register::A0, 1);
+        }
+        SyscallAction::Continue
+    });
+
+    let buf = syscall(&mut emu, LinuxSyscall::brk, &[0]);
+    syscall(&mut emu, LinuxSyscall::brk, &[buf + 0x10]);
+    emu.core.mem.write_slice(mmu::VirtAddr(buf as usize), b"hi").unwrap();
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, buf, 2]), 2);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[2, buf, 1]), 1);
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+    assert_eq!(emu.vfs.stdout, b"hih");
+    assert!(emu.vfs.stderr.is_empty());
+
+    // forks share the hooks
+    let mut fork = emu.fork();
+    syscall(&mut fork, LinuxSyscall::write, &[1, buf, 2]);
+    assert_eq!(calls.load(Ordering::Relaxed), 3);
+}
+
+#[test]
+fn test_stop() {
+    let mut emu = new_emu();
+    emu.hook_syscall(LinuxSyscall::socket, |core, args| {
+        core.write_reg(Register::A1, args[0] + 1);
+        SyscallAction::Stop(0xdead)
+    });
+    emu.core.write_reg(Register::A0, 41);
+    match syscall_stop(&mut emu, LinuxSyscall::socket as u64) {
+        LinuxEmuError::HookStop(0xdead) => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+    assert_eq!(emu.core.read_reg(Register::A1), 42);
+    // the pc is after the ecall, so the emulation can be resumed
+    assert_eq!(emu.core.pc, CODE + 4);
+}
+
+#[test]
+fn test_unknown_policy() {
+    let mut emu = new_emu();
+    assert_eq!(emu.unknown_syscall_policy, UnknownSyscallPolicy::Enosys);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::socket, &[]), to_ret(ENOSYS));
+    // not even a valid number
+    emu.core.write_reg(Register::A7, 1000);
+    emu.core.pc = CODE;
+    assert!(matches!(emu.run(), LinuxEmuError::Breakpoint));
+    assert_eq!(emu.core.read_reg(Register::A0), to_ret(ENOSYS));
+
+    emu.unknown_syscall_policy = UnknownSyscallPolicy::Stop;
+    match syscall_stop(&mut emu, LinuxSyscall::socket as u64) {
+        LinuxEmuError::BadSyscall(nr) => assert_eq!(nr, LinuxSyscall::socket as u64),
+        e => panic!("unexpected stop {:?}", e),
+    }
+    match syscall_stop(&mut emu, 1000) {
+        LinuxEmuError::BadSyscall(1000) => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+    // a hook makes the syscall known
+    emu.hook_syscall(LinuxSyscall::socket, |_core, _args| SyscallAction::Return(3));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::socket, &[]), 3);
+}
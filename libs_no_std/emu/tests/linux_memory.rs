@@ -0,0 +1,120 @@
+//! Tests of the memory management syscalls of [`LinuxEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+use emu::riscv64gc::mman::*;
+use mmu::{MmuError, VirtAddr};
+
+mod common;
+use common::*;
+
+#[test]
+fn test_brk() {
+    let mut emu = new_emu();
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[0]), BRK);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x2100]), BRK + 0x2100);
+    emu.core.mem.write(VirtAddr(BRK as usize + 0x20f8), 1_u64).unwrap();
+    // failures return the current break
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[BRK - 0x1000]), BRK + 0x2100);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[0]), BRK + 0x2100);
+}
+
+#[test]
+fn test_mmap_munmap() {
+    let mut emu = new_emu();
+    let anon = MAP_PRIVATE | MAP_ANONYMOUS;
+    let rw = PROT_READ | PROT_WRITE;
+    let fd = (-1_i64) as u64;
+
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1800, rw, anon, fd, 0]);
+    assert_eq!(addr % PAGE_SIZE, 0);
+    // the length is rounded to pages
+    emu.core.mem.write(VirtAddr(addr as usize + 0x1ff8), 1_u64).unwrap();
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(addr as usize)).unwrap(), 0);
+
+    // read-only
+    let ro = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, PROT_READ, anon, fd, 0]);
+    assert!(emu.core.mem.write(VirtAddr(ro as usize), 1_u64).is_err());
+
+    // fixed
+    let fixed = 0x100_0000;
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[fixed, 0x1000, rw, anon | MAP_FIXED, fd, 0]), fixed);
+    assert_eq!(
+        syscall(&mut emu, LinuxSyscall::mmap, &[fixed, 0x1000, rw, anon | MAP_FIXED_NOREPLACE, fd, 0]),
+        to_ret(EEXIST),
+    );
+
+    // errors
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[0, 0, rw, anon, fd, 0]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, rw, MAP_ANONYMOUS, fd, 0]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[fixed + 1, 0x1000, rw, anon | MAP_FIXED, fd, 0]), to_ret(EINVAL));
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::munmap, &[addr, 0x1000]), 0);
+    assert!(emu.core.mem.read::<u64>(VirtAddr(addr as usize)).is_err());
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(addr as usize + 0x1ff8)).unwrap(), 1);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::munmap, &[addr + 1, 0x1000]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::munmap, &[addr, 0]), to_ret(EINVAL));
+}
+
+#[test]
+fn test_memory_limit() {
+    let mut emu = new_emu();
+    let anon = MAP_PRIVATE | MAP_ANONYMOUS;
+    let rw = PROT_READ | PROT_WRITE;
+    let fd = (-1_i64) as u64;
+
+    // more than the host could allocate
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[0, 1 << 44, rw, anon, fd, 0]), to_ret(ENOMEM));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[BRK + (1 << 44)]), BRK);
+    // past the end of the address space
+    let top = u64::MAX & !(PAGE_SIZE - 1);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[top, 0x2000, rw, anon | MAP_FIXED, fd, 0]), to_ret(ENOMEM));
+
+    emu.core.mem.memory_limit = emu.core.mem.len() + 0x2000;
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, rw, anon, fd, 0]);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x2000, rw, anon, fd, 0]), to_ret(ENOMEM));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mremap, &[addr, 0x1000, 0x3000, MREMAP_MAYMOVE]), to_ret(ENOMEM));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x2000]), BRK);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x1000]), BRK + 0x1000);
+}
+
+#[test]
+fn test_mremap_overflow() {
+    let mut emu = new_emu();
+    let top = u64::MAX & !(PAGE_SIZE - 1);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mremap, &[top, 0x2000, 0x1000, 0]), to_ret(EFAULT));
+    assert_eq!(
+        syscall(&mut emu, LinuxSyscall::mremap, &[BRK, 0x1000, 0x2000, MREMAP_MAYMOVE | MREMAP_FIXED, top]),
+        to_ret(EINVAL),
+    );
+    assert!(matches!(
+        emu.core.mem.mremap(VirtAddr(CODE as usize), usize::MAX, 0x1000, true, None),
+        Err(MmuError::SegmentNotFound { .. }),
+    ));
+}
+
+#[test]
+fn test_mremap() {
+    let mut emu = new_emu();
+    let anon = MAP_PRIVATE | MAP_ANONYMOUS;
+    let rw = PROT_READ | PROT_WRITE;
+    let fd = (-1_i64) as u64;
+
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, rw, anon, fd, 0]);
+    emu.core.mem.write(VirtAddr(addr as usize), 0x1337_u64).unwrap();
+    // something right after it so it can't grow in place
+    syscall(&mut emu, LinuxSyscall::mmap, &[addr + 0x1000, 0x1000, rw, anon | MAP_FIXED, fd, 0]);
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mremap, &[addr, 0x1000, 0x3000, 0]), to_ret(ENOMEM));
+    let moved = syscall(&mut emu, LinuxSyscall::mremap, &[addr, 0x1000, 0x3000, MREMAP_MAYMOVE]);
+    assert_ne!(moved, addr);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(moved as usize)).unwrap(), 0x1337);
+    emu.core.mem.write(VirtAddr(moved as usize + 0x2ff8), 1_u64).unwrap();
+
+    // errors
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mremap, &[addr, 0x1000, 0x2000, MREMAP_MAYMOVE]), to_ret(EFAULT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mremap, &[moved, 0x1000, 0x2000, MREMAP_FIXED, addr]), to_ret(EINVAL));
+    assert_eq!(
+        syscall(&mut emu, LinuxSyscall::mremap, &[moved, 0x3000, 0x3000, MREMAP_FIXED | MREMAP_MAYMOVE, moved + 0x1000]),
+        to_ret(EINVAL),
+    );
+}
@@ -0,0 +1,161 @@
+//! Tests of the process and file syscalls of [`LinuxEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+use emu::riscv64gc::mman::*;
+use mmu::VirtAddr;
+
+mod common;
+use common::*;
+
+/// Write the null-terminated `path` at `addr`
+fn write_path(emu: &mut LinuxEmu, addr: u64, path: &[u8]) {
+    emu.core.mem.write_slice(VirtAddr(addr as usize), path).unwrap();
+    emu.core.mem.write::<u8>(VirtAddr(addr as usize + path.len()), 0).unwrap();
+}
+
+#[test]
+fn test_stdio() {
+    let mut emu = new_emu();
+    let buf = buffer(&mut emu);
+    emu.core.mem.write_slice(VirtAddr(buf as usize), b"hello world\n").unwrap();
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, buf, 12]), 12);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[2, buf, 5]), 5);
+    assert_eq!(emu.vfs.stdout, b"hello world\n");
+    assert_eq!(emu.vfs.stderr, b"hello");
+    // stdin is empty
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[0, buf, 12]), 0);
+
+    // errors
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[3, buf, 12]), to_ret(EBADF));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, 0, 12]), to_ret(EFAULT));
+    // no fd is a terminal
+    assert_eq!(syscall(&mut emu, LinuxSyscall::ioctl, &[1, 0x5401, buf]), to_ret(ENOTTY));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::ioctl, &[7, 0x5401, buf]), to_ret(EBADF));
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::close, &[1]), 0);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, buf, 12]), to_ret(EBADF));
+}
+
+#[test]
+fn test_files() {
+    let mut emu = new_emu();
+    let content: Vec<u8> = (0..0x1800).map(|i| i as u8).collect();
+    emu.vfs.add_file(b"/lib/libc.so.6", content.clone());
+    let buf = buffer(&mut emu);
+    write_path(&mut emu, buf, b"/lib/libc.so.6");
+    let at_fdcwd = AT_FDCWD as u64;
+
+    let fd = syscall(&mut emu, LinuxSyscall::openat, &[at_fdcwd, buf, 0]);
+    assert_eq!(fd, 3);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::faccessat, &[at_fdcwd, buf, 0]), 0);
+
+    // fstat through the fd
+    write_path(&mut emu, buf, b"");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::newfstatat, &[fd, buf, buf + 0x100, AT_EMPTY_PATH]), 0);
+    let mode = emu.core.mem.read::<u32>(VirtAddr(buf as usize + 0x100 + 16)).unwrap();
+    assert_eq!(mode & 0o170000, S_IFREG);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(buf as usize + 0x100 + 48)).unwrap(), 0x1800);
+
+    // reads advance the offset
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 0x10]), 0x10);
+    assert_eq!(read_bytes(&mut emu, buf, 0x10), &content[..0x10]);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 0x10]), 0x10);
+    assert_eq!(read_bytes(&mut emu, buf, 0x10), &content[0x10..0x20]);
+
+    // file-backed mappings
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x2000, PROT_READ, MAP_PRIVATE, fd, 0x1000]);
+    assert_eq!(read_bytes(&mut emu, addr, 0x800), &content[0x1000..]);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(addr as usize + 0x800)).unwrap(), 0);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::mprotect, &[addr, 0x1000, PROT_READ | PROT_WRITE]), 0);
+    emu.core.mem.write::<u64>(VirtAddr(addr as usize), 1).unwrap();
+    assert!(emu.core.mem.write::<u64>(VirtAddr(addr as usize + 0x1000), 1).is_err());
+
+    // the lowest fd is reused
+    assert_eq!(syscall(&mut emu, LinuxSyscall::close, &[fd]), 0);
+    write_path(&mut emu, buf, b"/lib/libc.so.6");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::openat, &[at_fdcwd, buf, 0]), 3);
+
+    // errors
+    assert_eq!(syscall(&mut emu, LinuxSyscall::openat, &[at_fdcwd, buf, 2]), to_ret(EACCES));
+    write_path(&mut emu, buf, b"/lib/libm.so.6");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::openat, &[at_fdcwd, buf, 0]), to_ret(ENOENT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::faccessat, &[at_fdcwd, buf, 0]), to_ret(ENOENT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::newfstatat, &[at_fdcwd, buf, buf + 0x100, 0]), to_ret(ENOENT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::openat, &[at_fdcwd, 0, 0]), to_ret(EFAULT));
+}
+
+#[test]
+fn test_readlink() {
+    let mut emu = new_emu();
+    emu.vfs.add_link(b"/proc/self/exe", b"/test_fuzz");
+    let buf = buffer(&mut emu);
+    write_path(&mut emu, buf, b"/proc/self/exe");
+    let at_fdcwd = AT_FDCWD as u64;
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::readlinkat, &[at_fdcwd, buf, buf + 0x100, 0x100]), 10);
+    assert_eq!(read_bytes(&mut emu, buf + 0x100, 10), b"/test_fuzz");
+    // truncated
+    assert_eq!(syscall(&mut emu, LinuxSyscall::readlinkat, &[at_fdcwd, buf, buf + 0x200, 4]), 4);
+    assert_eq!(read_bytes(&mut emu, buf + 0x200, 4), b"/tes");
+    write_path(&mut emu, buf, b"/proc/self/cwd");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::readlinkat, &[at_fdcwd, buf, buf + 0x100, 0x100]), to_ret(ENOENT));
+}
+
+#[test]
+fn test_process() {
+    let mut emu = new_emu();
+    let buf = buffer(&mut emu);
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::set_tid_address, &[buf]), GUEST_PID);
+    assert_eq!(emu.threading.current().clear_child_tid, buf);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::set_robust_list, &[buf, 24]), 0);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::set_robust_list, &[buf, 8]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::rseq, &[buf, 32, 0, 0]), to_ret(ENOSYS));
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::prlimit64, &[0, RLIMIT_STACK, 0, buf]), 0);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(buf as usize)).unwrap(), 8 << 20);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(buf as usize + 8)).unwrap(), 8 << 20);
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::newuname, &[buf]), 0);
+    assert_eq!(read_bytes(&mut emu, buf, 6), b"Linux\0");
+    assert_eq!(read_bytes(&mut emu, buf + 4 * 65, 8), b"riscv64\0");
+
+    emu.core.write_reg(Register::A7, LinuxSyscall::exit_group as u64);
+    emu.core.write_reg(Register::A0, 42);
+    emu.core.pc = CODE;
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(42)));
+}
+
+#[test]
+fn test_huge_buffers() {
+    let mut emu = new_emu();
+    let buf = buffer(&mut emu);
+
+    // the copy stops at the end of the mapping
+    for count in [u64::MAX, 1 << 40] {
+        assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, buf, count]), 0x1000);
+        assert_eq!(syscall(&mut emu, LinuxSyscall::getrandom, &[buf, count, 0]), 0x1000);
+    }
+    assert_eq!(emu.vfs.stdout.len(), 0x2000);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, buf + 0x1000, u64::MAX]), to_ret(EFAULT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getrandom, &[buf + 0x1000, u64::MAX, 0]), to_ret(EFAULT));
+}
+
+#[test]
+fn test_getrandom_is_deterministic() {
+    let mut emu = new_emu();
+    let buf = buffer(&mut emu);
+    let start = emu.fork();
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getrandom, &[buf, 13, 0]), 13);
+    let first = read_bytes(&mut emu, buf, 13);
+    assert_ne!(first, [0; 13]);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getrandom, &[buf, 13, 0]), 13);
+    assert_ne!(read_bytes(&mut emu, buf, 13), first);
+
+    // a reset emulator sees the same bytes
+    emu.reset(&start);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::getrandom, &[buf, 13, 0]), 13);
+    assert_eq!(read_bytes(&mut emu, buf, 13), first);
+}
@@ -0,0 +1,313 @@
+//! Tests of the threads of [`LinuxEmu`] and of their scheduler
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+use emu::riscv64gc::Register::*;
+use mmu::VirtAddr;
+
+mod common;
+use common::*;
+
+/// Where the child returns its tid, zeroed when it exits
+const CHILD_TID: u64 = DATA + 0x20;
+/// The counter incremented by the racy programs
+const COUNTER: u64 = DATA + 0x28;
+/// Where each thread writes its tid and `tp`, indexed by the tid
+const TIDS: u64 = DATA + 0x100;
+
+fn syscall_inst(program: &mut Program, nr: LinuxSyscall) {
+    program
+        .inst(AssemblerRV64GC.addi(A7, Zero, nr as i32))
+        .inst(AssemblerRV64GC.ecall());
+}
+
+/// A program that clones a thread, runs `body` on both threads with `s0`
+/// pointing to the data and `s1` zero in the child, and then joins the
+/// child and exits with code 7
+fn threaded(body: impl Fn(&mut Program)) -> LinuxEmu {
+    joined(body, |_| {})
+}
+
+/// Like [`threaded`], running `after` in the parent once it joined
+fn joined(body: impl Fn(&mut Program), after: impl Fn(&mut Program)) -> LinuxEmu {
+    let mut program = Program::default();
+    let flags = CLONE_VM | CLONE_SIGHAND | CLONE_THREAD | CLONE_SETTLS
+        | CLONE_PARENT_SETTID | CLONE_CHILD_SETTID | CLONE_CHILD_CLEARTID;
+    program
+        .inst(AssemblerRV64GC.lui(S0, DATA as u32))
+        .inst(AssemblerRV64GC.lui(A0, (flags as u32 + 0x800) & !0xfff))
+        .inst(AssemblerRV64GC.addi(A0, A0, (flags as i32) << 20 >> 20))
+        .inst(AssemblerRV64GC.addi(A1, S0, 0x400))
+        .inst(AssemblerRV64GC.addi(A2, S0, 0x10))
+        .inst(AssemblerRV64GC.addi(A3, Zero, 0x123))
+        .inst(AssemblerRV64GC.addi(A4, S0, 0x20));
+    syscall_inst(&mut program, LinuxSyscall::clone);
+    program.inst(AssemblerRV64GC.addi(S1, A0, 0));
+    body(&mut program);
+
+    // the child exits
+    program.inst(AssemblerRV64GC.bne(S1, Zero, 16));
+    program.inst(AssemblerRV64GC.addi(A0, Zero, 0));
+    syscall_inst(&mut program, LinuxSyscall::exit);
+
+    // the parent waits until the child tid is cleared
+    let join = program.code.len() as i32;
+    program
+        .inst(AssemblerRV64GC.lw(T0, S0, 0x20))
+        .inst(AssemblerRV64GC.beq(T0, Zero, 32))
+        .inst(AssemblerRV64GC.addi(A0, S0, 0x20))
+        .inst(AssemblerRV64GC.addi(A1, Zero, (FUTEX_WAIT | FUTEX_PRIVATE_FLAG) as i32))
+        .inst(AssemblerRV64GC.addi(A2, T0, 0))
+        .inst(AssemblerRV64GC.addi(A3, Zero, 0));
+    syscall_inst(&mut program, LinuxSyscall::futex);
+    let back = join - program.code.len() as i32;
+    program
+        .inst(AssemblerRV64GC.jal(Zero, back))
+        // acquire, like pthread_join
+        .inst(AssemblerRV64GC.fence());
+    after(&mut program);
+    program
+        .inst(AssemblerRV64GC.addi(A0, Zero, 7))
+        .inst(AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit_group as i32));
+
+    let mut emu = LinuxEmu::new(mmu::Mmu::new());
+    emu.core = program.build();
+    emu.core.set_instruction_budget(1_000_000);
+    emu
+}
+
+/// Both threads increment the counter `count` times without atomics
+fn racy(count: i32) -> LinuxEmu {
+    threaded(|program| {
+        program.inst(AssemblerRV64GC.addi(S2, Zero, count));
+        let start = program.code.len() as i32;
+        program
+            .inst(AssemblerRV64GC.ld(T0, S0, 0x28))
+            .inst(AssemblerRV64GC.addi(T0, T0, 1))
+            .inst(AssemblerRV64GC.sd(S0, T0, 0x28))
+            .inst(AssemblerRV64GC.addi(S2, S2, -1));
+        let back = start - program.code.len() as i32;
+        program.inst(AssemblerRV64GC.bne(S2, Zero, back));
+    })
+}
+
+fn read_u64(emu: &mut LinuxEmu, addr: u64) -> u64 {
+    emu.core.mem.read::<u64>(VirtAddr(addr as usize)).unwrap()
+}
+
+#[test]
+fn test_clone_and_join() {
+    let mut emu = threaded(|program| {
+        syscall_inst(program, LinuxSyscall::gettid);
+        program
+            .inst(AssemblerRV64GC.addi(T0, A0, -(GUEST_PID as i32)))
+            .inst(AssemblerRV64GC.slli(T0, T0, 4))
+            .inst(AssemblerRV64GC.add(T0, T0, S0))
+            .inst(AssemblerRV64GC.sd(T0, A0, 0x100))
+            .inst(AssemblerRV64GC.sd(T0, Tp, 0x108));
+    });
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    let child = GUEST_PID + 1;
+    assert_eq!(emu.core.mem.read::<u32>(VirtAddr(DATA as usize + 0x10)).unwrap() as u64, child);
+    // set by CLONE_CHILD_SETTID and cleared on exit
+    assert_eq!(emu.core.mem.read::<u32>(VirtAddr(CHILD_TID as usize)).unwrap(), 0);
+    assert_eq!(read_u64(&mut emu, TIDS), GUEST_PID);
+    assert_eq!(read_u64(&mut emu, TIDS + 8), 0);
+    assert_eq!(read_u64(&mut emu, TIDS + 0x10), child);
+    assert_eq!(read_u64(&mut emu, TIDS + 0x18), 0x123);
+    assert_eq!(emu.threading.threads.len(), 1);
+    assert_eq!(emu.threading.current().tid, GUEST_PID);
+    assert!(emu.threading.switches > 0);
+}
+
+#[test]
+fn test_replay() {
+    let run = |seed| {
+        let mut emu = racy(100);
+        emu.threading.set_seed(seed);
+        assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+        (read_u64(&mut emu, COUNTER), emu.core.instructions_executed, emu.threading.switches)
+    };
+    let runs: Vec<_> = (0..16).map(run).collect();
+    for (seed, expected) in runs.iter().enumerate() {
+        assert_eq!(run(seed as u64), *expected);
+    }
+    // the seeds give different schedules, and switching at every memory
+    // access loses some increments
+    assert!(runs.iter().any(|(_, _, switches)| *switches != runs[0].2));
+    assert!(runs.iter().any(|(counter, _, _)| *counter < 200));
+
+    // the forks replay the same way
+    let mut emu = racy(100);
+    emu.threading.set_seed(3);
+    let snapshot = emu.fork();
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    emu.reset(&snapshot);
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    assert_eq!(read_u64(&mut emu, COUNTER), runs[3].0);
+}
+
+#[test]
+fn test_quantum() {
+    // the threads only switch at the end of their quantum, which is longer
+    // than the loop, or when the parent blocks
+    for seed in 0..16 {
+        let mut emu = racy(100);
+        emu.threading.set_seed(seed);
+        emu.threading.memory_yields = false;
+        emu.threading.quantum = 10_000;
+        assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+        assert_eq!(read_u64(&mut emu, COUNTER), 200);
+    }
+
+    // a thread that never blocks is preempted anyway
+    let mut emu = racy(2000);
+    emu.threading.memory_yields = false;
+    emu.threading.quantum = 100;
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    assert!(emu.threading.switches >= 100);
+
+    // the JIT is preempted at the same instructions
+    #[cfg(all(feature="jit", target_arch="x86_64", target_os="linux"))]
+    {
+        let mut jit = racy(2000);
+        jit.core.backend = Backend::Jit;
+        jit.threading.memory_yields = false;
+        jit.threading.quantum = 100;
+        assert!(matches!(jit.run(), LinuxEmuError::Exit(7)));
+        assert!(!jit.core.jit.is_empty());
+        assert_eq!(jit.core.instructions_executed, emu.core.instructions_executed);
+        assert_eq!(jit.threading.switches, emu.threading.switches);
+        assert_eq!(read_u64(&mut jit, COUNTER), read_u64(&mut emu, COUNTER));
+    }
+
+    // the budget still stops the process
+    let mut emu = racy(2000);
+    emu.core.set_instruction_budget(10_000);
+    assert!(matches!(emu.run(), LinuxEmuError::Timeout));
+    assert_eq!(emu.core.instructions_executed, 10_000);
+}
+
+#[test]
+fn test_futex() {
+    let mut emu = new_emu();
+    let word = BRK;
+    syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x1000]);
+    emu.core.mem.write::<u32>(VirtAddr(word as usize), 5).unwrap();
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::gettid, &[]), GUEST_PID);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word, FUTEX_WAIT, 4, 0]), to_ret(EAGAIN));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word + 1, FUTEX_WAIT, 5, 0]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[0, FUTEX_WAIT, 5, 0]), to_ret(EFAULT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word, FUTEX_WAKE, 1, 0]), 0);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word, FUTEX_WAKE_BITSET, 1, 0, 0, 0]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word, 5, 1, 0]), to_ret(ENOSYS));
+    // a timed wait times out as nothing else can run
+    assert_eq!(syscall(&mut emu, LinuxSyscall::futex, &[word, FUTEX_WAIT_BITSET, 5, BRK + 8, 0, 1]), to_ret(ETIMEDOUT));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::sched_yield, &[]), 0);
+
+    // processes can't be created
+    assert_eq!(syscall(&mut emu, LinuxSyscall::clone, &[0, 0, 0, 0, 0]), to_ret(ENOSYS));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::clone3, &[0, 0]), to_ret(ENOSYS));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::clone, &[CLONE_THREAD, 0, 0, 0, 0]), to_ret(EINVAL));
+
+    // waiting forever with nobody to wake the thread
+    emu.core.write_reg(A0, word);
+    emu.core.write_reg(A1, FUTEX_WAIT | FUTEX_PRIVATE_FLAG);
+    emu.core.write_reg(A2, 5);
+    emu.core.write_reg(A3, 0);
+    emu.core.write_reg(A7, LinuxSyscall::futex as u64);
+    emu.core.pc = CODE;
+    assert!(matches!(emu.run(), LinuxEmuError::Deadlock));
+}
+
+#[test]
+fn test_memory_yields() {
+    let mut core = Program::default()
+        .c_inst(AssemblerRV64GC.c_addi4spn(A1, 16))
+        .c_inst(AssemblerRV64GC.c_lw(A0, A1, 0))
+        .c_inst(AssemblerRV64GC.c_sdsp(A0, 8))
+        .c_inst(AssemblerRV64GC.c_ldsp(A2, 8))
+        .c_inst(AssemblerRV64GC.c_slli(A2, 1))
+        .c_inst(AssemblerRV64GC.c_mv(A3, A2))
+        .inst(AssemblerRV64GC.amoadd_w(A4, A1, A2, false, false))
+        .inst(AssemblerRV64GC.addi(A5, A4, 1))
+        .build();
+    core.memory_yields = true;
+    // after every access, the decoded blocks too
+    let snapshot = core.fork();
+    for _ in 0..2 {
+        let mut stops = Vec::new();
+        loop {
+            match core.run() {
+                CoreEmuError::Yield => stops.push(core.pc - CODE),
+                CoreEmuError::Syscall => break,
+                e => panic!("unexpected stop {:?}", e),
+            }
+        }
+        assert_eq!(stops, [4, 6, 8, 16]);
+        assert_eq!(core.pc - CODE, 24);
+        core.reset(&snapshot);
+    }
+}
+
+#[test]
+fn test_race_detector() {
+    let detect = |mut emu: LinuxEmu, seed| {
+        emu.threading.set_seed(seed);
+        emu.core.race_detector = Some(RaceDetector::new(GUEST_PID));
+        emu.run()
+    };
+
+    // the increments race, whoever gets there first
+    let loop_pcs = CODE..CODE + 0x80;
+    for seed in 0..16 {
+        let LinuxEmuError::DataRace(race) = detect(racy(100), seed) else {
+            panic!("no race with seed {}", seed);
+        };
+        assert_eq!(race.addr, COUNTER);
+        assert_eq!(race.size, 8);
+        assert_ne!(race.tid, race.previous_tid);
+        assert!([GUEST_PID, GUEST_PID + 1].contains(&race.tid));
+        assert!([GUEST_PID, GUEST_PID + 1].contains(&race.previous_tid));
+        assert!(race.is_write || race.previous_is_write);
+        assert!(loop_pcs.contains(&race.pc) && loop_pcs.contains(&race.previous_pc));
+        // and it's found at the same access every time
+        assert!(matches!(detect(racy(100), seed), LinuxEmuError::DataRace(other) if other == race));
+    }
+
+    // the atomic increments synchronize
+    let atomic = || threaded(|program| {
+        program
+            .inst(AssemblerRV64GC.addi(S2, Zero, 100))
+            .inst(AssemblerRV64GC.addi(T1, Zero, 1))
+            .inst(AssemblerRV64GC.addi(T2, S0, 0x28))
+            .inst(AssemblerRV64GC.amoadd_d(Zero, T2, T1, true, true))
+            .inst(AssemblerRV64GC.addi(S2, S2, -1))
+            .inst(AssemblerRV64GC.bne(S2, Zero, -8));
+    });
+    // the child writes a result that the parent reads after the join
+    let result = || joined(|program| {
+        program
+            .inst(AssemblerRV64GC.bne(S1, Zero, 8))
+            .inst(AssemblerRV64GC.sd(S0, S0, 0x30));
+    }, |program| {
+        program.inst(AssemblerRV64GC.ld(T0, S0, 0x30));
+    });
+    for seed in 0..16 {
+        let mut emu = atomic();
+        emu.threading.set_seed(seed);
+        emu.core.race_detector = Some(RaceDetector::new(GUEST_PID));
+        assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+        assert_eq!(read_u64(&mut emu, COUNTER), 200);
+        assert!(matches!(detect(result(), seed), LinuxEmuError::Exit(7)));
+    }
+
+    // the shadow is reset with the memory, so the forks replay the same way
+    let mut emu = racy(100);
+    emu.threading.set_seed(5);
+    emu.core.race_detector = Some(RaceDetector::new(GUEST_PID));
+    let snapshot = emu.fork();
+    let LinuxEmuError::DataRace(race) = emu.run() else { panic!() };
+    emu.reset(&snapshot);
+    assert!(matches!(emu.run(), LinuxEmuError::DataRace(other) if other == race));
+}
@@ -0,0 +1,107 @@
+//! Tests of the syscall tracer of [`LinuxEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+use mmu::VirtAddr;
+
+mod common;
+use common::*;
+
+/// An emulator with a tracer and a 0x100 bytes buffer at `BRK`
+fn traced_emu() -> LinuxEmu {
+    let mut emu = new_emu();
+    emu.tracer = Some(SyscallTracer::default());
+    syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x100]);
+    emu.core.mem.write_slice(VirtAddr(BRK as usize), &[0; 0x100]).unwrap();
+    emu.tracer.as_mut().unwrap().clear();
+    emu
+}
+
+fn lines(emu: &LinuxEmu) -> Vec<String> {
+    emu.tracer.as_ref().unwrap().events.iter()
+        .map(|event| event.to_string())
+        .collect()
+}
+
+#[test]
+fn test_events() {
+    let mut emu = traced_emu();
+    emu.vfs.add_file(b"/etc/passwd", b"root:x:0:0:root:/root:/bin/sh\n".to_vec());
+    emu.core.mem.write_slice(VirtAddr(BRK as usize), b"/etc/passwd\0").unwrap();
+    emu.core.mem.write_slice(VirtAddr(BRK as usize + 0x10), b"/missing\0").unwrap();
+
+    let fd = syscall(&mut emu, LinuxSyscall::openat, &[AT_FDCWD as u64, BRK, O_RDONLY]);
+    syscall(&mut emu, LinuxSyscall::read, &[fd, BRK + 0x20, 4]);
+    syscall(&mut emu, LinuxSyscall::write, &[1, BRK + 0x20, 5]);
+    syscall(&mut emu, LinuxSyscall::openat, &[AT_FDCWD as u64, BRK + 0x10, O_RDONLY]);
+    syscall(&mut emu, LinuxSyscall::close, &[9]);
+
+    assert_eq!(lines(&emu), [
+        "[0000000000010000] openat(AT_FDCWD, \"/etc/passwd\", 0x0, 0x0) = 3",
+        "[0000000000010000] read(3, \"root\", 4, 0) = 4",
+        "[0000000000010000] write(1, \"root\\x00\", 5) = 5",
+        "[0000000000010000] openat(AT_FDCWD, \"/missing\", 0x0, 0x0) = -1 ENOENT",
+        "[0000000000010000] close(9) = -1 EBADF",
+    ]);
+
+    let event = &emu.tracer.as_ref().unwrap().events[1];
+    assert_eq!(event.syscall(), Some(LinuxSyscall::read));
+    assert_eq!(event.number, LinuxSyscall::read as u64);
+    assert_eq!(event.ret, Some(4));
+    assert_eq!(event.args[1], SyscallArg::Buf { data: b"root".to_vec(), truncated: false });
+}
+
+#[test]
+fn test_truncation() {
+    let mut emu = traced_emu();
+    let long = [b'a'; 0x40];
+    emu.core.mem.write_slice(VirtAddr(BRK as usize), &long).unwrap();
+
+    syscall(&mut emu, LinuxSyscall::write, &[2, BRK, 0x40]);
+    // unreadable pointers are printed as such
+    syscall(&mut emu, LinuxSyscall::faccessat, &[AT_FDCWD as u64, 0x1337, 0]);
+    assert_eq!(lines(&emu), [
+        format!("[0000000000010000] write(2, \"{}\"..., 64) = 64", "a".repeat(STRING_LIMIT)),
+        "[0000000000010000] faccessat(AT_FDCWD, 0x1337, 0x0) = -1 EFAULT".to_string(),
+    ]);
+}
+
+#[test]
+fn test_no_return() {
+    let mut emu = traced_emu();
+    emu.unknown_syscall_policy = UnknownSyscallPolicy::Stop;
+    emu.core.write_reg(Register::A0, 3);
+    emu.core.write_reg(Register::A7, 1000);
+    emu.core.pc = CODE;
+    assert!(matches!(emu.run(), LinuxEmuError::BadSyscall(1000)));
+
+    emu.core.write_reg(Register::A7, LinuxSyscall::exit_group as u64);
+    emu.core.pc = CODE;
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(3)));
+
+    assert_eq!(lines(&emu), [
+        "[0000000000010000] syscall_0x3e8(0x3, 0x0, 0x0, 0x0, 0x0, 0x0) = ?",
+        "[0000000000010000] exit_group(3) = ?",
+    ]);
+}
+
+#[test]
+fn test_limit_and_disable() {
+    let mut emu = traced_emu();
+    emu.tracer = Some(SyscallTracer::new(2));
+    for _ in 0..3 {
+        syscall(&mut emu, LinuxSyscall::getpid, &[]);
+    }
+    syscall(&mut emu, LinuxSyscall::rseq, &[0, 0x20, 0, 0]);
+    let tracer = emu.tracer.as_ref().unwrap();
+    assert_eq!(tracer.events.len(), 2);
+    assert_eq!(tracer.to_string(), format!(
+        "[0000000000010000] getpid() = {}\n\
+         [0000000000010000] rseq(NULL, 0x20, 0x0, 0x0) = -1 ENOSYS\n",
+        GUEST_PID,
+    ));
+    assert_eq!(tracer.events[1].ret, Some(to_ret(ENOSYS)));
+
+    emu.tracer = None;
+    syscall(&mut emu, LinuxSyscall::getpid, &[]);
+    assert!(emu.tracer.is_none());
+}
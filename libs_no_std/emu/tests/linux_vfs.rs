@@ -0,0 +1,137 @@
+//! Tests of the in-memory filesystem of [`LinuxEmu`]
+use std::sync::Arc;
+use emu::riscv64gc::*;
+use emu::riscv64gc::errno::*;
+use emu::riscv64gc::mman::*;
+use mmu::VirtAddr;
+
+mod common;
+use common::*;
+
+/// Open `path` in the guest and return the fd
+fn open(emu: &mut LinuxEmu, buf: u64, path: &[u8]) -> u64 {
+    emu.core.mem.write_slice(VirtAddr(buf as usize), path).unwrap();
+    emu.core.mem.write::<u8>(VirtAddr(buf as usize + path.len()), 0).unwrap();
+    syscall(emu, LinuxSyscall::openat, &[AT_FDCWD as u64, buf, O_RDONLY])
+}
+
+#[test]
+fn test_input_path() {
+    let mut emu = new_emu();
+    emu.vfs.set_input_path(b"/input");
+    emu.vfs.set_input(b"fuzz me");
+    let buf = buffer(&mut emu);
+
+    let fd = open(&mut emu, buf, b"/input");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::newfstat, &[fd, buf]), 0);
+    assert_eq!(emu.core.mem.read::<u64>(VirtAddr(buf as usize + 48)).unwrap(), 7);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 0x100]), 7);
+    assert_eq!(read_bytes(&mut emu, buf, 7), b"fuzz me");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 0x100]), 0);
+
+    // the new input is seen by the next open
+    emu.vfs.set_input(b"again");
+    let fd = open(&mut emu, buf, b"/input");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 0x100]), 5);
+    assert_eq!(read_bytes(&mut emu, buf, 5), b"again");
+}
+
+#[test]
+fn test_input_fd() {
+    let mut emu = new_emu();
+    emu.vfs.set_input_fd(0);
+    emu.vfs.set_input(b"from stdin");
+    let buf = buffer(&mut emu);
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[0, buf, 4]), 4);
+    assert_eq!(read_bytes(&mut emu, buf, 4), b"from");
+    // the input can also be mapped
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, PROT_READ, MAP_PRIVATE, 0, 0]);
+    assert_eq!(read_bytes(&mut emu, addr, 10), b"from stdin");
+
+    // on a new fd
+    emu.vfs.set_input_fd(5);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[5, buf, 0x100]), 10);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[4, buf, 0x100]), to_ret(EBADF));
+}
+
+#[test]
+fn test_lseek_pread() {
+    let mut emu = new_emu();
+    emu.vfs.add_file(b"/etc/config", b"0123456789".to_vec());
+    let buf = buffer(&mut emu);
+    let fd = open(&mut emu, buf, b"/etc/config");
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, 4, SEEK_SET]), 4);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, 2, SEEK_CUR]), 6);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 2]), 2);
+    assert_eq!(read_bytes(&mut emu, buf, 2), b"67");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, -3_i64 as u64, SEEK_END]), 7);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, 0x100, SEEK_END]), 0x10a);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, buf, 2]), 0);
+
+    // pread doesn't move the offset
+    assert_eq!(syscall(&mut emu, LinuxSyscall::pread64, &[fd, buf, 3, 1]), 3);
+    assert_eq!(read_bytes(&mut emu, buf, 3), b"123");
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, 0, SEEK_CUR]), 0x10a);
+
+    // errors
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, -1_i64 as u64, SEEK_SET]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[fd, 0, 7]), to_ret(EINVAL));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[1, 0, SEEK_SET]), to_ret(ESPIPE));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::pread64, &[0, buf, 3, 0]), to_ret(ESPIPE));
+    assert_eq!(syscall(&mut emu, LinuxSyscall::lseek, &[9, 0, SEEK_SET]), to_ret(EBADF));
+}
+
+#[test]
+fn test_iovec() {
+    let mut emu = new_emu();
+    emu.vfs.add_file(b"/data", b"abcdef".to_vec());
+    let buf = buffer(&mut emu);
+    let fd = open(&mut emu, buf, b"/data");
+
+    // two iovecs of 4 bytes at buf + 0x100 and buf + 0x200
+    let iov = buf + 0x10;
+    for (i, base) in [buf + 0x100, buf + 0x200].iter().enumerate() {
+        emu.core.mem.write::<u64>(VirtAddr((iov + i as u64 * 16) as usize), *base).unwrap();
+        emu.core.mem.write::<u64>(VirtAddr((iov + i as u64 * 16 + 8) as usize), 4).unwrap();
+    }
+    assert_eq!(syscall(&mut emu, LinuxSyscall::readv, &[fd, iov, 2]), 6);
+    assert_eq!(read_bytes(&mut emu, buf + 0x100, 4), b"abcd");
+    assert_eq!(read_bytes(&mut emu, buf + 0x200, 2), b"ef");
+
+    assert_eq!(syscall(&mut emu, LinuxSyscall::writev, &[1, iov, 2]), 8);
+    assert_eq!(emu.vfs.stdout, b"abcdef\0\0");
+}
+
+#[test]
+fn test_reset() {
+    let mut start = Vfs::new();
+    start.add_file(b"/lib/libc.so.6", vec![0x7f; 0x1000]);
+    start.set_input_path(b"/input");
+    start.write(1, b"before the fork\n").unwrap();
+
+    let mut vfs = start.clone();
+    vfs.set_input(b"case");
+    let fd = vfs.open(b"/input", O_RDONLY).unwrap();
+    vfs.read(fd, 2, None).unwrap();
+    vfs.open(b"/lib/libc.so.6", O_RDONLY).unwrap();
+    vfs.close(0).unwrap();
+    vfs.write(1, b"after\n").unwrap();
+    vfs.write(2, b"oops\n").unwrap();
+
+    vfs.reset(&start);
+    assert_eq!(vfs.fds.len(), 3);
+    assert!(vfs.get(0).is_ok());
+    assert_eq!(vfs.stdout, b"before the fork\n");
+    assert!(vfs.stderr.is_empty());
+    // the files are shared and the input is kept
+    assert!(Arc::ptr_eq(&vfs.files, &start.files));
+    let fd = vfs.open(b"/input", O_RDONLY).unwrap();
+    assert_eq!(vfs.read(fd, 0x100, None).unwrap(), b"case");
+
+    // files added after the fork disappear
+    vfs.add_file(b"/tmp/new", vec![1]);
+    vfs.reset(&start);
+    assert_eq!(vfs.open(b"/tmp/new", O_RDONLY), Err(ENOENT));
+}
@@ -0,0 +1,217 @@
+//! Tests of the [`RaceDetector`] of [`CoreEmu`], the threads are simulated
+//! by switching the current one of the detector between runs
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use emu::riscv64gc::FloatRegister::*;
+
+mod common;
+use common::*;
+
+const WRITE: u64 = 0;
+const READ: u64 = 8;
+const WRITE_BYTE_0: u64 = 16;
+const WRITE_BYTE_1: u64 = 24;
+const ATOMIC: u64 = 32;
+const FENCE: u64 = 40;
+const READ_BYTE_0: u64 = 48;
+
+/// Snippets ending with an `ecall` at the offsets above, accessing `DATA`
+/// with `s0` and `DATA + 0x10` with `t1`
+fn new_core() -> CoreEmu {
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.sd(S0, A0, 0))
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.ld(T0, S0, 0))
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.sb(S0, A0, 0))
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.sb(S0, A0, 1))
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.amoadd_w(Zero, T1, A0, true, true))
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.fence())
+        .inst(AssemblerRV64GC.ecall())
+        .inst(AssemblerRV64GC.lbu(T0, S0, 0))
+        .build();
+    core.write_reg(S0, DATA);
+    core.write_reg(T1, DATA + 0x10);
+    core.race_detector = Some(RaceDetector::new(1));
+    core
+}
+
+/// Run the snippet at `offset` as the thread `tid`
+fn run_as(core: &mut CoreEmu, tid: u64, offset: u64) -> Result<(), DataRace> {
+    core.race_detector.as_mut().unwrap().set_current(tid);
+    core.pc = CODE + offset;
+    loop {
+        match core.run() {
+            // the fences yield
+            CoreEmuError::Yield => continue,
+            CoreEmuError::Syscall => return Ok(()),
+            CoreEmuError::DataRace(race) => return Err(race),
+            e => panic!("unexpected stop {:?}", e),
+        }
+    }
+}
+
+#[test]
+fn test_decode() {
+    let mut regs = [0; 32];
+    regs[A1 as usize] = DATA + 0x100;
+    regs[Sp as usize] = DATA + 0x800;
+    let access = |inst: u32, addr: u64, size: u64, kind: AccessKind| {
+        assert_eq!(MemoryAccess::decode(inst, &regs), Some(MemoryAccess { addr, size, kind }),
+            "{:08x}", inst);
+    };
+    let base = DATA + 0x100;
+    let sp = DATA + 0x800;
+
+    access(AssemblerRV64GC.lbu(A0, A1, -2048).unwrap(), base - 2048, 1, AccessKind::Read);
+    access(AssemblerRV64GC.ld(A0, A1, 2047).unwrap(), base + 2047, 8, AccessKind::Read);
+    access(AssemblerRV64GC.flw(FT0, A1, 12).unwrap(), base + 12, 4, AccessKind::Read);
+    access(AssemblerRV64GC.sh(A1, A0, -3).unwrap(), base - 3, 2, AccessKind::Write);
+    access(AssemblerRV64GC.sd(A1, A0, 0x7ff).unwrap(), base + 0x7ff, 8, AccessKind::Write);
+    access(AssemblerRV64GC.fsd(A1, FT1, -0x800).unwrap(), base - 0x800, 8, AccessKind::Write);
+    access(AssemblerRV64GC.amoadd_w(A0, A1, A2, false, false).unwrap(), base, 4, AccessKind::Atomic);
+    access(AssemblerRV64GC.lr_d(A0, A1, true, false).unwrap(), base, 8, AccessKind::Atomic);
+
+    // every bit of the compressed offsets
+    access(AssemblerRV64GC.c_lw(A0, A1, 0x7c).unwrap() as u32, base + 0x7c, 4, AccessKind::Read);
+    access(AssemblerRV64GC.c_ld(A0, A1, 0xf8).unwrap() as u32, base + 0xf8, 8, AccessKind::Read);
+    access(AssemblerRV64GC.c_fld(FS0, A1, 0xa8).unwrap() as u32, base + 0xa8, 8, AccessKind::Read);
+    access(AssemblerRV64GC.c_sw(A1, A0, 0x44).unwrap() as u32, base + 0x44, 4, AccessKind::Write);
+    access(AssemblerRV64GC.c_sd(A1, A0, 0xf8).unwrap() as u32, base + 0xf8, 8, AccessKind::Write);
+    access(AssemblerRV64GC.c_lwsp(A0, 0xfc).unwrap() as u32, sp + 0xfc, 4, AccessKind::Read);
+    access(AssemblerRV64GC.c_ldsp(A0, 0x1f8).unwrap() as u32, sp + 0x1f8, 8, AccessKind::Read);
+    access(AssemblerRV64GC.c_fldsp(FT0, 0x128).unwrap() as u32, sp + 0x128, 8, AccessKind::Read);
+    access(AssemblerRV64GC.c_swsp(A0, 0xfc).unwrap() as u32, sp + 0xfc, 4, AccessKind::Write);
+    access(AssemblerRV64GC.c_sdsp(A0, 0x1f8).unwrap() as u32, sp + 0x1f8, 8, AccessKind::Write);
+    access(AssemblerRV64GC.c_fsdsp(FT0, 0xd0).unwrap() as u32, sp + 0xd0, 8, AccessKind::Write);
+
+    for inst in [
+        AssemblerRV64GC.addi(A0, A1, 8).unwrap(),
+        AssemblerRV64GC.fence().unwrap(),
+        AssemblerRV64GC.c_addi4spn(A1, 16).unwrap() as u32,
+        AssemblerRV64GC.c_mv(A0, A1).unwrap() as u32,
+        AssemblerRV64GC.c_addi(A0, 1).unwrap() as u32,
+    ] {
+        assert_eq!(MemoryAccess::decode(inst, &regs), None, "{:08x}", inst);
+    }
+}
+
+#[test]
+fn test_conflicts() {
+    // write after write
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    let race = run_as(&mut core, 2, WRITE).unwrap_err();
+    assert_eq!(race, DataRace {
+        addr: DATA,
+        size: 8,
+        pc: CODE + WRITE,
+        tid: 2,
+        is_write: true,
+        previous_pc: CODE + WRITE,
+        previous_tid: 1,
+        previous_is_write: true,
+    });
+
+    // read after write and write after read
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    let race = run_as(&mut core, 2, READ).unwrap_err();
+    assert!(!race.is_write && race.previous_is_write);
+    let mut core = new_core();
+    run_as(&mut core, 1, READ).unwrap();
+    let race = run_as(&mut core, 2, WRITE).unwrap_err();
+    assert!(race.is_write && !race.previous_is_write);
+    assert_eq!(race.previous_pc, CODE + READ);
+
+    // the reads don't conflict with each other, nor the accesses of the
+    // same thread
+    let mut core = new_core();
+    run_as(&mut core, 1, READ).unwrap();
+    run_as(&mut core, 2, READ).unwrap();
+    run_as(&mut core, 3, WRITE).unwrap_err();
+    let mut core = new_core();
+    run_as(&mut core, 1, READ).unwrap();
+    run_as(&mut core, 1, WRITE).unwrap();
+    run_as(&mut core, 1, READ).unwrap();
+
+    // different bytes of the same word don't conflict
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE_BYTE_0).unwrap();
+    run_as(&mut core, 2, WRITE_BYTE_1).unwrap();
+    let race = run_as(&mut core, 3, WRITE).unwrap_err();
+    assert_eq!(race.previous_tid, 2);
+
+    // the atomics synchronize but don't race
+    let mut core = new_core();
+    run_as(&mut core, 1, ATOMIC).unwrap();
+    run_as(&mut core, 2, ATOMIC).unwrap();
+}
+
+#[test]
+fn test_synchronization() {
+    // a write released by an atomic and acquired by another
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    run_as(&mut core, 1, ATOMIC).unwrap();
+    run_as(&mut core, 2, ATOMIC).unwrap();
+    run_as(&mut core, 2, READ).unwrap();
+    run_as(&mut core, 2, WRITE).unwrap();
+    // the thread 1 did not acquire the write of 2
+    run_as(&mut core, 1, READ).unwrap_err();
+
+    // a byte released keeps its clock when the next one is written after
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE_BYTE_0).unwrap();
+    run_as(&mut core, 1, ATOMIC).unwrap();
+    run_as(&mut core, 1, WRITE_BYTE_1).unwrap();
+    run_as(&mut core, 2, ATOMIC).unwrap();
+    run_as(&mut core, 2, READ_BYTE_0).unwrap();
+    let race = run_as(&mut core, 2, READ).unwrap_err();
+    assert_eq!(race.previous_pc, CODE + WRITE_BYTE_1);
+
+    // the same with the fences
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    run_as(&mut core, 1, FENCE).unwrap();
+    run_as(&mut core, 2, FENCE).unwrap();
+    run_as(&mut core, 2, WRITE).unwrap();
+
+    // a thread knows what its parent did before creating it, but not after
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    core.race_detector.as_mut().unwrap().spawn(2);
+    run_as(&mut core, 2, READ).unwrap();
+    run_as(&mut core, 1, WRITE).unwrap_err();
+
+    // the woken threads acquire what the waker released on the futex
+    let mut core = new_core();
+    run_as(&mut core, 2, WRITE).unwrap();
+    core.race_detector.as_mut().unwrap().release(DATA + 0x20);
+    core.race_detector.as_mut().unwrap().acquire_for(1, DATA + 0x20);
+    run_as(&mut core, 1, WRITE).unwrap();
+}
+
+#[test]
+fn test_reset() {
+    let mut core = new_core();
+    run_as(&mut core, 1, WRITE).unwrap();
+    let snapshot = core.fork();
+    let race = run_as(&mut core, 2, WRITE).unwrap_err();
+    run_as(&mut core, 3, READ).unwrap_err();
+
+    // the shadow and the clocks are back to the snapshot
+    core.reset(&snapshot);
+    assert_eq!(run_as(&mut core, 2, WRITE), Err(race));
+    core.reset(&snapshot);
+    run_as(&mut core, 1, READ).unwrap();
+
+    // the detector can be turned off
+    core.reset(&snapshot);
+    core.race_detector = None;
+    core.pc = CODE + WRITE;
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+}
@@ -0,0 +1,151 @@
+//! Tests of the RV64A atomic instructions and of the LR/SC reservation set
+use emu::riscv64gc::*;
+use mmu::{Mmu, PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+type AmoInst = for<'a> fn(&'a mut AssemblerRV64GC, Register, Register, Register, bool, bool)
+    -> Result<u32, &'a str>;
+
+/// Run `inst` with `A1 = DATA`, `A2 = b` and `M[DATA] = a`, return the value
+/// written to `rd` and the new memory value
+fn run_amo(inst: AmoInst, a: u64, b: u64) -> (u64, u64) {
+    let mut core = Program::default()
+        .inst(inst(&mut AssemblerRV64GC, Register::A0, Register::A1, Register::A2, false, false))
+        .run(|core| {
+            core.mem.write(VirtAddr(DATA as usize), a).unwrap();
+            core.write_reg(Register::A1, DATA);
+            core.write_reg(Register::A2, b);
+        });
+    let mem: u64 = core.mem.read(VirtAddr(DATA as usize)).unwrap();
+    (core.read_reg(Register::A0), mem)
+}
+
+#[test]
+fn test_encoding() {
+    // reference encodings, funct7 is funct5 followed by aq and rl
+    assert_eq!(AssemblerRV64GC.lr_w(Register::A0, Register::A1, false, false), Ok(0x1005a52f));
+    assert_eq!(AssemblerRV64GC.lr_d(Register::A0, Register::A1, true, true), Ok(0x1605b52f));
+    assert_eq!(AssemblerRV64GC.sc_w(Register::A0, Register::A2, Register::A1, false, true), Ok(0x1ab6252f));
+    assert_eq!(AssemblerRV64GC.amoadd_d(Register::A0, Register::A2, Register::A1, true, false), Ok(0x04b6352f));
+    assert_eq!(AssemblerRV64GC.amomaxu_w(Register::A0, Register::A2, Register::A1, false, false), Ok(0xe0b6252f));
+}
+
+#[test]
+fn test_amo_d() {
+    let a = 0xffff_ffff_ffff_fff0;
+    let b = 0x10;
+    let cases: &[(AmoInst, u64)] = &[
+        (AssemblerRV64GC::amoswap_d, b),
+        (AssemblerRV64GC::amoadd_d,  0),
+        (AssemblerRV64GC::amoxor_d,  0xffff_ffff_ffff_ffe0),
+        (AssemblerRV64GC::amoand_d,  0x10),
+        (AssemblerRV64GC::amoor_d,   0xffff_ffff_ffff_fff0),
+        (AssemblerRV64GC::amomin_d,  a),
+        (AssemblerRV64GC::amomax_d,  b),
+        (AssemblerRV64GC::amominu_d, b),
+        (AssemblerRV64GC::amomaxu_d, a),
+    ];
+    for (i, &(inst, expected)) in cases.iter().enumerate() {
+        assert_eq!(run_amo(inst, a, b), (a, expected), "case {}", i);
+    }
+}
+
+#[test]
+fn test_amo_w() {
+    // the upper half of the memory must be left untouched and the loaded
+    // word sign-extended
+    let a = 0x1234_5678_8000_0000;
+    let b = 0xdead_beef_0000_0001;
+    let old = 0xffff_ffff_8000_0000;
+    let cases: &[(AmoInst, u64)] = &[
+        (AssemblerRV64GC::amoswap_w, 0x1234_5678_0000_0001),
+        (AssemblerRV64GC::amoadd_w,  0x1234_5678_8000_0001),
+        (AssemblerRV64GC::amoxor_w,  0x1234_5678_8000_0001),
+        (AssemblerRV64GC::amoand_w,  0x1234_5678_0000_0000),
+        (AssemblerRV64GC::amoor_w,   0x1234_5678_8000_0001),
+        (AssemblerRV64GC::amomin_w,  0x1234_5678_8000_0000),
+        (AssemblerRV64GC::amomax_w,  0x1234_5678_0000_0001),
+        (AssemblerRV64GC::amominu_w, 0x1234_5678_0000_0001),
+        (AssemblerRV64GC::amomaxu_w, 0x1234_5678_8000_0000),
+    ];
+    for (i, &(inst, expected)) in cases.iter().enumerate() {
+        assert_eq!(run_amo(inst, a, b), (old, expected), "case {}", i);
+    }
+}
+
+#[test]
+fn test_lr_sc() {
+    let mut core = Program::default()
+        // successful pair
+        .inst(AssemblerRV64GC.lr_d(Register::A0, Register::S0, true, false))
+        .inst(AssemblerRV64GC.sc_d(Register::A1, Register::S0, Register::S1, false, true))
+        // no reservation anymore
+        .inst(AssemblerRV64GC.sc_d(Register::A2, Register::S0, Register::S1, false, false))
+        // reservation on a different address
+        .inst(AssemblerRV64GC.lr_w(Register::A3, Register::S0, false, false))
+        .inst(AssemblerRV64GC.sc_w(Register::A4, Register::T0, Register::S1, false, false))
+        // the failed sc cleared the reservation too
+        .inst(AssemblerRV64GC.sc_w(Register::A5, Register::S0, Register::S1, false, false))
+        .run(|core| {
+            core.mem.write(VirtAddr(DATA as usize), 0x8000_0000_u64).unwrap();
+            core.write_reg(Register::S0, DATA);
+            core.write_reg(Register::S1, 0x1122_3344_5566_7788);
+            core.write_reg(Register::T0, DATA + 8);
+        });
+    assert_eq!(core.read_reg(Register::A0), 0x8000_0000);
+    assert_eq!(core.read_reg(Register::A1), 0);
+    assert_eq!(core.read_reg(Register::A2), 1);
+    assert_eq!(core.read_reg(Register::A3), 0x5566_7788);
+    assert_eq!(core.read_reg(Register::A4), 1);
+    assert_eq!(core.read_reg(Register::A5), 1);
+    assert_eq!(core.reservation, None);
+    let mem: u64 = core.mem.read(VirtAddr(DATA as usize)).unwrap();
+    assert_eq!(mem, 0x1122_3344_5566_7788);
+    let mem: u64 = core.mem.read(VirtAddr(DATA as usize + 8)).unwrap();
+    assert_eq!(mem, 0);
+}
+
+#[test]
+fn test_reservation_invalidation() {
+    let mut core = CoreEmu::new(Mmu::new());
+    core.reservation = Some((DATA, 8));
+    // a store next to the reservation doesn't touch it
+    core.invalidate_reservation(DATA + 8, 4);
+    core.invalidate_reservation(DATA - 4, 4);
+    assert_eq!(core.reservation, Some((DATA, 8)));
+    // an overlapping one does
+    core.invalidate_reservation(DATA + 7, 1);
+    assert_eq!(core.reservation, None);
+
+    core.reservation = Some((DATA, 4));
+    core.clear_reservation();
+    assert_eq!(core.reservation, None);
+}
+
+#[test]
+fn test_misaligned() {
+    let mut program = Program::default();
+    program.inst(AssemblerRV64GC.amoadd_d(Register::A0, Register::A1, Register::A2, false, false));
+    let mut core = core_with_code(&program.code, PermField::Read | PermField::Executable);
+    core.write_reg(Register::A1, DATA + 4);
+    match core.run() {
+        CoreEmuError::MisalignedAtomic(addr) => assert_eq!(addr, DATA + 4),
+        e => panic!("unexpected stop {:?}", e),
+    }
+}
+
+#[test]
+fn test_illegal() {
+    // funct5 0b00101 is unassigned, funct3 0b000 and 0b100 are neither .w
+    // nor .d, and lr.w needs rs2 to be zero
+    for inst in [0x2800_202f, 0x0000_002f, 0x0000_402f, 0x1010_202f] {
+        let mut core = Program::default().inst(Ok(inst)).build();
+        match core.run() {
+            CoreEmuError::IllegalInstruction => {},
+            e => panic!("unexpected stop {:?} for {:#x}", e, inst),
+        }
+        assert_eq!(core.pc, CODE);
+    }
+}
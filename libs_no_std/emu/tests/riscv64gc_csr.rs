@@ -0,0 +1,101 @@
+//! Tests of the Zicsr instructions and of the user-level CSR file
+use emu::riscv64gc::*;
+use emu::riscv64gc::csr::*;
+use mmu::PermField;
+
+mod common;
+use common::*;
+
+/// Run a single instruction and return the error that stopped the core
+fn run_single(inst: Result<u32, &str>, setup: impl FnOnce(&mut CoreEmu)) -> CoreEmuError {
+    let mut core = core_with_code(
+        &inst.unwrap().to_le_bytes(), PermField::Read | PermField::Executable,
+    );
+    setup(&mut core);
+    core.run()
+}
+
+#[test]
+fn test_encoding() {
+    // frrm a0 / fsflags a1, a2 / rdtime a3 / csrwi frm, 3
+    assert_eq!(AssemblerRV64GC.csrrs(Register::A0, Register::Zero, FRM), Ok(0x00202573));
+    assert_eq!(AssemblerRV64GC.csrrw(Register::A1, Register::A2, FFLAGS), Ok(0x001615f3));
+    assert_eq!(AssemblerRV64GC.csrrs(Register::A3, Register::Zero, TIME), Ok(0xc01026f3));
+    assert_eq!(AssemblerRV64GC.csrrwi(Register::Zero, 3, FRM), Ok(0x0021d073));
+    assert!(AssemblerRV64GC.csrrw(Register::A0, Register::A0, 4096).is_err());
+    assert!(AssemblerRV64GC.csrrsi(Register::A0, 32, FCSR).is_err());
+}
+
+#[test]
+fn test_float_csrs() {
+    let core = Program::default()
+        // fcsr = 0b101_10101
+        .inst(AssemblerRV64GC.csrrw(Register::A0, Register::A1, FCSR))
+        .inst(AssemblerRV64GC.csrrs(Register::A2, Register::Zero, FRM))
+        .inst(AssemblerRV64GC.csrrs(Register::A3, Register::Zero, FFLAGS))
+        // clear NV and set NX
+        .inst(AssemblerRV64GC.csrrci(Register::A4, 0b10000, FFLAGS))
+        .inst(AssemblerRV64GC.csrrsi(Register::Zero, 0b00001, FFLAGS))
+        // frm = RTZ, the upper bits are ignored
+        .inst(AssemblerRV64GC.csrrwi(Register::A5, 0b11001, FRM))
+        .inst(AssemblerRV64GC.csrrc(Register::A6, Register::Zero, FCSR))
+        .run(|core| {
+            core.fcsr = 0b010_00011;
+            core.write_reg(Register::A1, 0xffff_ff00 | 0b101_10101);
+        });
+    assert_eq!(core.read_reg(Register::A0), 0b010_00011);
+    assert_eq!(core.read_reg(Register::A2), 0b101);
+    assert_eq!(core.read_reg(Register::A3), 0b10101);
+    assert_eq!(core.read_reg(Register::A4), 0b10101);
+    assert_eq!(core.read_reg(Register::A5), 0b101);
+    assert_eq!(core.read_reg(Register::A6), 0b001_00101);
+    assert_eq!(core.fcsr, 0b001_00101);
+}
+
+#[test]
+fn test_counters() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::Zero, 1))
+        .inst(AssemblerRV64GC.csrrs(Register::A1, Register::Zero, INSTRET))
+        .inst(AssemblerRV64GC.csrrs(Register::A2, Register::Zero, CYCLE))
+        .inst(AssemblerRV64GC.csrrs(Register::A3, Register::Zero, TIME))
+        .run(|core| core.time_offset = 1000);
+    let instret = core.read_reg(Register::A1);
+    assert_eq!(instret, 2);
+    assert_eq!(core.read_reg(Register::A2), instret + 1);
+    assert_eq!(core.read_reg(Register::A3), 1000 + (instret + 2) * TICKS_PER_INSTRUCTION);
+    assert_eq!(core.time(), 1000 + core.instructions_executed as u64 * TICKS_PER_INSTRUCTION);
+}
+
+#[test]
+fn test_read_only() {
+    // writing a counter is illegal, even if the value doesn't change
+    match run_single(AssemblerRV64GC.csrrw(Register::A0, Register::Zero, CYCLE), |_| {}) {
+        CoreEmuError::IllegalInstruction => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+    match run_single(AssemblerRV64GC.csrrsi(Register::A0, 1, TIME), |_| {}) {
+        CoreEmuError::IllegalInstruction => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+}
+
+#[test]
+fn test_unknown_csr() {
+    // mstatus isn't accessible from user mode
+    match run_single(AssemblerRV64GC.csrrs(Register::A0, Register::Zero, 0x300), |_| {}) {
+        CoreEmuError::UnknownCsr(0x300) => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+}
+
+#[test]
+fn test_illegal() {
+    // funct3 0b100 is reserved, wfi is privileged
+    for inst in [0x0000_4073, 0x1050_0073] {
+        match run_single(Ok(inst), |_| {}) {
+            CoreEmuError::IllegalInstruction => {},
+            e => panic!("unexpected stop {:?} for {:#x}", e, inst),
+        }
+    }
+}
@@ -0,0 +1,237 @@
+//! Per-instruction tests of the F and D extensions, every program is
+//! assembled with [`AssemblerRV64GC`] and terminated by an `ecall`
+use emu::riscv64gc::*;
+use emu::riscv64gc::FloatRegister::*;
+use emu::riscv64gc::FloatRoundingMode::*;
+use emu::riscv64gc::softfloat::*;
+use mmu::VirtAddr;
+
+mod common;
+use common::*;
+
+type FRInst = for<'a> fn(&'a mut AssemblerRV64GC, FloatRegister, FloatRegister, FloatRegister,
+    FloatRoundingMode) -> Result<u32, &'a str>;
+
+/// Check `inst` on single precision values, the result is computed by the
+/// host as RISC-V rounds to nearest like it
+fn check_s(inst: FRInst, op: fn(f32, f32) -> f32, cases: &[(f32, f32)]) {
+    for &(a, b) in cases {
+        let core = Program::default()
+            .inst(inst(&mut AssemblerRV64GC, FT0, FT1, FT2, DYN))
+            .run(|core| {
+                core.write_freg_s(FT1, a.to_bits() as u64);
+                core.write_freg_s(FT2, b.to_bits() as u64);
+            });
+        let expected = 0xffff_ffff_0000_0000 | op(a, b).to_bits() as u64;
+        assert_eq!(core.read_freg(FT0), expected, "{} {}", a, b);
+    }
+}
+
+/// Check `inst` on double precision values, like [`check_s`]
+fn check_d(inst: FRInst, op: fn(f64, f64) -> f64, cases: &[(f64, f64)]) {
+    for &(a, b) in cases {
+        let core = Program::default()
+            .inst(inst(&mut AssemblerRV64GC, FT0, FT1, FT2, DYN))
+            .run(|core| {
+                core.write_freg(FT1, a.to_bits());
+                core.write_freg(FT2, b.to_bits());
+            });
+        assert_eq!(core.read_freg(FT0), op(a, b).to_bits(), "{} {}", a, b);
+    }
+}
+
+const CASES_S: [(f32, f32); 4] = [(1.0, 2.5), (0.1, 0.2), (-3.75, 1e10), (1e-30, -7.0)];
+const CASES_D: [(f64, f64); 4] = [(1.0, 2.5), (0.1, 0.2), (-3.75, 1e300), (1e-300, -7.0)];
+
+#[test]
+fn test_reserved_rounding_modes() {
+    // fadd.s ft0, ft0, ft0 with rm 5 and 6
+    for inst in [0x0000_5053, 0x0000_6053] {
+        let mut core = Program::default().inst(Ok(inst)).build();
+        match core.run() {
+            CoreEmuError::IllegalInstruction => {},
+            e => panic!("unexpected stop {:?}", e),
+        }
+        assert_eq!(core.pc, CODE);
+    }
+}
+
+#[test]
+fn test_arithmetic() {
+    check_s(AssemblerRV64GC::fadd_s, |a, b| a + b, &CASES_S);
+    check_s(AssemblerRV64GC::fsub_s, |a, b| a - b, &CASES_S);
+    check_s(AssemblerRV64GC::fmul_s, |a, b| a * b, &CASES_S);
+    check_s(AssemblerRV64GC::fdiv_s, |a, b| a / b, &CASES_S);
+    check_d(AssemblerRV64GC::fadd_d, |a, b| a + b, &CASES_D);
+    check_d(AssemblerRV64GC::fsub_d, |a, b| a - b, &CASES_D);
+    check_d(AssemblerRV64GC::fmul_d, |a, b| a * b, &CASES_D);
+    check_d(AssemblerRV64GC::fdiv_d, |a, b| a / b, &CASES_D);
+
+    // fused, sign injection and comparisons
+    let core = Program::default()
+        .inst(AssemblerRV64GC.fmadd_d(FT0, FT1, FT1, FT2, DYN))
+        .inst(AssemblerRV64GC.fnmsub_s(FT3, FT4, FT4, FT5, DYN))
+        .inst(AssemblerRV64GC.fsgnjn_d(FT6, FT1, FT1))
+        .inst(AssemblerRV64GC.flt_d(Register::A0, FT6, FT1))
+        .inst(AssemblerRV64GC.feq_s(Register::A1, FT4, FT5))
+        .inst(AssemblerRV64GC.fclass_d(Register::A2, FT6))
+        .run(|core| {
+            core.write_freg(FT1, 2.5_f64.to_bits());
+            core.write_freg(FT2, 1.0_f64.to_bits());
+            core.write_freg_s(FT4, 3.0_f32.to_bits() as u64);
+            core.write_freg_s(FT5, 3.0_f32.to_bits() as u64);
+        });
+    assert_eq!(core.read_freg(FT0), 7.25_f64.to_bits());
+    // -(3 * 3) + 3
+    assert_eq!(core.read_freg_s(FT3), (-6.0_f32).to_bits() as u64);
+    assert_eq!(core.read_freg(FT6), (-2.5_f64).to_bits());
+    assert_eq!(core.read_reg(Register::A0), 1);
+    assert_eq!(core.read_reg(Register::A1), 1);
+    // negative normal
+    assert_eq!(core.read_reg(Register::A2), 1 << 1);
+    assert_eq!(core.fcsr, 0);
+}
+
+#[test]
+fn test_nan_boxing() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.fmv_w_x(FT0, Register::A0))
+        // ft1 is not NaN-boxed, so it's read as the canonical NaN
+        .inst(AssemblerRV64GC.fadd_s(FT2, FT0, FT1, DYN))
+        .inst(AssemblerRV64GC.fclass_s(Register::A1, FT1))
+        // the moves and the stores use the raw bits
+        .inst(AssemblerRV64GC.fmv_x_w(Register::A2, FT1, RNE))
+        .inst(AssemblerRV64GC.fmv_w_x(FT3, Register::A3))
+        .inst(AssemblerRV64GC.fmv_x_w(Register::A4, FT3, RNE))
+        .inst(AssemblerRV64GC.fcvt_d_s(FT4, FT0))
+        .inst(AssemblerRV64GC.fcvt_s_d(FT5, FT4, DYN))
+        .inst(AssemblerRV64GC.fcvt_d_s(FT6, FT1))
+        .run(|core| {
+            core.write_reg(Register::A0, 0xdead_beef_3f80_0000);
+            core.write_reg(Register::A3, (-1.0_f32).to_bits() as u64);
+            core.write_freg(FT1, 1.0_f32.to_bits() as u64);
+        });
+    assert_eq!(core.read_freg(FT0), 0xffff_ffff_3f80_0000);
+    assert_eq!(core.read_freg(FT2), 0xffff_ffff_7fc0_0000);
+    // quiet NaN
+    assert_eq!(core.read_reg(Register::A1), 1 << 9);
+    assert_eq!(core.read_reg(Register::A2), 0x3f80_0000);
+    assert_eq!(core.read_reg(Register::A4), 0xffff_ffff_bf80_0000);
+    assert_eq!(core.read_freg(FT4), 1.0_f64.to_bits());
+    assert_eq!(core.read_freg(FT5), 0xffff_ffff_3f80_0000);
+    assert_eq!(core.read_freg(FT6), 0x7ff8_0000_0000_0000);
+    // the canonical NaN is quiet, so nothing is invalid
+    assert_eq!(core.fcsr, 0);
+}
+
+#[test]
+fn test_loads_stores() {
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.flw(FT0, Register::A0, 0))
+        .inst(AssemblerRV64GC.fld(FT1, Register::A0, 8))
+        .inst(AssemblerRV64GC.fsw(Register::A0, FT0, 16))
+        .inst(AssemblerRV64GC.fsd(Register::A0, FT1, 24))
+        // the single precision stores ignore the NaN-boxing
+        .inst(AssemblerRV64GC.fsw(Register::A0, FT2, 32))
+        .c_inst(AssemblerRV64GC.c_fld(FS0, Register::A0, 8))
+        .c_inst(AssemblerRV64GC.c_fsd(Register::A0, FS0, 40))
+        .c_inst(AssemblerRV64GC.c_fldsp(FT3, 8))
+        .c_inst(AssemblerRV64GC.c_fsdsp(FT3, 504))
+        .run(|core| {
+            core.write_reg(Register::A0, DATA);
+            core.write_freg(FT2, 0x1234_5678_9abc_def0);
+            core.mem.write(VirtAddr(DATA as usize), 2.5_f32.to_bits()).unwrap();
+            core.mem.write(VirtAddr(DATA as usize + 8), 2.5_f64.to_bits()).unwrap();
+            core.mem.write(VirtAddr(DATA as usize + 0x808), 0.1_f64.to_bits()).unwrap();
+        });
+    let mut read = |offset: usize| core.mem.read::<u64>(VirtAddr(DATA as usize + offset)).unwrap();
+    assert_eq!(read(16) as u32, 2.5_f32.to_bits());
+    assert_eq!(read(24), 2.5_f64.to_bits());
+    assert_eq!(read(32) as u32, 0x9abc_def0);
+    assert_eq!(read(40), 2.5_f64.to_bits());
+    assert_eq!(read(0x800 + 504), 0.1_f64.to_bits());
+    assert_eq!(core.read_freg(FT0), 0xffff_ffff_0000_0000 | 2.5_f32.to_bits() as u64);
+    assert_eq!(core.read_freg(FT1), 2.5_f64.to_bits());
+    assert_eq!(core.read_freg(FS0), 2.5_f64.to_bits());
+    assert_eq!(core.read_freg(FT3), 0.1_f64.to_bits());
+}
+
+#[test]
+fn test_dynamic_rounding() {
+    // rounding of 2.5 and -2.5 to integers with each mode in frm
+    let cases = [(RNE, 2, -2), (RTZ, 2, -2), (RDN, 2, -3), (RUP, 3, -2), (RMM, 3, -3)];
+    for (frm, positive, negative) in cases {
+        let core = Program::default()
+            .inst(AssemblerRV64GC.fcvt_w_s(Register::A0, FT0, DYN))
+            .inst(AssemblerRV64GC.fcvt_l_d(Register::A1, FT1, DYN))
+            // the static rounding mode ignores frm
+            .inst(AssemblerRV64GC.fcvt_w_s(Register::A2, FT0, RTZ))
+            .run(|core| {
+                core.fcsr = u32::from(frm) << 5;
+                core.write_freg_s(FT0, 2.5_f32.to_bits() as u64);
+                core.write_freg(FT1, (-2.5_f64).to_bits());
+            });
+        assert_eq!(core.read_reg(Register::A0), positive as u64, "{:?}", frm);
+        assert_eq!(core.read_reg(Register::A1), negative as i64 as u64, "{:?}", frm);
+        assert_eq!(core.read_reg(Register::A2), 2, "{:?}", frm);
+        assert_eq!(core.fcsr, u32::from(frm) << 5 | FFLAGS_NX);
+    }
+
+    // 1/3 is rounded down or up
+    let third = |frm: FloatRoundingMode| {
+        Program::default()
+            .inst(AssemblerRV64GC.fcvt_d_l(FT0, Register::A0, DYN))
+            .inst(AssemblerRV64GC.fcvt_d_l(FT1, Register::A1, DYN))
+            .inst(AssemblerRV64GC.fdiv_d(FT2, FT0, FT1, DYN))
+            .run(|core| {
+                core.fcsr = u32::from(frm) << 5;
+                core.write_reg(Register::A0, 1);
+                core.write_reg(Register::A1, 3);
+            })
+            .read_freg(FT2)
+    };
+    assert_eq!(third(RDN) + 1, third(RUP));
+    assert_eq!(third(RNE), (1.0_f64 / 3.0).to_bits());
+
+    // a reserved rounding mode in frm makes the instruction illegal
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.fcvt_w_s(Register::A0, FT0, DYN))
+        .build();
+    core.fcsr = 0b101 << 5;
+    match core.run() {
+        CoreEmuError::IllegalInstruction => {},
+        e => panic!("unexpected stop {:?}", e),
+    }
+    assert_eq!(core.pc, CODE);
+}
+
+#[test]
+fn test_fflags() {
+    let core = Program::default()
+        // 1 / 0
+        .inst(AssemblerRV64GC.fdiv_s(FT2, FT1, FT0, DYN))
+        // an exact operation doesn't clear the flags
+        .inst(AssemblerRV64GC.fadd_s(FT3, FT1, FT1, DYN))
+        // sqrt(-1)
+        .inst(AssemblerRV64GC.fsqrt_d(FT4, FT5, DYN))
+        // the signaling comparisons of a NaN
+        .inst(AssemblerRV64GC.flt_d(Register::A0, FT4, FT4))
+        .inst(AssemblerRV64GC.csrrs(Register::A1, Register::Zero, csr::FFLAGS))
+        // 1e300 overflows a single
+        .inst(AssemblerRV64GC.fcvt_s_d(FT6, FT7, DYN))
+        .run(|core| {
+            core.fcsr = u32::from(RUP) << 5;
+            core.write_freg_s(FT0, 0);
+            core.write_freg_s(FT1, 1.0_f32.to_bits() as u64);
+            core.write_freg(FT5, (-1.0_f64).to_bits());
+            core.write_freg(FT7, 1e300_f64.to_bits());
+        });
+    assert_eq!(core.read_freg_s(FT2), f32::INFINITY.to_bits() as u64);
+    assert_eq!(core.read_freg_s(FT3), 2.0_f32.to_bits() as u64);
+    assert_eq!(core.read_freg(FT4), 0x7ff8_0000_0000_0000);
+    assert_eq!(core.read_reg(Register::A0), 0);
+    assert_eq!(core.read_reg(Register::A1), (FFLAGS_DZ | FFLAGS_NV) as u64);
+    // rounded up to infinity
+    assert_eq!(core.read_freg_s(FT6), f32::INFINITY.to_bits() as u64);
+    assert_eq!(core.fcsr, u32::from(RUP) << 5 | FFLAGS_DZ | FFLAGS_NV | FFLAGS_OF | FFLAGS_NX);
+}
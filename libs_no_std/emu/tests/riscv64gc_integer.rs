@@ -0,0 +1,381 @@
+//! Per-instruction tests of the RV64IM(C) integer instructions, every program
+//! is assembled with [`AssemblerRV64GC`] and terminated by an `ecall`
+use emu::riscv64gc::*;
+use mmu::PermField;
+
+mod common;
+use common::*;
+
+type RInst = for<'a> fn(&'a mut AssemblerRV64GC, Register, Register, Register)
+    -> Result<u32, &'a str>;
+type IInst = for<'a> fn(&'a mut AssemblerRV64GC, Register, Register, i32)
+    -> Result<u32, &'a str>;
+type CAInst = for<'a> fn(&'a mut AssemblerRV64GC, Register, Register)
+    -> Result<u16, &'a str>;
+
+fn check_r(inst: RInst, cases: &[(u64, u64, u64)]) {
+    for &(a, b, expected) in cases {
+        let core = Program::default()
+            .inst(inst(&mut AssemblerRV64GC, Register::A0, Register::A1, Register::A2))
+            .run(|core| {
+                core.write_reg(Register::A1, a);
+                core.write_reg(Register::A2, b);
+            });
+        assert_eq!(core.read_reg(Register::A0), expected, "{:x} {:x}", a, b);
+    }
+}
+
+fn check_i(inst: IInst, cases: &[(u64, i32, u64)]) {
+    for &(a, imm, expected) in cases {
+        let core = Program::default()
+            .inst(inst(&mut AssemblerRV64GC, Register::A0, Register::A1, imm))
+            .run(|core| core.write_reg(Register::A1, a));
+        assert_eq!(core.read_reg(Register::A0), expected, "{:x} {}", a, imm);
+    }
+}
+
+fn check_ca(inst: CAInst, cases: &[(u64, u64, u64)]) {
+    for &(a, b, expected) in cases {
+        let core = Program::default()
+            .c_inst(inst(&mut AssemblerRV64GC, Register::S0, Register::S1))
+            .run(|core| {
+                core.write_reg(Register::S0, a);
+                core.write_reg(Register::S1, b);
+            });
+        assert_eq!(core.read_reg(Register::S0), expected, "{:x} {:x}", a, b);
+    }
+}
+
+const MIN: u64 = i64::MIN as u64;
+const MIN_W: u64 = i32::MIN as i64 as u64;
+
+#[test]
+fn test_upper_immediates() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.lui(Register::A0, 0x8765_4000))
+        .inst(AssemblerRV64GC.auipc(Register::A1, 0xfffff000))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::A0), 0xffff_ffff_8765_4000);
+    assert_eq!(core.read_reg(Register::A1), CODE + 4 - 0x1000);
+}
+
+#[test]
+fn test_immediate_ops() {
+    check_i(AssemblerRV64GC::addi, &[(5, -7, -2_i64 as u64), (u64::MAX, 1, 0)]);
+    check_i(AssemblerRV64GC::slti, &[(-3_i64 as u64, -2, 1), (3, -2, 0), (3, 3, 0)]);
+    check_i(|asm, rd, rs1, imm| asm.sltiu(rd, rs1, imm as u32), &[
+        // the immediate is sign extended and then compared as unsigned
+        (5, -1, 1), (u64::MAX, -1, 0), (0, 1, 1), (1, 1, 0),
+    ]);
+    check_i(AssemblerRV64GC::xori, &[(0xff, -1, !0xff), (0b1010, 0b0110, 0b1100)]);
+    check_i(AssemblerRV64GC::ori, &[(0xf0, 0x0f, 0xff), (0, -2048, -2048_i64 as u64)]);
+    check_i(AssemblerRV64GC::andi, &[(u64::MAX, 0x7ff, 0x7ff), (0xf0f0, -16, 0xf0f0)]);
+    check_i(AssemblerRV64GC::slli, &[(1, 63, MIN), (0x3, 4, 0x30)]);
+    check_i(AssemblerRV64GC::srli, &[(MIN, 63, 1), (u64::MAX, 60, 0xf)]);
+    check_i(AssemblerRV64GC::srai, &[(MIN, 63, u64::MAX), (0x40, 4, 4)]);
+    check_i(AssemblerRV64GC::addiw, &[
+        (0x7fff_ffff, 1, MIN_W), (0xffff_ffff_0000_0001, 0, 1),
+    ]);
+    check_i(AssemblerRV64GC::slliw, &[(1, 31, MIN_W), (0x1_0000_0001, 1, 2)]);
+    check_i(AssemblerRV64GC::srliw, &[(MIN_W, 31, 1), (0xffff_ffff, 0, u64::MAX)]);
+    check_i(AssemblerRV64GC::sraiw, &[(MIN_W, 31, u64::MAX), (0x1_0000_0040, 4, 4)]);
+    assert!(AssemblerRV64GC.slli(Register::A0, Register::A0, 64).is_err());
+    assert!(AssemblerRV64GC.slliw(Register::A0, Register::A0, 32).is_err());
+}
+
+#[test]
+fn test_register_ops() {
+    check_r(AssemblerRV64GC::add, &[(1, 2, 3), (u64::MAX, 1, 0)]);
+    check_r(AssemblerRV64GC::sub, &[(1, 2, u64::MAX), (MIN, 1, i64::MAX as u64)]);
+    // only the low 6 bits of rs2 are the shift amount
+    check_r(AssemblerRV64GC::sll, &[(1, 63, MIN), (1, 64, 1), (3, 65, 6)]);
+    check_r(AssemblerRV64GC::slt, &[(u64::MAX, 0, 1), (0, u64::MAX, 0), (4, 4, 0)]);
+    check_r(AssemblerRV64GC::sltu, &[(u64::MAX, 0, 0), (0, u64::MAX, 1), (4, 4, 0)]);
+    check_r(AssemblerRV64GC::xor, &[(0b1100, 0b1010, 0b0110)]);
+    check_r(AssemblerRV64GC::srl, &[(MIN, 63, 1), (MIN, 64, MIN), (MIN, 127, 1)]);
+    check_r(AssemblerRV64GC::sra, &[(MIN, 63, u64::MAX), (MIN, 64, MIN), (0x40, 66, 0x10)]);
+    check_r(AssemblerRV64GC::or, &[(0b1100, 0b1010, 0b1110)]);
+    check_r(AssemblerRV64GC::and, &[(0b1100, 0b1010, 0b1000)]);
+}
+
+#[test]
+fn test_word_ops() {
+    check_r(AssemblerRV64GC::addw, &[
+        (0x7fff_ffff, 1, MIN_W), (0x1_0000_0001, 0x2_0000_0002, 3),
+    ]);
+    check_r(AssemblerRV64GC::subw, &[(0, 1, u64::MAX), (MIN_W, 1, 0x7fff_ffff)]);
+    // only the low 5 bits of rs2 are the shift amount
+    check_r(AssemblerRV64GC::sllw, &[(1, 31, MIN_W), (1, 32, 1), (0xffff_ffff, 4, 0xffff_ffff_ffff_fff0)]);
+    check_r(AssemblerRV64GC::srlw, &[(MIN_W, 31, 1), (0xffff_ffff, 0, u64::MAX), (MIN_W, 33, 0x4000_0000)]);
+    check_r(AssemblerRV64GC::sraw, &[(MIN_W, 31, u64::MAX), (MIN_W, 32, MIN_W), (0x1_0000_0040, 4, 4)]);
+}
+
+#[test]
+fn test_multiplications() {
+    check_r(AssemblerRV64GC::mul, &[(3, -2_i64 as u64, -6_i64 as u64), (MIN, 2, 0)]);
+    check_r(AssemblerRV64GC::mulh, &[
+        (MIN, MIN, 1 << 62), (-1_i64 as u64, 1, u64::MAX), (1 << 32, 1 << 32, 1),
+    ]);
+    check_r(AssemblerRV64GC::mulhsu, &[
+        (-1_i64 as u64, u64::MAX, u64::MAX), (2, u64::MAX, 1), (MIN, 2, u64::MAX),
+    ]);
+    check_r(AssemblerRV64GC::mulhu, &[
+        (u64::MAX, u64::MAX, u64::MAX - 1), (-1_i64 as u64, 1, 0), (MIN, 4, 2),
+    ]);
+    check_r(AssemblerRV64GC::mulw, &[
+        (0x1_0000_0003, 0x7fff_ffff, 0x7fff_fffd), (0x10000, 0x10000, 0),
+        (-2_i64 as u64, 3, -6_i64 as u64),
+    ]);
+}
+
+#[test]
+fn test_divisions() {
+    check_r(AssemblerRV64GC::div, &[
+        (7, -2_i64 as u64, -3_i64 as u64), (-7_i64 as u64, 2, -3_i64 as u64),
+        // division by zero and overflow
+        (7, 0, u64::MAX), (MIN, u64::MAX, MIN),
+    ]);
+    check_r(AssemblerRV64GC::divu, &[(7, 2, 3), (7, 0, u64::MAX), (MIN, u64::MAX, 0)]);
+    check_r(AssemblerRV64GC::rem, &[
+        (7, -2_i64 as u64, 1), (-7_i64 as u64, 2, u64::MAX),
+        (7, 0, 7), (MIN, u64::MAX, 0),
+    ]);
+    check_r(AssemblerRV64GC::remu, &[(7, 2, 1), (7, 0, 7), (u64::MAX, 10, 5)]);
+    check_r(AssemblerRV64GC::divw, &[
+        (0x1_0000_0007, -2_i64 as u64, -3_i64 as u64),
+        (7, 0x1_0000_0000, u64::MAX), (MIN_W, u64::MAX, MIN_W),
+    ]);
+    check_r(AssemblerRV64GC::divuw, &[
+        (0xffff_fffe, 2, 0x7fff_ffff), (7, 0, u64::MAX), (0xffff_ffff, 1, u64::MAX),
+    ]);
+    check_r(AssemblerRV64GC::remw, &[
+        (-7_i64 as u64, 2, u64::MAX), (0x1_0000_0007, 0, 7), (MIN_W, u64::MAX, 0),
+    ]);
+    check_r(AssemblerRV64GC::remuw, &[
+        (0xffff_ffff, 0x10, 0xf), (0xffff_fff0, 0, 0xffff_ffff_ffff_fff0),
+    ]);
+}
+
+#[test]
+fn test_branches() {
+    type BInst = for<'a> fn(&'a mut AssemblerRV64GC, Register, Register, i32)
+        -> Result<u32, &'a str>;
+    let cases: &[(BInst, u64, u64, bool)] = &[
+        (AssemblerRV64GC::beq, 1, 1, true), (AssemblerRV64GC::beq, 1, 2, false),
+        (AssemblerRV64GC::bne, 1, 2, true), (AssemblerRV64GC::bne, 1, 1, false),
+        (AssemblerRV64GC::blt, u64::MAX, 0, true), (AssemblerRV64GC::blt, 0, 0, false),
+        (AssemblerRV64GC::bge, 0, 0, true), (AssemblerRV64GC::bge, u64::MAX, 0, false),
+        (AssemblerRV64GC::bltu, 0, u64::MAX, true), (AssemblerRV64GC::bltu, 3, 3, false),
+        (AssemblerRV64GC::bgeu, 3, 3, true), (AssemblerRV64GC::bgeu, 0, u64::MAX, false),
+    ];
+    for &(inst, a, b, taken) in cases {
+        // if taken skip the addi
+        let core = Program::default()
+            .inst(inst(&mut AssemblerRV64GC, Register::A1, Register::A2, 8))
+            .inst(AssemblerRV64GC.addi(Register::A0, Register::A0, 1))
+            .run(|core| {
+                core.write_reg(Register::A1, a);
+                core.write_reg(Register::A2, b);
+            });
+        assert_eq!(core.read_reg(Register::A0), !taken as u64, "{:x} {:x}", a, b);
+    }
+
+    // backward branch: count down from 5
+    let core = Program::default()
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::Zero, 5))
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::A0, -1))
+        .inst(AssemblerRV64GC.addi(Register::A1, Register::A1, 2))
+        .inst(AssemblerRV64GC.bne(Register::A0, Register::Zero, -8))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::A1), 10);
+}
+
+#[test]
+fn test_jumps() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.jal(Register::Ra, 12))
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::Zero, 1))
+        .inst(AssemblerRV64GC.jal(Register::Zero, 12))
+        // function: a1 = 2; return
+        .inst(AssemblerRV64GC.addi(Register::A1, Register::Zero, 2))
+        .inst(AssemblerRV64GC.jalr(Register::T0, Register::Ra, 0))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::A0), 1);
+    assert_eq!(core.read_reg(Register::A1), 2);
+    assert_eq!(core.read_reg(Register::Ra), CODE + 4);
+    assert_eq!(core.read_reg(Register::T0), CODE + 20);
+
+    // jalr clears the lowest bit of the target
+    let core = Program::default()
+        .inst(AssemblerRV64GC.jalr(Register::Ra, Register::A1, 5))
+        .inst(AssemblerRV64GC.addi(Register::A0, Register::Zero, 1))
+        .run(|core| core.write_reg(Register::A1, CODE + 3));
+    assert_eq!(core.read_reg(Register::A0), 0);
+    assert_eq!(core.read_reg(Register::Ra), CODE + 4);
+}
+
+#[test]
+fn test_loads_stores() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.sd(Register::Sp, Register::A1, -8))
+        .inst(AssemblerRV64GC.lb(Register::A2, Register::Sp, -1))
+        .inst(AssemblerRV64GC.lbu(Register::A3, Register::Sp, -1))
+        .inst(AssemblerRV64GC.lh(Register::A4, Register::Sp, -2))
+        .inst(AssemblerRV64GC.lhu(Register::A5, Register::Sp, -2))
+        .inst(AssemblerRV64GC.lw(Register::A6, Register::Sp, -4))
+        .inst(AssemblerRV64GC.lwu(Register::A7, Register::Sp, -4))
+        .inst(AssemblerRV64GC.ld(Register::S2, Register::Sp, -8))
+        .inst(AssemblerRV64GC.sb(Register::Sp, Register::A1, 0))
+        .inst(AssemblerRV64GC.sh(Register::Sp, Register::A1, 2))
+        .inst(AssemblerRV64GC.sw(Register::Sp, Register::A1, 4))
+        .inst(AssemblerRV64GC.ld(Register::S3, Register::Sp, 0))
+        .run(|core| core.write_reg(Register::A1, 0x8081_8283_8485_8687));
+    assert_eq!(core.read_reg(Register::A2), 0xffff_ffff_ffff_ff80);
+    assert_eq!(core.read_reg(Register::A3), 0x80);
+    assert_eq!(core.read_reg(Register::A4), 0xffff_ffff_ffff_8081);
+    assert_eq!(core.read_reg(Register::A5), 0x8081);
+    assert_eq!(core.read_reg(Register::A6), 0xffff_ffff_8081_8283);
+    assert_eq!(core.read_reg(Register::A7), 0x8081_8283);
+    assert_eq!(core.read_reg(Register::S2), 0x8081_8283_8485_8687);
+    assert_eq!(core.read_reg(Register::S3), 0x8485_8687_8687_0087);
+}
+
+#[test]
+fn test_writes_to_zero_are_ignored() {
+    let core = Program::default()
+        .inst(AssemblerRV64GC.addi(Register::Zero, Register::Zero, 5))
+        .inst(AssemblerRV64GC.add(Register::A0, Register::Zero, Register::Zero))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::Zero), 0);
+    assert_eq!(core.read_reg(Register::A0), 0);
+}
+
+#[test]
+fn test_compressed_alu() {
+    check_ca(AssemblerRV64GC::c_sub, &[(1, 2, u64::MAX)]);
+    check_ca(AssemblerRV64GC::c_xor, &[(0b1100, 0b1010, 0b0110)]);
+    check_ca(AssemblerRV64GC::c_or, &[(0b1100, 0b1010, 0b1110)]);
+    check_ca(AssemblerRV64GC::c_and, &[(0b1100, 0b1010, 0b1000)]);
+    check_ca(AssemblerRV64GC::c_subw, &[(MIN_W, 1, 0x7fff_ffff)]);
+    check_ca(AssemblerRV64GC::c_addw, &[(0x7fff_ffff, 1, MIN_W)]);
+    check_ca(AssemblerRV64GC::c_mv, &[(1, 7, 7)]);
+    check_ca(AssemblerRV64GC::c_add, &[(u64::MAX, 2, 1)]);
+
+    let core = Program::default()
+        .c_inst(AssemblerRV64GC.c_li(Register::A0, -32))
+        .c_inst(AssemblerRV64GC.c_li(Register::A1, 31))
+        .c_inst(AssemblerRV64GC.c_addi(Register::A1, -1))
+        .c_inst(AssemblerRV64GC.c_addiw(Register::A2, 1))
+        .c_inst(AssemblerRV64GC.c_lui(Register::T1, -4096))
+        .c_inst(AssemblerRV64GC.c_lui(Register::T2, 0x1f000))
+        .c_inst(AssemblerRV64GC.c_slli(Register::T3, 63))
+        .c_inst(AssemblerRV64GC.c_srli(Register::S0, 33))
+        .c_inst(AssemblerRV64GC.c_srai(Register::S1, 60))
+        .c_inst(AssemblerRV64GC.c_andi(Register::A4, -3))
+        .c_inst(AssemblerRV64GC.c_andi(Register::A5, 0x1f))
+        .run(|core| {
+            core.write_reg(Register::A2, 0x7fff_ffff);
+            core.write_reg(Register::T3, 1);
+            core.write_reg(Register::S0, MIN);
+            core.write_reg(Register::S1, MIN);
+            core.write_reg(Register::A4, 0xff);
+            core.write_reg(Register::A5, 0xff);
+        });
+    assert_eq!(core.read_reg(Register::A0), -32_i64 as u64);
+    assert_eq!(core.read_reg(Register::A1), 30);
+    assert_eq!(core.read_reg(Register::A2), MIN_W);
+    assert_eq!(core.read_reg(Register::T1), -4096_i64 as u64);
+    assert_eq!(core.read_reg(Register::T2), 0x1f000);
+    assert_eq!(core.read_reg(Register::T3), MIN);
+    assert_eq!(core.read_reg(Register::S0), 1 << 30);
+    assert_eq!(core.read_reg(Register::S1), u64::MAX << 3);
+    assert_eq!(core.read_reg(Register::A4), 0xfd);
+    assert_eq!(core.read_reg(Register::A5), 0x1f);
+}
+
+#[test]
+fn test_compressed_stack() {
+    let core = Program::default()
+        .c_inst(AssemblerRV64GC.c_addi16sp(-496))
+        .c_inst(AssemblerRV64GC.c_addi4spn(Register::S0, 1020))
+        .c_inst(AssemblerRV64GC.c_sdsp(Register::A1, 504))
+        .c_inst(AssemblerRV64GC.c_swsp(Register::A1, 252))
+        .c_inst(AssemblerRV64GC.c_ldsp(Register::A2, 504))
+        .c_inst(AssemblerRV64GC.c_lwsp(Register::A3, 252))
+        .run(|core| core.write_reg(Register::A1, 0x0123_4567_89ab_cdef));
+    let sp = DATA + 0x800 - 496;
+    assert_eq!(core.read_reg(Register::Sp), sp);
+    assert_eq!(core.read_reg(Register::S0), sp + 1020);
+    assert_eq!(core.read_reg(Register::A2), 0x0123_4567_89ab_cdef);
+    assert_eq!(core.read_reg(Register::A3), 0xffff_ffff_89ab_cdef);
+}
+
+#[test]
+fn test_compressed_loads_stores() {
+    let core = Program::default()
+        .c_inst(AssemblerRV64GC.c_sw(Register::S0, Register::S1, 68))
+        .c_inst(AssemblerRV64GC.c_sw(Register::S0, Register::S1, 4))
+        .c_inst(AssemblerRV64GC.c_sd(Register::S0, Register::S1, 248))
+        .c_inst(AssemblerRV64GC.c_sd(Register::S0, Register::S1, 120))
+        .c_inst(AssemblerRV64GC.c_lw(Register::A0, Register::S0, 68))
+        .c_inst(AssemblerRV64GC.c_lw(Register::A1, Register::S0, 4))
+        .c_inst(AssemblerRV64GC.c_ld(Register::A2, Register::S0, 248))
+        .c_inst(AssemblerRV64GC.c_lw(Register::A3, Register::S0, 124))
+        .run(|core| {
+            core.write_reg(Register::S0, DATA);
+            core.write_reg(Register::S1, 0x1234_5678_9abc_def0);
+        });
+    assert_eq!(core.read_reg(Register::A0), 0xffff_ffff_9abc_def0);
+    assert_eq!(core.read_reg(Register::A1), 0xffff_ffff_9abc_def0);
+    assert_eq!(core.read_reg(Register::A2), 0x1234_5678_9abc_def0);
+    assert_eq!(core.read_reg(Register::A3), 0x1234_5678);
+}
+
+#[test]
+fn test_compressed_control_flow() {
+    let core = Program::default()
+        // 0: skip the next instruction
+        .c_inst(AssemblerRV64GC.c_j(4))
+        .c_inst(AssemblerRV64GC.c_li(Register::A0, 1))
+        // 4: taken, skip the next one
+        .c_inst(AssemblerRV64GC.c_beqz(Register::S0, 4))
+        .c_inst(AssemblerRV64GC.c_li(Register::A1, 1))
+        // 8: not taken
+        .c_inst(AssemblerRV64GC.c_bnez(Register::S0, 4))
+        .c_inst(AssemblerRV64GC.c_li(Register::A2, 1))
+        // 12: call the function at 20
+        .c_inst(AssemblerRV64GC.c_jalr(Register::S1))
+        // 14: jump over the function to the ecall
+        .inst(AssemblerRV64GC.jal(Register::Zero, 10))
+        // 18: padding
+        .c_inst(AssemblerRV64GC.c_nop())
+        // 20: function
+        .c_inst(AssemblerRV64GC.c_li(Register::A3, 1))
+        .c_inst(AssemblerRV64GC.c_jr(Register::Ra))
+        .run(|core| core.write_reg(Register::S1, CODE + 20));
+    assert_eq!(core.read_reg(Register::A0), 0);
+    assert_eq!(core.read_reg(Register::A1), 0);
+    assert_eq!(core.read_reg(Register::A2), 1);
+    assert_eq!(core.read_reg(Register::A3), 1);
+    assert_eq!(core.read_reg(Register::Ra), CODE + 14);
+
+    // backward compressed branch: count down from 3
+    let core = Program::default()
+        .c_inst(AssemblerRV64GC.c_li(Register::S0, 3))
+        .c_inst(AssemblerRV64GC.c_addi(Register::S0, -1))
+        .c_inst(AssemblerRV64GC.c_addi(Register::A0, 3))
+        .c_inst(AssemblerRV64GC.c_bnez(Register::S0, -4))
+        .run(|_| {});
+    assert_eq!(core.read_reg(Register::A0), 9);
+}
+
+#[test]
+fn test_compressed_at_segment_end() {
+    // a compressed instruction in the last two bytes of the code must not
+    // fetch past the segment
+    let mut code = vec![0; 0xffe];
+    code.extend_from_slice(&AssemblerRV64GC.c_ebreak().unwrap().to_le_bytes());
+    let mut core = core_with_code(&code, PermField::Read | PermField::Executable);
+    core.pc = CODE + 0xffe;
+    assert!(matches!(core.run(), CoreEmuError::Breakpoint));
+}
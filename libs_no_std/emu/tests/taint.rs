@@ -0,0 +1,221 @@
+//! Tests of the [`TaintTracker`], the input is in the first bytes of `DATA`
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use emu::riscv64gc::FloatRegister::*;
+use emu::riscv64gc::FloatRoundingMode::RNE;
+use mmu::{PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+/// Run `program` with `s0` pointing to `DATA`, where the first 8 bytes
+/// are the input
+fn run(program: &mut Program) -> TaintTracker {
+    let mut core = program.run(|core| {
+        core.write_reg(S0, DATA);
+        let mut taint = TaintTracker::default();
+        taint.taint_input(DATA, 0, 8);
+        core.taint = Some(taint);
+    });
+    core.taint.take().unwrap()
+}
+
+#[test]
+fn test_propagation() {
+    let mut taint = run(Program::default()
+        .inst(AssemblerRV64GC.lbu(T0, S0, 0))
+        .inst(AssemblerRV64GC.lbu(T1, S0, 1))
+        .inst(AssemblerRV64GC.add(T2, T0, T1))
+        .inst(AssemblerRV64GC.addi(T3, T2, 5))
+        .inst(AssemblerRV64GC.mul(T4, T0, T3))
+        .inst(AssemblerRV64GC.lui(T5, 0x1000))
+        .inst(AssemblerRV64GC.xor(T6, T5, T1))
+        // the link of the jump is clean
+        .inst(AssemblerRV64GC.addi(Ra, T0, 0))
+        .inst(AssemblerRV64GC.jal(Ra, 4))
+        // the compressed ones
+        .c_inst(AssemblerRV64GC.c_mv(S1, T0))
+        .c_inst(AssemblerRV64GC.c_add(S1, T1))
+        .c_inst(AssemblerRV64GC.c_li(A0, 3))
+        .c_inst(AssemblerRV64GC.c_addi4spn(A1, 16))
+        // the floats
+        .inst(AssemblerRV64GC.fmv_d_x(FT0, T0, RNE))
+        .inst(AssemblerRV64GC.fadd_d(FT1, FT0, FT2, RNE))
+        .inst(AssemblerRV64GC.fmv_x_d(A2, FT1, RNE))
+    );
+    let (i0, i1) = (taint.input(0), taint.input(1));
+    let both = taint.union(i0, i1);
+    assert_eq!(taint.offsets(both), [0, 1]);
+    for (reg, label) in [
+        (T0, i0), (T1, i1), (T2, both), (T3, both), (T4, both), (T5, Label::CLEAN),
+        (T6, i1), (Ra, Label::CLEAN), (S1, both), (A0, Label::CLEAN), (A1, Label::CLEAN),
+        (A2, i0), (Zero, Label::CLEAN),
+    ] {
+        assert_eq!(taint.reg(reg), label, "{:?}", reg);
+    }
+    assert_eq!((taint.freg(FT0), taint.freg(FT1), taint.freg(FT2)), (i0, i0, Label::CLEAN));
+}
+
+#[test]
+fn test_memory() {
+    let mut taint = run(Program::default()
+        .inst(AssemblerRV64GC.lbu(T0, S0, 0))
+        .inst(AssemblerRV64GC.lbu(T1, S0, 1))
+        .inst(AssemblerRV64GC.or(T2, T0, T1))
+        // every byte stored gets the label of the register
+        .inst(AssemblerRV64GC.sd(S0, T2, 16))
+        .inst(AssemblerRV64GC.sb(S0, Zero, 17))
+        .inst(AssemblerRV64GC.lhu(A0, S0, 16))
+        .inst(AssemblerRV64GC.lbu(A1, S0, 17))
+        .inst(AssemblerRV64GC.lw(A2, S0, 20))
+        .inst(AssemblerRV64GC.ld(A3, S0, 0))
+        .inst(AssemblerRV64GC.addi(S1, T0, 0))
+        .c_inst(AssemblerRV64GC.c_sd(S0, S1, 32))
+        .c_inst(AssemblerRV64GC.c_ld(A4, S0, 32))
+        .inst(AssemblerRV64GC.fsd(S0, FT0, 20))
+        .inst(AssemblerRV64GC.lw(A5, S0, 16))
+        // the address does not taint the value
+        .inst(AssemblerRV64GC.add(T3, S0, T0))
+        .inst(AssemblerRV64GC.lbu(A6, T3, 0x100))
+        // the AMOs store the union and return the old value
+        .inst(AssemblerRV64GC.addi(T4, S0, 40))
+        .inst(AssemblerRV64GC.sw(T4, T0, 0))
+        .inst(AssemblerRV64GC.amoadd_w(A7, T4, T1, false, false))
+    );
+    let (i0, i1) = (taint.input(0), taint.input(1));
+    let both = taint.union(i0, i1);
+    assert_eq!(taint.reg(A0), both);
+    assert_eq!(taint.reg(A1), Label::CLEAN);
+    assert_eq!(taint.reg(A2), both);
+    assert_eq!(taint.offsets(taint.reg(A3)), [0, 1, 2, 3, 4, 5, 6, 7]);
+    assert_eq!(taint.reg(A4), i0);
+    assert_eq!(taint.reg(A5), both);
+    assert_eq!(taint.reg(A6), Label::CLEAN);
+    assert_eq!(taint.reg(A7), i0);
+    assert_eq!(taint.memory(DATA + 40, 4), both);
+    assert_eq!(taint.memory(DATA + 17, 1), Label::CLEAN);
+    assert_eq!(taint.memory(DATA + 16, 24), both);
+}
+
+#[test]
+fn test_comparisons() {
+    let mut program = Program::default();
+    program
+        .inst(AssemblerRV64GC.lbu(T0, S0, 2))
+        .inst(AssemblerRV64GC.lbu(T1, S0, 3))
+        .inst(AssemblerRV64GC.beq(T0, T1, 4))
+        .inst(AssemblerRV64GC.blt(Zero, T1, 4))
+        .inst(AssemblerRV64GC.slti(A0, T0, 3))
+        .inst(AssemblerRV64GC.sltu(A1, T1, T0))
+        // not a comparison
+        .inst(AssemblerRV64GC.mulhsu(A2, T1, T0))
+        .inst(AssemblerRV64GC.addi(S1, T0, 0))
+        .c_inst(AssemblerRV64GC.c_bnez(S1, 2))
+        .inst(AssemblerRV64GC.bne(Zero, S0, 4));
+    let mut taint = run(&mut program);
+    let (i2, i3) = (taint.input(2), taint.input(3));
+    for (pc, labels) in [
+        (8, (i2, i3)),
+        (12, (Label::CLEAN, i3)),
+        (16, (i2, Label::CLEAN)),
+        (20, (i3, i2)),
+        (32, (i2, Label::CLEAN)),
+    ] {
+        assert_eq!(taint.comparison(CODE + pc), Some(labels), "{:#x}", pc);
+    }
+    assert_eq!(taint.comparisons().count(), 5);
+    assert_eq!(taint.comparison(CODE + 24), None);
+    assert_eq!(taint.comparison(CODE + 34), None);
+
+    // the labels are accumulated over the executions
+    let mut core = program.build();
+    core.write_reg(S0, DATA);
+    let mut taint = TaintTracker::default();
+    taint.taint_input(DATA + 2, 0, 1);
+    core.taint = Some(taint);
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    core.taint.as_mut().unwrap().taint_input(DATA + 2, 5, 1);
+    core.pc = CODE;
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    let taint = core.taint.as_ref().unwrap();
+    let (lhs, rhs) = taint.comparison(CODE + 8).unwrap();
+    assert_eq!((taint.offsets(lhs), rhs), (&[0, 5][..], Label::CLEAN));
+}
+
+#[test]
+fn test_syscalls() {
+    let mut emu = new_emu();
+    emu.core.mem.allocate_segment(
+        Some(VirtAddr(DATA as usize)), 0x1000,
+        PermField::Read | PermField::Write,
+    ).unwrap();
+    emu.vfs.add_file(b"/file", b"file".to_vec());
+    emu.vfs.set_input_path(b"/input");
+    emu.vfs.set_input_fd(0);
+    emu.vfs.set_input(b"0123456789");
+    emu.core.taint = Some(TaintTracker::default());
+
+    // the bytes read get the label of their offset in the input
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[0, DATA, 4]), 4);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[0, DATA + 8, 2]), 2);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::pread64, &[0, DATA + 16, 2, 7]), 2);
+    let taint = emu.core.taint.as_mut().unwrap();
+    for (addr, offset) in [(DATA, 0), (DATA + 3, 3), (DATA + 8, 4), (DATA + 9, 5), (DATA + 17, 8)] {
+        let label = taint.memory(addr, 1);
+        assert_eq!(taint.offsets(label), [offset]);
+    }
+    assert_eq!(taint.memory(DATA + 4, 4), Label::CLEAN);
+
+    // the other reads are clean
+    let path = DATA + 0x100;
+    emu.core.mem.write_slice(VirtAddr(path as usize), b"/file\0").unwrap();
+    let fd = syscall(&mut emu, LinuxSyscall::openat, &[AT_FDCWD as u64, path, 0]);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::read, &[fd, DATA + 1, 2]), 2);
+    let taint = emu.core.taint.as_mut().unwrap();
+    assert_eq!(taint.memory(DATA + 1, 2), Label::CLEAN);
+    assert_ne!(taint.memory(DATA, 1), Label::CLEAN);
+
+    // the mappings of the input are tainted too
+    emu.core.mem.write_slice(VirtAddr(path as usize), b"/input\0").unwrap();
+    let fd = syscall(&mut emu, LinuxSyscall::openat, &[AT_FDCWD as u64, path, 0]);
+    let addr = syscall(&mut emu, LinuxSyscall::mmap, &[0, 0x1000, 1, 2, fd, 0]);
+    let taint = emu.core.taint.as_mut().unwrap();
+    let label = taint.memory(addr + 9, 1);
+    assert_eq!(taint.offsets(label), [9]);
+
+    // the tainted arguments are recorded and the results are clean
+    let label = taint.input(3);
+    taint.set_reg(A2, label);
+    taint.set_reg(A0, label);
+    assert_eq!(syscall(&mut emu, LinuxSyscall::write, &[1, DATA, 4]), 4);
+    let taint = emu.core.taint.as_ref().unwrap();
+    let write = taint.syscall(CODE).unwrap();
+    assert_eq!(write.number, LinuxSyscall::write as u64);
+    assert_eq!(write.args, [label, Label::CLEAN, label, Label::CLEAN, Label::CLEAN, Label::CLEAN]);
+    assert_eq!(taint.reg(A0), Label::CLEAN);
+    assert_eq!(taint.syscalls().count(), 1);
+}
+
+#[test]
+fn test_reset() {
+    let mut core = Program::default()
+        .inst(AssemblerRV64GC.lbu(T0, S0, 0))
+        .inst(AssemblerRV64GC.sb(S0, T0, 8))
+        .build();
+    core.write_reg(S0, DATA);
+    let mut taint = TaintTracker::default();
+    taint.taint_input(DATA, 0, 1);
+    core.taint = Some(taint);
+    let snapshot = core.fork();
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+    assert!(!core.taint.as_mut().unwrap().memory(DATA + 8, 1).is_clean());
+
+    core.reset(&snapshot);
+    let taint = core.taint.as_mut().unwrap();
+    assert!(taint.reg(T0).is_clean());
+    assert!(taint.memory(DATA + 8, 1).is_clean());
+
+    // the tracking can be turned off
+    core.taint = None;
+    assert!(matches!(core.run(), CoreEmuError::Syscall));
+}
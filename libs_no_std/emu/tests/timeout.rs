@@ -0,0 +1,57 @@
+//! Tests of the instruction and time budgets
+use emu::riscv64gc::*;
+use mmu::PermField;
+
+mod common;
+use common::*;
+
+/// A core stuck in `addi a0, a0, 1; j -4`
+fn looping_core() -> CoreEmu {
+    let mut code = Vec::new();
+    code.extend_from_slice(&AssemblerRV64GC.addi(Register::A0, Register::A0, 1).unwrap().to_le_bytes());
+    code.extend_from_slice(&AssemblerRV64GC.jal(Register::Zero, -4).unwrap().to_le_bytes());
+    core_with_code(&code, PermField::Read | PermField::Executable)
+}
+
+#[test]
+fn test_instruction_limit() {
+    let mut core = looping_core();
+    core.set_instruction_budget(11);
+    assert!(matches!(core.run(), CoreEmuError::Timeout));
+    assert_eq!(core.instructions_executed, 11);
+    // the sixth addi wasn't executed
+    assert_eq!(core.read_reg(Register::A0), 6);
+    assert_eq!(core.pc, CODE + 4);
+
+    // it can be resumed
+    core.set_instruction_budget(1);
+    assert!(matches!(core.run(), CoreEmuError::Timeout));
+    assert_eq!(core.instructions_executed, 12);
+    assert_eq!(core.pc, CODE);
+
+    // the limit is absolute, so forks share it
+    let mut fork = core.fork();
+    fork.instruction_limit = 100;
+    assert!(matches!(fork.run(), CoreEmuError::Timeout));
+    assert_eq!(fork.instructions_executed, 100);
+    assert!(matches!(fork.fork().run(), CoreEmuError::Timeout));
+}
+
+#[test]
+fn test_linux_timeout() {
+    let mut emu = LinuxEmu::new(looping_core().mem);
+    emu.core.pc = CODE;
+    emu.core.instruction_limit = 1000;
+    assert!(matches!(emu.run(), LinuxEmuError::Timeout));
+    assert_eq!(emu.core.instructions_executed, 1000);
+}
+
+#[cfg(feature="std")]
+#[test]
+fn test_deadline() {
+    let mut core = looping_core();
+    core.set_time_budget(Some(std::time::Duration::from_millis(10)));
+    assert!(matches!(core.run(), CoreEmuError::Timeout));
+    assert_eq!(core.instructions_executed % DEADLINE_POLL_INTERVAL, 0);
+    assert!(core.deadline.unwrap() <= std::time::Instant::now());
+}
@@ -0,0 +1,147 @@
+//! Tests of the [`UninitTracker`] and of the reads of uninitialized memory
+//! of [`LinuxEmu`]
+use emu::riscv64gc::*;
+use emu::riscv64gc::Register::*;
+use emu::riscv64gc::mman::*;
+use mmu::{MmuError, PermField, VirtAddr};
+
+mod common;
+use common::*;
+
+/// Top of a stack that is readable only after it's written
+const STACK: u64 = 0x8_0000;
+
+/// A process running `program` on the stack, with the origins tracked
+fn tracked(program: &mut Program) -> LinuxEmu {
+    program.inst(AssemblerRV64GC.addi(A7, Zero, LinuxSyscall::exit_group as i32));
+    let mut emu = new_emu();
+    emu.core = program.build();
+    emu.core.mem.allocate_segment(
+        Some(VirtAddr((STACK - 0x1000) as usize)), 0x1000,
+        PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    emu.core.write_reg(Sp, STACK);
+    emu.core.uninit = Some(UninitTracker::default());
+    emu
+}
+
+/// A function reading the second word of its frame before writing it
+fn read_frame() -> Program {
+    let mut program = Program::default();
+    program
+        .inst(AssemblerRV64GC.addi(Sp, Sp, -32))
+        .inst(AssemblerRV64GC.sd(Sp, Zero, 0))
+        .inst(AssemblerRV64GC.ld(T0, Sp, 0))
+        .inst(AssemblerRV64GC.ld(T1, Sp, 8))
+        .inst(AssemblerRV64GC.ld(T1, Sp, 12))
+        .inst(AssemblerRV64GC.addi(A0, T0, 7));
+    program
+}
+
+#[test]
+fn test_regions() {
+    let mut uninit = UninitTracker::default();
+    uninit.record(0x100, 0x200, UninitOrigin::Brk { pc: 1 });
+    uninit.record(0x180, 0x280, UninitOrigin::Mmap { pc: 2 });
+    uninit.record(0x120, 0x140, UninitOrigin::StackFrame { pc: 3 });
+    uninit.record(0x300, 0x300, UninitOrigin::StackFrame { pc: 4 });
+    let regions: Vec<_> = uninit.regions()
+        .map(|region| (region.start, region.end, region.origin))
+        .collect();
+    assert_eq!(regions, [
+        (0x100, 0x120, UninitOrigin::Brk { pc: 1 }),
+        (0x120, 0x140, UninitOrigin::StackFrame { pc: 3 }),
+        (0x140, 0x180, UninitOrigin::Brk { pc: 1 }),
+        (0x180, 0x280, UninitOrigin::Mmap { pc: 2 }),
+    ]);
+    assert_eq!(uninit.region(0x17f).unwrap().start, 0x140);
+    assert_eq!(uninit.region(0x280), None);
+    assert_eq!(uninit.region(0xff), None);
+
+    // covering everything replaces it
+    uninit.record(0, 0x1000, UninitOrigin::HeapAlloc { pc: 5 });
+    assert_eq!(uninit.regions().count(), 1);
+}
+
+#[test]
+fn test_stack_frame() {
+    let mut emu = tracked(&mut read_frame());
+    let LinuxEmuError::UninitRead(read) = emu.run() else {
+        panic!("unexpected stop");
+    };
+    assert_eq!(read, UninitRead {
+        addr: STACK - 24,
+        pc: CODE + 12,
+        region: Some(UninitRegion {
+            start: STACK - 32,
+            end: STACK,
+            origin: UninitOrigin::StackFrame { pc: CODE },
+        }),
+    });
+    assert_eq!(emu.core.pc, CODE + 12);
+
+    // the frame of the last call replaces the previous ones
+    let mut program = Program::default();
+    program
+        .inst(AssemblerRV64GC.addi(Sp, Sp, -64))
+        .inst(AssemblerRV64GC.addi(Sp, Sp, 64))
+        .inst(AssemblerRV64GC.addi(Sp, Sp, -16))
+        .inst(AssemblerRV64GC.ld(T0, Sp, 0));
+    let mut emu = tracked(&mut program);
+    let LinuxEmuError::UninitRead(read) = emu.run() else {
+        panic!("unexpected stop");
+    };
+    assert_eq!(read.region.unwrap().origin, UninitOrigin::StackFrame { pc: CODE + 8 });
+    let uninit = emu.core.uninit.as_ref().unwrap();
+    assert_eq!(uninit.region(STACK - 64).unwrap().origin, UninitOrigin::StackFrame { pc: CODE });
+
+    // without the tracker it's the plain fault
+    let mut emu = tracked(&mut read_frame());
+    emu.core.uninit = None;
+    assert!(matches!(emu.run(), LinuxEmuError::MmuError(MmuError::PermissionsFault { .. })));
+}
+
+#[test]
+fn test_keep_going() {
+    let mut emu = tracked(&mut read_frame());
+    emu.core.uninit.as_mut().unwrap().keep_going = true;
+    let snapshot = emu.fork();
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    // each byte is reported once, the second read only for the new ones
+    let reads = &emu.core.uninit.as_ref().unwrap().reads;
+    assert_eq!(reads.iter().map(|read| (read.addr, read.pc)).collect::<Vec<_>>(),
+        [(STACK - 24, CODE + 12), (STACK - 16, CODE + 16)]);
+    assert!(reads.iter().all(|read| read.region.is_some()));
+    assert_eq!(emu.core.read_reg(T1), 0);
+
+    // the bytes are uninitialized again after a reset
+    emu.reset(&snapshot);
+    assert!(emu.core.uninit.as_ref().unwrap().reads.is_empty());
+    assert!(matches!(emu.run(), LinuxEmuError::Exit(7)));
+    assert_eq!(emu.core.uninit.as_ref().unwrap().reads.len(), 2);
+}
+
+#[test]
+fn test_syscalls() {
+    let mut emu = new_emu();
+    emu.core.uninit = Some(UninitTracker::default());
+
+    let brk = syscall(&mut emu, LinuxSyscall::brk, &[BRK + 0x100]);
+    assert_eq!(brk, BRK + 0x100);
+    let map = syscall(&mut emu, LinuxSyscall::mmap,
+        &[0, 0x1000, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, u64::MAX, 0]);
+    let moved = syscall(&mut emu, LinuxSyscall::mremap, &[map, 0x1000, 0x3000, MREMAP_MAYMOVE]);
+
+    let uninit = emu.core.uninit.as_ref().unwrap();
+    assert_eq!(uninit.region(BRK + 0x80), Some(UninitRegion {
+        start: BRK,
+        end: BRK + 0x100,
+        origin: UninitOrigin::Brk { pc: CODE },
+    }));
+    assert_eq!(uninit.region(map).unwrap().origin, UninitOrigin::Mmap { pc: CODE });
+    assert_eq!(uninit.region(moved + 0x2fff).unwrap().origin, UninitOrigin::Mmap { pc: CODE });
+
+    // the break doesn't move on failure
+    syscall(&mut emu, LinuxSyscall::brk, &[1]);
+    assert_eq!(emu.core.uninit.as_ref().unwrap().regions().count(), 3);
+}
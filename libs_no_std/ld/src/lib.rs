@@ -5,6 +5,7 @@ use goblin::Object;
 use goblin::elf::Elf;
 use goblin::elf64::program_header::*;
 use goblin::elf64::header::*;
+use emu::riscv64gc::Symbol;
 
 pub struct LoadingInfo {
     pub file_baseaddress: VirtAddr,
@@ -14,6 +15,9 @@ pub struct LoadingInfo {
     pub rsp: VirtAddr,
 
     pub loader_entry: VirtAddr,
+
+    /// The functions of the executable and of the interpreter
+    pub symbols: Vec<Symbol>,
 }
 
 pub struct Loader<'a> {
@@ -48,6 +52,7 @@ impl<'a> Loader<'a> {
     
         // mmap in the file segments
         load_segments(file_bytes, &elf, mmu, file_baseaddress);
+        let mut symbols = elf_symbols(&elf, file_baseaddress);
     
         // find the last segment to allocate the brk area
         let max_addr = mmu.segments.iter().map(|(base_addr, segment)| 
@@ -69,6 +74,7 @@ impl<'a> Loader<'a> {
                 _ => panic!("The given ELF loader is not an elf OwO"),
             };
             load_segments(self.ld_bytes, &ld_elf, mmu, self.ld_addr);
+            symbols.extend(elf_symbols(&ld_elf, self.ld_addr));
             self.ld_addr + ld_elf.entry as usize
         } else {
             VirtAddr(0x0)
@@ -207,11 +213,38 @@ impl<'a> Loader<'a> {
             loader_entry,
             rsp: rsp,
             start_address,
+            symbols,
         }
     }
 }
 
 
+/// The functions defined in the symbol tables of `elf`, which is loaded at
+/// `base_addr`
+fn elf_symbols(elf: &Elf, base_addr: VirtAddr) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    let mut seen = std::collections::BTreeSet::new();
+    for (syms, strtab) in [(&elf.syms, &elf.strtab), (&elf.dynsyms, &elf.dynstrtab)] {
+        for sym in syms.iter() {
+            if !sym.is_function() || sym.is_import() || sym.st_value == 0 {
+                continue;
+            }
+            let Some(name) = strtab.get_at(sym.st_name) else {
+                continue;
+            };
+            // the dynamic symbols are usually in the symbol table too
+            if seen.insert((sym.st_value, name)) {
+                symbols.push(Symbol {
+                    addr: base_addr.0 as u64 + sym.st_value,
+                    size: sym.st_size,
+                    name: name.to_string(),
+                });
+            }
+        }
+    }
+    symbols
+}
+
 fn load_segments(file_bytes: &[u8], elf: &Elf, mmu: &mut Mmu, base_addr: VirtAddr) {
     // load the memory segments
     for segment in &elf.program_headers {
@@ -0,0 +1,87 @@
+//! Benchmarks of the address to segment lookup on a memory map like the one
+//! of a dynamically linked binary, run with `cargo +nightly bench -p mmu`
+#![feature(test)]
+extern crate test;
+use mmu::*;
+use test::{black_box, Bencher};
+
+/// Number of mappings, a glibc binary with a few libraries has 20 to 30
+const SEGMENTS: usize = 24;
+
+/// An mmu with `SEGMENTS` mappings of 4 pages each and the addresses of the
+/// first qword of every mapping, in a scattered order
+fn loader_mmu() -> (Mmu, Vec<VirtAddr>) {
+    let mut mmu: Mmu = Mmu::new();
+    let rw = PermField::Read | PermField::Write;
+    let mut addrs = (0..SEGMENTS)
+        .map(|_| mmu.mmap(MapPlacement::Any, 0x4000, rw).unwrap())
+        .collect::<Vec<_>>();
+    for addr in addrs.iter() {
+        mmu.write::<u64>(*addr, 0x1337).unwrap();
+    }
+    // jump around, like loads from code, stack, heap and libraries
+    let len = addrs.len();
+    for i in 0..len {
+        addrs.swap(i, (i * 7 + 3) % len);
+    }
+    (mmu, addrs)
+}
+
+/// The lookup done before the index was added
+fn linear(mmu: &mut Mmu, addr: VirtAddr) -> Option<usize> {
+    mmu.segments.iter().position(|(start, smmu)| {
+        (start.0..start.0 + smmu.len()).contains(&addr.0)
+    })
+}
+
+#[bench]
+fn bench_linear_scattered(b: &mut Bencher) {
+    let (mut mmu, addrs) = loader_mmu();
+    b.iter(|| {
+        for addr in addrs.iter() {
+            black_box(linear(&mut mmu, black_box(*addr)));
+        }
+    });
+}
+
+#[bench]
+fn bench_indexed_scattered(b: &mut Bencher) {
+    let (mut mmu, addrs) = loader_mmu();
+    b.iter(|| {
+        for addr in addrs.iter() {
+            black_box(mmu.find_segment(black_box(*addr)));
+        }
+    });
+}
+
+#[bench]
+fn bench_linear_same_segment(b: &mut Bencher) {
+    let (mut mmu, addrs) = loader_mmu();
+    let addr = *addrs.iter().max().unwrap();
+    b.iter(|| {
+        for _ in 0..SEGMENTS {
+            black_box(linear(&mut mmu, black_box(addr)));
+        }
+    });
+}
+
+#[bench]
+fn bench_indexed_same_segment(b: &mut Bencher) {
+    let (mut mmu, addrs) = loader_mmu();
+    let addr = *addrs.iter().max().unwrap();
+    b.iter(|| {
+        for _ in 0..SEGMENTS {
+            black_box(mmu.find_segment(black_box(addr)));
+        }
+    });
+}
+
+#[bench]
+fn bench_read_scattered(b: &mut Bencher) {
+    let (mut mmu, addrs) = loader_mmu();
+    b.iter(|| {
+        for addr in addrs.iter() {
+            black_box(mmu.read::<u64>(black_box(*addr)).unwrap());
+        }
+    });
+}
@@ -1,20 +1,21 @@
-//use mmu::*;
+use mmu::*;
 
 fn main() {
-    /*
     let mut mmu = Mmu::<
         256,    // dirty block size
         true,   // RAW
         true,   // TAINT
-    >::new(0x10_000).unwrap();
+    >::new();
 
-    // We did not write anything so we should not be able to read it
+    // We did not map anything so we should not be able to read it
     let null = VirtAddr(0);
     assert!(mmu.read::<u32>(null).is_err());
     assert!(mmu.write::<u64>(null, 0x1337).is_err());
 
-    // allocate memory the allocations start from address 0x1000
-    let addr = mmu.allocate(0x100).unwrap();
+    // allocate memory the allocations start from `segments_alloc_addr`
+    let addr = mmu.mmap(
+        MapPlacement::Any, 0x100, PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
 
     // error on uninitalized memory
     assert!(mmu.read::<u16>(addr).is_err());
@@ -40,7 +41,4 @@ fn main() {
     // check that the state was **actually** resetted
     assert!(mmu2.read::<u16>(addr + 8).is_err());
     assert_eq!(1337, mmu.read::<u64>(addr).unwrap());
-     */
 }
-
-
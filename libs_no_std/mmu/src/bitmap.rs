@@ -56,6 +56,13 @@ impl Bitmap {
         self.0.resize((size + BITS_IN_WORD - 1)  / BITS_IN_WORD, fill_value);
     }
 
+    /// Pointer to the words of the bitmap, bit `index` is bit `index % 64` of
+    /// the word `index / 64`
+    #[inline]
+    pub fn as_ptr(&self) -> *const usize {
+        self.0.as_ptr()
+    }
+
     /// Clean the bitmap (as in reset everything to zero)
     #[inline]
     pub fn clear(&mut self) {
@@ -53,6 +53,14 @@ impl DirtyState {
 
     #[inline]
     pub fn resize(&mut self, size: usize) {
+        // drop the blocks that don't exist anymore
+        let bitmap = &mut self.dirty_bitmap;
+        self.dirty_indices.retain(|&idx| {
+            if idx >= size {
+                bitmap.reset(idx);
+            }
+            idx < size
+        });
         self.dirty_bitmap.resize(size, false);
         self.len = size;
     }
@@ -63,6 +71,13 @@ impl DirtyState {
         self.dirty_bitmap.clear();
     }
 
+    /// The bitmap of the dirty blocks, a block whose bit is set is already
+    /// in the list and dirtying it again does nothing
+    #[inline]
+    pub fn bitmap(&self) -> &Bitmap {
+        &self.dirty_bitmap
+    }
+
     /// Return the size with which the dirty state was initialized
     #[inline]
     pub fn len(&self) -> usize {
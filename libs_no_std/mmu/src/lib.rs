@@ -36,6 +36,8 @@ mod perm;
 pub use perm::*;
 mod mmu_read_write_impls;
 pub use mmu_read_write_impls::*;
+mod shadow;
+pub use shadow::*;
 mod segment_mmu;
 pub use segment_mmu::*;
 mod mmu;
@@ -53,8 +55,8 @@ pub enum MmuError {
     /// to have an early stop and figure it out rather than continue silently.
     UselessReadAfterWrite,
 
-    /// This error is raised when the requested allocation memory is bigger than
-    /// the **initial** size of memory handable by this MMU
+    /// This error is raised when there is no free range of virtual addresses
+    /// big enough for the requested allocation
     CannotAllocate {
         virtual_address: VirtAddr,
         mmu_length: usize,
@@ -102,4 +104,16 @@ pub enum MmuError {
     SegmentNotFound{
         virtual_address: VirtAddr,
     },
+
+    /// A null terminated string was longer than the allowed maximum
+    CStrTooLong{
+        virtual_address: VirtAddr,
+    },
+
+    /// A mapping was requested at a fixed address without replacing the
+    /// mappings already there, but the range is not free
+    AddressInUse{
+        virtual_address: VirtAddr,
+        size: usize,
+    },
 }
\ No newline at end of file
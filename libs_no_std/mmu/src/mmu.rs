@@ -1,6 +1,25 @@
 use crate::*;
 use traits::*;
 
+/// Granularity of the automatic address selection
+pub const PAGE_SIZE: usize = 0x1000;
+
+/// Default of [`Mmu::memory_limit`], like the rss limit of libFuzzer
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 31;
+
+/// Where [`Mmu::mmap`] should place a new mapping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapPlacement {
+    /// Anywhere, searching from `segments_alloc_addr`
+    Any,
+    /// At the given address if the range is free, otherwise anywhere
+    Hint(VirtAddr),
+    /// Exactly at the given address, replacing the overlapping mappings
+    Fixed(VirtAddr),
+    /// Exactly at the given address, failing if the range is not free
+    FixedNoReplace(VirtAddr),
+}
+
 #[derive(Debug)]
 pub struct Mmu<
     // size of the dirty blocks
@@ -15,6 +34,21 @@ pub struct Mmu<
     pub stack_segment_idx: usize,
     pub segments_alloc_addr: VirtAddr,
     pub segment_redzone: usize,
+    /// Maximum number of bytes mapped, the allocations and the growths
+    /// beyond it fail as the host would have to allocate them too
+    pub memory_limit: usize,
+    /// `(start address, index in segments)` sorted by address, used to find
+    /// segments with a binary search
+    segment_index: alloc::vec::Vec<(usize, usize)>,
+    /// Index of the segment found by the last lookup, most accesses hit the
+    /// same segment as the previous one
+    last_segment: usize,
+    /// Changed every time executable memory is written, unmapped or has its
+    /// permissions changed, so that caches of decoded code know when they
+    /// are stale. `reset` restores the value of the reference, which is still
+    /// a change if the code was modified after the fork, but the caches must
+    /// be dropped as the versions after it will be reused
+    pub code_version: u64,
 } 
 
 impl<
@@ -33,16 +67,82 @@ impl<
             stack_segment_idx: 0,
             segments_alloc_addr: VirtAddr(0x0000004000000000),
             segment_redzone: 0x1000,
+            memory_limit: DEFAULT_MEMORY_LIMIT,
+            segment_index: alloc::vec::Vec::with_capacity(10),
+            last_segment: 0,
+            code_version: 0,
         }
     }
 
     #[inline]
     pub fn resolve_segment(&mut self, addr: VirtAddr) -> Result<&mut (VirtAddr, SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>), MmuError> {
-        self.segments.iter_mut().find(|(start_addr, smmu)| {
-            (start_addr.0..start_addr.0 + smmu.len()).contains(&addr.0)
-        }).ok_or_else(|| {
+        let idx = self.find_segment(addr).ok_or(
             MmuError::SegmentNotFound { virtual_address: addr }
-        })
+        )?;
+        Ok(&mut self.segments[idx])
+    }
+
+    /// Index of the segment containing `addr`, first checking the last
+    /// segment found, then doing a binary search on the index and, if the
+    /// index is stale, a linear scan
+    #[inline]
+    pub fn find_segment(&mut self, addr: VirtAddr) -> Option<usize> {
+        let contains = |idx: usize| self.segments.get(idx)
+            .map(|(start_addr, smmu)| {
+                (start_addr.0..start_addr.0 + smmu.len()).contains(&addr.0)
+            })
+            .unwrap_or(false);
+
+        let last = self.last_segment;
+        if contains(last) {
+            return Some(last);
+        }
+
+        // the last segment starting before or at addr, the loop always does
+        // log2(len) steps so the only branch that depends on addr is the
+        // comparison, which the compiler can turn into a conditional move
+        let mut base = 0;
+        let mut size = self.segment_index.len();
+        while size > 1 {
+            let half = size / 2;
+            if self.segment_index[base + half].0 <= addr.0 {
+                base += half;
+            }
+            size -= half;
+        }
+        let candidate = self.segment_index.get(base).map(|(_, idx)| *idx);
+        let idx = match candidate {
+            Some(idx) if contains(idx) => idx,
+            // the index might be stale if `segments` was modified directly,
+            // so double check with a linear scan and fix the index
+            _ => {
+                let idx = self.segments.iter().position(|(start_addr, smmu)| {
+                    (start_addr.0..start_addr.0 + smmu.len()).contains(&addr.0)
+                })?;
+                self.update_index();
+                idx
+            }
+        };
+        self.last_segment = idx;
+        Some(idx)
+    }
+
+    /// Rebuild the sorted index of the segments, must be called every time
+    /// a segment is added, removed or moved
+    fn update_index(&mut self) {
+        self.segment_index.clear();
+        self.segment_index.extend(
+            self.segments.iter().enumerate().map(|(idx, (addr, _))| (addr.0, idx))
+        );
+        self.segment_index.sort_unstable();
+    }
+
+    /// Bump `code_version` if any of `perms` is executable
+    #[inline]
+    fn invalidate_code(code_version: &mut u64, perms: &[Perm]) {
+        if unlikely(perms.iter().any(|perm| perm.is_superset_of(PermField::Executable))) {
+            *code_version = code_version.wrapping_add(1);
+        }
     }
 
     #[inline]
@@ -50,9 +150,117 @@ impl<
         self.segments.iter().map(|(_addr, smmu)| smmu.len()).sum()
     }
 
+    /// Check that `size` more bytes, to be mapped at `addr`, fit in
+    /// `memory_limit` and in the address space
+    fn check_limit(&self, addr: VirtAddr, size: usize) -> Result<(), MmuError> {
+        let fits = self.len().checked_add(size)
+            .is_some_and(|len| len <= self.memory_limit);
+        if !fits || addr.0.checked_add(size).is_none() {
+            return Err(MmuError::CannotAllocate {
+                virtual_address: addr,
+                mmu_length: size,
+            });
+        }
+        Ok(())
+    }
+
+    #[cfg(feature="std")]
     pub fn vmmap(&self) {
-        for (virtaddr, segment) in self.segments.iter() {
-            println!("{:016x}-{:016x} - 0x{:06x} - {:?}", virtaddr.0, virtaddr.0 + segment.len() - 8, segment.len(), segment.permissions[0]);
+        let mut vmmap = alloc::string::String::new();
+        self.write_vmmap(&mut vmmap).unwrap();
+        print!("{}", vmmap);
+    }
+
+    /// Write the lines printed by [`Mmu::vmmap`] to `w`
+    pub fn write_vmmap<W: core::fmt::Write>(&self, w: &mut W) -> core::fmt::Result {
+        for idx in 0..self.segments.len() {
+            self.write_vmmap_entry(w, idx)?;
+        }
+        Ok(())
+    }
+
+    /// Write the line of [`Mmu::vmmap`] of the `idx`-th segment to `w`
+    pub fn write_vmmap_entry<W: core::fmt::Write>(&self, w: &mut W, idx: usize) -> core::fmt::Result {
+        let (virtaddr, segment) = &self.segments[idx];
+        let end = virtaddr.0 + segment.len().saturating_sub(8);
+        match segment.permissions.first() {
+            Some(perm) => writeln!(w, "{:016x}-{:016x} - 0x{:06x} - {:?}", virtaddr.0, end, segment.len(), perm),
+            // the brk and stack segments can be emptied by the guest
+            None => writeln!(w, "{:016x}-{:016x} - 0x{:06x} - empty", virtaddr.0, end, 0),
+        }
+    }
+
+    /// Index of the segment containing `addr`, like [`Mmu::find_segment`]
+    /// but without updating the cache of the last segment hit
+    pub fn lookup_segment(&self, addr: VirtAddr) -> Option<usize> {
+        self.segments.iter().position(|(seg_addr, smmu)| {
+            seg_addr.0 <= addr.0 && addr.0 < seg_addr.0 + smmu.len()
+        })
+    }
+
+    /// Check if the range of `size` bytes at `addr`, extended by `redzone`
+    /// bytes on both sides, doesn't overlap any segment
+    pub fn is_free(&self, addr: VirtAddr, size: usize, redzone: usize) -> bool {
+        let start = addr.0.saturating_sub(redzone);
+        let end = addr.0.saturating_add(size).saturating_add(redzone);
+        self.segments.iter().all(|(seg_addr, smmu)| {
+            seg_addr.0 + smmu.len() <= start || end <= seg_addr.0
+        })
+    }
+
+    /// Find the first page-aligned free range of `size` bytes starting from
+    /// `segments_alloc_addr` leaving `segment_redzone` bytes between it and
+    /// the other segments
+    pub fn find_free_range(&self, size: usize) -> Result<VirtAddr, MmuError> {
+        let cannot_allocate = MmuError::CannotAllocate {
+            virtual_address: self.segments_alloc_addr,
+            mmu_length: size,
+        };
+        let mut addr = self.segments_alloc_addr.0
+            .checked_next_multiple_of(PAGE_SIZE).ok_or(cannot_allocate)?;
+        loop {
+            let end = addr.checked_add(size)
+                .and_then(|end| end.checked_add(self.segment_redzone))
+                .ok_or(MmuError::CannotAllocate {
+                    virtual_address: self.segments_alloc_addr,
+                    mmu_length: size,
+                })?;
+            let start = addr.saturating_sub(self.segment_redzone);
+            // skip after the first segment in the way, as addresses only grow
+            // this always terminates
+            let overlap = self.segments.iter().find(|(seg_addr, smmu)| {
+                !(seg_addr.0 + smmu.len() <= start || end <= seg_addr.0)
+            });
+            match overlap {
+                None => return Ok(VirtAddr(addr)),
+                Some((seg_addr, smmu)) => {
+                    addr = (seg_addr.0 + smmu.len())
+                        .checked_add(self.segment_redzone)
+                        .and_then(|addr| addr.checked_next_multiple_of(PAGE_SIZE))
+                        .ok_or(MmuError::CannotAllocate {
+                            virtual_address: self.segments_alloc_addr,
+                            mmu_length: size,
+                        })?;
+                }
+            }
+        }
+    }
+
+    /// Remove the segment at index `idx` keeping `brk_idx` and
+    /// `stack_segment_idx` pointing to the same segments. The brk and stack
+    /// segments are emptied instead of removed so they can grow again.
+    fn remove_segment(&mut self, idx: usize) {
+        if idx == self.brk_idx || idx == self.stack_segment_idx {
+            self.segments[idx].1.split_off(0);
+            return;
+        }
+        self.segments.remove(idx);
+        self.update_index();
+        if self.brk_idx > idx {
+            self.brk_idx -= 1;
+        }
+        if self.stack_segment_idx > idx {
+            self.stack_segment_idx -= 1;
         }
     }
 
@@ -73,11 +281,62 @@ impl<
             stack_segment_idx: self.stack_segment_idx,
             segments_alloc_addr: self.segments_alloc_addr,
             segment_redzone: self.segment_redzone,
+            memory_limit: self.memory_limit,
+            segment_index: self.segment_index.clone(),
+            last_segment: self.last_segment,
+            code_version: self.code_version,
         }
     }
 
+    /// Reset the memory to the state of `reference_memory`, which this mmu
+    /// was forked from. Only the dirty blocks of the segments are copied, the
+    /// segments mapped since the fork are dropped and the unmapped ones are
+    /// forked again from the reference.
     pub fn reset(&mut self, reference_memory: &Self) {
-        todo!()
+        // fast path, no segment was added or removed, at most resized
+        let same_layout = self.segments.len() == reference_memory.segments.len()
+            && self.segments.iter().zip(reference_memory.segments.iter())
+                .all(|((addr, smmu), (ref_addr, ref_smmu))| {
+                    addr == ref_addr && smmu.id == ref_smmu.id
+                });
+
+        if same_layout {
+            for ((_, smmu), (_, ref_smmu)) in self.segments.iter_mut()
+                .zip(reference_memory.segments.iter()) {
+                smmu.reset(ref_smmu);
+            }
+        } else {
+            // rebuild the segments in the order of the reference, so that 
+            // the indices are the same
+            let mut old_segments = core::mem::replace(
+                &mut self.segments,
+                alloc::vec::Vec::with_capacity(reference_memory.segments.len()),
+            );
+            for (ref_addr, ref_smmu) in reference_memory.segments.iter() {
+                let old = old_segments.iter().position(|(addr, smmu)| {
+                    addr == ref_addr && smmu.id == ref_smmu.id
+                });
+                let smmu = match old {
+                    Some(idx) => {
+                        let (_, mut smmu) = old_segments.swap_remove(idx);
+                        smmu.reset(ref_smmu);
+                        smmu
+                    }
+                    // it was unmapped, or split and mapped again
+                    None => ref_smmu.fork(),
+                };
+                self.segments.push((*ref_addr, smmu));
+            }
+        }
+        self.segment_index.clone_from(&reference_memory.segment_index);
+        self.last_segment = reference_memory.last_segment;
+        self.code_version = reference_memory.code_version;
+
+        self.brk_idx = reference_memory.brk_idx;
+        self.stack_segment_idx = reference_memory.stack_segment_idx;
+        self.segments_alloc_addr = reference_memory.segments_alloc_addr;
+        self.segment_redzone = reference_memory.segment_redzone;
+        self.memory_limit = reference_memory.memory_limit;
     }
 
     /// Read a value from memory at address `address` with native endianess
@@ -90,18 +349,59 @@ impl<
     {
         let (base_addr, segment_mmu) = self.resolve_segment(address)?;
         segment_mmu.read_with_perm(VirtAddr(address.0 - base_addr.0), perm)
+            .map_err(|e| Self::absolute_error(e, address))
+    }
+
+    /// The segments report the faults with the offset in the segment, make
+    /// them report the address of the access instead
+    fn absolute_error(error: MmuError, address: VirtAddr) -> MmuError {
+        match error {
+            MmuError::PermissionsFault { is_read, permissions, size, .. } => {
+                MmuError::PermissionsFault {
+                    is_read,
+                    virtual_address: address,
+                    permissions,
+                    size,
+                }
+            }
+            MmuError::OutOfBound { is_read, .. } => {
+                MmuError::OutOfBound { is_read, virtual_address: address }
+            }
+            e => e,
+        }
     }
     
     pub unsafe fn write_from_slice(&mut self, address: VirtAddr, slice: &[u8]) -> Result<(), MmuError> 
     {
-        let (base_addr, segment_mmu) = self.resolve_segment(address)?;
-        segment_mmu.write_from_slice(VirtAddr(address.0 - base_addr.0), slice)
+        let idx = self.find_segment(address)
+            .ok_or(MmuError::SegmentNotFound { virtual_address: address })?;
+        let (base_addr, segment_mmu) = &mut self.segments[idx];
+        let offset = address.0 - base_addr.0;
+        segment_mmu.write_from_slice(VirtAddr(offset), slice)?;
+        Self::invalidate_code(
+            &mut self.code_version, 
+            &segment_mmu.permissions[offset..offset + slice.len()],
+        );
+        Ok(())
     }
 
     pub unsafe fn write_from_slice_with_perm(&mut self, address: VirtAddr, slice: &[u8], perm: Perm) -> Result<(), MmuError> 
     {
-        let (base_addr, segment_mmu) = self.resolve_segment(address)?;
-        segment_mmu.write_from_slice_with_perm(VirtAddr(address.0 - base_addr.0), slice, perm)
+        let idx = self.find_segment(address)
+            .ok_or(MmuError::SegmentNotFound { virtual_address: address })?;
+        let (base_addr, segment_mmu) = &mut self.segments[idx];
+        let offset = address.0 - base_addr.0;
+        // the old permissions might have been executable
+        Self::invalidate_code(
+            &mut self.code_version, 
+            segment_mmu.permissions.get(offset..offset + slice.len()).unwrap_or(&[]),
+        );
+        segment_mmu.write_from_slice_with_perm(VirtAddr(offset), slice, perm)?;
+        Self::invalidate_code(
+            &mut self.code_version, 
+            &segment_mmu.permissions[offset..offset + slice.len()],
+        );
+        Ok(())
     }
 
     /// Read a value from memory at address `address` with native endianess
@@ -112,22 +412,38 @@ impl<
     {   
 
         let (base_addr, segment_mmu) = self.resolve_segment(address)?;
-        segment_mmu.read(VirtAddr(address.0 - base_addr.0)).map_err(|e| match e {
-            MmuError::PermissionsFault { 
-                is_read, 
-                permissions, 
-                size,
-                ..
-            } => {
-                MmuError::PermissionsFault { 
-                    is_read, 
-                    virtual_address: address, 
-                    permissions, 
-                    size
-                } 
-            }
-            e => e,  
-        })
+        segment_mmu.read(VirtAddr(address.0 - base_addr.0)).map_err(|e| Self::absolute_error(e, address))
+    }
+
+    /// Read `buf.len()` bytes starting from `address` checking the permissions
+    pub fn read_slice(&mut self, address: VirtAddr, buf: &mut [u8]) -> Result<(), MmuError> {
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = self.read(address + i)?;
+        }
+        Ok(())
+    }
+
+    /// Read a null terminated string starting from `address`, without the
+    /// terminator, reading at most `max_len` bytes
+    pub fn read_cstr(&mut self, address: VirtAddr, max_len: usize) 
+        -> Result<alloc::vec::Vec<u8>, MmuError> {
+        let mut result = alloc::vec::Vec::new();
+        for i in 0..max_len {
+            let byte: u8 = self.read(address + i)?;
+            if byte == 0 {
+                return Ok(result);
+            }
+            result.push(byte);
+        }
+        Err(MmuError::CStrTooLong { virtual_address: address })
+    }
+
+    /// Write `slice` starting from `address` checking the permissions
+    pub fn write_slice(&mut self, address: VirtAddr, slice: &[u8]) -> Result<(), MmuError> {
+        for (i, byte) in slice.iter().enumerate() {
+            self.write(address + i, *byte)?;
+        }
+        Ok(())
     }
 
     /// Write a value `value` to memory at address `address` with native endianess
@@ -136,23 +452,16 @@ impl<
         T: Copy + Word,
         SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>: MmuReadWrite<T>,
     {
-        let (base_addr, segment_mmu) = self.resolve_segment(address)?;
-        segment_mmu.write(VirtAddr(address.0 - base_addr.0), value).map_err(|e| match e {
-            MmuError::PermissionsFault { 
-                is_read, 
-                permissions, 
-                size,
-                ..
-            } => {
-                MmuError::PermissionsFault { 
-                    is_read, 
-                    virtual_address: address, 
-                    permissions, 
-                    size
-                } 
-            }
-            e => e,  
-        })
+        let idx = self.find_segment(address)
+            .ok_or(MmuError::SegmentNotFound { virtual_address: address })?;
+        let (base_addr, segment_mmu) = &mut self.segments[idx];
+        let offset = address.0 - base_addr.0;
+        segment_mmu.write(VirtAddr(offset), value).map_err(|e| Self::absolute_error(e, address))?;
+        Self::invalidate_code(
+            &mut self.code_version, 
+            &segment_mmu.permissions[offset..offset + T::BYTES],
+        );
+        Ok(())
     }
 }
 
@@ -166,17 +475,21 @@ impl<
     TAINT,
 > {
     pub fn allocate_segment(&mut self, addr: Option<VirtAddr>, size: usize, perm: Perm) -> Result<(usize, &mut SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>), MmuError> {
-        let new_segment = <SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>>::new(size, perm)?;
         let idx = self.segments.len();
         
-        let addr = match addr {
-            Some(addr) => addr,
-            None => {
-                todo!();
-            }
+        let (addr, automatic) = match addr {
+            Some(addr) => (addr, false),
+            None => (self.find_free_range(size)?, true),
         };
+        self.check_limit(addr, size)?;
+        let new_segment = <SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>>::new(size, perm)?;
+        if automatic {
+            // the next automatic allocation will start after this one
+            self.segments_alloc_addr = VirtAddr(addr.0 + size + self.segment_redzone);
+        }
 
         self.segments.push((addr, new_segment));
+        self.update_index();
         self.validate();
         Ok((idx, &mut self.segments[idx].1))
     }
@@ -194,33 +507,225 @@ impl<
 > {
     ///  brk() sets the end of the data segment to the value specified by
     ///  addr, when that value is reasonable, the system has enough
-    ///  memory, and the process does not exceed its maximum data size
-    pub fn brk(&mut self, addr: VirtAddr) -> Result<(), MmuError> {
-        let (data_addr, data_seg) = &mut self.segments[self.brk_idx];
+    ///  memory, and the process does not exceed its maximum data size.
+    /// 
+    /// Like the syscall, the new program break is returned, which is the
+    /// current one if the request can't be satisfied, so `brk(0)` can be used
+    /// to query it.
+    pub fn brk(&mut self, addr: VirtAddr) -> Result<VirtAddr, MmuError> {
+        let (data_addr, data_seg) = &self.segments[self.brk_idx];
+        let (data_addr, current_len) = (*data_addr, data_seg.len());
+        let current_brk = VirtAddr(data_addr.0 + current_len);
+
+        // can't move the break before the start of the data segment
+        if addr.0 < data_addr.0 {
+            return Ok(current_brk);
+        }
         let segment_length = addr.0 - data_addr.0;
+        // the grown part must not overlap other segments nor exceed the limit
+        if segment_length > current_len 
+            && (!self.is_free(current_brk, segment_length - current_len, 0)
+                || self.check_limit(current_brk, segment_length - current_len).is_err()) {
+            return Ok(current_brk);
+        }
+
+        let (_, data_seg) = &mut self.segments[self.brk_idx];
+        if segment_length < current_len {
+            Self::invalidate_code(
+                &mut self.code_version, &data_seg.permissions[segment_length..],
+            );
+        }
         data_seg.resize(segment_length, PermField::Write | PermField::ReadAfterWrite)?;
-        Ok(())
+        Ok(addr)
     }
 
     /// sbrk() increments the program's data space by increment bytes.
     /// Calling sbrk() with an increment of 0 can be used to find the
     /// current location of the program break.
     pub fn sbrk(&mut self, increment: isize) -> Result<VirtAddr, MmuError> {
-        let (data_addr, data_seg) = &mut self.segments[self.brk_idx];
+        let (data_addr, data_seg) = &self.segments[self.brk_idx];
+        let current_brk = VirtAddr(data_addr.0 + data_seg.len());
         let new_length = data_seg.len().checked_add_signed(increment).unwrap();
+        if increment > 0 {
+            self.check_limit(current_brk, increment as usize)?;
+        }
+        let (data_addr, data_seg) = &mut self.segments[self.brk_idx];
         data_seg.resize(new_length, PermField::Write | PermField::ReadAfterWrite)?;
         Ok(VirtAddr(data_addr.0 + new_length))
     }
 
-    pub fn mmap() {
-        todo!()
+    /// Set the permissions of the `size` bytes starting from `addr` to `perm`,
+    /// the whole range must be mapped, otherwise nothing is changed.
+    pub fn mprotect(&mut self, addr: VirtAddr, size: usize, perm: Perm) -> Result<(), MmuError> {
+        let (start, end) = (addr.0, addr.0.saturating_add(size));
+        let overlap = |seg_addr: &VirtAddr, smmu: &SegmentMmu<DIRTY_BLOCK_SIZE, RAW, TAINT>| {
+            let seg_end = seg_addr.0 + smmu.len();
+            (start.max(seg_addr.0), end.min(seg_end))
+        };
+        // segments don't overlap so the range is covered if the sizes match
+        let covered: usize = self.segments.iter().map(|(seg_addr, smmu)| {
+            let (ov_start, ov_end) = overlap(seg_addr, smmu);
+            ov_end.saturating_sub(ov_start)
+        }).sum();
+        if covered != end - start {
+            return Err(MmuError::SegmentNotFound { virtual_address: addr });
+        }
+
+        for (seg_addr, smmu) in self.segments.iter_mut() {
+            let (ov_start, ov_end) = overlap(seg_addr, smmu);
+            if ov_start < ov_end {
+                let range = ov_start - seg_addr.0..ov_end - seg_addr.0;
+                Self::invalidate_code(&mut self.code_version, &smmu.permissions[range]);
+                smmu.set_permissions(
+                    VirtAddr(ov_start - seg_addr.0)..VirtAddr(ov_end - seg_addr.0),
+                    perm,
+                )?;
+            }
+        }
+        Ok(())
     }
 
-    pub fn memremap() {
-        todo!()
+    /// Map a new zeroed segment of `size` bytes with permissions `perm`, 
+    /// placed according to `placement`, and return its address.
+    /// File-backed mappings can be initialized with
+    /// [`Mmu::write_from_slice`] on the returned address.
+    pub fn mmap(&mut self, placement: MapPlacement, size: usize, perm: Perm) 
+        -> Result<VirtAddr, MmuError> {
+        let addr = match placement {
+            MapPlacement::Any => None,
+            MapPlacement::Hint(addr) => {
+                if addr.0 != 0 && self.is_free(addr, size, 0) {
+                    Some(addr)
+                } else {
+                    None
+                }
+            }
+            MapPlacement::Fixed(addr) => {
+                self.munmap(addr, size)?;
+                Some(addr)
+            }
+            MapPlacement::FixedNoReplace(addr) => {
+                if !self.is_free(addr, size, 0) {
+                    return Err(MmuError::AddressInUse { 
+                        virtual_address: addr, 
+                        size,
+                    });
+                }
+                Some(addr)
+            }
+        };
+        let (idx, _) = self.allocate_segment(addr, size, perm)?;
+        Ok(self.segments[idx].0)
+    }
+
+    /// Change the size and / or the address of the mapping of `old_size`
+    /// bytes at `old_addr`, the mapping must be contained in a single segment.
+    /// The mapping is grown in place if there is space after it, otherwise it's
+    /// moved if `may_move` is set, to `new_addr` if given (replacing the
+    /// mappings there) or to a free range.
+    /// The new bytes get the permissions of the last byte of the old mapping.
+    pub fn mremap(&mut self, old_addr: VirtAddr, old_size: usize, 
+        new_size: usize, may_move: bool, new_addr: Option<VirtAddr>,
+    ) -> Result<VirtAddr, MmuError> {
+        let idx = self.segments.iter().position(|(seg_addr, smmu)| {
+            (seg_addr.0..seg_addr.0 + smmu.len()).contains(&old_addr.0)
+        }).ok_or(MmuError::SegmentNotFound { virtual_address: old_addr })?;
+        let (seg_addr, smmu) = &self.segments[idx];
+        let seg_end = seg_addr.0 + smmu.len();
+        let offset = old_addr.0 - seg_addr.0;
+        if old_addr.0.checked_add(old_size).map_or(true, |old_end| old_end > seg_end) {
+            return Err(MmuError::SegmentNotFound { 
+                virtual_address: VirtAddr(seg_end),
+            });
+        }
+
+        if new_addr.is_none() {
+            // shrink in place
+            if new_size <= old_size {
+                self.munmap(VirtAddr(old_addr.0 + new_size), old_size - new_size)?;
+                return Ok(old_addr);
+            }
+            // grow in place
+            if old_addr.0 + old_size == seg_end 
+                && self.is_free(VirtAddr(seg_end), new_size - old_size, 0) {
+                self.check_limit(VirtAddr(seg_end), new_size - old_size)?;
+                let (_, smmu) = &mut self.segments[idx];
+                let perm = smmu.permissions[offset + old_size - 1];
+                smmu.resize(offset + new_size, perm)?;
+                return Ok(old_addr);
+            }
+        }
+
+        if !may_move {
+            return Err(MmuError::AddressInUse { 
+                virtual_address: VirtAddr(old_addr.0 + old_size), 
+                size: new_size.saturating_sub(old_size),
+            });
+        }
+
+        // save the content before touching the segments
+        let copy_len = old_size.min(new_size);
+        let memory = smmu.memory[offset..offset + copy_len].to_vec();
+        let permissions = smmu.permissions[offset..offset + copy_len].to_vec();
+        let perm = smmu.permissions[offset + old_size - 1];
+
+        let placement = match new_addr {
+            Some(new_addr) => MapPlacement::Fixed(new_addr),
+            None => MapPlacement::Any,
+        };
+        let addr = self.mmap(placement, new_size, perm)?;
+        let (_, smmu) = self.resolve_segment(addr)?;
+        smmu.memory[..copy_len].copy_from_slice(&memory);
+        smmu.permissions[..copy_len].copy_from_slice(&permissions);
+
+        self.munmap(old_addr, old_size)?;
+        Ok(addr)
     }
 
-    pub fn munmap() {
-        todo!()
+    /// Remove all the mappings in the `size` bytes starting from `addr`,
+    /// splitting the segments that are only partially covered. Unmapping
+    /// a range without mappings is not an error.
+    pub fn munmap(&mut self, addr: VirtAddr, size: usize) -> Result<(), MmuError> {
+        let (start, end) = (addr.0, addr.0.saturating_add(size));
+        let mut idx = 0;
+        while idx < self.segments.len() {
+            let (seg_addr, smmu) = &mut self.segments[idx];
+            let (seg_start, seg_end) = (seg_addr.0, seg_addr.0 + smmu.len());
+            // the empty segments have nothing to unmap, this also skips the
+            // brk and stack segments once `remove_segment` emptied them
+            if seg_start == seg_end || seg_end <= start || end <= seg_start {
+                idx += 1;
+                continue;
+            }
+            Self::invalidate_code(
+                &mut self.code_version, 
+                &smmu.permissions[start.max(seg_start) - seg_start..end.min(seg_end) - seg_start],
+            );
+
+            // the part after the range survives
+            let tail = if end < seg_end {
+                Some(smmu.split_off(end - seg_start))
+            } else {
+                None
+            };
+
+            if seg_start < start {
+                // the part before the range survives too
+                smmu.split_off(start - seg_start);
+                if let Some(tail) = tail {
+                    self.segments.push((VirtAddr(end), tail));
+                }
+            } else if let Some(tail) = tail {
+                // only the part after the range survives, keep the index
+                self.segments[idx] = (VirtAddr(end), tail);
+            } else {
+                self.remove_segment(idx);
+                continue;
+            }
+            idx += 1;
+        }
+        self.update_index();
+        self.validate();
+        Ok(())
     }
 }
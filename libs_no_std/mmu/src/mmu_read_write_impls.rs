@@ -69,7 +69,7 @@ impl<
         // Get the permissions while checking for out of bounds
         let perms = self.permissions.get(address.0..address.0 + <$ty>::BYTES)
             .ok_or_else(|| MmuError::OutOfBound{
-                is_read: false,
+                is_read: true,
                 virtual_address: address,
         })?;
         
@@ -174,8 +174,9 @@ impl<
             }
         }
 
-        // Update the dirty list
+        // Update the dirty list, the write can straddle two blocks
         self.dirty.dirty(address.0 / DIRTY_BLOCK_SIZE);
+        self.dirty.dirty((address.0 + <$ty>::BYTES - 1) / DIRTY_BLOCK_SIZE);
 
         Ok(())
     }
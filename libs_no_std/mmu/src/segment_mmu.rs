@@ -2,6 +2,7 @@ use super::*;
 use traits::Word;
 use alloc::vec::Vec;
 use alloc::vec;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 /// An contiguous isolated memory space
 /// 
@@ -31,6 +32,23 @@ pub struct SegmentMmu<
 
     /// Keep track of what was dirtied and what wasn't
     pub dirty: DirtyState,
+
+    /// The last accesses to each word, empty until asked for with
+    /// [`SegmentMmu::shadow_words`]
+    pub shadow: Vec<ShadowWord>,
+
+    /// Identifier shared only with the forks of this segment, so that 
+    /// [`Mmu::reset`] can tell apart a segment that was unmapped and mapped 
+    /// again at the same address
+    pub id: u64,
+}
+
+/// Source of the [`SegmentMmu::id`]s
+static NEXT_SEGMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[inline]
+fn new_segment_id() -> u64 {
+    NEXT_SEGMENT_ID.fetch_add(1, Ordering::Relaxed)
 }
 
 impl<
@@ -42,6 +60,8 @@ impl<
     RAW,
     TAINT,
 > {
+    /// Size of the blocks tracked by [`SegmentMmu::dirty`]
+    pub const DIRTY_BLOCK_SIZE: usize = DIRTY_BLOCK_SIZE;
 
     #[inline(always)]
     pub fn len(&self) -> usize {
@@ -65,9 +85,23 @@ impl<
             dirty: DirtyState::new(
                 (size + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE // ceil
             ).unwrap(),
+            shadow: Vec::new(),
+            id: new_segment_id(),
         })
     }   
 
+    /// Pointers to the memory, the permissions and the dirty bitmap, so that a
+    /// JIT can inline the fast path of the reads and writes and leave the
+    /// rest to the methods of this struct. They are valid until the segment
+    /// is resized or dropped
+    pub fn raw_parts(&mut self) -> (*mut u8, *mut Perm, *const usize) {
+        (
+            self.memory.as_mut_ptr(),
+            self.permissions.as_mut_ptr(),
+            self.dirty.bitmap().as_ptr(),
+        )
+    }
+
     /// Create a copy of the current memory resetting the dirty bytes infos so
     /// that when calling reset it will reset to the state of the memory at 
     /// the fork time.
@@ -78,16 +112,27 @@ impl<
 
             // The size is already checked on creation so this cannot fail
             dirty: unsafe{DirtyState::new(self.dirty.len()).unwrap_unchecked()},
+            shadow: self.shadow.clone(),
+            id: self.id,
         }
     }
 
-    /// Reset the memory to the state it was at creation. 
+    /// Reset the memory to the state of `reference_memory`, which this 
+    /// segment was forked from, copying only the dirty blocks.
+    /// If the segment was resized since the fork its length is restored too.
     pub fn reset(&mut self, reference_memory: &Self) {
+        // the blocks beyond either end don't need to be copied, they are 
+        // either dropped or copied whole below
+        let len = self.memory.len().min(reference_memory.len());
         // Clean the blocks and remove the indices from the vector
         for dirty_block_index in self.dirty.drain() {
             // Compute the range of bytes we need to reset
             let start = DIRTY_BLOCK_SIZE * dirty_block_index;
-            let end   = DIRTY_BLOCK_SIZE + start;
+            // the last block might be partial if the segment was resized
+            let end   = (DIRTY_BLOCK_SIZE + start).min(len);
+            if start >= end {
+                continue;
+            }
 
             // Reset the data
             self.memory[start..end].copy_from_slice(
@@ -97,23 +142,109 @@ impl<
             self.permissions[start..end].copy_from_slice(
                 &reference_memory.permissions[start..end]
             );          
+            // Reset the shadow, an empty one has no accesses
+            if !self.shadow.is_empty() {
+                let words = start / SHADOW_WORD_SIZE..end.div_ceil(SHADOW_WORD_SIZE);
+                match reference_memory.shadow.is_empty() {
+                    true => self.shadow[words].fill(ShadowWord::default()),
+                    false => self.shadow[words.clone()]
+                        .copy_from_slice(&reference_memory.shadow[words]),
+                }
+            }
+        }
+
+        // restore the length, the bytes that were removed since the fork
+        // can't be recovered so they are copied
+        if self.memory.len() != reference_memory.len() {
+            self.memory.truncate(len);
+            self.permissions.truncate(len);
+            self.memory.extend_from_slice(&reference_memory.memory[len..]);
+            self.permissions.extend_from_slice(&reference_memory.permissions[len..]);
+            self.dirty.resize(reference_memory.dirty.len());
+            if !self.shadow.is_empty() {
+                let words = reference_memory.len().div_ceil(SHADOW_WORD_SIZE);
+                if reference_memory.shadow.is_empty() {
+                    self.shadow.resize(words, ShadowWord::default());
+                } else {
+                    self.shadow.truncate(len / SHADOW_WORD_SIZE);
+                    self.shadow.extend_from_slice(&reference_memory.shadow[len / SHADOW_WORD_SIZE..]);
+                }
+            }
+        }
+        // the reference allocated its shadow after the fork
+        if self.shadow.is_empty() && !reference_memory.shadow.is_empty() {
+            self.shadow.clone_from(&reference_memory.shadow);
         }
 
         // Reset the adress informations
         // on debug check (**expensive**) that the reset is done correctly
         debug_assert_eq!(self.permissions, reference_memory.permissions);
         debug_assert_eq!(self.memory, reference_memory.memory);
+        debug_assert!(self.shadow == reference_memory.shadow || (reference_memory.shadow.is_empty()
+            && self.shadow.iter().all(|word| *word == ShadowWord::default())));
+    }
+
+    /// Mark as dirty the blocks that overlap the bytes in `range`
+    #[inline]
+    pub fn dirty_range(&mut self, range: Range<usize>) {
+        if range.start >= range.end {
+            return;
+        }
+        for idx in range.start / DIRTY_BLOCK_SIZE..=(range.end - 1) / DIRTY_BLOCK_SIZE {
+            self.dirty.dirty(idx);
+        }
     }
 
     pub fn resize(&mut self, size: usize, perm: Perm) -> Result<(), MmuError> {
         // TODO! should we leave the allocation? is better an out of bound or
         // a permission denied?
+        let old_size = self.memory.len();
         self.memory.resize(size, 0);
         self.permissions.resize(size, perm);
-        self.dirty.resize(size);
+        self.dirty.resize((size + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE);
+        if !self.shadow.is_empty() {
+            self.shadow.resize(size.div_ceil(SHADOW_WORD_SIZE), ShadowWord::default());
+        }
+        // the new bytes might have had a different content at fork time, if
+        // the segment shrunk in the meantime
+        self.dirty_range(old_size..size);
         Ok(())
     }
 
+    /// Split the segment in two at offset `at`, `self` keeps `[0, at)` and
+    /// the returned segment holds `[at, len)`.
+    /// The returned segment starts with a clean dirty state.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let memory = self.memory.split_off(at);
+        let permissions = self.permissions.split_off(at);
+        // the split is at a page boundary, so at a word boundary too
+        let shadow = match self.shadow.is_empty() {
+            true => Vec::new(),
+            false => self.shadow.split_off(at / SHADOW_WORD_SIZE),
+        };
+        self.dirty.resize((at + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE);
+        let blocks = (memory.len() + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE;
+        SegmentMmu {
+            memory,
+            permissions,
+            // The size is already checked on creation so this cannot fail
+            dirty: unsafe{DirtyState::new(blocks).unwrap_unchecked()},
+            shadow,
+            id: new_segment_id(),
+        }
+    }
+
+    /// The shadow words of the bytes at offsets `range`, which must be in
+    /// the segment, allocating the shadow if needed. Their blocks are marked
+    /// as dirty, so that the shadow is reset with the memory
+    pub fn shadow_words(&mut self, range: Range<usize>) -> &mut [ShadowWord] {
+        if self.shadow.is_empty() {
+            self.shadow = vec![ShadowWord::default(); self.len().div_ceil(SHADOW_WORD_SIZE)];
+        }
+        self.dirty_range(range.clone());
+        &mut self.shadow[range.start / SHADOW_WORD_SIZE..range.end.div_ceil(SHADOW_WORD_SIZE)]
+    }
+
     /// Set the given permissions to a given range of virtual addresses
     pub fn set_permissions(&mut self, range: Range<VirtAddr>, permissions: Perm) 
         -> Result<(), MmuError> {
@@ -137,16 +268,10 @@ impl<
         let range_to_modify = range.start.0..range.end.0; 
 
         // apply the permissions
-        self.permissions[range_to_modify].fill(permissions);
+        self.permissions[range_to_modify.clone()].fill(permissions);
 
-        // compute the dirty range
-        let dirty_start = (range.start.0 + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE;
-        let dirty_end   = (range.end.0   + DIRTY_BLOCK_SIZE - 1) / DIRTY_BLOCK_SIZE;
-
-        // dirty the blocks
-        for idx in dirty_start..dirty_end {
-            self.dirty.dirty(idx);
-        }
+        // dirty the blocks, including the partial ones at the ends
+        self.dirty_range(range_to_modify);
 
         Ok(())
     }
@@ -171,12 +296,15 @@ impl<
     /// unsafe. 
     pub unsafe fn write_from_slice(&mut self, address: VirtAddr, slice: &[u8]) -> Result<(), MmuError> {
         self.memory[address.0..address.0 + slice.len()].copy_from_slice(slice);
+        // so that a harness can write to a forked memory
+        self.dirty_range(address.0..address.0 + slice.len());
         Ok(())
     }
 
     pub unsafe fn write_from_slice_with_perm(&mut self, address: VirtAddr, slice: &[u8], perm: Perm) -> Result<(), MmuError> {
         self.memory[address.0..address.0 + slice.len()].copy_from_slice(slice);
         self.permissions[address.0..address.0 + slice.len()].fill(perm);
+        self.dirty_range(address.0..address.0 + slice.len());
         Ok(())
     }
 
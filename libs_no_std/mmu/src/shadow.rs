@@ -0,0 +1,30 @@
+//! Shadow memory recording the last accesses to each word of a segment, the
+//! metadata of a race detector.
+//!
+//! The shadow of a segment is allocated the first time it's asked for, so
+//! the segments that are never checked don't pay for it. Its words are reset
+//! with the dirty blocks that contain them, so the shadow is only restored
+//! correctly if whoever updates it also dirties the blocks, as
+//! [`SegmentMmu::shadow_words`](crate::SegmentMmu::shadow_words) does.
+
+/// Size in bytes of the memory covered by a [`ShadowWord`]
+pub const SHADOW_WORD_SIZE: usize = 8;
+
+/// An access to some bytes of a word by a thread
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowAccess {
+    pub pc: u64,
+    /// Logical clock of the thread when it accessed the word
+    pub clock: u32,
+    /// Index of the thread, as numbered by the race detector
+    pub thread: u16,
+    /// Bytes of the word accessed, zero if there was no access
+    pub mask: u8,
+}
+
+/// The last write and the last read of a word
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShadowWord {
+    pub write: ShadowAccess,
+    pub read: ShadowAccess,
+}
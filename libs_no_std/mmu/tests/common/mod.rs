@@ -0,0 +1,15 @@
+//! Helpers shared by the integration tests
+#![allow(dead_code)]
+use mmu::*;
+
+pub const BRK: usize = 0x1_0000;
+
+/// An mmu with an empty brk segment
+pub fn new_mmu() -> Mmu {
+    let mut mmu: Mmu = Mmu::new();
+    let (brk_idx, _) = mmu.allocate_segment(
+        Some(VirtAddr(BRK)), 0, PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    mmu.brk_idx = brk_idx;
+    mmu
+}
@@ -0,0 +1,80 @@
+//! Tests of the address to segment lookup of the [`Mmu`]
+use mmu::*;
+
+/// Addresses and sizes of the mappings of a dynamically linked binary,
+/// mapped out of order like a loader does
+const MAPPINGS: &[(usize, usize)] = &[
+    (0x1_0000, 0x2000), (0x1_3000, 0x1000), (0x1_5000, 0x1000),
+    (0x40_0000_0000, 0x1000), (0x40_0000_2000, 0x2000),
+    (0x40_0001_0000, 0x1b000), (0x40_0002_c000, 0x2000), (0x40_0002_f000, 0x1000),
+    (0x40_0010_0000, 0x13f000), (0x40_0024_0000, 0x4000), (0x40_0024_5000, 0x2000),
+    (0x40_0024_8000, 0xd000), (0x3f_ffff_0000, 0x10000),
+    (0x40_0030_0000, 0x1000), (0x40_0030_2000, 0x1000),
+];
+
+fn loader_mmu() -> Mmu {
+    let mut mmu: Mmu = Mmu::new();
+    let rw = PermField::Read | PermField::Write;
+    for (addr, size) in MAPPINGS.iter().rev() {
+        mmu.mmap(MapPlacement::FixedNoReplace(VirtAddr(*addr)), *size, rw).unwrap();
+    }
+    mmu
+}
+
+/// The index of the segment containing `addr` found with a linear scan
+fn linear(mmu: &Mmu, addr: usize) -> Option<usize> {
+    mmu.segments.iter().position(|(start, smmu)| {
+        (start.0..start.0 + smmu.len()).contains(&addr)
+    })
+}
+
+/// Check every segment boundary and the gaps between them
+fn assert_lookups(mmu: &mut Mmu) {
+    let mut addrs = Vec::new();
+    for (start, smmu) in mmu.segments.iter() {
+        let end = start.0 + smmu.len();
+        addrs.extend([start.0 - 1, start.0, start.0 + 1, end - 1, end]);
+    }
+    // in order and then backwards, to miss the last segment cache
+    for addr in addrs.iter().chain(addrs.iter().rev()) {
+        assert_eq!(mmu.find_segment(VirtAddr(*addr)), linear(mmu, *addr), "{:x}", addr);
+    }
+}
+
+#[test]
+fn test_lookup() {
+    let mut mmu = loader_mmu();
+    assert_lookups(&mut mmu);
+    assert!(mmu.resolve_segment(VirtAddr(0)).is_err());
+    assert!(mmu.resolve_segment(VirtAddr(usize::MAX)).is_err());
+}
+
+#[test]
+fn test_lookup_after_changes() {
+    let mut mmu = loader_mmu();
+    let rw = PermField::Read | PermField::Write;
+    // split a segment in three and remove another
+    mmu.munmap(VirtAddr(0x40_0011_0000), 0x1000).unwrap();
+    mmu.munmap(VirtAddr(0x40_0024_0000), 0x4000).unwrap();
+    assert_lookups(&mut mmu);
+    // replace part of a segment and keep only its tail
+    mmu.mmap(MapPlacement::Fixed(VirtAddr(0x40_0001_0000)), 0x2000, rw).unwrap();
+    mmu.munmap(VirtAddr(0x40_0001_0000), 0x4000).unwrap();
+    assert_lookups(&mut mmu);
+    // move a segment
+    mmu.mremap(VirtAddr(0x1_3000), 0x1000, 0x3000, true, None).unwrap();
+    assert_lookups(&mut mmu);
+
+    let fork = mmu.fork();
+    mmu.munmap(VirtAddr(0x3f_ffff_0000), 0x10000).unwrap();
+    mmu.mmap(MapPlacement::Any, 0x5000, rw).unwrap();
+    assert!(mmu.resolve_segment(VirtAddr(0x3f_ffff_0000)).is_err());
+    mmu.reset(&fork);
+    assert_lookups(&mut mmu);
+    assert!(mmu.resolve_segment(VirtAddr(0x3f_ffff_0000)).is_ok());
+
+    // segments changed without going through the mmu are still found
+    mmu.segments.swap(0, 5);
+    mmu.segments[2].0 = VirtAddr(0x50_0000_0000);
+    assert_lookups(&mut mmu);
+}
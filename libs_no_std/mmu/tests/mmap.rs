@@ -0,0 +1,173 @@
+//! Tests of the memory mapping functions of the [`Mmu`]
+use mmu::*;
+
+mod common;
+use common::*;
+
+#[test]
+fn test_auto_placement() {
+    let mut mmu = new_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let a = mmu.mmap(MapPlacement::Any, 0x3000, rw).unwrap();
+    let b = mmu.mmap(MapPlacement::Any, 0x1000, rw).unwrap();
+    assert_eq!(a, <Mmu>::new().segments_alloc_addr);
+    // the redzone is left between the two
+    assert_eq!(b.0, a.0 + 0x3000 + mmu.segment_redzone);
+    assert_eq!(b.0 % PAGE_SIZE, 0);
+
+    // a fixed mapping in the way is skipped, with its redzone
+    let next = mmu.segments_alloc_addr;
+    mmu.mmap(MapPlacement::Fixed(next), 0x1000, rw).unwrap();
+    let c = mmu.mmap(MapPlacement::Any, 0x1000, rw).unwrap();
+    assert_eq!(c.0, next.0 + 0x1000 + mmu.segment_redzone);
+
+    // the memory is zeroed and usable
+    assert_eq!(mmu.read::<u64>(c).unwrap(), 0);
+    mmu.write::<u64>(c + 8, 0x1337).unwrap();
+    assert_eq!(mmu.read::<u64>(c + 8).unwrap(), 0x1337);
+}
+
+#[test]
+fn test_fixed_placement() {
+    let mut mmu = new_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let addr = VirtAddr(0x10_0000);
+    mmu.mmap(MapPlacement::FixedNoReplace(addr), 0x2000, rw).unwrap();
+    mmu.write::<u32>(addr, 0xdead).unwrap();
+    assert!(matches!(
+        mmu.mmap(MapPlacement::FixedNoReplace(addr + 0x1000), 0x1000, rw),
+        Err(MmuError::AddressInUse{..})
+    ));
+    // a hint on a used range is ignored
+    let hinted = mmu.mmap(MapPlacement::Hint(addr), 0x1000, rw).unwrap();
+    assert_ne!(hinted, addr);
+    // fixed replaces the old mapping
+    mmu.mmap(MapPlacement::Fixed(addr), 0x1000, PermField::Read.into()).unwrap();
+    assert_eq!(mmu.read::<u32>(addr).unwrap(), 0);
+    assert!(mmu.write::<u32>(addr, 1).is_err());
+    // and keeps the part after it
+    mmu.write::<u32>(addr + 0x1000, 1).unwrap();
+}
+
+#[test]
+fn test_munmap() {
+    let mut mmu = new_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let addr = mmu.mmap(MapPlacement::Any, 0x5000, rw).unwrap();
+    for page in 0..5 {
+        mmu.write::<u64>(addr + page * 0x1000, page as u64).unwrap();
+    }
+    let len = mmu.len();
+
+    // hole in the middle
+    mmu.munmap(addr + 0x2000, 0x1000).unwrap();
+    assert!(mmu.read::<u64>(addr + 0x2000).is_err());
+    assert_eq!(mmu.read::<u64>(addr + 0x1000).unwrap(), 1);
+    assert_eq!(mmu.read::<u64>(addr + 0x3000).unwrap(), 3);
+    // head
+    mmu.munmap(addr, 0x1000).unwrap();
+    assert!(mmu.read::<u64>(addr).is_err());
+    assert_eq!(mmu.read::<u64>(addr + 0x1000).unwrap(), 1);
+    // tail
+    mmu.munmap(addr + 0x4000, 0x1000).unwrap();
+    assert!(mmu.read::<u64>(addr + 0x4000).is_err());
+    assert_eq!(mmu.read::<u64>(addr + 0x3000).unwrap(), 3);
+    assert_eq!(mmu.len(), len - 0x3000);
+    // across the two remaining segments, and some unmapped memory
+    mmu.munmap(addr, 0x10000).unwrap();
+    assert_eq!(mmu.len(), len - 0x5000);
+    // the brk segment is still the same
+    assert_eq!(mmu.segments[mmu.brk_idx].0, VirtAddr(BRK));
+}
+
+#[test]
+fn test_munmap_keeps_brk_idx() {
+    let mut mmu: Mmu = Mmu::new();
+    let rw = PermField::Read | PermField::Write;
+    let addr = mmu.mmap(MapPlacement::Any, 0x1000, rw).unwrap();
+    let (brk_idx, _) = mmu.allocate_segment(
+        Some(VirtAddr(BRK)), 0, PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    mmu.brk_idx = brk_idx;
+    mmu.munmap(addr, 0x1000).unwrap();
+    assert_eq!(mmu.segments[mmu.brk_idx].0, VirtAddr(BRK));
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x100)).unwrap(), VirtAddr(BRK + 0x100));
+}
+
+#[test]
+fn test_munmap_brk_and_stack() {
+    // the brk segment is emptied instead of removed
+    let mut mmu = new_mmu();
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x2000)).unwrap(), VirtAddr(BRK + 0x2000));
+    mmu.munmap(VirtAddr(BRK), 0x2000).unwrap();
+    assert_eq!(mmu.segments[mmu.brk_idx].1.len(), 0);
+    mmu.munmap(VirtAddr(0), 0x10_0000).unwrap();
+    assert_eq!(mmu.segments[mmu.brk_idx].0, VirtAddr(BRK));
+
+    // so is the first segment while the indices are still the default
+    let mut mmu: Mmu = Mmu::new();
+    let addr = mmu.mmap(MapPlacement::Any, 0x1000, PermField::Read.into()).unwrap();
+    mmu.munmap(addr, 0x1000).unwrap();
+    assert!(mmu.read::<u8>(addr).is_err());
+}
+
+#[test]
+fn test_brk() {
+    let mut mmu = new_mmu();
+    assert_eq!(mmu.brk(VirtAddr(0)).unwrap(), VirtAddr(BRK));
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x1234)).unwrap(), VirtAddr(BRK + 0x1234));
+    // uninitialized memory can't be read
+    assert!(mmu.read::<u8>(VirtAddr(BRK + 0x100)).is_err());
+    mmu.write::<u8>(VirtAddr(BRK + 0x100), 1).unwrap();
+    assert_eq!(mmu.read::<u8>(VirtAddr(BRK + 0x100)).unwrap(), 1);
+    // shrink
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x10)).unwrap(), VirtAddr(BRK + 0x10));
+    assert!(mmu.write::<u8>(VirtAddr(BRK + 0x100), 1).is_err());
+    // can't go below the start or into another mapping
+    assert_eq!(mmu.brk(VirtAddr(BRK - 1)).unwrap(), VirtAddr(BRK + 0x10));
+    mmu.mmap(MapPlacement::Fixed(VirtAddr(BRK + 0x2000)), 0x1000, PermField::Read.into()).unwrap();
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x3000)).unwrap(), VirtAddr(BRK + 0x10));
+    assert_eq!(mmu.brk(VirtAddr(BRK + 0x2000)).unwrap(), VirtAddr(BRK + 0x2000));
+}
+
+#[test]
+fn test_mremap() {
+    let mut mmu = new_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let addr = mmu.mmap(MapPlacement::Any, 0x2000, rw).unwrap();
+    mmu.write::<u64>(addr, 0x1337).unwrap();
+    mmu.write::<u64>(addr + 0x1ff8, 0x4242).unwrap();
+
+    // grow in place, there's nothing after it
+    assert_eq!(mmu.mremap(addr, 0x2000, 0x3000, false, None).unwrap(), addr);
+    assert_eq!(mmu.read::<u64>(addr + 0x2ff8).unwrap(), 0);
+    // shrink in place
+    assert_eq!(mmu.mremap(addr, 0x3000, 0x2000, false, None).unwrap(), addr);
+    assert!(mmu.read::<u64>(addr + 0x2000).is_err());
+
+    // block the growth
+    mmu.mmap(MapPlacement::Fixed(addr + 0x2000), 0x1000, rw).unwrap();
+    assert!(matches!(
+        mmu.mremap(addr, 0x2000, 0x4000, false, None),
+        Err(MmuError::AddressInUse{..})
+    ));
+    // so it has to move
+    let moved = mmu.mremap(addr, 0x2000, 0x4000, true, None).unwrap();
+    assert_ne!(moved, addr);
+    assert!(mmu.read::<u64>(addr).is_err());
+    assert_eq!(mmu.read::<u64>(moved).unwrap(), 0x1337);
+    assert_eq!(mmu.read::<u64>(moved + 0x1ff8).unwrap(), 0x4242);
+    mmu.write::<u64>(moved + 0x3ff8, 1).unwrap();
+
+    // to a fixed address
+    let fixed = VirtAddr(0x20_0000);
+    assert_eq!(mmu.mremap(moved, 0x1000, 0x1000, true, Some(fixed)).unwrap(), fixed);
+    assert_eq!(mmu.read::<u64>(fixed).unwrap(), 0x1337);
+    assert!(mmu.read::<u64>(moved).is_err());
+    assert_eq!(mmu.read::<u64>(moved + 0x1ff8).unwrap(), 0x4242);
+
+    assert!(matches!(
+        mmu.mremap(VirtAddr(0x30_0000), 0x1000, 0x2000, true, None),
+        Err(MmuError::SegmentNotFound{..})
+    ));
+}
@@ -0,0 +1,182 @@
+//! Tests of the differential reset of the [`Mmu`], in debug builds every
+//! segment reset also checks that the whole segment equals the reference
+use mmu::*;
+
+mod common;
+use common::*;
+
+const DATA: usize = 0x10_0000;
+
+/// An mmu with a brk segment and a read-after-write data segment
+fn new_data_mmu() -> Mmu {
+    let mut mmu = new_mmu();
+    mmu.allocate_segment(
+        Some(VirtAddr(DATA)), 0x1000, PermField::Write | PermField::ReadAfterWrite,
+    ).unwrap();
+    mmu
+}
+
+/// Check that `mmu` has the same segments as `reference`
+fn assert_same_layout(mmu: &Mmu, reference: &Mmu) {
+    let layout = |mmu: &Mmu| mmu.segments.iter()
+        .map(|(addr, smmu)| (*addr, smmu.len(), smmu.id))
+        .collect::<Vec<_>>();
+    assert_eq!(layout(mmu), layout(reference));
+    for ((_, smmu), (_, ref_smmu)) in mmu.segments.iter().zip(reference.segments.iter()) {
+        assert_eq!(smmu.memory, ref_smmu.memory);
+        assert_eq!(smmu.permissions, ref_smmu.permissions);
+    }
+    assert_eq!(mmu.brk_idx, reference.brk_idx);
+    assert_eq!(mmu.stack_segment_idx, reference.stack_segment_idx);
+    assert_eq!(mmu.segments_alloc_addr, reference.segments_alloc_addr);
+}
+
+#[test]
+fn test_simple() {
+    let mut mmu = new_data_mmu();
+    let addr = VirtAddr(DATA + 0x100);
+
+    // error on uninitalized memory
+    assert!(mmu.read::<u16>(addr).is_err());
+
+    // write to the memory and read it back
+    mmu.write::<u64>(addr, 1337).unwrap();
+    assert_eq!(1337, mmu.read::<u64>(addr).unwrap());
+    // even one byte of uninitialized memory causes an error
+    assert!(mmu.read::<u64>(addr + 1).is_err());
+    assert!(mmu.read::<u64>(addr - 1).is_err());
+
+    // create a new mmu forking the current state
+    let mut mmu2 = mmu.fork();
+    assert_eq!(1337, mmu2.read::<u64>(addr).unwrap());
+
+    // modify the state
+    mmu2.write::<u16>(addr + 8, 420).unwrap();
+    assert_eq!(420, mmu2.read::<u16>(addr + 8).unwrap());
+
+    // reset differentially the mmu to the fork state
+    mmu2.reset(&mmu);
+
+    // check that the state was **actually** resetted
+    assert!(mmu2.read::<u16>(addr + 8).is_err());
+    assert_eq!(1337, mmu2.read::<u64>(addr).unwrap());
+    assert_same_layout(&mmu2, &mmu);
+}
+
+#[test]
+fn test_block_boundaries() {
+    let mmu = new_data_mmu();
+    let mut fork = mmu.fork();
+    // across the first two dirty blocks
+    fork.write::<u64>(VirtAddr(DATA + 0xfc), u64::MAX).unwrap();
+    // a slice written by a harness
+    unsafe{fork.write_from_slice(VirtAddr(DATA + 0x2f0), &[0x41; 0x20])}.unwrap();
+    // permissions starting in the middle of a block
+    fork.mprotect(VirtAddr(DATA + 0x480), 0x100, PermField::Read.into()).unwrap();
+    fork.reset(&mmu);
+    assert_same_layout(&fork, &mmu);
+    assert!(fork.read::<u8>(VirtAddr(DATA + 0x100)).is_err());
+    fork.write::<u8>(VirtAddr(DATA + 0x500), 1).unwrap();
+}
+
+#[test]
+fn test_added_and_removed_segments() {
+    let mut mmu = new_data_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let mapped = mmu.mmap(MapPlacement::Any, 0x3000, rw).unwrap();
+    mmu.write::<u64>(mapped + 0x1000, 0x1337).unwrap();
+    let mut fork = mmu.fork();
+
+    for _ in 0..3 {
+        // a new mapping
+        let new = fork.mmap(MapPlacement::Any, 0x1000, rw).unwrap();
+        fork.write::<u64>(new, 1).unwrap();
+        // a removed one
+        fork.munmap(VirtAddr(DATA), 0x1000).unwrap();
+        // and a hole in the middle of another
+        fork.munmap(mapped + 0x1000, 0x1000).unwrap();
+        assert!(fork.read::<u64>(mapped + 0x1000).is_err());
+
+        fork.reset(&mmu);
+        assert_same_layout(&fork, &mmu);
+        assert!(fork.read::<u64>(new).is_err());
+        assert_eq!(fork.read::<u64>(mapped + 0x1000).unwrap(), 0x1337);
+        fork.write::<u64>(VirtAddr(DATA), 1).unwrap();
+        fork.reset(&mmu);
+    }
+}
+
+#[test]
+fn test_mapped_again_at_the_same_address() {
+    let mut mmu = new_data_mmu();
+    let rw = PermField::Read | PermField::Write;
+    let mapped = mmu.mmap(MapPlacement::Any, 0x2000, rw).unwrap();
+    mmu.write::<u64>(mapped, 0x1337).unwrap();
+    let mut fork = mmu.fork();
+
+    // same address and size, but a new zeroed mapping
+    fork.mmap(MapPlacement::Fixed(mapped), 0x2000, rw).unwrap();
+    assert_eq!(fork.read::<u64>(mapped).unwrap(), 0);
+    fork.reset(&mmu);
+    assert_same_layout(&fork, &mmu);
+    assert_eq!(fork.read::<u64>(mapped).unwrap(), 0x1337);
+}
+
+#[test]
+fn test_resized_brk() {
+    let mut mmu = new_data_mmu();
+    mmu.brk(VirtAddr(BRK + 0x180)).unwrap();
+    mmu.write::<u64>(VirtAddr(BRK + 0x178), 0x1337).unwrap();
+    let mut fork = mmu.fork();
+
+    // grow
+    fork.brk(VirtAddr(BRK + 0x3000)).unwrap();
+    fork.write::<u64>(VirtAddr(BRK + 0x2000), 1).unwrap();
+    fork.reset(&mmu);
+    assert_same_layout(&fork, &mmu);
+    assert_eq!(fork.brk(VirtAddr(0)).unwrap(), VirtAddr(BRK + 0x180));
+
+    // shrink and grow again, the lost bytes are restored
+    fork.brk(VirtAddr(BRK + 0x100)).unwrap();
+    fork.brk(VirtAddr(BRK + 0x200)).unwrap();
+    assert!(fork.read::<u64>(VirtAddr(BRK + 0x178)).is_err());
+    fork.reset(&mmu);
+    assert_same_layout(&fork, &mmu);
+    assert_eq!(fork.read::<u64>(VirtAddr(BRK + 0x178)).unwrap(), 0x1337);
+
+    // brk stays usable after a reset that rebuilt the segments
+    fork.mmap(MapPlacement::Any, 0x1000, PermField::Read.into()).unwrap();
+    fork.munmap(VirtAddr(DATA), 0x1000).unwrap();
+    fork.reset(&mmu);
+    assert_same_layout(&fork, &mmu);
+    assert_eq!(fork.brk(VirtAddr(BRK + 0x1000)).unwrap(), VirtAddr(BRK + 0x1000));
+}
+
+#[test]
+fn test_shadow() {
+    let mmu = new_data_mmu();
+    let mut fork = mmu.fork();
+    let data = fork.find_segment(VirtAddr(DATA)).unwrap();
+    let access = ShadowAccess { pc: 0x1234, clock: 1, thread: 2, mask: 0xff };
+
+    // allocated when first asked for and reset to empty accesses
+    let words = fork.segments[data].1.shadow_words(0x100..0x108);
+    assert_eq!(words.len(), 1);
+    words[0].write = access;
+    fork.reset(&mmu);
+    assert!(fork.segments[data].1.shadow.iter().all(|word| *word == ShadowWord::default()));
+
+    // the accesses at fork time are restored, in the grown segments too
+    let mut reference = fork.fork();
+    reference.segments[data].1.shadow_words(0x104..0x10c)
+        .iter_mut().for_each(|word| word.read = access);
+    let mut fork = reference.fork();
+    fork.segments[data].1.shadow_words(0x108..0x110)[0] = ShadowWord::default();
+    fork.brk(VirtAddr(BRK + 0x1000)).unwrap();
+    let brk = fork.find_segment(VirtAddr(BRK)).unwrap();
+    fork.segments[brk].1.shadow_words(0xff8..0x1000)[0].write = access;
+    fork.reset(&reference);
+    assert_same_layout(&fork, &reference);
+    assert_eq!(fork.segments[data].1.shadow, reference.segments[data].1.shadow);
+    assert_eq!(fork.segments[data].1.shadow[0x108 / SHADOW_WORD_SIZE].read, access);
+}